@@ -227,12 +227,7 @@ fn handle_zoom(
                 use std::io::Write;
                 std::io::stdout().flush().ok().unwrap();
 
-                if let Some(hit) = tree
-                    .tree
-                    .read()
-                    .expect("Expected to be able to read Tree from GPU host")
-                    .get_by_ray(&ray)
-                {
+                if let Some(hit) = tree.tree().get_by_ray(&ray) {
                     let (data, _, normal) = hit;
                     //Because both vector should be normalized, the dot product should be 1*1*cos(angle)
                     //That means it is in range -1, +1, which should be accounted for
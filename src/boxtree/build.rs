@@ -0,0 +1,369 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeEntry, BrickData, NodeChildren, NodeContent, NodeData, OctreeError,
+            PaletteIndexValues,
+        },
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        lut::SECTANT_OFFSET_LUT,
+        math::{flat_projection, vector::V3c},
+        CubeSides,
+    },
+};
+use std::collections::HashMap;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Bricks of a leaf node sampled from a voxel source, before they are added to the tree
+/// Voxels inside the bricks are indices into the palette of the leaf, where 0 is always empty
+struct SampledLeaf<'a, T: VoxelData> {
+    palette: Vec<BoxTreeEntry<'a, T>>,
+    bricks: [BrickData<PaletteIndexValues>; BOX_NODE_CHILDREN_COUNT],
+}
+
+/// Applies the given function on each of the given items, in parallel if the `parallel` feature is enabled
+fn map_items<I: Send, R: Send>(items: Vec<I>, function: impl Fn(I) -> R + Send + Sync) -> Vec<R> {
+    #[cfg(feature = "parallel")]
+    {
+        items.into_par_iter().map(function).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.into_iter().map(function).collect()
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    //####################################################################################
+    //  ███████████  █████  █████ █████ █████       ██████████
+    // ░░███░░░░░███░░███  ░░███ ░░███ ░░███       ░░███░░░░███
+    //  ░███    ░███ ░███   ░███  ░███  ░███        ░███   ░░███
+    //  ░██████████  ░███   ░███  ░███  ░███        ░███    ░███
+    //  ░███░░░░░███ ░███   ░███  ░███  ░███        ░███    ░███
+    //  ░███    ░███ ░███   ░███  ░███  ░███      █ ░███    ███
+    //  ███████████  ░░████████   █████ ███████████ ██████████
+    // ░░░░░░░░░░░    ░░░░░░░░   ░░░░░ ░░░░░░░░░░░ ░░░░░░░░░░
+    //####################################################################################
+    /// Creates a boxtree with the given size, with each voxel set by the given function
+    /// Bricks are built independently, in parallel if the `parallel` feature is enabled,
+    /// then nodes are assembled bottom-up, so no simplification is needed afterwards.
+    /// MIP maps are disabled in the created tree, enabling them calculates every MIP once.
+    /// * `size` - must be `brick_dimension * (4^x)`, see @BoxTree::new
+    /// * `brick_dimension` - must be one of `(2^x)` and smaller than the size of the boxtree
+    /// * `voxel_at` - provides the data to set at each position of the tree
+    pub fn from_fn<'a, E, F>(
+        size: u32,
+        brick_dimension: u32,
+        voxel_at: F,
+    ) -> Result<Self, OctreeError>
+    where
+        E: Into<BoxTreeEntry<'a, T>>,
+        F: Fn(&V3c<u32>) -> E + Send + Sync,
+        T: 'a,
+    {
+        let mut tree = Self::new(size, brick_dimension)?;
+        let leaf_size = brick_dimension * BOX_NODE_DIMENSION as u32;
+        let leaf_count_per_dimension = (size / leaf_size) as usize;
+
+        // Sample every leaf node with its own palette
+        let sampled_leaves = map_items(
+            (0..leaf_count_per_dimension.pow(3)).collect(),
+            |leaf_index| {
+                let leaf_position = V3c::new(
+                    leaf_index % leaf_count_per_dimension,
+                    (leaf_index / leaf_count_per_dimension) % leaf_count_per_dimension,
+                    leaf_index / leaf_count_per_dimension.pow(2),
+                ) * leaf_size as usize;
+                Self::sample_leaf(&V3c::from(leaf_position), brick_dimension, &voxel_at)
+            },
+        );
+
+        // Collect the palette entries of every leaf into the palettes of the tree
+        let sampled_leaves = sampled_leaves
+            .into_iter()
+            .map(|leaf| {
+                let palette_mapping = leaf
+                    .palette
+                    .iter()
+                    .map(|entry| tree.add_to_palette(entry))
+                    .collect::<Vec<_>>();
+                (palette_mapping, leaf.bricks)
+            })
+            .collect::<Vec<_>>();
+
+        // Convert bricks to refer to the palettes of the tree
+        let color_palette = &tree.voxel_color_palette;
+        let data_palette = &tree.voxel_data_palette;
+        let mut level_nodes = map_items(sampled_leaves, |(palette_mapping, bricks)| {
+            Self::build_leaf_node(
+                &palette_mapping,
+                bricks,
+                brick_dimension,
+                color_palette,
+                data_palette,
+            )
+        });
+
+        // Assemble nodes bottom-up, each parent takes 64 nodes from the level below
+        let mut level_dimension = leaf_count_per_dimension;
+        while 1 < level_dimension {
+            let parent_dimension = level_dimension / BOX_NODE_DIMENSION;
+            let mut parent_nodes = Vec::with_capacity(parent_dimension.pow(3));
+            for z in 0..parent_dimension {
+                for y in 0..parent_dimension {
+                    for x in 0..parent_dimension {
+                        let children = (0..BOX_NODE_CHILDREN_COUNT)
+                            .map(|sectant| {
+                                let child_position = V3c::new(x, y, z) * BOX_NODE_DIMENSION
+                                    + V3c::<usize>::from(
+                                        SECTANT_OFFSET_LUT[sectant] * BOX_NODE_DIMENSION as f32,
+                                    );
+                                level_nodes[flat_projection(
+                                    child_position.x,
+                                    child_position.y,
+                                    child_position.z,
+                                    level_dimension,
+                                )]
+                                .take()
+                            })
+                            .collect::<Vec<_>>();
+                        parent_nodes.push(tree.build_parent_node(children));
+                    }
+                }
+            }
+            level_nodes = parent_nodes;
+            level_dimension = parent_dimension;
+        }
+
+        debug_assert_eq!(1, level_nodes.len());
+        if let Some(root_node) = level_nodes.pop().flatten() {
+            *tree.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = root_node;
+            tree.set_occlusion_below(&mut vec![(Self::ROOT_NODE_KEY as usize, 0)]);
+        }
+        Ok(tree)
+    }
+
+    /// Creates a boxtree with the given size, with each voxel set from the given array
+    /// See @BoxTree::from_fn for details
+    /// * `size` - must be `brick_dimension * (4^x)`, see @BoxTree::new
+    /// * `brick_dimension` - must be one of `(2^x)` and smaller than the size of the boxtree
+    /// * `voxels` - the data for each position in the tree, indexed by `x + (y * size) + (z * size * size)`
+    pub fn from_dense<'a, E>(
+        size: u32,
+        brick_dimension: u32,
+        voxels: &'a [E],
+    ) -> Result<Self, OctreeError>
+    where
+        E: Sync,
+        &'a E: Into<BoxTreeEntry<'a, T>>,
+        T: 'a,
+    {
+        if voxels.len() != (size as usize).pow(3) {
+            return Err(OctreeError::InvalidStructure(
+                format!(
+                    "Expected {} voxels for a boxtree of size {size}, instead of {}",
+                    (size as usize).pow(3),
+                    voxels.len()
+                )
+                .into(),
+            ));
+        }
+        Self::from_fn(size, brick_dimension, |position| {
+            &voxels[flat_projection(
+                position.x as usize,
+                position.y as usize,
+                position.z as usize,
+                size as usize,
+            )]
+        })
+    }
+
+    /// Samples the voxels of the leaf node at the given position
+    /// * `leaf_position` - the lowest corner of the leaf node
+    /// * `brick_dimension` - the size of one brick inside the leaf node
+    /// * `voxel_at` - provides the data to set at each position of the tree
+    fn sample_leaf<'a, E, F>(
+        leaf_position: &V3c<u32>,
+        brick_dimension: u32,
+        voxel_at: &F,
+    ) -> SampledLeaf<'a, T>
+    where
+        E: Into<BoxTreeEntry<'a, T>>,
+        F: Fn(&V3c<u32>) -> E,
+        T: 'a,
+    {
+        let leaf_size = brick_dimension * BOX_NODE_DIMENSION as u32;
+        let mut palette = vec![BoxTreeEntry::Empty];
+        let mut palette_index_for =
+            HashMap::<(Option<&Albedo>, Option<&T>), PaletteIndexValues>::new();
+        let bricks = std::array::from_fn(|sectant| {
+            let brick_position =
+                *leaf_position + V3c::from(SECTANT_OFFSET_LUT[sectant] * leaf_size as f32);
+            let mut voxels = vec![0; brick_dimension.pow(3) as usize];
+            for z in 0..brick_dimension {
+                for y in 0..brick_dimension {
+                    for x in 0..brick_dimension {
+                        let entry: BoxTreeEntry<'a, T> =
+                            voxel_at(&(brick_position + V3c::new(x, y, z))).into();
+                        if entry.is_none() {
+                            continue;
+                        }
+                        voxels[flat_projection(
+                            x as usize,
+                            y as usize,
+                            z as usize,
+                            brick_dimension as usize,
+                        )] = *palette_index_for
+                            .entry((entry.albedo(), entry.data()))
+                            .or_insert_with(|| {
+                                palette.push(entry);
+                                palette.len() as PaletteIndexValues - 1
+                            });
+                    }
+                }
+            }
+
+            let mut brick = BrickData::parted(voxels);
+            match brick.get_homogeneous_data() {
+                Some(0) => BrickData::Empty,
+                Some(voxel) => BrickData::Solid(voxel),
+                None => {
+                    brick.narrow();
+                    brick
+                }
+            }
+        });
+        SampledLeaf { palette, bricks }
+    }
+
+    /// Creates a leaf node from the given sampled bricks
+    /// * `palette_mapping` - The palette index value in the tree for each index in the palette of the bricks
+    /// * Returns with None if the leaf node would be empty
+    fn build_leaf_node(
+        palette_mapping: &[PaletteIndexValues],
+        bricks: [BrickData<PaletteIndexValues>; BOX_NODE_CHILDREN_COUNT],
+        brick_dimension: u32,
+        color_palette: &[Albedo],
+        data_palette: &[T],
+    ) -> Option<NodeData> {
+        let mut occupied_bits = 0;
+        let mut bricks = bricks.map(|brick| match brick {
+            BrickData::Empty => BrickData::Empty,
+            BrickData::Solid(voxel) => BrickData::Solid(palette_mapping[voxel as usize]),
+            BrickData::Parted(voxels) => BrickData::parted(
+                voxels
+                    .iter()
+                    .map(|voxel| palette_mapping[voxel as usize])
+                    .collect(),
+            ),
+            BrickData::Packed(mut brick) => {
+                for voxel in brick.palette.iter_mut() {
+                    *voxel = palette_mapping[*voxel as usize];
+                }
+                BrickData::Packed(brick)
+            }
+        });
+        for (sectant, brick) in bricks.iter().enumerate() {
+            if !matches!(brick, BrickData::Empty) {
+                occupied_bits |= 0x01 << sectant;
+            }
+        }
+        if 0 == occupied_bits {
+            return None;
+        }
+
+        let (_, uniform_brick) =
+            Self::simplify_leaf_bricks(&mut bricks, brick_dimension, color_palette, data_palette);
+        Some(match uniform_brick {
+            Some(BrickData::Solid(voxel)) => NodeData::uniform_solid_node(voxel),
+            Some(uniform_brick) => NodeData::uniform_parted_node(uniform_brick, occupied_bits),
+            None => NodeData {
+                content: NodeContent::Leaf(bricks),
+                children: NodeChildren::NoChildren,
+                mip: BrickData::Empty,
+                occupied_bits,
+                occlusion_bits: 0,
+            },
+        })
+    }
+
+    /// Creates a parent node for the given children, adding the children to the tree if needed
+    /// * `children` - the node for each sectant of the parent, if any
+    /// * Returns with None if the parent node would be empty
+    fn build_parent_node(&mut self, children: Vec<Option<NodeData>>) -> Option<NodeData> {
+        debug_assert_eq!(BOX_NODE_CHILDREN_COUNT, children.len());
+        if children.iter().all(Option::is_none) {
+            return None;
+        }
+
+        // In case all children are the same solid, the parent can be solid too
+        if let Some(Some(NodeData {
+            content: NodeContent::UniformLeaf(BrickData::Solid(voxel)),
+            ..
+        })) = children.first()
+            && children.iter().all(|child| {
+                child.as_ref().is_some_and(|child| {
+                    child.content == NodeContent::UniformLeaf(BrickData::Solid(*voxel))
+                })
+            })
+        {
+            return Some(NodeData::uniform_solid_node(*voxel));
+        }
+
+        let mut occupied_bits = 0;
+        let mut child_keys = [empty_marker::<u32>(); BOX_NODE_CHILDREN_COUNT];
+        for (sectant, child) in children.into_iter().enumerate() {
+            if let Some(child) = child {
+                child_keys[sectant] = self.nodes.push(child) as u32;
+                occupied_bits |= 0x01 << sectant;
+            }
+        }
+        Some(NodeData {
+            content: NodeContent::Internal,
+            children: NodeChildren::Children(child_keys),
+            mip: BrickData::Empty,
+            occupied_bits,
+            occlusion_bits: 0,
+        })
+    }
+
+    /// Sets the occlusion bits of every node next to a full node below the node at the end of the given stack
+    /// * `node_stack` - the access path to the node to start from; the sectant of the last element is overwritten
+    fn set_occlusion_below(&mut self, node_stack: &mut Vec<(usize, u8)>) {
+        let node_key = node_stack.last().unwrap().0;
+        for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+            let child_key = self.nodes.get(node_key).child(sectant);
+            if !self.nodes.key_is_valid(child_key) {
+                continue;
+            }
+
+            node_stack.last_mut().unwrap().1 = sectant;
+            if u64::MAX == self.nodes.get(child_key).occupied_bits {
+                for (direction, side) in [
+                    (V3c::new(-1., 0., 0.), CubeSides::Right),
+                    (V3c::new(1., 0., 0.), CubeSides::Left),
+                    (V3c::new(0., -1., 0.), CubeSides::Top),
+                    (V3c::new(0., 1., 0.), CubeSides::Bottom),
+                    (V3c::new(0., 0., -1.), CubeSides::Front),
+                    (V3c::new(0., 0., 1.), CubeSides::Back),
+                ]
+                .iter()
+                {
+                    if let Some((sibling_node, _sibling_sectant)) =
+                        self.get_sibling_by_stack(*direction, node_stack)
+                    {
+                        self.nodes.get_mut(sibling_node).set_occlusion(*side, true);
+                    }
+                }
+            }
+
+            node_stack.push((child_key, 0));
+            self.set_occlusion_below(node_stack);
+            node_stack.pop();
+        }
+    }
+}
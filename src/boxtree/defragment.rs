@@ -0,0 +1,75 @@
+use crate::{
+    boxtree::{
+        types::{DefragmentOrder, NodeChildren},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::{empty_marker, ObjectPool},
+};
+use std::collections::HashMap;
+
+/// The position of the given sectant along a Z-order curve inside its parent node
+pub(crate) fn morton_code(sectant: usize) -> usize {
+    let x = sectant % BOX_NODE_DIMENSION;
+    let y = (sectant / BOX_NODE_DIMENSION) % BOX_NODE_DIMENSION;
+    let z = sectant / (BOX_NODE_DIMENSION * BOX_NODE_DIMENSION);
+    let mut code = 0;
+    for bit in 0..BOX_NODE_DIMENSION.trailing_zeros() as usize {
+        code |= ((x >> bit) & 0x01) << (bit * 3);
+        code |= ((y >> bit) & 0x01) << (bit * 3 + 1);
+        code |= ((z >> bit) & 0x01) << (bit * 3 + 2);
+    }
+    code
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Rebuilds the node storage without the gaps left behind by removed nodes,
+    /// placing the nodes in the given order. The contents of the tree are not changed,
+    /// but the key of every node might be; GPU views displaying the tree need to be reloaded
+    /// Nodes of paged trees are loaded into memory, and the tree is detached from its storage
+    /// * `order` - The order of the nodes in the rebuilt storage, the root node always stays first
+    /// * Returns with the new key of every node, by its previous key
+    pub fn defragment(&mut self, order: DefragmentOrder) -> HashMap<usize, usize> {
+        let mut sectant_order = (0..BOX_NODE_CHILDREN_COUNT).collect::<Vec<_>>();
+        if DefragmentOrder::Morton == order {
+            sectant_order.sort_by_key(|sectant| morton_code(*sectant));
+        }
+
+        // Assign the new keys in the order the nodes are visited
+        let mut key_map = HashMap::new();
+        let mut visit_order = vec![];
+        let mut node_stack = vec![Self::ROOT_NODE_KEY as usize];
+        while let Some(node_key) = node_stack.pop() {
+            if !self.nodes.key_is_valid(node_key) || key_map.contains_key(&node_key) {
+                continue;
+            }
+            key_map.insert(node_key, visit_order.len());
+            visit_order.push(node_key);
+            if let NodeChildren::Children(children) = &self.nodes.get(node_key).children {
+                // Children are pushed in reverse, so the first one is visited next
+                node_stack.extend(
+                    sectant_order
+                        .iter()
+                        .rev()
+                        .map(|sectant| children[*sectant])
+                        .filter(|child_key| *child_key != empty_marker::<u32>())
+                        .map(|child_key| child_key as usize),
+                );
+            }
+        }
+
+        let mut nodes = ObjectPool::with_capacity(visit_order.len());
+        for node_key in visit_order {
+            let mut node = self.nodes.pop(node_key);
+            if let NodeChildren::Children(children) = &mut node.children {
+                for child_key in children.iter_mut() {
+                    *child_key = key_map
+                        .get(&(*child_key as usize))
+                        .map_or(empty_marker(), |new_key| *new_key as u32);
+                }
+            }
+            nodes.push(node);
+        }
+        self.nodes = nodes;
+        key_map
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     boxtree::{
         types::{
             Albedo, BoxTree, NodeChildren, NodeContent, NodeData, PaletteIndexValues,
-            SerializableVoxelData,
+            PaletteIndexWidth, SerializableVoxelData,
         },
         BrickData, V3c, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
     },
@@ -122,6 +122,24 @@ impl Zero for Albedo {
     }
 }
 
+impl PaletteIndexWidth {
+    /// The maximum number of colors a tree can hold with this width
+    pub fn max_colors(&self) -> usize {
+        match self {
+            PaletteIndexWidth::Narrow => empty_marker::<u16>() as usize,
+            PaletteIndexWidth::Wide => 0x7FFFFFFF,
+        }
+    }
+
+    /// The maximum number of user data values a tree can hold with this width
+    pub fn max_data(&self) -> usize {
+        match self {
+            PaletteIndexWidth::Narrow => empty_marker::<u16>() as usize,
+            PaletteIndexWidth::Wide => empty_marker::<u32>() as usize,
+        }
+    }
+}
+
 //####################################################################################
 //  ███████████     ███████    █████ █████ ███████████ ███████████   ██████████ ██████████
 // ░░███░░░░░███  ███░░░░░███ ░░███ ░░███ ░█░░░███░░░█░░███░░░░░███ ░░███░░░░░█░░███░░░░░█
@@ -163,10 +181,10 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                     &self.voxel_color_palette,
                     &self.voxel_data_palette,
                 ),
-                BrickData::Parted(_brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     if let Some(data) = bricks[target_sectant as usize].get_homogeneous_data() {
                         NodeContent::pix_points_to_empty(
-                            data,
+                            &data,
                             &self.voxel_color_palette,
                             &self.voxel_data_palette,
                         )
@@ -182,7 +200,7 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                     &self.voxel_color_palette,
                     &self.voxel_data_palette,
                 ),
-                BrickData::Parted(brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     let check_start = V3c::from(
                         (SECTANT_OFFSET_LUT[target_sectant as usize] * self.brick_dim as f32)
                             .floor(),
@@ -192,11 +210,16 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                     for x in check_start.x..(check_start.x + check_size) {
                         for y in check_start.y..(check_start.y + check_size) {
                             for z in check_start.z..(check_start.z + check_size) {
-                                if !NodeContent::pix_points_to_empty(
-                                    &brick[flat_projection(x, y, z, self.brick_dim as usize)],
-                                    &self.voxel_color_palette,
-                                    &self.voxel_data_palette,
-                                ) {
+                                if brick
+                                    .voxel(flat_projection(x, y, z, self.brick_dim as usize))
+                                    .is_some_and(|voxel| {
+                                        !NodeContent::pix_points_to_empty(
+                                            &voxel,
+                                            &self.voxel_color_palette,
+                                            &self.voxel_data_palette,
+                                        )
+                                    })
+                                {
                                     return false;
                                 }
                             }
@@ -277,20 +300,19 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                                 .get_mut(node_new_children[sectant] as usize)
                                 .content = NodeContent::UniformLeaf(BrickData::Solid(voxel));
                         }
-                        BrickData::Parted(brick) => {
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
                             // Calculcate the occupancy bitmap for the new leaf child node
                             // As it is a higher resolution, than the current bitmap, it needs to be bruteforced
                             self.nodes
                                 .get_mut(node_new_children[sectant] as usize)
-                                .occupied_bits = bricks[sectant].calculate_occupied_bits(
+                                .occupied_bits = brick.calculate_occupied_bits(
                                 self.brick_dim as usize,
                                 &self.voxel_color_palette,
                                 &self.voxel_data_palette,
                             );
                             self.nodes
                                 .get_mut(node_new_children[sectant] as usize)
-                                .content =
-                                NodeContent::UniformLeaf(BrickData::Parted(brick.clone()));
+                                .content = NodeContent::UniformLeaf(brick);
                         }
                     }
                 }
@@ -312,22 +334,23 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                                 self.nodes.push(NodeData::uniform_solid_node(voxel)) as u32;
                         }
                     }
-                    BrickData::Parted(brick) => {
+                    BrickData::Parted(_) | BrickData::Packed(_) => {
                         // Each brick is mapped to take up one subsection of the current data
-                        let children_bricks = Self::dilute_brick_data(brick, self.brick_dim);
+                        let children_bricks =
+                            Self::dilute_brick_data(brick.into_voxels().unwrap(), self.brick_dim);
                         for (sectant, new_brick) in children_bricks.into_iter().enumerate() {
                             // Push in the new child
                             let child_occupied_bits = BrickData::calculate_brick_occupied_bits(
-                                &new_brick,
+                                |index| new_brick[index],
                                 self.brick_dim as usize,
                                 &self.voxel_color_palette,
                                 &self.voxel_data_palette,
                             );
-                            node_new_children[sectant] =
-                                self.nodes.push(NodeData::uniform_parted_node(
-                                    BrickData::Parted(new_brick),
-                                    child_occupied_bits,
-                                )) as u32;
+                            let mut new_brick = BrickData::parted(new_brick);
+                            new_brick.narrow();
+                            node_new_children[sectant] = self.nodes.push(
+                                NodeData::uniform_parted_node(new_brick, child_occupied_bits),
+                            ) as u32;
                         }
                     }
                 }
@@ -1,11 +1,14 @@
 use crate::{
     boxtree::{
-        types::{BoxTreeNodeAccessStack, NodeContent},
+        types::{BoxTreeEntry, BoxTreeNodeAccessStack, BrickData, NodeContent, PaletteIndexValues},
         Albedo, BoxTree, MIPResamplingMethods, VoxelData, BOX_NODE_CHILDREN_COUNT,
         BOX_NODE_DIMENSION,
     },
     spatial::{
-        math::vector::{V3c, V3cf32},
+        math::{
+            flat_projection,
+            vector::{V3c, V3cf32},
+        },
         step_sectant, Cube,
     },
 };
@@ -60,11 +63,15 @@ pub(crate) fn execute_for_relevant_sectants<F: FnMut(V3c<u32>, V3c<u32>, u8, &Cu
     let update_size = V3c::from(*position_) + V3c::unit(update_size as f32) - position;
     let cell_size = node_bounds.size / BOX_NODE_DIMENSION as f32;
 
-    let mut shifted_position = position;
+    // Step through the cells from the one containing the position,
+    // so stepping from an unaligned position does not skip the last cell
+    let start_position = node_bounds.min_position
+        + ((position - node_bounds.min_position) / cell_size).floor() * cell_size;
+    let mut shifted_position = start_position;
     while shifted_position.x <= (position.x + update_size.x) {
-        shifted_position.y = position.y;
+        shifted_position.y = start_position.y;
         while shifted_position.y <= (position.y + update_size.y) {
-            shifted_position.z = position.z;
+            shifted_position.z = start_position.z;
             while shifted_position.z <= (position.z + update_size.z) {
                 if !node_bounds.contains(&shifted_position) {
                     shifted_position.z += cell_size;
@@ -301,8 +308,9 @@ impl<T: VoxelData> BoxTree<T> {
             "Expected position to be inside given bounds"
         );
 
+        // Only the content of the resulting node is needed, the rest are only passed through
         loop {
-            match self.nodes.get(current_node_key).content {
+            match self.nodes.get_outline(current_node_key).content {
                 NodeContent::Nothing | NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
                     // Check if uniform leaf has matching occupied bits
                     return Some(current_node_key);
@@ -312,7 +320,7 @@ impl<T: VoxelData> BoxTree<T> {
                     let child_sectant_at_position = node_bounds.sectant_for(position);
                     let child_at_position = self
                         .nodes
-                        .get(current_node_key)
+                        .get_outline(current_node_key)
                         .child(child_sectant_at_position);
 
                     // There is a valid child at the given position inside the node, recurse into it
@@ -321,14 +329,14 @@ impl<T: VoxelData> BoxTree<T> {
                             0,
                             self
                         .nodes
-                        .get(current_node_key).occupied_bits & (0x01 << child_sectant_at_position),
+                        .get_outline(current_node_key).occupied_bits & (0x01 << child_sectant_at_position),
                             "Node[{:?}] under {:?} \n has a child in sectant[{:?}](global position: {:?}), which is incompatible with the occupancy bitmap: {:#10X}; \n child node: {:?}; child node children: {:?};",
                             current_node_key,
                             node_bounds,
                             child_sectant_at_position,
                             position,
-                            self.nodes.get(current_node_key).occupied_bits,
-                            self.nodes.get(child_at_position),
+                            self.nodes.get_outline(current_node_key).occupied_bits,
+                            self.nodes.get_outline(child_at_position),
                             child_at_position,
                         );
                         current_node_key = child_at_position;
@@ -558,3 +566,330 @@ impl MIPResamplingFunction for MIPResamplingMethods {
         }
     }
 }
+
+/// A part of the tree where voxels are stored together, see @BoxTree::bricks
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxTreeBrick<'a, T: VoxelData> {
+    /// The lowest corner of the brick
+    pub position: V3c<u32>,
+
+    /// The extent of the brick in each dimension
+    pub size: u32,
+
+    /// The data of every voxel inside the brick, if they are all the same
+    pub solid: Option<BoxTreeEntry<'a, T>>,
+}
+
+/// The content of a brick found while traversing the tree
+pub(crate) enum BrickSource {
+    /// Every voxel inside the brick is the same
+    Solid(PaletteIndexValues),
+
+    /// The brick of a leaf node at the given sectant, or the brick of a uniform leaf node if there's no sectant
+    Parted {
+        node_key: usize,
+        sectant: Option<u8>,
+    },
+}
+
+/// Depth first traversal of the occupied bricks inside an area of the tree
+pub(crate) struct BrickTraversal<'a, T: VoxelData> {
+    tree: &'a BoxTree<T>,
+
+    /// The lowest corner of the area to traverse
+    min: V3c<u32>,
+
+    /// The highest corner of the area to traverse (exclusive)
+    max: V3c<u32>,
+
+    /// The nodes under traversal, with their bounds and the next sectant to visit inside them
+    node_stack: Vec<(usize, Cube, u8)>,
+}
+
+impl<'a, T: VoxelData> BrickTraversal<'a, T> {
+    pub(crate) fn new(tree: &'a BoxTree<T>, min: V3c<u32>, max: V3c<u32>) -> Self {
+        Self {
+            tree,
+            min,
+            max,
+            node_stack: vec![(
+                BoxTree::<T>::ROOT_NODE_KEY as usize,
+                Cube::root_bounds(tree.boxtree_size as f32),
+                0,
+            )],
+        }
+    }
+
+    /// Returns with true if the given bounds overlap with the traversed area
+    fn overlaps(&self, bounds: &Cube) -> bool {
+        let bounds_min: V3c<u32> = bounds.min_position.into();
+        let bounds_max = bounds_min + V3c::unit(bounds.size as u32);
+        bounds_min.x < self.max.x
+            && bounds_min.y < self.max.y
+            && bounds_min.z < self.max.z
+            && self.min.x < bounds_max.x
+            && self.min.y < bounds_max.y
+            && self.min.z < bounds_max.z
+    }
+
+    /// Returns with true if the given voxel doesn't contain any data
+    fn is_empty(&self, voxel: &PaletteIndexValues) -> bool {
+        NodeContent::pix_points_to_empty(
+            voxel,
+            &self.tree.voxel_color_palette,
+            &self.tree.voxel_data_palette,
+        )
+    }
+
+    /// Provides the bounds and content of the next occupied brick, skipping empty nodes
+    pub(crate) fn next_brick(&mut self) -> Option<(Cube, BrickSource)> {
+        let tree = self.tree;
+        while let Some((node_key, node_bounds, next_sectant)) = self.node_stack.last().cloned() {
+            let node = tree.nodes.get(node_key);
+            match &node.content {
+                NodeContent::Nothing => {
+                    self.node_stack.pop();
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    self.node_stack.pop();
+                    if !self.overlaps(&node_bounds) {
+                        continue;
+                    }
+                    match brick {
+                        BrickData::Empty => {}
+                        BrickData::Solid(voxel) => {
+                            if !self.is_empty(voxel) {
+                                return Some((node_bounds, BrickSource::Solid(*voxel)));
+                            }
+                        }
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
+                            return Some((
+                                node_bounds,
+                                BrickSource::Parted {
+                                    node_key,
+                                    sectant: None,
+                                },
+                            ));
+                        }
+                    }
+                }
+                NodeContent::Leaf(_) | NodeContent::Internal => {
+                    if BOX_NODE_CHILDREN_COUNT <= next_sectant as usize {
+                        self.node_stack.pop();
+                        continue;
+                    }
+                    self.node_stack.last_mut().unwrap().2 += 1;
+
+                    // Skip empty children
+                    let child_bounds = node_bounds.child_bounds_for(next_sectant);
+                    if 0 == (node.occupied_bits & (0x01 << next_sectant))
+                        || !self.overlaps(&child_bounds)
+                    {
+                        continue;
+                    }
+
+                    if let NodeContent::Leaf(bricks) = &node.content {
+                        match &bricks[next_sectant as usize] {
+                            BrickData::Empty => {}
+                            BrickData::Solid(voxel) => {
+                                if !self.is_empty(voxel) {
+                                    return Some((child_bounds, BrickSource::Solid(*voxel)));
+                                }
+                            }
+                            BrickData::Parted(_) | BrickData::Packed(_) => {
+                                return Some((
+                                    child_bounds,
+                                    BrickSource::Parted {
+                                        node_key,
+                                        sectant: Some(next_sectant),
+                                    },
+                                ));
+                            }
+                        }
+                    } else {
+                        let child_key = node.child(next_sectant);
+                        if tree.nodes.key_is_valid(child_key) {
+                            self.node_stack.push((child_key, child_bounds, 0));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the occupied bricks of a tree, see @BoxTree::bricks
+pub struct BoxTreeBricks<'a, T: VoxelData> {
+    traversal: BrickTraversal<'a, T>,
+}
+
+impl<'a, T: VoxelData> Iterator for BoxTreeBricks<'a, T> {
+    type Item = BoxTreeBrick<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.traversal.tree;
+        let (bounds, source) = self.traversal.next_brick()?;
+        Some(BoxTreeBrick {
+            position: bounds.min_position.into(),
+            size: bounds.size as u32,
+            solid: match source {
+                BrickSource::Solid(voxel) => Some(NodeContent::pix_get_ref(
+                    &voxel,
+                    &tree.voxel_color_palette,
+                    &tree.voxel_data_palette,
+                )),
+                BrickSource::Parted { .. } => None,
+            },
+        })
+    }
+}
+
+/// Iterator over the occupied voxels of a tree, see @BoxTree::voxels
+pub struct BoxTreeVoxels<'a, T: VoxelData> {
+    traversal: BrickTraversal<'a, T>,
+
+    /// Regions of the same voxel still to be iterated: (position, size, voxel)
+    runs: Vec<(V3c<u32>, u32, PaletteIndexValues)>,
+
+    /// The region currently iterated: (start, end(exclusive), data)
+    current_run: Option<(V3c<u32>, V3c<u32>, BoxTreeEntry<'a, T>)>,
+
+    /// The next position to provide inside the current region
+    cursor: V3c<u32>,
+}
+
+impl<'a, T: VoxelData> BoxTreeVoxels<'a, T> {
+    /// Collects the regions of the same voxel inside the given brick into @runs
+    fn collect_runs(&mut self, bounds: &Cube, source: BrickSource) {
+        let position: V3c<u32> = bounds.min_position.into();
+        let size = bounds.size as u32;
+        let (node_key, sectant) = match source {
+            BrickSource::Solid(voxel) => {
+                self.runs.push((position, size, voxel));
+                return;
+            }
+            BrickSource::Parted { node_key, sectant } => (node_key, sectant),
+        };
+
+        let tree = self.traversal.tree;
+        let node = tree.nodes.get(node_key);
+        let brick = match (&node.content, sectant) {
+            (NodeContent::Leaf(bricks), Some(sectant)) => &bricks[sectant as usize],
+            (NodeContent::UniformLeaf(brick), None) => brick,
+            _ => unreachable!("Expected brick source to point to a brick"),
+        };
+        let voxels = brick
+            .voxels()
+            .expect("Expected brick source to contain separate voxels");
+
+        // One voxel inside the brick might cover multiple voxels of the tree
+        let brick_dim = tree.brick_dim;
+        let cell_size = (size / brick_dim).max(1);
+        for z in (0..brick_dim).rev() {
+            for y in (0..brick_dim).rev() {
+                for x in (0..brick_dim).rev() {
+                    self.runs.push((
+                        position + V3c::new(x, y, z) * cell_size,
+                        cell_size,
+                        voxels[flat_projection(
+                            x as usize,
+                            y as usize,
+                            z as usize,
+                            brick_dim as usize,
+                        )],
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: VoxelData> Iterator for BoxTreeVoxels<'a, T> {
+    type Item = (V3c<u32>, BoxTreeEntry<'a, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((start, end, entry)) = &self.current_run {
+                if self.cursor.z < end.z {
+                    let position = self.cursor;
+                    self.cursor.x += 1;
+                    if self.cursor.x == end.x {
+                        self.cursor.x = start.x;
+                        self.cursor.y += 1;
+                        if self.cursor.y == end.y {
+                            self.cursor.y = start.y;
+                            self.cursor.z += 1;
+                        }
+                    }
+                    return Some((position, entry.clone()));
+                }
+                self.current_run = None;
+            }
+
+            if let Some((position, size, voxel)) = self.runs.pop() {
+                if self.traversal.is_empty(&voxel) {
+                    continue;
+                }
+
+                // Only the part of the run inside the iterated area is provided
+                let (min, max) = (self.traversal.min, self.traversal.max);
+                let start = V3c::new(
+                    position.x.max(min.x),
+                    position.y.max(min.y),
+                    position.z.max(min.z),
+                );
+                let end = V3c::new(
+                    (position.x + size).min(max.x),
+                    (position.y + size).min(max.y),
+                    (position.z + size).min(max.z),
+                );
+                if start.x >= end.x || start.y >= end.y || start.z >= end.z {
+                    continue;
+                }
+
+                let tree = self.traversal.tree;
+                let entry = NodeContent::pix_get_ref(
+                    &voxel,
+                    &tree.voxel_color_palette,
+                    &tree.voxel_data_palette,
+                );
+                self.current_run = Some((start, end, entry));
+                self.cursor = start;
+                continue;
+            }
+
+            let (bounds, source) = self.traversal.next_brick()?;
+            self.collect_runs(&bounds, source);
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Provides an iterator over every occupied voxel inside the tree, along with their data
+    /// Empty parts of the tree are skipped, regions of the same data are provided without lookups
+    pub fn voxels(&self) -> BoxTreeVoxels<'_, T> {
+        self.voxels_in(&V3c::unit(0), &V3c::unit(self.boxtree_size))
+    }
+
+    /// Provides an iterator over every occupied voxel inside the given area, along with their data
+    /// * `min` - the lowest corner of the area
+    /// * `max` - the highest corner of the area (exclusive)
+    pub fn voxels_in(&self, min: &V3c<u32>, max: &V3c<u32>) -> BoxTreeVoxels<'_, T> {
+        BoxTreeVoxels {
+            traversal: BrickTraversal::new(self, *min, *max),
+            runs: vec![],
+            current_run: None,
+            cursor: V3c::unit(0),
+        }
+    }
+
+    /// Provides an iterator over the occupied bricks inside the tree
+    /// Bricks where every voxel is the same are provided with their data,
+    /// the voxels of other bricks can be iterated through @voxels_in
+    pub fn bricks(&self) -> BoxTreeBricks<'_, T> {
+        BoxTreeBricks {
+            traversal: BrickTraversal::new(self, V3c::unit(0), V3c::unit(self.boxtree_size)),
+        }
+    }
+}
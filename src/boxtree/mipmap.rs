@@ -3,8 +3,8 @@ use crate::{
     boxtree::{
         iterate::MIPResamplingFunction,
         types::{
-            BoxTreeEntry, BrickData, MIPMapStrategy, MIPResamplingMethods, NodeChildren,
-            NodeContent, PaletteIndexValues, StrategyUpdater,
+            BoxTreeChangeKind, BoxTreeEntry, BrickData, MIPMapStrategy, MIPResamplingMethods,
+            NodeChildren, NodeContent, PaletteIndexValues, StrategyUpdater,
         },
         Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
     },
@@ -43,6 +43,24 @@ impl<T: VoxelData> BoxTree<T> {
         if !self.mip_map_strategy.enabled {
             return;
         }
+
+        // Leaf nodes subdivided from uniform leaves have no MIP yet, so it is sampled as a whole
+        let node = self.nodes.get(node_key);
+        let mip_missing =
+            matches!(node.content, NodeContent::Leaf(_)) && matches!(node.mip, BrickData::Empty);
+        std::mem::drop(node);
+        if mip_missing {
+            StrategyUpdater(self).recalculate_mip(node_key, node_bounds);
+        } else {
+            self.resample_mip_at(node_key, node_bounds, position);
+        }
+    }
+
+    /// Samples the MIP voxel of the given node at the given position. It expects that MIPS of child nodes are up-to-date.
+    /// * `node_key` - The node to update the MIP for
+    /// * `node_bounds` - The bounds of the target node
+    /// * `position` - The global position in alignment with node bounds
+    fn resample_mip_at(&mut self, node_key: usize, node_bounds: &Cube, position: &V3c<u32>) {
         debug_assert_eq!(
             0,
             node_bounds.size as u32 % self.brick_dim,
@@ -64,7 +82,7 @@ impl<T: VoxelData> BoxTree<T> {
             };
 
         // determine the sampling range
-        let mut node = self.nodes.get_mut(node_key);
+        let node = self.nodes.get_mut(node_key);
         let (sample_start, sample_size) = match &node.content {
             NodeContent::Nothing => {
                 debug_assert!(
@@ -185,7 +203,6 @@ impl<T: VoxelData> BoxTree<T> {
                 (sample_start, sample_size)
             }
         };
-        std::mem::drop(node);
 
         let sampled_color = match self.nodes.get(node_key).content {
             NodeContent::Nothing | NodeContent::UniformLeaf(_) => None,
@@ -249,7 +266,7 @@ impl<T: VoxelData> BoxTree<T> {
                             )
                             .albedo()
                             .copied(),
-                            BrickData::Parted(brick) => {
+                            brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                                 let mip_index = flat_projection(
                                     pos_in_child_mip.x as usize,
                                     pos_in_child_mip.y as usize,
@@ -257,7 +274,7 @@ impl<T: VoxelData> BoxTree<T> {
                                     self.brick_dim as usize,
                                 );
                                 NodeContent::pix_get_ref(
-                                    &brick[mip_index],
+                                    &brick.voxel(mip_index).unwrap(),
                                     &self.voxel_color_palette,
                                     &self.voxel_data_palette,
                                 )
@@ -284,7 +301,7 @@ impl<T: VoxelData> BoxTree<T> {
                     if color.distance_from(&self.voxel_color_palette[palette_index])
                         < color_distance_threshold
                     {
-                        similar_color = Some(palette_index as u16);
+                        similar_color = Some(palette_index as u32);
                         break;
                     }
                 }
@@ -307,8 +324,11 @@ impl<T: VoxelData> BoxTree<T> {
             None
         };
 
-        if let Some(mip_entry) = mip_entry {
-            // Set MIP entry
+        // Set MIP entry, or erase it if there's nothing to sample from anymore
+        let mip_entry = mip_entry.unwrap_or(empty_marker());
+        if mip_entry != empty_marker::<PaletteIndexValues>()
+            || !matches!(self.nodes.get(node_key).mip, BrickData::Empty)
+        {
             let pos_in_mip = matrix_index_for(node_bounds, position, self.brick_dim);
             let flat_pos_in_mip = flat_projection(
                 pos_in_mip.x,
@@ -322,15 +342,17 @@ impl<T: VoxelData> BoxTree<T> {
                     let mut new_brick_data =
                         vec![empty_marker::<PaletteIndexValues>(); self.brick_dim.pow(3) as usize];
                     new_brick_data[flat_pos_in_mip] = mip_entry;
-                    *mip = BrickData::Parted(new_brick_data);
+                    *mip = BrickData::parted(new_brick_data);
+                    mip.narrow();
                 }
                 BrickData::Solid(voxel) => {
                     let mut new_brick_data = vec![*voxel; self.brick_dim.pow(3) as usize];
                     new_brick_data[flat_pos_in_mip] = mip_entry;
-                    *mip = BrickData::Parted(new_brick_data);
+                    *mip = BrickData::parted(new_brick_data);
+                    mip.narrow();
                 }
-                BrickData::Parted(brick) => {
-                    brick[flat_pos_in_mip] = mip_entry;
+                BrickData::Parted(_) | BrickData::Packed(_) => {
+                    mip.set_voxel(flat_pos_in_mip, mip_entry);
                 }
             }
         }
@@ -587,6 +609,14 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
                 }
             }
         }
+
+        if self.0.mip_map_strategy.enabled {
+            self.0.signal_change(
+                BoxTreeChangeKind::MIP,
+                &V3c::unit(0),
+                &V3c::unit(self.0.boxtree_size),
+            );
+        }
     }
 
     /// Enables or disables mipmap feature for albedo values
@@ -621,7 +651,7 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
                         + (V3c::<f32>::new(x as f32, y as f32, z as f32) * node_bounds.size
                             / tree.brick_dim as f32)
                             .round();
-                    tree.update_mip(node_key, node_bounds, &V3c::from(pos));
+                    tree.resample_mip_at(node_key, node_bounds, &V3c::from(pos));
                 }
             }
         }
@@ -651,7 +681,7 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
                 &tree.voxel_color_palette,
                 &tree.voxel_data_palette,
             ),
-            BrickData::Parted(brick) => {
+            brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                 let flat_index = flat_projection(
                     position.x as usize,
                     position.y as usize,
@@ -659,7 +689,7 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
                     tree.brick_dim as usize,
                 );
                 NodeContent::pix_get_ref(
-                    &brick[flat_index],
+                    &brick.voxel(flat_index).unwrap(),
                     &tree.voxel_color_palette,
                     &tree.voxel_data_palette,
                 )
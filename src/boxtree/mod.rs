@@ -1,7 +1,12 @@
+mod build;
+mod defragment;
 mod detail;
 pub(crate) mod iterate;
 pub(crate) mod mipmap;
 mod node;
+mod snapshot;
+mod stats;
+mod validate;
 
 /// The inner structure of the container
 pub mod types;
@@ -13,10 +18,17 @@ pub mod update;
 mod tests;
 
 pub use crate::spatial::math::vector::{V3c, V3cf32};
+pub use iterate::{BoxTreeBrick, BoxTreeBricks, BoxTreeVoxels};
+pub use stats::{BoxTreeLevelStats, BoxTreeStats};
 pub use types::{
-    Albedo, BoxTree, BoxTreeEntry, MIPMapStrategy, MIPResamplingMethods, StrategyUpdater, VoxelData,
+    Albedo, BoxTree, BoxTreeAxis, BoxTreeChange, BoxTreeChangeKind, BoxTreeEdit, BoxTreeEntry,
+    BoxTreeSnapshot, DefragmentOrder, MIPMapStrategy, MIPResamplingMethods, PaletteIndexWidth,
+    PasteMode, SharedBoxTree, StrategyUpdater, SubscriptionHandle, ValidationError, VoxelData,
 };
 
+#[cfg(feature = "bytecode")]
+pub use types::SerializationError;
+
 use crate::{
     boxtree::types::{BrickData, NodeContent, NodeData, OctreeError, PaletteIndexValues},
     object_pool::{empty_marker, ObjectPool},
@@ -27,13 +39,16 @@ use crate::{
 };
 use std::{collections::HashMap, path::Path};
 
+#[cfg(feature = "bytecode")]
+use crate::convert::bytecode::Compressed;
+
 #[cfg(feature = "bytecode")]
 use bendy::{decoding::FromBencode, encoding::ToBencode};
 
 #[cfg(feature = "bytecode")]
 use std::{
     fs::File,
-    io::{Error, Read, Write},
+    io::{Read, Write},
 };
 
 //####################################################################################
@@ -145,41 +160,95 @@ macro_rules! make_tree {
 
 impl<T: VoxelData> BoxTree<T> {
     /// converts the data structure to a byte representation
+    /// Panics if nodes of a tree loaded with `load_paged` could not be loaded, see `take_load_errors`
     #[cfg(feature = "bytecode")]
     pub fn to_bytes(&self) -> Vec<u8> {
-        self.to_bencode()
-            .expect("Failed to serialize Boxtree to Bytes")
+        let bytes = self
+            .to_bencode()
+            .expect("Failed to serialize Boxtree to Bytes");
+        self.check_nodes_loaded()
+            .expect("Failed to serialize Boxtree to Bytes");
+        bytes
+    }
+
+    /// converts the data structure to a byte representation, with the bricks compressed:
+    /// each brick is stored with its own palette, bit packed and run-length encoded
+    /// * The result can be parsed the same way as the uncompressed one, i.e. with `try_from_bytes`
+    /// * Panics if nodes of a tree loaded with `load_paged` could not be loaded, see `take_load_errors`
+    #[cfg(feature = "bytecode")]
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        let bytes = Compressed(self)
+            .to_bencode()
+            .expect("Failed to serialize Boxtree to Bytes");
+        self.check_nodes_loaded()
+            .expect("Failed to serialize Boxtree to Bytes");
+        bytes
     }
 
     /// parses the data structure from a byte string
+    /// Panics if the bytes can not be parsed, see `try_from_bytes` for the fallible variant
     #[cfg(feature = "bytecode")]
     pub fn from_bytes(bytes: Vec<u8>) -> Self {
-        Self::from_bencode(&bytes).expect("Failed to de-serialize Boxtree from bytes")
+        Self::try_from_bytes(&bytes).expect("Failed to de-serialize Boxtree from bytes")
     }
 
+    /// parses the data structure from a byte string
+    /// * Returns with an error if the bytes were created by an incompatible library version,
+    ///   or if they do not describe a consistent boxtree
     #[cfg(feature = "bytecode")]
-    pub fn version<P: AsRef<Path>>(path: P) -> Result<crate::Version, Error> {
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let tree_version = Self::parse_version(bytes)?;
+        let library_version = crate::version();
+        if !library_version.compatible(&tree_version) {
+            return Err(SerializationError::VersionMismatch {
+                library: library_version,
+                tree: tree_version,
+            });
+        }
+        let tree = Self::from_bencode(bytes)?;
+        tree.check_loaded_structure()?;
+        Ok(tree)
+    }
+
+    /// Provides the library version the boxtree stored at the given path was created with
+    #[cfg(feature = "bytecode")]
+    pub fn version<P: AsRef<Path>>(path: P) -> Result<crate::Version, SerializationError> {
         let mut file = File::open(path)?;
         let mut bytes = vec![0; Self::bytes_until_version()];
         file.read_exact(&mut bytes)?;
-        Ok(Self::parse_version(&bytes).expect("Expected to be able to parse Boxtree vrsion"))
+        Ok(Self::parse_version(&bytes)?)
     }
 
     /// saves the data structure to the given file path
     #[cfg(feature = "bytecode")]
-    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializationError> {
+        let bytes = self.to_bencode()?;
+        self.check_nodes_loaded()?;
+        let mut file = File::create(path)?;
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// saves the data structure to the given file path, with compressed bricks
+    /// see `to_bytes_compressed`; The file can be loaded with `load`
+    #[cfg(feature = "bytecode")]
+    pub fn save_compressed<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializationError> {
+        let bytes = Compressed(self).to_bencode()?;
+        self.check_nodes_loaded()?;
         let mut file = File::create(path)?;
-        file.write_all(&self.to_bytes())?;
+        file.write_all(&bytes)?;
         Ok(())
     }
 
     /// loads the data structure from the given file path
+    /// Subscriptions are not stored with the tree, they can be registered again on the loaded one, see @subscribe
+    /// The edit journal is not stored either, the loaded tree starts with no history, see @switch_journal
     #[cfg(feature = "bytecode")]
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SerializationError> {
         let mut file = File::open(path)?;
         let mut bytes = Vec::new();
         file.read_to_end(&mut bytes)?;
-        Ok(Self::from_bytes(bytes))
+        Self::try_from_bytes(&bytes)
     }
 
     /// creates an boxtree with the given size
@@ -206,6 +275,7 @@ impl<T: VoxelData> BoxTree<T> {
         assert!(root_node_key == 0);
         Ok(Self {
             auto_simplify: true,
+            compact_palettes_on_save: false,
             boxtree_size: size,
             brick_dim: brick_dimension,
             nodes,
@@ -213,8 +283,12 @@ impl<T: VoxelData> BoxTree<T> {
             voxel_data_palette: vec![],
             map_to_color_index_in_palette: HashMap::new(),
             map_to_data_index_in_palette: HashMap::new(),
+            palette_index_width: PaletteIndexWidth::Narrow,
+            palette_revision: 0,
             mip_map_strategy: MIPMapStrategy::default(),
             update_triggers: vec![],
+            subscriptions: vec![],
+            journal: None,
         })
     }
 
@@ -271,9 +345,10 @@ impl<T: VoxelData> BoxTree<T> {
                 let child_sectant_at_position = current_bounds.sectant_for(&position_);
 
                 // If the child exists, query it for the voxel
-                match &bricks[child_sectant_at_position as usize] {
+                let brick = &bricks[child_sectant_at_position as usize];
+                match brick {
                     BrickData::Empty => empty_marker(),
-                    BrickData::Parted(brick) => {
+                    BrickData::Parted(_) | BrickData::Packed(_) => {
                         current_bounds =
                             Cube::child_bounds_for(&current_bounds, child_sectant_at_position);
                         let mat_index = matrix_index_for(&current_bounds, position, self.brick_dim);
@@ -283,12 +358,13 @@ impl<T: VoxelData> BoxTree<T> {
                             mat_index.z as usize,
                             self.brick_dim as usize,
                         );
+                        let voxel = brick.voxel(mat_index).unwrap();
                         if !NodeContent::pix_points_to_empty(
-                            &brick[mat_index],
+                            &voxel,
                             &self.voxel_color_palette,
                             &self.voxel_data_palette,
                         ) {
-                            return brick[mat_index];
+                            return voxel;
                         }
                         empty_marker()
                     }
@@ -297,7 +373,7 @@ impl<T: VoxelData> BoxTree<T> {
             }
             NodeContent::UniformLeaf(brick) => match brick {
                 BrickData::Empty => empty_marker(),
-                BrickData::Parted(brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     let mat_index = matrix_index_for(&current_bounds, position, self.brick_dim);
                     let mat_index = flat_projection(
                         mat_index.x as usize,
@@ -305,7 +381,7 @@ impl<T: VoxelData> BoxTree<T> {
                         mat_index.z as usize,
                         self.brick_dim as usize,
                     );
-                    brick[mat_index]
+                    brick.voxel(mat_index).unwrap()
                 }
                 BrickData::Solid(voxel) => *voxel,
             },
@@ -2,7 +2,8 @@ use crate::{
     boxtree::{
         empty_marker,
         types::{
-            Albedo, BrickData, NodeChildren, NodeContent, NodeData, PaletteIndexValues, VoxelData,
+            Albedo, BrickData, CompactVoxel, NodeChildren, NodeContent, NodeData, PackedBrick,
+            PaletteIndexValues, PartedBrick, VoxelData,
         },
         BoxTreeEntry, V3c, BOX_NODE_CHILDREN_COUNT,
     },
@@ -11,7 +12,7 @@ use crate::{
         CubeSides,
     },
 };
-use std::matches;
+use std::{borrow::Cow, collections::HashMap, hash::Hash, matches};
 
 //####################################################################################
 //  ███████████  ███████████   █████   █████████  █████   ████
@@ -31,10 +32,380 @@ use std::matches;
 //  ██████████   █████   █████    █████    █████   █████
 // ░░░░░░░░░░   ░░░░░   ░░░░░    ░░░░░    ░░░░░   ░░░░░
 //####################################################################################
+impl CompactVoxel for PaletteIndexValues {
+    /// Palette indices are compacted into the layout of @PaletteIndexWidth::Narrow
+    fn compacted(&self) -> Option<u32> {
+        let fits = |is_some: bool, palette_index: usize| {
+            !is_some || palette_index < empty_marker::<u16>() as usize
+        };
+        if fits(
+            NodeContent::pix_color_is_some(self),
+            NodeContent::pix_color_index(self),
+        ) && fits(
+            NodeContent::pix_data_is_some(self),
+            NodeContent::pix_data_index(self),
+        ) {
+            Some(NodeContent::pix_narrowed(self) as u32)
+        } else {
+            None
+        }
+    }
+
+    fn expanded(compact: u32) -> Self {
+        NodeContent::pix_widened(&(compact as PaletteIndexValues))
+    }
+}
+
+impl CompactVoxel for u32 {
+    fn compacted(&self) -> Option<u32> {
+        Some(*self)
+    }
+
+    fn expanded(compact: u32) -> Self {
+        compact
+    }
+}
+
+impl CompactVoxel for Albedo {
+    fn compacted(&self) -> Option<u32> {
+        Some(u32::from_be_bytes([self.r, self.g, self.b, self.a]))
+    }
+
+    fn expanded(compact: u32) -> Self {
+        compact.into()
+    }
+}
+
+impl<T: Clone + CompactVoxel> PartedBrick<T> {
+    /// Stores the given voxels in their compact form, if every one of them has one
+    pub(crate) fn new(voxels: Vec<T>) -> Self {
+        match voxels
+            .iter()
+            .map(T::compacted)
+            .collect::<Option<Vec<u32>>>()
+        {
+            Some(compact) => PartedBrick::Compact(compact),
+            None => PartedBrick::Full(voxels),
+        }
+    }
+
+    /// The number of voxels inside the brick
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            PartedBrick::Compact(voxels) => voxels.len(),
+            PartedBrick::Full(voxels) => voxels.len(),
+        }
+    }
+
+    /// The voxel at the given flat index, if it is inside the brick
+    pub(crate) fn get(&self, index: usize) -> Option<T> {
+        match self {
+            PartedBrick::Compact(voxels) => voxels.get(index).map(|voxel| T::expanded(*voxel)),
+            PartedBrick::Full(voxels) => voxels.get(index).cloned(),
+        }
+    }
+
+    /// Iterates over the voxels of the brick in flat index order
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = T> + '_ {
+        (0..self.len()).map(|index| self.get(index).unwrap())
+    }
+
+    /// Updates the voxel at the given flat index;
+    /// Compact voxels are restored to their original form if the new voxel has no compact form
+    pub(crate) fn set(&mut self, index: usize, voxel: T) {
+        match self {
+            PartedBrick::Compact(voxels) => match voxel.compacted() {
+                Some(compact) => voxels[index] = compact,
+                None => {
+                    let mut voxels = self.iter().collect::<Vec<_>>();
+                    voxels[index] = voxel;
+                    *self = PartedBrick::Full(voxels);
+                }
+            },
+            PartedBrick::Full(voxels) => voxels[index] = voxel,
+        }
+    }
+
+    /// Provides the voxels as a plain array
+    pub(crate) fn as_slice(&self) -> Cow<'_, [T]> {
+        match self {
+            PartedBrick::Compact(_) => Cow::Owned(self.iter().collect()),
+            PartedBrick::Full(voxels) => Cow::Borrowed(voxels),
+        }
+    }
+
+    /// Takes the voxels as a plain array
+    pub(crate) fn into_vec(self) -> Vec<T> {
+        match self {
+            PartedBrick::Compact(voxels) => voxels.into_iter().map(T::expanded).collect(),
+            PartedBrick::Full(voxels) => voxels,
+        }
+    }
+
+    /// The number of bytes the voxels of the brick take up
+    pub(crate) fn memory_usage(&self) -> usize {
+        match self {
+            PartedBrick::Compact(voxels) => std::mem::size_of_val(voxels.as_slice()),
+            PartedBrick::Full(voxels) => std::mem::size_of_val(voxels.as_slice()),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> PackedBrick<T> {
+    /// The possible number of bits one palette index might take up
+    const INDEX_BITS: [u32; 5] = [1, 2, 4, 8, 16];
+
+    /// The smallest index width able to address the given number of palette entries
+    fn index_bits_for(palette_size: usize) -> Option<u32> {
+        Self::INDEX_BITS
+            .iter()
+            .copied()
+            .find(|bits| palette_size <= 1 << bits)
+    }
+
+    /// Packs the given palette indices with the given index width
+    /// Indices never span across two words, as each possible index width divides 64
+    fn pack_indices(palette_indices: impl Iterator<Item = usize>, index_bits: u32) -> Vec<u64> {
+        let indices_per_word = (u64::BITS / index_bits) as usize;
+        let mut indices = Vec::new();
+        for (voxel_index, palette_index) in palette_indices.enumerate() {
+            if 0 == voxel_index % indices_per_word {
+                indices.push(0);
+            }
+            *indices.last_mut().unwrap() |=
+                (palette_index as u64) << ((voxel_index % indices_per_word) as u32 * index_bits);
+        }
+        indices
+    }
+
+    /// The number of voxels inside the brick
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The index inside the palette of the voxel at the given flat index
+    fn palette_index(&self, index: usize) -> usize {
+        let indices_per_word = (u64::BITS / self.index_bits) as usize;
+        let shift = (index % indices_per_word) as u32 * self.index_bits;
+        ((self.indices[index / indices_per_word] >> shift) & ((1 << self.index_bits) - 1)) as usize
+    }
+
+    /// Sets the index inside the palette of the voxel at the given flat index
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let indices_per_word = (u64::BITS / self.index_bits) as usize;
+        let shift = (index % indices_per_word) as u32 * self.index_bits;
+        let word = &mut self.indices[index / indices_per_word];
+        *word &= !(((1 << self.index_bits) - 1) << shift);
+        *word |= (palette_index as u64) << shift;
+    }
+
+    /// Provides a reference to the voxel at the given flat index
+    pub(crate) fn get(&self, index: usize) -> &T {
+        &self.palette[self.palette_index(index)]
+    }
+
+    /// Iterates over the voxels of the brick in flat index order
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+
+    /// Converts the brick to a plain array of voxels
+    pub(crate) fn unpack(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+
+    /// The number of bytes the voxels of the brick take up
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.indices.len() * std::mem::size_of::<u64>()
+            + self.palette.len() * std::mem::size_of::<T>()
+    }
+
+    /// Updates the voxel at the given flat index
+    /// * Returns false if the voxel can not be stored, because the brick would need more,
+    ///   than the largest possible number of different voxels
+    pub(crate) fn set(&mut self, index: usize, voxel: T) -> bool {
+        if *self.get(index) == voxel {
+            return true;
+        }
+        let palette_index = match self.palette.iter().position(|entry| *entry == voxel) {
+            Some(palette_index) => palette_index,
+            None => {
+                if self.palette.len() == 1 << self.index_bits && !self.repack_with_space() {
+                    return false;
+                }
+                self.palette.push(voxel);
+                self.palette.len() - 1
+            }
+        };
+        self.set_palette_index(index, palette_index);
+        true
+    }
+
+    /// Removes unused palette entries, and widens the indices if there is still
+    /// no space for another palette entry
+    /// * Returns false if there is no possible index width with space for another entry
+    fn repack_with_space(&mut self) -> bool {
+        let mut used = vec![false; self.palette.len()];
+        for index in 0..self.len {
+            used[self.palette_index(index)] = true;
+        }
+        let mut new_palette_index = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (palette_index, entry) in self.palette.iter().enumerate() {
+            if used[palette_index] {
+                new_palette_index[palette_index] = palette.len();
+                palette.push(entry.clone());
+            }
+        }
+        let Some(index_bits) = Self::index_bits_for(palette.len() + 1) else {
+            return false;
+        };
+        self.indices = Self::pack_indices(
+            (0..self.len).map(|index| new_palette_index[self.palette_index(index)]),
+            index_bits,
+        );
+        self.palette = palette;
+        self.index_bits = index_bits;
+        true
+    }
+}
+
+impl<T: Clone + Eq + Hash> PackedBrick<T> {
+    /// Packs the given voxels into a brick
+    /// * Returns None if there are too many different voxels to pack
+    pub(crate) fn pack(voxels: impl IntoIterator<Item = T>) -> Option<Self> {
+        let mut palette = Vec::new();
+        let mut palette_index_for = HashMap::new();
+        let mut palette_indices = Vec::new();
+        for voxel in voxels {
+            palette_indices.push(*palette_index_for.entry(voxel).or_insert_with_key(|voxel| {
+                palette.push(voxel.clone());
+                palette.len() - 1
+            }));
+        }
+        let index_bits = Self::index_bits_for(palette.len())?;
+        Some(Self {
+            palette,
+            index_bits,
+            len: palette_indices.len(),
+            indices: Self::pack_indices(palette_indices.into_iter(), index_bits),
+        })
+    }
+}
+
+impl<T: Clone + PartialEq + CompactVoxel> PartialEq for BrickData<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (BrickData::Empty, BrickData::Empty) => true,
+            (BrickData::Solid(voxel), BrickData::Solid(other_voxel)) => voxel == other_voxel,
+            (BrickData::Parted(brick), BrickData::Parted(other_brick)) => {
+                brick.len() == other_brick.len() && brick.iter().eq(other_brick.iter())
+            }
+            (BrickData::Packed(brick), BrickData::Packed(other_brick)) => {
+                brick.len() == other_brick.len() && brick.iter().eq(other_brick.iter())
+            }
+            (BrickData::Parted(brick), BrickData::Packed(packed))
+            | (BrickData::Packed(packed), BrickData::Parted(brick)) => {
+                brick.len() == packed.len() && brick.iter().eq(packed.iter().cloned())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + CompactVoxel> BrickData<T> {
+    /// Creates a Parted brick from the given voxels
+    pub(crate) fn parted(voxels: Vec<T>) -> Self {
+        BrickData::Parted(PartedBrick::new(voxels))
+    }
+
+    /// Provides the voxel at the given flat index, if the brick has any
+    pub(crate) fn voxel(&self, index: usize) -> Option<T> {
+        match self {
+            BrickData::Empty => None,
+            BrickData::Solid(voxel) => Some(voxel.clone()),
+            BrickData::Parted(brick) => brick.get(index),
+            BrickData::Packed(brick) => Some(brick.get(index).clone()),
+        }
+    }
+
+    /// Iterates over the voxels of a Parted or Packed brick in flat index order
+    pub(crate) fn voxels_iter(&self) -> Option<Box<dyn ExactSizeIterator<Item = T> + '_>> {
+        match self {
+            BrickData::Empty | BrickData::Solid(_) => None,
+            BrickData::Parted(brick) => Some(Box::new(brick.iter())),
+            BrickData::Packed(brick) => Some(Box::new(brick.iter().cloned())),
+        }
+    }
+
+    /// The number of bytes the voxels of the brick take up outside of the brick itself
+    pub(crate) fn memory_usage(&self) -> usize {
+        match self {
+            BrickData::Empty | BrickData::Solid(_) => 0,
+            BrickData::Parted(brick) => brick.memory_usage(),
+            BrickData::Packed(brick) => brick.memory_usage(),
+        }
+    }
+
+    /// Provides the voxels of a Parted or Packed brick as a plain array
+    pub(crate) fn voxels(&self) -> Option<Cow<'_, [T]>> {
+        match self {
+            BrickData::Empty | BrickData::Solid(_) => None,
+            BrickData::Parted(brick) => Some(brick.as_slice()),
+            BrickData::Packed(brick) => Some(Cow::Owned(brick.unpack())),
+        }
+    }
+
+    /// Takes the voxels of a Parted or Packed brick as a plain array
+    pub(crate) fn into_voxels(self) -> Option<Vec<T>> {
+        match self {
+            BrickData::Empty | BrickData::Solid(_) => None,
+            BrickData::Parted(brick) => Some(brick.into_vec()),
+            BrickData::Packed(brick) => Some(brick.unpack()),
+        }
+    }
+
+    /// Converts a Packed brick into a Parted one, so its voxels can be directly accessed
+    pub(crate) fn widen(&mut self) {
+        if let BrickData::Packed(brick) = self {
+            *self = BrickData::parted(brick.unpack());
+        }
+    }
+
+    /// Updates the voxel at the given flat index of a Parted or Packed brick;
+    /// Packed bricks are widened if the voxel can not be stored in them
+    pub(crate) fn set_voxel(&mut self, index: usize, voxel: T) {
+        match self {
+            BrickData::Parted(brick) => brick.set(index, voxel),
+            BrickData::Packed(brick) => {
+                if !brick.set(index, voxel.clone()) {
+                    self.widen();
+                    self.set_voxel(index, voxel);
+                }
+            }
+            BrickData::Empty | BrickData::Solid(_) => {
+                panic!("Expected brick to contain separate voxels to update")
+            }
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash + CompactVoxel> BrickData<T> {
+    /// Converts a Parted brick into a Packed one, if it takes up less memory that way
+    pub(crate) fn narrow(&mut self) {
+        if let BrickData::Parted(brick) = self
+            && let Some(packed) = PackedBrick::pack(brick.iter())
+            && packed.memory_usage() < brick.memory_usage()
+        {
+            *self = BrickData::Packed(packed);
+        }
+    }
+}
+
 impl BrickData<PaletteIndexValues> {
     /// Calculates the Occupancy bitmap for the given Voxel brick
     pub(crate) fn calculate_brick_occupied_bits<V: VoxelData>(
-        brick: &[PaletteIndexValues],
+        voxel_at: impl Fn(usize) -> PaletteIndexValues,
         brick_dimension: usize,
         color_palette: &[Albedo],
         data_palette: &[V],
@@ -45,7 +416,7 @@ impl BrickData<PaletteIndexValues> {
                 for z in 0..brick_dimension {
                     let flat_index = flat_projection(x, y, z, brick_dimension);
                     if !NodeContent::pix_points_to_empty(
-                        &brick[flat_index],
+                        &voxel_at(flat_index),
                         color_palette,
                         data_palette,
                     ) {
@@ -80,7 +451,13 @@ impl BrickData<PaletteIndexValues> {
                 }
             }
             BrickData::Parted(brick) => Self::calculate_brick_occupied_bits(
-                brick,
+                |index| brick.get(index).unwrap(),
+                brick_dimension,
+                color_palette,
+                data_palette,
+            ),
+            BrickData::Packed(brick) => Self::calculate_brick_occupied_bits(
+                |index| *brick.get(index),
                 brick_dimension,
                 color_palette,
                 data_palette,
@@ -88,18 +465,27 @@ impl BrickData<PaletteIndexValues> {
         }
     }
 
-    /// In case all contained voxels are the same, returns with a reference to the data
-    pub(crate) fn get_homogeneous_data(&self) -> Option<&PaletteIndexValues> {
+    /// In case all contained voxels are the same, returns with the data
+    pub(crate) fn get_homogeneous_data(&self) -> Option<PaletteIndexValues> {
         match self {
             BrickData::Empty => None,
-            BrickData::Solid(voxel) => Some(voxel),
+            BrickData::Solid(voxel) => Some(*voxel),
             BrickData::Parted(brick) => {
+                let first = brick.get(0)?;
                 for voxel in brick.iter() {
-                    if *voxel != brick[0] {
+                    if voxel != first {
                         return None;
                     }
                 }
-                Some(&brick[0])
+                Some(first)
+            }
+            BrickData::Packed(brick) => {
+                let first = brick.get(0);
+                if brick.iter().all(|voxel| voxel == first) {
+                    Some(*first)
+                } else {
+                    None
+                }
             }
         }
     }
@@ -116,29 +502,34 @@ impl BrickData<PaletteIndexValues> {
             }
             BrickData::Parted(brick) => {
                 for voxel in brick.iter() {
-                    if !NodeContent::pix_points_to_empty(voxel, color_palette, data_palette) {
+                    if !NodeContent::pix_points_to_empty(&voxel, color_palette, data_palette) {
                         return false;
                     }
                 }
                 true
             }
+            BrickData::Packed(brick) => brick
+                .iter()
+                .all(|voxel| NodeContent::pix_points_to_empty(voxel, color_palette, data_palette)),
         }
     }
 
     /// Tries to simplify brick data, returns true if the view was simplified during function call
+    /// Bricks which can not be simplified are narrowed to take up less memory
     pub(crate) fn simplify<V: VoxelData>(
         &mut self,
         color_palette: &[Albedo],
         data_palette: &[V],
     ) -> bool {
         if let Some(homogeneous_type) = self.get_homogeneous_data() {
-            if NodeContent::pix_points_to_empty(homogeneous_type, color_palette, data_palette) {
+            if NodeContent::pix_points_to_empty(&homogeneous_type, color_palette, data_palette) {
                 *self = BrickData::Empty;
             } else {
-                *self = BrickData::Solid(*homogeneous_type);
+                *self = BrickData::Solid(homogeneous_type);
             }
             true
         } else {
+            self.narrow();
             false
         }
     }
@@ -200,7 +591,10 @@ impl NodeData {
     }
 
     /// Creates a node where all contained voxels are te same
-    pub(crate) fn uniform_parted_node(brick: BrickData<u32>, occupied_bits: u64) -> Self {
+    pub(crate) fn uniform_parted_node(
+        brick: BrickData<PaletteIndexValues>,
+        occupied_bits: u64,
+    ) -> Self {
         NodeData {
             content: NodeContent::UniformLeaf(brick),
             children: NodeChildren::NoChildren,
@@ -242,7 +636,8 @@ impl NodeData {
         debug_assert!(child_index < BOX_NODE_CHILDREN_COUNT);
         if let NodeChildren::Children(ref mut c) = self.children {
             c[child_index] = empty_marker();
-            if 8 == c.iter().filter(|e| **e == empty_marker::<u32>()).count() {
+            if BOX_NODE_CHILDREN_COUNT == c.iter().filter(|e| **e == empty_marker::<u32>()).count()
+            {
                 self.children = NodeChildren::NoChildren;
             }
         }
@@ -257,30 +652,30 @@ impl NodeData {
 }
 
 impl NodeContent<PaletteIndexValues> {
-    pub(crate) fn pix_visual(color_index: u16) -> PaletteIndexValues {
-        (color_index as u32) | ((empty_marker::<u16>() as u32) << 16)
+    pub(crate) fn pix_visual(color_index: u32) -> PaletteIndexValues {
+        (color_index as u64) | ((empty_marker::<u32>() as u64) << 32)
     }
 
-    pub(crate) fn pix_informal(data_index: u16) -> PaletteIndexValues {
-        (empty_marker::<u16>() as u32) | ((data_index as u32) << 16)
+    pub(crate) fn pix_informal(data_index: u32) -> PaletteIndexValues {
+        (empty_marker::<u32>() as u64) | ((data_index as u64) << 32)
     }
 
-    pub(crate) fn pix_complex(color_index: u16, data_index: u16) -> PaletteIndexValues {
-        (color_index as u32) | ((data_index as u32) << 16)
+    pub(crate) fn pix_complex(color_index: u32, data_index: u32) -> PaletteIndexValues {
+        (color_index as u64) | ((data_index as u64) << 32)
     }
 
     pub(crate) fn pix_color_index(index: &PaletteIndexValues) -> usize {
-        (index & 0x0000FFFF) as usize
+        (index & 0x00000000FFFFFFFF) as usize
     }
     pub(crate) fn pix_data_index(index: &PaletteIndexValues) -> usize {
-        ((index & 0xFFFF0000) >> 16) as usize
+        ((index & 0xFFFFFFFF00000000) >> 32) as usize
     }
 
     pub(crate) fn pix_overwrite_color(
         mut index: PaletteIndexValues,
         delta: &PaletteIndexValues,
     ) -> PaletteIndexValues {
-        index = (index & 0xFFFF0000) | (delta & 0x0000FFFF);
+        index = (index & 0xFFFFFFFF00000000) | (delta & 0x00000000FFFFFFFF);
         index
     }
 
@@ -288,12 +683,52 @@ impl NodeContent<PaletteIndexValues> {
         mut index: PaletteIndexValues,
         delta: &PaletteIndexValues,
     ) -> PaletteIndexValues {
-        index = (index & 0x0000FFFF) | (delta & 0xFFFF0000);
+        index = (index & 0x00000000FFFFFFFF) | (delta & 0xFFFFFFFF00000000);
         index
     }
 
+    /// Converts the index value into the layout of @PaletteIndexWidth::Narrow,
+    /// where both palette indices are stored on 16 bits
+    pub(crate) fn pix_narrowed(index: &PaletteIndexValues) -> PaletteIndexValues {
+        let narrow = |is_some: bool, palette_index: usize| {
+            debug_assert!(
+                !is_some || palette_index < empty_marker::<u16>() as usize,
+                "Expected palette index {palette_index} to fit into 16 bits"
+            );
+            if is_some {
+                palette_index as u64
+            } else {
+                empty_marker::<u16>() as u64
+            }
+        };
+        narrow(Self::pix_color_is_some(index), Self::pix_color_index(index))
+            | (narrow(Self::pix_data_is_some(index), Self::pix_data_index(index)) << 16)
+    }
+
+    /// Converts the index value from the layout of @PaletteIndexWidth::Narrow, see @pix_narrowed
+    pub(crate) fn pix_widened(index: &PaletteIndexValues) -> PaletteIndexValues {
+        let widen = |palette_index: u64| {
+            if empty_marker::<u16>() as u64 == palette_index {
+                empty_marker::<u32>()
+            } else {
+                palette_index as u32
+            }
+        };
+        Self::pix_complex(widen(index & 0x0000FFFF), widen((index & 0xFFFF0000) >> 16))
+    }
+
+    /// The value representing the voxel on the GPU: its color index, or an empty marker without color
+    #[cfg(feature = "bevy_wgpu")]
+    pub(crate) fn pix_gpu(index: &PaletteIndexValues) -> u32 {
+        if Self::pix_color_is_some(index) {
+            Self::pix_color_index(index) as u32
+        } else {
+            empty_marker::<u32>()
+        }
+    }
+
     pub(crate) fn pix_color_is_some(index: &PaletteIndexValues) -> bool {
-        Self::pix_color_index(index) < empty_marker::<u16>() as usize
+        Self::pix_color_index(index) < empty_marker::<u32>() as usize
     }
 
     pub(crate) fn pix_color_is_none(index: &PaletteIndexValues) -> bool {
@@ -301,7 +736,7 @@ impl NodeContent<PaletteIndexValues> {
     }
 
     pub(crate) fn pix_data_is_none(index: &PaletteIndexValues) -> bool {
-        Self::pix_data_index(index) == empty_marker::<u16>() as usize
+        Self::pix_data_index(index) == empty_marker::<u32>() as usize
     }
 
     pub(crate) fn pix_data_is_some(index: &PaletteIndexValues) -> bool {
@@ -384,13 +819,8 @@ impl NodeContent<PaletteIndexValues> {
                 BrickData::Solid(voxel) => {
                     Self::pix_points_to_empty(voxel, color_palette, data_palette)
                 }
-                BrickData::Parted(brick) => {
-                    for voxel in brick.iter() {
-                        if !Self::pix_points_to_empty(voxel, color_palette, data_palette) {
-                            return false;
-                        }
-                    }
-                    true
+                BrickData::Parted(_) | BrickData::Packed(_) => {
+                    brick.contains_nothing(color_palette, data_palette)
                 }
             },
             NodeContent::Leaf(bricks) => {
@@ -404,11 +834,9 @@ impl NodeContent<PaletteIndexValues> {
                                 return false;
                             }
                         }
-                        BrickData::Parted(brick) => {
-                            for voxel in brick.iter() {
-                                if !Self::pix_points_to_empty(voxel, color_palette, data_palette) {
-                                    return false;
-                                }
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
+                            if !mat.contains_nothing(color_palette, data_palette) {
+                                return false;
                             }
                         }
                     }
@@ -426,9 +854,9 @@ impl NodeContent<PaletteIndexValues> {
             NodeContent::UniformLeaf(brick) => match brick {
                 BrickData::Empty => false,
                 BrickData::Solid(voxel) => voxel == data,
-                BrickData::Parted(_brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     if let Some(homogeneous_type) = brick.get_homogeneous_data() {
-                        homogeneous_type == data
+                        homogeneous_type == *data
                     } else {
                         false
                     }
@@ -439,9 +867,9 @@ impl NodeContent<PaletteIndexValues> {
                     let brick_is_all_data = match mat {
                         BrickData::Empty => false,
                         BrickData::Solid(voxel) => voxel == data,
-                        BrickData::Parted(_brick) => {
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
                             if let Some(homogeneous_type) = mat.get_homogeneous_data() {
-                                homogeneous_type == data
+                                homogeneous_type == *data
                             } else {
                                 false
                             }
@@ -0,0 +1,95 @@
+use crate::boxtree::{
+    types::{BoxTreeSnapshot, SharedBoxTree},
+    BoxTree, VoxelData,
+};
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex, RwLock},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates a read-only copy of the tree, which shares every node and brick with the tree
+    /// Nodes are stored in chunks, which are only copied once they are modified in either of them,
+    /// so an edit only copies the chunks of the nodes it updates
+    /// The snapshot can be read on another thread while the tree is being edited
+    /// The palettes are copied; update signals, subscriptions and the journal are left out
+    pub fn snapshot(&self) -> BoxTreeSnapshot<T> {
+        BoxTreeSnapshot {
+            tree: BoxTree {
+                nodes: self.nodes.clone(),
+                voxel_color_palette: self.voxel_color_palette.clone(),
+                voxel_data_palette: self.voxel_data_palette.clone(),
+                map_to_color_index_in_palette: self.map_to_color_index_in_palette.clone(),
+                map_to_data_index_in_palette: self.map_to_data_index_in_palette.clone(),
+                mip_map_strategy: self.mip_map_strategy.clone(),
+                update_triggers: vec![],
+                subscriptions: vec![],
+                journal: None,
+                ..*self
+            },
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTreeSnapshot<T> {
+    /// Creates an editable tree from the snapshot, e.g. to restore an earlier state
+    /// The nodes are shared with the snapshot until they are modified
+    pub fn to_tree(&self) -> BoxTree<T> {
+        self.tree.clone()
+    }
+}
+
+impl<T: VoxelData> Deref for BoxTreeSnapshot<T> {
+    type Target = BoxTree<T>;
+
+    fn deref(&self) -> &BoxTree<T> {
+        &self.tree
+    }
+}
+
+impl<T: VoxelData> SharedBoxTree<T> {
+    pub fn new(tree: BoxTree<T>) -> Self {
+        let published = Arc::new(RwLock::new(Arc::new(tree.snapshot())));
+        Self {
+            tree: Arc::new(Mutex::new(tree)),
+            published,
+        }
+    }
+
+    /// Provides the state of the tree after the latest finished edit
+    /// The returned snapshot is not affected by later edits
+    pub fn read(&self) -> Arc<BoxTreeSnapshot<T>> {
+        self.published
+            .read()
+            .expect("Expected to be able to read published tree")
+            .clone()
+    }
+
+    /// Edits the tree with the given function, then publishes the result to the readers
+    /// Readers keep accessing the previous state of the tree until the edit is finished;
+    /// Edits from multiple threads are applied one after another
+    pub fn edit<R>(&self, edit: impl FnOnce(&mut BoxTree<T>) -> R) -> R {
+        self.edit_then(edit, |_| {})
+    }
+
+    /// Edits the tree with the given function, and publishes the result to the readers
+    /// `published` is called after the edited tree is available to readers, but before any other edit starts
+    pub(crate) fn edit_then<R>(
+        &self,
+        edit: impl FnOnce(&mut BoxTree<T>) -> R,
+        published: impl FnOnce(&R),
+    ) -> R {
+        let mut tree = self
+            .tree
+            .lock()
+            .expect("Expected to be able to lock shared tree for editing");
+        let result = edit(&mut tree);
+        let snapshot = Arc::new(tree.snapshot());
+        *self
+            .published
+            .write()
+            .expect("Expected to be able to publish edited tree") = snapshot;
+        published(&result);
+        result
+    }
+}
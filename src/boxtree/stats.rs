@@ -0,0 +1,214 @@
+use crate::boxtree::{
+    types::{BrickData, NodeContent, NodeData, PaletteIndexValues},
+    Albedo, BoxTree, VoxelData, BOX_NODE_DIMENSION,
+};
+
+/// Number of nodes, bricks and voxels in a part of the tree, see @BoxTreeStats
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BoxTreeLevelStats {
+    /// Number of nodes without any content
+    pub empty_nodes: usize,
+
+    /// Number of nodes only referencing other nodes
+    pub internal_nodes: usize,
+
+    /// Number of nodes with a brick for each of their children
+    pub leaf_nodes: usize,
+
+    /// Number of nodes with a single brick taking up the whole node
+    pub uniform_leaf_nodes: usize,
+
+    /// Number of bricks without any voxels
+    pub empty_bricks: usize,
+
+    /// Number of bricks where every voxel is the same
+    pub solid_bricks: usize,
+
+    /// Number of bricks storing each of their voxels separately
+    /// Bit packed bricks are included
+    pub parted_bricks: usize,
+
+    /// Number of parted bricks stored in a bit packed form
+    pub packed_bricks: usize,
+
+    /// Number of voxels with color or data
+    pub voxels: u64,
+
+    /// Number of nodes with a MIP brick
+    pub mip_bricks: usize,
+}
+
+/// Statistics about the structure and memory usage of a tree, see @BoxTree::stats
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BoxTreeStats {
+    /// Sum of the statistics of every depth
+    pub total: BoxTreeLevelStats,
+
+    /// Statistics for every depth of the tree, starting from the root node
+    pub depths: Vec<BoxTreeLevelStats>,
+
+    /// Number of entries in the color palette, including the ones not used anymore
+    pub color_palette_size: usize,
+
+    /// Number of entries in the data palette, including the ones not used anymore
+    pub data_palette_size: usize,
+
+    /// Estimated number of bytes used by the node storage, without the bricks
+    /// Storage shared with snapshots of the tree is divided between them
+    pub node_bytes: usize,
+
+    /// Estimated number of bytes used by the voxels of the bricks and MIPs
+    pub brick_bytes: usize,
+
+    /// Estimated number of bytes used by the palettes and their lookup tables
+    pub palette_bytes: usize,
+}
+
+impl BoxTreeStats {
+    /// The estimated number of bytes the tree takes up on the heap
+    pub fn heap_bytes(&self) -> usize {
+        self.node_bytes + self.brick_bytes + self.palette_bytes
+    }
+}
+
+impl BoxTreeLevelStats {
+    fn add(&mut self, other: &BoxTreeLevelStats) {
+        self.empty_nodes += other.empty_nodes;
+        self.internal_nodes += other.internal_nodes;
+        self.leaf_nodes += other.leaf_nodes;
+        self.uniform_leaf_nodes += other.uniform_leaf_nodes;
+        self.empty_bricks += other.empty_bricks;
+        self.solid_bricks += other.solid_bricks;
+        self.parted_bricks += other.parted_bricks;
+        self.packed_bricks += other.packed_bricks;
+        self.voxels += other.voxels;
+        self.mip_bricks += other.mip_bricks;
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Collects statistics about the nodes, bricks and memory usage of the tree
+    /// Nodes of paged trees are loaded during collection
+    pub fn stats(&self) -> BoxTreeStats {
+        let mut stats = BoxTreeStats {
+            color_palette_size: self.voxel_color_palette.len(),
+            data_palette_size: self.voxel_data_palette.len(),
+            node_bytes: self.nodes.memory_usage(),
+            palette_bytes: self.voxel_color_palette.capacity() * std::mem::size_of::<Albedo>()
+                + self.voxel_data_palette.capacity() * std::mem::size_of::<T>()
+                + self.map_to_color_index_in_palette.capacity()
+                    * std::mem::size_of::<(Albedo, usize)>()
+                + self.map_to_data_index_in_palette.capacity() * std::mem::size_of::<(T, usize)>(),
+            ..Default::default()
+        };
+
+        let mut node_stack = vec![(Self::ROOT_NODE_KEY as usize, 0, self.boxtree_size as u64)];
+        while let Some((node_key, depth, node_size)) = node_stack.pop() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            if stats.depths.len() <= depth {
+                stats.depths.resize(depth + 1, BoxTreeLevelStats::default());
+            }
+            let node = self.nodes.get(node_key);
+            self.collect_node_stats(&node, node_size, &mut stats.depths[depth]);
+            stats.brick_bytes += node.mip.memory_usage();
+            stats.brick_bytes += match &node.content {
+                NodeContent::Nothing | NodeContent::Internal => 0,
+                NodeContent::UniformLeaf(brick) => brick.memory_usage(),
+                NodeContent::Leaf(bricks) => bricks.iter().map(BrickData::memory_usage).sum(),
+            };
+
+            if let Some(children) = node.children_iter() {
+                let child_size = node_size / BOX_NODE_DIMENSION as u64;
+                node_stack
+                    .extend(children.map(|child_key| (*child_key as usize, depth + 1, child_size)));
+            }
+        }
+
+        for level_stats in stats.depths.iter() {
+            stats.total.add(level_stats);
+        }
+        stats
+    }
+
+    /// Updates the given statistics with the content of a single node
+    /// * `node` - The node to collect the statistics from
+    /// * `node_size` - The extent of the node in each dimension
+    /// * `stats` - The statistics of the depth the node is at
+    fn collect_node_stats(&self, node: &NodeData, node_size: u64, stats: &mut BoxTreeLevelStats) {
+        if !matches!(node.mip, BrickData::Empty) {
+            stats.mip_bricks += 1;
+        }
+        match &node.content {
+            NodeContent::Nothing => stats.empty_nodes += 1,
+            NodeContent::Internal => stats.internal_nodes += 1,
+            NodeContent::UniformLeaf(brick) => {
+                stats.uniform_leaf_nodes += 1;
+                self.collect_brick_stats(brick, node_size, stats);
+            }
+            NodeContent::Leaf(bricks) => {
+                stats.leaf_nodes += 1;
+                let child_size = node_size / BOX_NODE_DIMENSION as u64;
+                for brick in bricks.iter() {
+                    self.collect_brick_stats(brick, child_size, stats);
+                }
+            }
+        }
+    }
+
+    /// Updates the given statistics with the content of a single brick
+    /// * `brick` - The brick to collect the statistics from
+    /// * `brick_size` - The extent of the area the brick covers in each dimension
+    /// * `stats` - The statistics of the depth the brick is at
+    fn collect_brick_stats(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_size: u64,
+        stats: &mut BoxTreeLevelStats,
+    ) {
+        match brick {
+            BrickData::Empty => stats.empty_bricks += 1,
+            BrickData::Solid(_) => stats.solid_bricks += 1,
+            BrickData::Parted(_) => stats.parted_bricks += 1,
+            BrickData::Packed(_) => {
+                stats.parted_bricks += 1;
+                stats.packed_bricks += 1;
+            }
+        }
+        stats.voxels += self.occupied_voxels_in(brick, brick_size);
+    }
+
+    /// The number of voxels with color or data inside the given brick
+    /// * `brick` - The brick to count the voxels in
+    /// * `brick_size` - The extent of the area the brick covers in each dimension
+    fn occupied_voxels_in(&self, brick: &BrickData<PaletteIndexValues>, brick_size: u64) -> u64 {
+        let is_occupied = |voxel: &PaletteIndexValues| {
+            !NodeContent::pix_points_to_empty(
+                voxel,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            )
+        };
+        match brick {
+            BrickData::Empty => 0,
+            BrickData::Solid(voxel) => {
+                if is_occupied(voxel) {
+                    brick_size.pow(3)
+                } else {
+                    0
+                }
+            }
+            BrickData::Parted(_) | BrickData::Packed(_) => {
+                // One voxel of the brick might cover multiple voxels of the tree
+                let voxel_size = (brick_size / self.brick_dim as u64).max(1);
+                let occupied_count = brick
+                    .voxels_iter()
+                    .unwrap()
+                    .filter(|voxel| is_occupied(voxel))
+                    .count() as u64;
+                occupied_count * voxel_size.pow(3)
+            }
+        }
+    }
+}
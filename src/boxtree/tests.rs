@@ -872,6 +872,115 @@ mod iterate_tests {
             (BOX_NODE_DIMENSION - 1) * BOX_NODE_DIMENSION * BOX_NODE_DIMENSION
         );
     }
+
+    #[test]
+    fn test_voxels_iterates_every_occupied_voxel() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.auto_simplify = false;
+        tree.insert(&V3c::new(3, 5, 7), &red).ok().unwrap();
+        tree.insert(&V3c::new(31, 0, 12), &green).ok().unwrap();
+        tree.insert_at_lod(&V3c::new(16, 16, 16), 4, &green)
+            .ok()
+            .unwrap();
+
+        let mut voxels = tree
+            .voxels()
+            .map(|(position, entry)| (position, entry.albedo().cloned()))
+            .collect::<Vec<_>>();
+        voxels.sort_by_key(|(position, _)| (position.x, position.y, position.z));
+
+        let mut expected = vec![
+            (V3c::new(3, 5, 7), Some(red)),
+            (V3c::new(31, 0, 12), Some(green)),
+        ];
+        for x in 16..20 {
+            for y in 16..20 {
+                for z in 16..20 {
+                    expected.push((V3c::new(x, y, z), Some(green)));
+                }
+            }
+        }
+        expected.sort_by_key(|(position, _)| (position.x, position.y, position.z));
+        assert_eq!(voxels, expected);
+    }
+
+    #[test]
+    fn test_voxels_match_get() {
+        let colors: Vec<Albedo> = (1..6).map(|i| Albedo::from(0x110000FF * i)).collect();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if (x * 7 + y * 3 + z) % 5 == 0 || y < 4 {
+                        tree.insert(&V3c::new(x, y, z), &colors[((x + z) % 5) as usize])
+                            .ok()
+                            .unwrap();
+                    }
+                }
+            }
+        }
+
+        let mut count = 0;
+        for (position, entry) in tree.voxels() {
+            assert!(tree.get(&position) == entry);
+            count += 1;
+        }
+        let mut expected_count = 0;
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if tree.get(&V3c::new(x, y, z)).is_some() {
+                        expected_count += 1;
+                    }
+                }
+            }
+        }
+        assert_eq!(count, expected_count);
+    }
+
+    #[test]
+    fn test_voxels_in_area() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(64, 64, 64), &red)
+            .ok()
+            .unwrap();
+
+        let voxels = tree
+            .voxels_in(&V3c::new(10, 20, 30), &V3c::new(13, 22, 31))
+            .collect::<Vec<_>>();
+        assert_eq!(voxels.len(), 3 * 2);
+        for (position, entry) in voxels {
+            assert!((10..13).contains(&position.x));
+            assert!((20..22).contains(&position.y));
+            assert_eq!(position.z, 30);
+            assert!(entry == (&red).into());
+        }
+    }
+
+    #[test]
+    fn test_bricks_provide_solid_regions_once() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+            .ok()
+            .unwrap();
+        tree.insert(&V3c::new(33, 33, 33), &green).ok().unwrap();
+
+        let bricks = tree.bricks().collect::<Vec<_>>();
+        assert_eq!(bricks.len(), 2);
+        assert_eq!(bricks[0].position, V3c::new(0, 0, 0));
+        assert_eq!(bricks[0].size, 16);
+        assert!(bricks[0].solid == Some((&red).into()));
+        assert_eq!(bricks[1].position, V3c::new(32, 32, 32));
+        assert_eq!(bricks[1].size, 4);
+        assert!(bricks[1].solid.is_none());
+
+        assert_eq!(BoxTree::<u32>::new(64, 4).ok().unwrap().bricks().count(), 0);
+    }
 }
 
 mod mipmap_tests {
@@ -1350,3 +1459,443 @@ mod mipmap_tests {
         );
     }
 }
+
+mod brick_tests {
+    use crate::{
+        boxtree::{
+            types::{BrickData, NodeContent, PackedBrick, PaletteIndexValues, PartedBrick},
+            Albedo, BoxTree, V3c,
+        },
+        object_pool::empty_marker,
+    };
+
+    #[test]
+    fn test_packed_brick_get_and_set() {
+        let voxels: Vec<u32> = (0..512).map(|i| (i % 3) * 10).collect();
+        let mut packed = PackedBrick::pack(voxels.clone()).expect("Expected brick to be packable");
+        assert_eq!(packed.index_bits, 2);
+        assert_eq!(packed.unpack(), voxels);
+
+        // Adding more different voxels widens the indices
+        for i in 0..512 {
+            assert!(packed.set(i, i as u32));
+            assert_eq!(*packed.get(i), i as u32);
+        }
+        assert_eq!(packed.index_bits, 16);
+        assert_eq!(packed.unpack(), (0..512).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_packed_brick_reuses_unused_palette_entries() {
+        let mut packed = PackedBrick::pack([7u32; 64]).expect("Expected brick to be packable");
+        assert_eq!(packed.index_bits, 1);
+
+        // Each voxel only ever has 2 different values, but palette entries go unused over time
+        for value in 0..100 {
+            for i in 0..64 {
+                assert!(packed.set(i, value));
+            }
+            assert!(packed.palette.len() <= 2);
+        }
+        assert_eq!(packed.index_bits, 1);
+        assert!(packed.iter().all(|voxel| *voxel == 99));
+    }
+
+    #[test]
+    fn test_packed_brick_too_many_voxels() {
+        let voxels: Vec<u32> = (0..(1 << 16) + 1).collect();
+        assert!(PackedBrick::pack(voxels).is_none());
+
+        // Bricks not able to store an additional voxel are widened
+        let mut brick = BrickData::Packed(
+            PackedBrick::pack(0..1u32 << 16).expect("Expected brick to be packable"),
+        );
+        brick.set_voxel(0, u32::MAX);
+        assert!(matches!(brick, BrickData::Parted(_)));
+        assert_eq!(brick.voxel(0), Some(u32::MAX));
+        assert_eq!(brick.voxel(1), Some(1));
+    }
+
+    #[test]
+    fn test_packed_brick_equals_parted() {
+        let voxels: Vec<u32> = (0..64).map(|i| i % 2).collect();
+        let mut brick = BrickData::parted(voxels.clone());
+        brick.narrow();
+        assert!(matches!(brick, BrickData::Packed(_)));
+        assert!(brick == BrickData::parted(voxels));
+        assert!(brick != BrickData::parted(vec![0; 64]));
+    }
+
+    #[test]
+    fn test_parted_brick_is_compact_for_narrow_indices() {
+        let voxels: Vec<PaletteIndexValues> = (0..64)
+            .map(|i| match i % 3 {
+                0 => NodeContent::pix_visual(i),
+                1 => NodeContent::pix_complex(i, 65534),
+                _ => empty_marker::<PaletteIndexValues>(),
+            })
+            .collect();
+        let mut brick = BrickData::parted(voxels.clone());
+        assert!(matches!(brick, BrickData::Parted(PartedBrick::Compact(_))));
+        assert_eq!(brick.memory_usage(), 64 * std::mem::size_of::<u32>());
+        assert_eq!(brick.into_voxels(), Some(voxels.clone()));
+
+        // Voxels not fitting into the narrow layout are kept in full
+        brick = BrickData::parted(voxels.clone());
+        brick.set_voxel(5, NodeContent::pix_visual(1 << 20));
+        assert!(matches!(brick, BrickData::Parted(PartedBrick::Full(_))));
+        assert_eq!(brick.voxel(5), Some(NodeContent::pix_visual(1 << 20)));
+        assert!((0..64)
+            .filter(|i| *i != 5)
+            .all(|i| brick.voxel(i) == Some(voxels[i])));
+    }
+
+    #[test]
+    fn test_tree_bricks_are_packed() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 8).ok().unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if 0 == (x + y + z) % 2 {
+                        let color = if x < 16 { &red } else { &green };
+                        tree.insert(&V3c::new(x, y, z), color).ok().unwrap();
+                    }
+                }
+            }
+        }
+
+        let mut packed_bricks = 0;
+        for node_key in 0..tree.nodes.len() {
+            if !tree.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            if let NodeContent::Leaf(bricks) = &tree.nodes.get(node_key).content {
+                packed_bricks += bricks
+                    .iter()
+                    .filter(|brick| matches!(brick, BrickData::Packed(_)))
+                    .count();
+            }
+        }
+        assert!(0 < packed_bricks);
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let expected = if 0 != (x + y + z) % 2 {
+                        None
+                    } else if x < 16 {
+                        Some(&red)
+                    } else {
+                        Some(&green)
+                    };
+                    assert_eq!(tree.get(&V3c::new(x, y, z)).albedo(), expected);
+                }
+            }
+        }
+
+        // Clearing voxels updates the packed bricks in place
+        for x in 0..32 {
+            tree.clear(&V3c::new(x, 0, 0)).ok().unwrap();
+            assert!(tree.get(&V3c::new(x, 0, 0)).albedo().is_none());
+        }
+        assert!(tree.get(&V3c::new(0, 1, 1)).albedo() == Some(&red));
+    }
+}
+
+mod build_tests {
+    use crate::boxtree::{
+        types::{BrickData, NodeContent},
+        Albedo, BoxTree, BoxTreeEntry, V3c,
+    };
+
+    #[test]
+    fn test_from_fn_matches_inserts() {
+        let colors: Vec<Albedo> = (1..6).map(|i| Albedo::from(0x110000FF * i)).collect();
+        let voxel_at = |position: &V3c<u32>| -> BoxTreeEntry<u32> {
+            if position.y < 5 + (position.x + position.z) % 7 {
+                (&colors[((position.x / 3 + position.z / 5) % 5) as usize]).into()
+            } else {
+                BoxTreeEntry::Empty
+            }
+        };
+
+        let mut reference_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    if let BoxTreeEntry::Visual(albedo) = voxel_at(&position) {
+                        reference_tree.insert(&position, albedo).ok().unwrap();
+                    }
+                }
+            }
+        }
+
+        let mut tree = BoxTree::from_fn(32, 2, voxel_at).ok().unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    assert!(
+                        reference_tree.get(&position) == tree.get(&position),
+                        "Mismatch at {:?}: {:?} <> {:?}",
+                        position,
+                        reference_tree.get(&position),
+                        tree.get(&position)
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            reference_tree
+                .nodes
+                .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+                .occupied_bits,
+            tree.nodes
+                .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+                .occupied_bits
+        );
+
+        // MIPs are calculated once they are enabled
+        reference_tree
+            .albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    let position = V3c::new(x, y, z);
+                    assert!(
+                        reference_tree
+                            .albedo_mip_map_resampling_strategy()
+                            .sample_root_mip(64, &position)
+                            == tree
+                                .albedo_mip_map_resampling_strategy()
+                                .sample_root_mip(64, &position)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_fn_full_tree_is_solid() {
+        let red: Albedo = 0xFF0000FF.into();
+        let tree: BoxTree = BoxTree::from_fn(64, 4, |_| &red).ok().unwrap();
+        assert!(matches!(
+            tree.nodes
+                .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+                .content,
+            NodeContent::UniformLeaf(BrickData::Solid(_))
+        ));
+        assert!(tree.get(&V3c::new(63, 0, 31)) == (&red).into());
+    }
+
+    #[test]
+    fn test_from_fn_sets_occlusion_bits() {
+        let red: Albedo = 0xFF0000FF.into();
+        // Every node is full, except the one at the origin, which has an empty brick
+        let tree: BoxTree = BoxTree::from_fn(32, 2, |position| {
+            if position.x < 2 && position.y < 2 && position.z < 2 {
+                BoxTreeEntry::Empty
+            } else {
+                (&red).into()
+            }
+        })
+        .ok()
+        .unwrap();
+
+        let root = tree.nodes.get(BoxTree::<u32>::ROOT_NODE_KEY as usize);
+        let partial_node = root.child(0);
+        let inner_node = root.child(21);
+        std::mem::drop(root);
+        assert_ne!(u64::MAX, tree.nodes.get(partial_node).occupied_bits);
+        assert_eq!(tree.nodes.get(inner_node).occlusion_bits, 0x3F);
+
+        // The partial node is only occluded from the sides facing other nodes
+        assert_eq!(tree.nodes.get(partial_node).occlusion_bits.count_ones(), 3);
+    }
+
+    #[test]
+    fn test_from_dense() {
+        let red: Albedo = 0xFF0000FF.into();
+        let empty = Albedo::default();
+        let voxels = (0..16 * 16 * 16)
+            .map(|i| if i % 3 == 0 { red } else { empty })
+            .collect::<Vec<_>>();
+        let tree: BoxTree = BoxTree::from_dense(16, 4, &voxels).ok().unwrap();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let expected = if (x + y * 16 + z * 256) % 3 == 0 {
+                        Some(&red)
+                    } else {
+                        None
+                    };
+                    assert!(tree.get(&V3c::new(x, y, z)).albedo() == expected);
+                }
+            }
+        }
+
+        assert!(BoxTree::<u32>::from_dense(16, 4, &voxels[1..]).is_err());
+    }
+}
+
+mod stats_tests {
+    use crate::boxtree::{Albedo, BoxTree, V3c};
+
+    #[test]
+    fn test_stats_of_empty_tree() {
+        let tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        let stats = tree.stats();
+        assert_eq!(stats.total.voxels, 0);
+        assert_eq!(stats.total.leaf_nodes + stats.total.uniform_leaf_nodes, 0);
+        assert_eq!(stats.depths.len(), 1);
+        assert!(0 < stats.heap_bytes());
+    }
+
+    #[test]
+    fn test_stats_counts_voxels_and_bricks() {
+        let red: Albedo = 0xFF0000FF.into();
+        let empty = Albedo::default();
+        let voxels = (0..16 * 16 * 16)
+            .map(|i| if i % 3 == 0 { red } else { empty })
+            .collect::<Vec<_>>();
+        let mut tree: BoxTree = BoxTree::from_dense(16, 4, &voxels).ok().unwrap();
+        let stats = tree.stats();
+        assert_eq!(stats.total.voxels, (16 * 16 * 16_u64).div_ceil(3));
+        assert_eq!(stats.depths.len(), 1);
+        assert_eq!(stats.total.leaf_nodes, 1);
+        assert_eq!(stats.total.parted_bricks, 64);
+        assert!(stats.total.packed_bricks <= stats.total.parted_bricks);
+        assert_eq!(stats.total, stats.depths[0]);
+        assert!(0 < stats.color_palette_size);
+        assert!(0 < stats.brick_bytes);
+
+        // Clearing a voxel is reflected in the counts
+        tree.clear(&V3c::new(0, 0, 0)).ok().unwrap();
+        assert_eq!(
+            tree.stats().total.voxels,
+            (16 * 16 * 16_u64).div_ceil(3) - 1
+        );
+    }
+
+    #[test]
+    fn test_stats_of_uniform_tree() {
+        let red: Albedo = 0xFF0000FF.into();
+        let tree: BoxTree = BoxTree::from_fn(64, 4, |_| &red).ok().unwrap();
+        let stats = tree.stats();
+        assert_eq!(stats.total.voxels, 64 * 64 * 64);
+        assert_eq!(stats.total.uniform_leaf_nodes, 1);
+        assert_eq!(stats.total.solid_bricks, 1);
+        assert_eq!(stats.brick_bytes, 0);
+    }
+}
+
+mod defragment_tests {
+    use crate::boxtree::{
+        defragment::morton_code, types::NodeChildren, Albedo, BoxTree, DefragmentOrder, V3c,
+        BOX_NODE_CHILDREN_COUNT,
+    };
+    use crate::object_pool::empty_marker;
+
+    /// Creates a tree with gaps in its node storage left behind by cleared areas
+    fn fragmented_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        for i in 0..40 {
+            tree.insert(&V3c::new(i * 3, i * 2, i), &red).ok().unwrap();
+        }
+        tree.fill_box(&V3c::new(70, 70, 70), &V3c::new(90, 80, 100), &green)
+            .ok()
+            .unwrap();
+        for i in 0..20 {
+            tree.clear(&V3c::new(i * 3, i * 2, i)).ok().unwrap();
+        }
+        tree
+    }
+
+    fn collect_voxels(tree: &BoxTree) -> Vec<Option<Albedo>> {
+        (0..128)
+            .flat_map(|x| (0..128).map(move |y| (x, y)))
+            .flat_map(|(x, y)| (0..128).map(move |z| V3c::new(x, y, z)))
+            .map(|position| tree.get(&position).albedo().copied())
+            .collect()
+    }
+
+    #[test]
+    fn test_defragment_keeps_contents() {
+        let mut tree = fragmented_tree();
+        let voxels = collect_voxels(&tree);
+        let original_node_count = tree.nodes.len();
+        let node_count = tree.stats().depths.iter().fold(0, |count, level| {
+            count
+                + level.empty_nodes
+                + level.internal_nodes
+                + level.leaf_nodes
+                + level.uniform_leaf_nodes
+        });
+        assert!(node_count < original_node_count);
+
+        let key_map = tree.defragment(DefragmentOrder::DepthFirst);
+        assert_eq!(key_map.len(), node_count);
+        assert_eq!(key_map[&(BoxTree::<u32>::ROOT_NODE_KEY as usize)], 0);
+        assert_eq!(tree.nodes.len(), node_count);
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(collect_voxels(&tree), voxels);
+
+        // The tree can still be edited after defragmentation
+        let blue: Albedo = 0x0000FFFF.into();
+        tree.insert(&V3c::new(127, 0, 127), &blue).ok().unwrap();
+        assert_eq!(tree.get(&V3c::new(127, 0, 127)).albedo(), Some(&blue));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_morton_code_of_sectants() {
+        assert_eq!(morton_code(0), 0);
+        assert_eq!(morton_code(1), 1);
+        assert_eq!(morton_code(4), 2);
+        assert_eq!(morton_code(16), 4);
+        assert_eq!(morton_code(2), 8);
+        assert_eq!(morton_code(63), 63);
+    }
+
+    #[test]
+    fn test_defragment_orders_children_after_parents() {
+        for order in [DefragmentOrder::DepthFirst, DefragmentOrder::Morton] {
+            let mut tree = fragmented_tree();
+            let voxels = collect_voxels(&tree);
+            tree.defragment(order);
+            assert_eq!(collect_voxels(&tree), voxels);
+            assert_eq!(tree.validate(), Ok(()));
+
+            // Every child is placed right after its parent, or after the subtree of its previous sibling
+            for node_key in 0..tree.nodes.len() {
+                let NodeChildren::Children(children) = tree.nodes.get(node_key).children else {
+                    continue;
+                };
+                let mut sectants = (0..BOX_NODE_CHILDREN_COUNT)
+                    .filter(|sectant| children[*sectant] != empty_marker::<u32>())
+                    .collect::<Vec<_>>();
+                if DefragmentOrder::Morton == order {
+                    sectants.sort_by_key(|sectant| morton_code(*sectant));
+                }
+                let child_keys = sectants
+                    .iter()
+                    .map(|sectant| children[*sectant])
+                    .collect::<Vec<_>>();
+                assert!(child_keys.is_sorted());
+                if let Some(first_child) = child_keys.first() {
+                    assert_eq!(*first_child as usize, node_key + 1);
+                }
+            }
+        }
+    }
+}
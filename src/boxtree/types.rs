@@ -1,5 +1,13 @@
-use crate::{boxtree::BOX_NODE_CHILDREN_COUNT, object_pool::ObjectPool};
-use std::{collections::HashMap, error::Error, hash::Hash, sync::Arc};
+use crate::{
+    boxtree::{V3c, BOX_NODE_CHILDREN_COUNT},
+    object_pool::ObjectPool,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    hash::Hash,
+    sync::{Arc, Mutex, RwLock},
+};
 
 #[cfg(feature = "bytecode")]
 use bendy::{decoding::FromBencode, encoding::ToBencode};
@@ -18,8 +26,201 @@ pub enum OctreeError {
 
     /// Octree query was attempted with an invalid position
     InvalidPosition { x: u32, y: u32, z: u32 },
+
+    /// The palettes have more entries than the requested palette index width can reference
+    PaletteOverflow { size: usize, limit: usize },
 }
 
+/// error types during saving or loading the boxtree
+#[cfg(feature = "bytecode")]
+#[derive(Debug)]
+pub enum SerializationError {
+    /// The underlying file could not be read or written
+    Io(std::io::Error),
+
+    /// The stored boxtree was created by a library version incompatible with the current one
+    VersionMismatch {
+        library: crate::Version,
+        tree: crate::Version,
+    },
+
+    /// The byte stream could not be interpreted as a boxtree ( refer to reason )
+    Malformed(String),
+
+    /// A stored node is inconsistent with the rest of the structure
+    MalformedNode { node_key: usize, reason: String },
+
+    /// A stored voxel references an entry outside of the stored palettes
+    PaletteIndexOutOfRange {
+        node_key: usize,
+        index: usize,
+        palette_size: usize,
+    },
+
+    /// Nodes of a paged boxtree could not be loaded from its file, so they would be lost on save
+    NodesNotLoaded { node_keys: Vec<usize> },
+}
+
+#[cfg(feature = "bytecode")]
+impl std::fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerializationError::Io(err) => write!(f, "I/O error: {err}"),
+            SerializationError::VersionMismatch { library, tree } => write!(
+                f,
+                "Boxtree version v{}.{}.{} is incompatible with library version v{}.{}.{}",
+                tree.major(),
+                tree.minor(),
+                tree.patch(),
+                library.major(),
+                library.minor(),
+                library.patch()
+            ),
+            SerializationError::Malformed(reason) => write!(f, "Malformed boxtree: {reason}"),
+            SerializationError::MalformedNode { node_key, reason } => {
+                write!(f, "Malformed node[{node_key}]: {reason}")
+            }
+            SerializationError::PaletteIndexOutOfRange {
+                node_key,
+                index,
+                palette_size,
+            } => write!(
+                f,
+                "Node[{node_key}] references palette index {index}, but palette size is {palette_size}"
+            ),
+            SerializationError::NodesNotLoaded { node_keys } => {
+                write!(f, "Nodes {node_keys:?} could not be loaded from the paged file")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl Error for SerializationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SerializationError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl From<std::io::Error> for SerializationError {
+    fn from(err: std::io::Error) -> Self {
+        SerializationError::Io(err)
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl From<bendy::decoding::Error> for SerializationError {
+    fn from(err: bendy::decoding::Error) -> Self {
+        SerializationError::Malformed(err.to_string())
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl From<bendy::encoding::Error> for SerializationError {
+    fn from(err: bendy::encoding::Error) -> Self {
+        SerializationError::Malformed(err.to_string())
+    }
+}
+
+/// Inconsistencies found inside the structure of the boxtree, see @BoxTree::validate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The root node of the boxtree is not a valid node
+    MissingRoot,
+
+    /// A node references a child which is not a valid node
+    InvalidChild {
+        node_key: usize,
+        sectant: u8,
+        child_key: usize,
+    },
+
+    /// A node is referenced as a child by more than one node
+    SharedNode { node_key: usize },
+
+    /// The content of a node contradicts the rest of the structure ( refer to reason )
+    InvalidContent { node_key: usize, reason: String },
+
+    /// A voxel of a node references an entry outside of the palettes
+    PaletteIndexOutOfRange {
+        node_key: usize,
+        index: usize,
+        palette_size: usize,
+    },
+
+    /// The occupied bits of a node do not agree with its content
+    OccupiedBitsMismatch {
+        node_key: usize,
+        stored: u64,
+        expected: u64,
+    },
+
+    /// A node is marked occluded from every side, but not every side has a full node next to it
+    OcclusionBitsMismatch {
+        node_key: usize,
+        stored: u8,
+        expected: u8,
+    },
+
+    /// The MIP of a node differs from the one resampled from its content
+    OutdatedMip { node_key: usize },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::MissingRoot => write!(f, "Root node is missing"),
+            ValidationError::InvalidChild {
+                node_key,
+                sectant,
+                child_key,
+            } => write!(
+                f,
+                "Node[{node_key}] references child key {child_key} at sectant {sectant}, which is not a valid node"
+            ),
+            ValidationError::SharedNode { node_key } => {
+                write!(f, "Node[{node_key}] is referenced by multiple parents")
+            }
+            ValidationError::InvalidContent { node_key, reason } => {
+                write!(f, "Invalid content in node[{node_key}]: {reason}")
+            }
+            ValidationError::PaletteIndexOutOfRange {
+                node_key,
+                index,
+                palette_size,
+            } => write!(
+                f,
+                "Node[{node_key}] references palette index {index}, but palette size is {palette_size}"
+            ),
+            ValidationError::OccupiedBitsMismatch {
+                node_key,
+                stored,
+                expected,
+            } => write!(
+                f,
+                "Node[{node_key}] has occupied bits {stored:#018x}, instead of {expected:#018x}"
+            ),
+            ValidationError::OcclusionBitsMismatch {
+                node_key,
+                stored,
+                expected,
+            } => write!(
+                f,
+                "Node[{node_key}] is marked occluded ({stored:#08b}), but only {expected:#08b} sides are occluded"
+            ),
+            ValidationError::OutdatedMip { node_key } => {
+                write!(f, "MIP of node[{node_key}] is not up to date")
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
 /// An entry for stored voxel data
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BoxTreeEntry<'a, T: VoxelData> {
@@ -37,26 +238,69 @@ pub enum BoxTreeEntry<'a, T: VoxelData> {
 }
 
 /// Data representation for a matrix of voxels
-#[derive(Debug, Default, Clone, PartialEq)]
+/// Equality is based on the contained voxels, so a Parted and a Packed brick might be equal
+#[derive(Debug, Default, Clone)]
 pub(crate) enum BrickData<T>
 where
-    T: Clone + PartialEq + Clone,
+    T: Clone + PartialEq + CompactVoxel,
 {
     /// Brick is empty
     #[default]
     Empty,
 
-    /// Brick is an NxNxN matrix, size is determined by the parent entity
-    Parted(Vec<T>),
+    /// Brick is an NxNxN matrix, size is determined by the parent entity, see `PartedBrick`
+    Parted(PartedBrick<T>),
+
+    /// Brick is an NxNxN matrix, stored in a bit packed form, see `PackedBrick`
+    Packed(PackedBrick<T>),
 
     /// Brick is a single item T, which takes up the entirety of the brick
     Solid(T),
 }
 
+/// Voxels of a brick stored one by one, in the order of their flat projection
+/// Voxels take up 32 bits each, whenever each voxel inside the brick fits into that, see `CompactVoxel`
+/// E.g. voxels of trees with @PaletteIndexWidth::Narrow are always stored on 32 bits
+#[derive(Debug, Clone)]
+pub(crate) enum PartedBrick<T> {
+    /// Voxels in their compact form
+    Compact(Vec<u32>),
+
+    /// Voxels in their original form
+    Full(Vec<T>),
+}
+
+/// Voxel values which might be stored on 32 bits inside Parted bricks
+pub(crate) trait CompactVoxel: Sized {
+    /// The compact form of the voxel, in case it fits into 32 bits
+    fn compacted(&self) -> Option<u32>;
+
+    /// Restores the voxel from its compact form
+    fn expanded(compact: u32) -> Self;
+}
+
+/// Voxels of a brick stored as indices into a palette local to the brick
+/// Indices are bit packed, one index takes up 1, 2, 4, 8 or 16 bits,
+/// depending on the number of different voxels inside the brick
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PackedBrick<T> {
+    /// The different voxels inside the brick; Might contain entries not used anymore
+    pub(crate) palette: Vec<T>,
+
+    /// The number of bits one index into the palette takes up
+    pub(crate) index_bits: u32,
+
+    /// The palette index of each voxel, packed together
+    pub(crate) indices: Vec<u64>,
+
+    /// The number of voxels inside the brick
+    pub(crate) len: usize,
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub(crate) enum NodeContent<T>
 where
-    T: Clone + PartialEq + Clone,
+    T: Clone + PartialEq + CompactVoxel,
 {
     /// Node is empty
     #[default]
@@ -108,7 +352,23 @@ pub struct Albedo {
     pub a: u8,
 }
 
-pub(crate) type PaletteIndexValues = u32;
+/// The color palette index in the lower 32 bits, and the data palette index in the upper 32 bits
+/// Stored in the layout of the tree's @PaletteIndexWidth when serialized, and inside Parted bricks, see `PartedBrick`
+pub(crate) type PaletteIndexValues = u64;
+
+/// The width of the palette indices a tree is serialized and rendered with,
+/// which limits the number of colors and data values the tree can hold
+/// Narrow trees are widened automatically when their palettes outgrow them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaletteIndexWidth {
+    /// Colors and data are referenced on 16 bits each, allowing 65535 of both
+    #[default]
+    Narrow,
+
+    /// Colors are referenced on 31 bits, because of the brick layout on the GPU,
+    /// and data on 32 bits; serialized voxels take more space
+    Wide,
+}
 pub type OctreeMIPMapStrategy = HashMap<usize, MIPResamplingMethods>;
 
 /// Implemented methods for MIP sampling. Default is set for
@@ -118,7 +378,8 @@ pub enum MIPResamplingMethods {
     /// MIP sampled from the MIPs below it, each voxel is the gamma corrected
     /// average of the voxels the cell contains on the same space one level below.
     /// (Gamma is set to be 2.)
-    /// Warning: this introduces a significant amount of new colors into the palette
+    /// Warning: this introduces a significant amount of new colors into the palette,
+    /// which might need @PaletteIndexWidth::Wide
     #[default]
     BoxFilter,
 
@@ -151,6 +412,63 @@ pub enum MIPResamplingMethods {
 /// A helper object for setting Octree MIP map resampling strategy
 pub struct StrategyUpdater<'a, T: Default + Clone + Eq + Hash>(pub(crate) &'a mut BoxTree<T>);
 
+/// A helper object collecting data updates for the boxtree, see @BoxTree::begin_edit
+/// Updates are applied together on @commit, simplification, MIP updates and
+/// update triggers are done once for every modified node instead of once per update
+/// Collected updates are discarded if the object is dropped without calling @commit
+pub struct BoxTreeEdit<'a, T: Default + Clone + Eq + Hash> {
+    pub(crate) tree: &'a mut BoxTree<T>,
+    pub(crate) operations: Vec<(V3c<u32>, EditOperation)>,
+}
+
+/// An update collected inside a @BoxTreeEdit
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum EditOperation {
+    Insert {
+        overwrite_if_empty: bool,
+        content: PaletteIndexValues,
+    },
+    Clear,
+}
+
+/// A box of voxels with the same content, recorded by the edit journal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct JournalRegion {
+    pub(crate) min: V3c<u32>,
+    pub(crate) max: V3c<u32>,
+    pub(crate) content: PaletteIndexValues,
+}
+
+/// The previous and the current content of an updated area, recorded by the edit journal
+/// Only the occupied parts of the area are stored, one region for each solid brick or brick voxel
+#[derive(Debug, Clone)]
+pub(crate) struct JournalEntry {
+    pub(crate) min: V3c<u32>,
+    pub(crate) max: V3c<u32>,
+    pub(crate) before: Vec<JournalRegion>,
+    pub(crate) after: Vec<JournalRegion>,
+}
+
+/// A named group of voxel updates, undone or redone together
+#[derive(Debug, Clone)]
+pub(crate) struct JournalTransaction {
+    pub(crate) name: String,
+    pub(crate) entries: Vec<JournalEntry>,
+}
+
+/// History of the updates done to the boxtree, see @BoxTree::switch_journal
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EditJournal {
+    /// Updates are collected into this transaction until it is ended
+    pub(crate) open_transaction: Option<JournalTransaction>,
+
+    /// Transactions which can be undone, the latest one is at the end
+    pub(crate) undo_stack: Vec<JournalTransaction>,
+
+    /// Transactions which can be redone, the latest undone one is at the end
+    pub(crate) redo_stack: Vec<JournalTransaction>,
+}
+
 /// Configuration object for storing MIP map strategy
 /// Don't forget to @recalculate_mip after you've enabled it, as it is
 /// only updated on boxtree updates otherwise.
@@ -211,6 +529,75 @@ pub(crate) type BoxTreeNodeAccessStack = Vec<(usize, u8)>;
 /// Fn(node_access_stack: Vec<(usize, u8)>, updated_sectants: Vec<u8>)
 pub(crate) type BoxTreeUpdatedSignal = dyn Fn(BoxTreeNodeAccessStack, Vec<u8>) + Send + Sync;
 
+/// The kind of change made to the contents of a BoxTree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoxTreeChangeKind {
+    /// Data was inserted, overwriting existing data
+    Insert,
+
+    /// Existing data was updated with the given components
+    Update,
+
+    /// Data was erased
+    Clear,
+
+    /// MIP maps were recalculated without changes to the data
+    MIP,
+}
+
+/// An axis of the tree to rotate around or mirror along, see @BoxTree::rotate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoxTreeAxis {
+    X,
+    Y,
+    Z,
+}
+
+/// Decides how the contents of two trees are combined, see @BoxTree::paste
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PasteMode {
+    /// The pasted area is replaced by the contents of the other tree, including its empty voxels
+    Overwrite,
+
+    /// Only empty voxels are filled with the contents of the other tree
+    KeepExisting,
+
+    /// Every voxel is replaced by the contents of the other tree, except where it is empty
+    SkipEmpty,
+}
+
+/// The order of nodes in the node storage after defragmentation, see @BoxTree::defragment
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefragmentOrder {
+    /// Every node is followed by its children, visited by their index within the parent
+    #[default]
+    DepthFirst,
+
+    /// Every node is followed by its children, visited along a Z-order curve,
+    /// so nodes close to each other in space are close to each other in storage
+    Morton,
+}
+
+/// A change made to the contents of a BoxTree, see @BoxTree::subscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoxTreeChange {
+    /// The kind of the change
+    pub kind: BoxTreeChangeKind,
+
+    /// The lowest corner of the area affected by the change
+    pub min: V3c<u32>,
+
+    /// The highest corner of the area affected by the change (exclusive)
+    pub max: V3c<u32>,
+}
+
+/// Identifies a subscription to the changes of a BoxTree, see @BoxTree::unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionHandle(pub(crate) u64);
+
+/// A function being called with every change made to a BoxTree
+pub(crate) type BoxTreeChangeSignal = dyn Fn(&BoxTreeChange) + Send + Sync;
+
 /// Sparse 64Tree of Voxel Bricks, where each leaf node contains a brick of voxels.
 /// A Brick is a 3 dimensional matrix, each element of it containing a voxel.
 /// A Brick can be indexed directly, as opposed to the boxtree which is essentially a
@@ -223,6 +610,10 @@ where
     /// Feature flag to enable/disable simplification attempts during boxtree update operations
     pub auto_simplify: bool,
 
+    /// Feature flag to leave out unused palette entries when the boxtree is serialized, see @BoxTree::compact_palettes
+    /// The boxtree itself is not modified by serialization
+    pub compact_palettes_on_save: bool,
+
     /// Size of one brick in a leaf node (dim^3)
     pub(crate) brick_dim: u32,
 
@@ -232,9 +623,8 @@ where
     /// Storing data at each position through palette index values
     pub(crate) nodes: ObjectPool<NodeData>,
 
-    /// The albedo colors used by the boxtree. Maximum 65535 colors can be used at once
-    /// because of a limitation on GPU raytracing, to spare space index values refering
-    /// the palettes are stored on 2 Bytes
+    /// The albedo colors used by the boxtree. The number of colors which can be used at once
+    /// depends on @palette_index_width
     pub(crate) voxel_color_palette: Vec<Albedo>, // referenced by @nodes
 
     /// The different instances of user data stored within the boxtree
@@ -247,9 +637,47 @@ where
     /// Cache variable to help find user data in the palette
     pub(crate) map_to_data_index_in_palette: HashMap<T, usize>,
 
+    /// The width of palette indices used to serialize and render the tree
+    pub(crate) palette_index_width: PaletteIndexWidth,
+
+    /// Incremented whenever existing entries of the color palette are changed,
+    /// so renderers know to upload the whole palette again
+    pub(crate) palette_revision: u32,
+
     /// The stored MIP map strategy
     pub(crate) mip_map_strategy: MIPMapStrategy,
 
     /// The signals to be called whenever the tree is updated
     pub(crate) update_triggers: Vec<Arc<BoxTreeUpdatedSignal>>,
+
+    /// The functions subscribed to changes of the tree, see @BoxTree::subscribe
+    pub(crate) subscriptions: Vec<(SubscriptionHandle, Arc<BoxTreeChangeSignal>)>,
+
+    /// The history of updates, if recording is enabled, see @BoxTree::switch_journal
+    pub(crate) journal: Option<EditJournal>,
+}
+
+/// A read-only copy of a BoxTree at the time it was taken, see @BoxTree::snapshot
+/// Every read operation of the tree is available through dereferencing
+#[derive(Clone)]
+pub struct BoxTreeSnapshot<T = u32>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// The copy of the tree, sharing its nodes with the original until they are modified
+    pub(crate) tree: BoxTree<T>,
+}
+
+/// A tree shared between a single writer and any number of readers, see @SharedBoxTree::edit
+/// Readers access the latest published snapshot of the tree, so they are never blocked by edits
+#[derive(Clone)]
+pub struct SharedBoxTree<T = u32>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// The tree being edited, edits are applied one after another
+    pub(crate) tree: Arc<Mutex<BoxTree<T>>>,
+
+    /// The state of the tree after the latest finished edit
+    pub(crate) published: Arc<RwLock<Arc<BoxTreeSnapshot<T>>>>,
 }
@@ -0,0 +1,271 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeChange, BoxTreeChangeKind, BoxTreeEdit, BoxTreeUpdatedSignalParams,
+            EditOperation, OctreeError,
+        },
+        BoxTree, BoxTreeEntry, StrategyUpdater, VoxelData, BOX_NODE_DIMENSION,
+    },
+    spatial::{math::vector::V3c, Cube},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Starts collecting data updates to be applied together, see @BoxTreeEdit
+    pub fn begin_edit(&mut self) -> BoxTreeEdit<'_, T> {
+        BoxTreeEdit {
+            tree: self,
+            operations: vec![],
+        }
+    }
+
+    /// Applies every update done through the given function together, see @BoxTreeEdit
+    /// * Returns with the result of the given function
+    pub fn batch<R, F: FnOnce(&mut BoxTreeEdit<T>) -> R>(&mut self, edit: F) -> R {
+        let mut transaction = self.begin_edit();
+        let result = edit(&mut transaction);
+        transaction.commit();
+        result
+    }
+
+    /// Checks if the given position is contained within the tree
+    fn check_position(&self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*position)) {
+            return Err(OctreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: VoxelData> BoxTreeEdit<'_, T> {
+    /// Inserts the given data into the given voxel position on @commit, see @BoxTree::insert
+    /// * `position` - the position to insert the data into, must be contained within the tree
+    pub fn insert<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        self.insert_internal(true, position, data.into())
+    }
+
+    /// Updates the given data at the given voxel position on @commit, see @BoxTree::update
+    /// * `position` - the position to insert the data into, must be contained within the tree
+    pub fn update<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        self.insert_internal(false, position, data.into())
+    }
+
+    /// Clears the voxel at the given position on @commit
+    /// * `position` - the position to clear, must be contained within the tree
+    pub fn clear(&mut self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        self.tree.check_position(position)?;
+        self.operations.push((*position, EditOperation::Clear));
+        Ok(())
+    }
+
+    /// The number of updates collected so far
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Returns with true if no updates were collected yet
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    fn insert_internal(
+        &mut self,
+        overwrite_if_empty: bool,
+        position: &V3c<u32>,
+        data: BoxTreeEntry<T>,
+    ) -> Result<(), OctreeError> {
+        self.tree.check_position(position)?;
+
+        // Nothing to do when no operations are requested
+        if data.is_none() {
+            return Ok(());
+        }
+        let content = self.tree.add_to_palette(&data);
+        self.operations.push((
+            *position,
+            EditOperation::Insert {
+                overwrite_if_empty,
+                content,
+            },
+        ));
+        Ok(())
+    }
+
+    /// Applies the collected updates in the order they were made
+    /// Simplification and MIP updates are done once for every modified node afterwards,
+    /// and update triggers are called once for each of them
+    /// Subscriptions are notified once for every kind of update, with the area covering all of them
+    pub fn commit(self) {
+        let tree = self.tree;
+        let mut operations = self.operations;
+        if operations.is_empty() {
+            return;
+        }
+
+        // Sort updates by the leaf node they belong to
+        // The sort is stable, so updates at the same position are kept in order
+        let leaf_size = tree.brick_dim * BOX_NODE_DIMENSION as u32;
+        operations.sort_by_key(|(position, _)| {
+            let leaf_position = *position / leaf_size;
+            (leaf_position.z, leaf_position.y, leaf_position.x)
+        });
+
+        // Collect the area changed by each kind of update
+        let mut changes: Vec<BoxTreeChange> = vec![];
+        for (position, operation) in operations.iter() {
+            let kind = match operation {
+                EditOperation::Insert {
+                    overwrite_if_empty: true,
+                    ..
+                } => BoxTreeChangeKind::Insert,
+                EditOperation::Insert { .. } => BoxTreeChangeKind::Update,
+                EditOperation::Clear => BoxTreeChangeKind::Clear,
+            };
+            if let Some(change) = changes.iter_mut().find(|change| change.kind == kind) {
+                change.min = V3c::new(
+                    change.min.x.min(position.x),
+                    change.min.y.min(position.y),
+                    change.min.z.min(position.z),
+                );
+                change.max = V3c::new(
+                    change.max.x.max(position.x + 1),
+                    change.max.y.max(position.y + 1),
+                    change.max.z.max(position.z + 1),
+                );
+            } else {
+                changes.push(BoxTreeChange {
+                    kind,
+                    min: *position,
+                    max: *position + V3c::unit(1),
+                });
+            }
+        }
+
+        // Defer simplification, MIP updates, update triggers and change notifications,
+        // while collecting the nodes modified by each update
+        let auto_simplify = std::mem::replace(&mut tree.auto_simplify, false);
+        let mips_enabled = std::mem::replace(&mut tree.mip_map_strategy.enabled, false);
+        let subscriptions = std::mem::take(&mut tree.subscriptions);
+        let update_triggers = std::mem::take(&mut tree.update_triggers);
+        let updated_nodes: Arc<Mutex<Vec<BoxTreeUpdatedSignalParams>>> = Arc::default();
+        let updated_nodes_clone = updated_nodes.clone();
+        tree.update_triggers
+            .push(Arc::new(move |node_stack, modified_sectants| {
+                updated_nodes_clone
+                    .lock()
+                    .expect("Expected to be able to collect updated nodes")
+                    .push((node_stack, modified_sectants));
+            }));
+
+        // Updates are recorded as one transaction, unless a transaction is already open
+        let record_transaction = tree
+            .journal
+            .as_ref()
+            .is_some_and(|journal| journal.open_transaction.is_none());
+        if record_transaction {
+            tree.begin_transaction("batch");
+        }
+        for (position, operation) in operations {
+            match operation {
+                EditOperation::Insert {
+                    overwrite_if_empty,
+                    content,
+                } => {
+                    let captured = tree.journal_capture(&position, &(position + V3c::unit(1)));
+                    tree.insert_content_at_lod(overwrite_if_empty, &position, 1, content);
+                    tree.journal_record("batch", captured);
+                }
+                EditOperation::Clear => tree
+                    .clear(&position)
+                    .expect("Expected edit position to be validated already"),
+            }
+        }
+        if record_transaction {
+            tree.end_transaction();
+        }
+
+        tree.update_triggers = update_triggers;
+        tree.subscriptions = subscriptions;
+        tree.auto_simplify = auto_simplify;
+        tree.mip_map_strategy.enabled = mips_enabled;
+        let updated_nodes = std::mem::take(
+            &mut *updated_nodes
+                .lock()
+                .expect("Expected to be able to read updated nodes"),
+        );
+
+        // Coalesce updates of the same node, keeping the latest path to it
+        let mut coalesced_updates: Vec<BoxTreeUpdatedSignalParams> = vec![];
+        let mut update_index_by_node = HashMap::<usize, usize>::new();
+        for (node_stack, modified_sectants) in updated_nodes {
+            let node_key = node_stack.last().unwrap().0;
+            if let Some(index) = update_index_by_node.get(&node_key) {
+                let (coalesced_stack, coalesced_sectants) = &mut coalesced_updates[*index];
+                *coalesced_stack = node_stack;
+                for sectant in modified_sectants {
+                    if !coalesced_sectants.contains(&sectant) {
+                        coalesced_sectants.push(sectant);
+                    }
+                }
+            } else {
+                update_index_by_node.insert(node_key, coalesced_updates.len());
+                coalesced_updates.push((node_stack, modified_sectants));
+            }
+        }
+
+        // Every node on the path of an update is to be processed, children before their parents
+        let mut dirty_nodes = HashMap::<usize, BoxTreeUpdatedSignalParams>::new();
+        for (node_stack, _) in coalesced_updates.iter() {
+            for depth in 1..=node_stack.len() {
+                dirty_nodes.insert(
+                    node_stack[depth - 1].0,
+                    (node_stack[..depth].to_vec(), vec![]),
+                );
+            }
+        }
+        let mut dirty_nodes = dirty_nodes.into_values().collect::<Vec<_>>();
+        dirty_nodes.sort_by_key(|(node_stack, _)| std::cmp::Reverse(node_stack.len()));
+        for (node_stack, _) in dirty_nodes {
+            if !tree.access_stack_is_valid(&node_stack) {
+                continue;
+            }
+            let node_key = node_stack.last().unwrap().0;
+            if auto_simplify {
+                tree.simplify(node_key, false);
+            }
+            if mips_enabled {
+                let mut node_bounds = Cube::root_bounds(tree.boxtree_size as f32);
+                for (_parent_key, sectant) in node_stack[..node_stack.len() - 1].iter() {
+                    node_bounds = node_bounds.child_bounds_for(*sectant);
+                }
+                StrategyUpdater(&mut *tree).recalculate_mip(node_key, &node_bounds);
+            }
+        }
+
+        tree.signal_updates(coalesced_updates);
+        for change in changes {
+            tree.signal_change(change.kind, &change.min, &change.max);
+        }
+    }
+}
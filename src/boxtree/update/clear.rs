@@ -1,7 +1,10 @@
 use crate::{
     boxtree::{
         iterate::execute_for_relevant_sectants,
-        types::{BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues},
+        types::{
+            BoxTreeChangeKind, BrickData, NodeChildren, NodeContent, OctreeError,
+            PaletteIndexValues,
+        },
         BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
     },
     object_pool::empty_marker,
@@ -39,6 +42,8 @@ impl<T: VoxelData> BoxTree<T> {
         if clear_size == 0 {
             return Ok(());
         }
+        let captured = self.journal_capture(position_u32, &(*position_u32 + V3c::unit(clear_size)));
+
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(
             Self::ROOT_NODE_KEY as usize,
@@ -138,7 +143,7 @@ impl<T: VoxelData> BoxTree<T> {
                                     &self.voxel_color_palette,
                                     &self.voxel_data_palette,
                                 ),
-                                BrickData::Parted(brick) => {
+                                brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                                     let index_in_matrix = position - current_bounds.min_position;
                                     let index_in_matrix = flat_projection(
                                         index_in_matrix.x as usize,
@@ -147,7 +152,7 @@ impl<T: VoxelData> BoxTree<T> {
                                         self.brick_dim as usize,
                                     );
                                     NodeContent::pix_points_to_empty(
-                                        &brick[index_in_matrix],
+                                        &brick.voxel(index_in_matrix).unwrap(),
                                         &self.voxel_color_palette,
                                         &self.voxel_data_palette,
                                     )
@@ -161,7 +166,7 @@ impl<T: VoxelData> BoxTree<T> {
                                         &self.voxel_color_palette,
                                         &self.voxel_data_palette,
                                     ),
-                                    BrickData::Parted(brick) => {
+                                    brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                                         let index_in_matrix =
                                             position - current_bounds.min_position;
                                         let index_in_matrix = flat_projection(
@@ -171,7 +176,7 @@ impl<T: VoxelData> BoxTree<T> {
                                             self.brick_dim as usize,
                                         );
                                         NodeContent::pix_points_to_empty(
-                                            &brick[index_in_matrix],
+                                            &brick.voxel(index_in_matrix).unwrap(),
                                             &self.voxel_color_palette,
                                             &self.voxel_data_palette,
                                         )
@@ -302,18 +307,14 @@ impl<T: VoxelData> BoxTree<T> {
 
         // processing higher level nodes
         while !node_stack.is_empty() {
-            erased_whole_sectants = if self.post_process_node_clear(
+            let node_erased = self.post_process_node_clear(
                 &node_stack,
                 bounds_stack.last().unwrap(),
                 &actual_update_size,
                 position_u32,
                 clear_size,
                 erased_whole_sectants,
-            ) {
-                vec![bounds_stack.last().unwrap().sectant_for(&position)]
-            } else {
-                vec![]
-            };
+            );
 
             // If any Nodes fail to simplify, no need to continue because their parents can not be simplified further
             if simplifyable {
@@ -322,6 +323,12 @@ impl<T: VoxelData> BoxTree<T> {
 
             node_stack.pop();
             bounds_stack.pop();
+
+            // An erased node is removed from its parent under the sectant it is stored at
+            erased_whole_sectants = match node_stack.last() {
+                Some((_parent_key, node_sectant)) if node_erased => vec![*node_sectant],
+                _ => vec![],
+            };
         }
 
         // Call update trigger for data updates
@@ -333,6 +340,12 @@ impl<T: VoxelData> BoxTree<T> {
                 every_updated_bottom_sectant.clone(),
             );
         }
+        self.journal_record("clear", captured);
+        self.signal_change(
+            BoxTreeChangeKind::Clear,
+            position_u32,
+            &(*position_u32 + V3c::unit(clear_size)),
+        );
 
         Ok(())
     }
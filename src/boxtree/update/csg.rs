@@ -0,0 +1,485 @@
+use crate::{
+    boxtree::{
+        iterate::{BrickSource, BrickTraversal},
+        types::{
+            BoxTreeEdit, EditOperation, NodeContent, OctreeError, PaletteIndexValues, PasteMode,
+        },
+        update::region::{Region, RegionShape},
+        BoxTree, BoxTreeEntry, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::collections::HashMap;
+
+/// The boolean operations to combine the contents of two trees with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+    Xor,
+}
+
+/// The outcome of a boolean operation on a voxel, or on an area of the same voxels
+enum CsgResult {
+    Keep,
+    Clear,
+    Set(PaletteIndexValues),
+}
+
+/// The contents of an area inside a tree
+enum AreaContent {
+    Empty,
+    Solid(PaletteIndexValues),
+    Mixed,
+}
+
+/// The parameters and the collected state of an ongoing boolean operation
+struct CsgContext<'a, T: VoxelData, F> {
+    other: &'a BoxTree<T>,
+
+    /// The position of the origin of the other tree inside the updated tree
+    offset: V3c<u32>,
+
+    operation: CsgOperation,
+    merge: F,
+
+    /// Palette indices of the other tree mapped to the palette of the updated tree
+    palette_map: HashMap<PaletteIndexValues, PaletteIndexValues>,
+
+    /// Results of the merge function for each pair of palette indices already merged
+    merged: HashMap<(PaletteIndexValues, PaletteIndexValues), PaletteIndexValues>,
+
+    /// Updates of single voxels, applied together after traversal
+    operations: Vec<(V3c<u32>, EditOperation)>,
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Adds the contents of the other tree to this tree
+    /// Voxels occupied in both trees are set to the result of the given merge function
+    /// * `other` - the tree to combine with, must have the same brick dimension
+    /// * `offset` - the position of the origin of the other tree inside this tree, must be contained within the tree
+    /// * `merge` - provides the data for voxels occupied in both trees from the data of this and the other tree,
+    ///   called once for each different pair of data
+    pub fn union<F>(&mut self, other: &Self, offset: &V3c<u32>, merge: F) -> Result<(), OctreeError>
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        self.combine(other, offset, CsgOperation::Union, merge)
+    }
+
+    /// Keeps only the voxels of this tree which are also occupied in the other tree
+    /// Everything outside the area covered by the other tree is cleared
+    /// * `other` - the tree to combine with, must have the same brick dimension
+    /// * `offset` - the position of the origin of the other tree inside this tree, must be contained within the tree
+    /// * `merge` - provides the data for kept voxels from the data of this and the other tree,
+    ///   called once for each different pair of data
+    pub fn intersection<F>(
+        &mut self,
+        other: &Self,
+        offset: &V3c<u32>,
+        merge: F,
+    ) -> Result<(), OctreeError>
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        self.combine(other, offset, CsgOperation::Intersection, merge)
+    }
+
+    /// Clears every voxel of this tree which is occupied in the other tree
+    /// * `other` - the tree to combine with, must have the same brick dimension
+    /// * `offset` - the position of the origin of the other tree inside this tree, must be contained within the tree
+    pub fn difference(&mut self, other: &Self, offset: &V3c<u32>) -> Result<(), OctreeError> {
+        self.combine(other, offset, CsgOperation::Difference, |own, _| own)
+    }
+
+    /// Keeps the voxels occupied in only one of the trees, clearing the ones occupied in both
+    /// * `other` - the tree to combine with, must have the same brick dimension
+    /// * `offset` - the position of the origin of the other tree inside this tree, must be contained within the tree
+    pub fn xor(&mut self, other: &Self, offset: &V3c<u32>) -> Result<(), OctreeError> {
+        self.combine(other, offset, CsgOperation::Xor, |own, _| own)
+    }
+
+    /// Applies the given boolean operation with the other tree, recorded into the journal as one transaction
+    fn combine<F>(
+        &mut self,
+        other: &Self,
+        offset: &V3c<u32>,
+        operation: CsgOperation,
+        merge: F,
+    ) -> Result<(), OctreeError>
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        if other.brick_dim != self.brick_dim {
+            return Err(OctreeError::InvalidBrickDimension(other.brick_dim));
+        }
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*offset)) {
+            return Err(OctreeError::InvalidPosition {
+                x: offset.x,
+                y: offset.y,
+                z: offset.z,
+            });
+        }
+
+        // Only the area covered by the other tree is updated, except for intersections
+        let area_max = V3c::new(
+            (offset.x + other.boxtree_size).min(self.boxtree_size),
+            (offset.y + other.boxtree_size).min(self.boxtree_size),
+            (offset.z + other.boxtree_size).min(self.boxtree_size),
+        );
+        let captured = if CsgOperation::Intersection == operation {
+            self.journal_capture(&V3c::unit(0), &V3c::unit(self.boxtree_size))
+        } else {
+            self.journal_capture(offset, &area_max)
+        };
+        let journal = self.journal.take();
+
+        if CsgOperation::Intersection == operation {
+            for (min, max) in Self::boxes_around(offset, &area_max, self.boxtree_size) {
+                self.clear_box(&min, &max)?;
+            }
+        }
+        let mut context = CsgContext {
+            other,
+            offset: *offset,
+            operation,
+            merge,
+            palette_map: HashMap::new(),
+            merged: HashMap::new(),
+            operations: vec![],
+        };
+        self.combine_area(
+            &mut context,
+            &Cube::root_bounds(self.boxtree_size as f32),
+            offset,
+            &area_max,
+        );
+        if !context.operations.is_empty() {
+            BoxTreeEdit {
+                tree: &mut *self,
+                operations: context.operations,
+            }
+            .commit();
+        }
+
+        self.journal = journal;
+        self.journal_record(
+            match operation {
+                CsgOperation::Union => "union",
+                CsgOperation::Intersection => "intersection",
+                CsgOperation::Difference => "difference",
+                CsgOperation::Xor => "xor",
+            },
+            captured,
+        );
+        Ok(())
+    }
+
+    /// Applies the boolean operation of the context inside the given bounds of the tree
+    /// Areas where both trees are empty or solid are updated as a whole, others are divided further
+    /// until they are the size of a brick, where every voxel is combined one by one
+    /// * `bounds` - the part of the tree to update
+    /// * `area_min` - the lowest corner of the area covered by the other tree
+    /// * `area_max` - the highest corner of the area covered by the other tree (exclusive)
+    fn combine_area<F>(
+        &mut self,
+        context: &mut CsgContext<T, F>,
+        bounds: &Cube,
+        area_min: &V3c<u32>,
+        area_max: &V3c<u32>,
+    ) where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        let bounds_min: V3c<u32> = bounds.min_position.into();
+        let bounds_size = bounds.size as u32;
+        let min = V3c::new(
+            bounds_min.x.max(area_min.x),
+            bounds_min.y.max(area_min.y),
+            bounds_min.z.max(area_min.z),
+        );
+        let max = V3c::new(
+            (bounds_min.x + bounds_size).min(area_max.x),
+            (bounds_min.y + bounds_size).min(area_max.y),
+            (bounds_min.z + bounds_size).min(area_max.z),
+        );
+        if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+            return;
+        }
+
+        let own_content = self.area_content(&min, &max);
+        let other_content = context
+            .other
+            .area_content(&(min - context.offset), &(max - context.offset));
+        let operation = context.operation;
+        let result = match (&own_content, &other_content) {
+            (AreaContent::Mixed, AreaContent::Empty) => {
+                if CsgOperation::Intersection == operation {
+                    CsgResult::Clear
+                } else {
+                    CsgResult::Keep
+                }
+            }
+            (AreaContent::Empty, AreaContent::Mixed) => {
+                if matches!(operation, CsgOperation::Union | CsgOperation::Xor) {
+                    // The contents of the other tree are copied as they are
+                    self.paste_area(
+                        context.other,
+                        &(min - context.offset),
+                        &(max - context.offset),
+                        &min,
+                        PasteMode::SkipEmpty,
+                    );
+                }
+                CsgResult::Keep
+            }
+            (AreaContent::Mixed, AreaContent::Solid(_))
+                if CsgOperation::Difference == operation =>
+            {
+                CsgResult::Clear
+            }
+            (
+                AreaContent::Empty | AreaContent::Solid(_),
+                AreaContent::Empty | AreaContent::Solid(_),
+            ) => {
+                let own_voxel = match own_content {
+                    AreaContent::Solid(voxel) => voxel,
+                    _ => empty_marker(),
+                };
+                let other_voxel = match other_content {
+                    AreaContent::Solid(voxel) => voxel,
+                    _ => empty_marker(),
+                };
+                self.combine_voxel(context, own_voxel, other_voxel)
+            }
+            _ => {
+                if bounds_size > self.brick_dim {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        self.combine_area(
+                            context,
+                            &bounds.child_bounds_for(sectant),
+                            area_min,
+                            area_max,
+                        );
+                    }
+                } else {
+                    self.combine_voxels(context, &min, &max);
+                }
+                CsgResult::Keep
+            }
+        };
+
+        let single_voxel = max - min == V3c::unit(1);
+        match result {
+            CsgResult::Keep => {}
+            CsgResult::Clear if single_voxel => {
+                context.operations.push((min, EditOperation::Clear))
+            }
+            CsgResult::Set(content) if single_voxel => context.operations.push((
+                min,
+                EditOperation::Insert {
+                    overwrite_if_empty: true,
+                    content,
+                },
+            )),
+            CsgResult::Clear => self.update_region(
+                &Region {
+                    min,
+                    max,
+                    shape: RegionShape::Box,
+                },
+                empty_marker(),
+            ),
+            CsgResult::Set(content) => self.update_region(
+                &Region {
+                    min,
+                    max,
+                    shape: RegionShape::Box,
+                },
+                content,
+            ),
+        }
+    }
+
+    /// Applies the boolean operation of the context on every voxel of the given area one by one
+    fn combine_voxels<F>(&mut self, context: &mut CsgContext<T, F>, min: &V3c<u32>, max: &V3c<u32>)
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let other_root_bounds = Cube::root_bounds(context.other.boxtree_size as f32);
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    let position = V3c::new(x, y, z);
+                    let own_voxel =
+                        self.get_internal(Self::ROOT_NODE_KEY as usize, root_bounds, &position);
+                    let other_voxel = context.other.get_internal(
+                        Self::ROOT_NODE_KEY as usize,
+                        other_root_bounds,
+                        &(position - context.offset),
+                    );
+                    match self.combine_voxel(context, own_voxel, other_voxel) {
+                        CsgResult::Keep => {}
+                        CsgResult::Clear => {
+                            context.operations.push((position, EditOperation::Clear))
+                        }
+                        CsgResult::Set(content) => context.operations.push((
+                            position,
+                            EditOperation::Insert {
+                                overwrite_if_empty: true,
+                                content,
+                            },
+                        )),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Provides the outcome of the boolean operation of the context for the given voxels
+    /// * `own_voxel` - the voxel of this tree
+    /// * `other_voxel` - the voxel of the other tree, in the palette of the other tree
+    fn combine_voxel<F>(
+        &mut self,
+        context: &mut CsgContext<T, F>,
+        own_voxel: PaletteIndexValues,
+        other_voxel: PaletteIndexValues,
+    ) -> CsgResult
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        let own_empty = NodeContent::pix_points_to_empty(
+            &own_voxel,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        let other_empty = NodeContent::pix_points_to_empty(
+            &other_voxel,
+            &context.other.voxel_color_palette,
+            &context.other.voxel_data_palette,
+        );
+        match (context.operation, own_empty, other_empty) {
+            (CsgOperation::Union | CsgOperation::Xor, _, true)
+            | (CsgOperation::Intersection | CsgOperation::Difference, true, _)
+            | (CsgOperation::Difference, _, true) => CsgResult::Keep,
+            (CsgOperation::Union | CsgOperation::Xor, true, false) => CsgResult::Set(
+                self.remap_from(context.other, other_voxel, &mut context.palette_map),
+            ),
+            (CsgOperation::Intersection, false, true)
+            | (CsgOperation::Difference | CsgOperation::Xor, false, false) => CsgResult::Clear,
+            (CsgOperation::Union | CsgOperation::Intersection, false, false) => {
+                let merged = self.merge_voxels(context, own_voxel, other_voxel);
+                if merged == own_voxel {
+                    CsgResult::Keep
+                } else if NodeContent::pix_points_to_empty(
+                    &merged,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                ) {
+                    CsgResult::Clear
+                } else {
+                    CsgResult::Set(merged)
+                }
+            }
+        }
+    }
+
+    /// Provides the result of the merge function of the context for the given voxels,
+    /// added to the palette of this tree
+    /// * `own_voxel` - the voxel of this tree
+    /// * `other_voxel` - the voxel of the other tree, in the palette of the other tree
+    fn merge_voxels<F>(
+        &mut self,
+        context: &mut CsgContext<T, F>,
+        own_voxel: PaletteIndexValues,
+        other_voxel: PaletteIndexValues,
+    ) -> PaletteIndexValues
+    where
+        F: for<'a> Fn(BoxTreeEntry<'a, T>, BoxTreeEntry<'a, T>) -> BoxTreeEntry<'a, T>,
+    {
+        if let Some(merged) = context.merged.get(&(own_voxel, other_voxel)) {
+            return *merged;
+        }
+
+        // The data of this tree is copied, so the palette can be extended with the result
+        let own_entry = NodeContent::pix_get_ref(
+            &own_voxel,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        let own_albedo = own_entry.albedo().copied();
+        let own_data = own_entry.data().cloned();
+        let own_entry = match (&own_albedo, &own_data) {
+            (Some(albedo), Some(data)) => BoxTreeEntry::Complex(albedo, data),
+            (Some(albedo), None) => BoxTreeEntry::Visual(albedo),
+            (None, Some(data)) => BoxTreeEntry::Informative(data),
+            (None, None) => BoxTreeEntry::Empty,
+        };
+        let other_entry = NodeContent::pix_get_ref(
+            &other_voxel,
+            &context.other.voxel_color_palette,
+            &context.other.voxel_data_palette,
+        );
+        let merged = self.add_to_palette(&(context.merge)(own_entry, other_entry));
+        context.merged.insert((own_voxel, other_voxel), merged);
+        merged
+    }
+
+    /// Describes the contents of the given area
+    /// * `min` - the lowest corner of the area, expected to be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), expected to be contained within the tree
+    fn area_content(&self, min: &V3c<u32>, max: &V3c<u32>) -> AreaContent {
+        let mut traversal = BrickTraversal::new(self, *min, *max);
+        let mut content = None;
+        let mut covered_volume = 0;
+        while let Some((bounds, brick_source)) = traversal.next_brick() {
+            let BrickSource::Solid(voxel) = brick_source else {
+                return AreaContent::Mixed;
+            };
+            if content.is_some_and(|content| content != voxel) {
+                return AreaContent::Mixed;
+            }
+            content = Some(voxel);
+            let brick_min: V3c<u32> = bounds.min_position.into();
+            let brick_max = brick_min + V3c::unit(bounds.size as u32);
+            covered_volume += (brick_max.x.min(max.x) - brick_min.x.max(min.x)) as u64
+                * (brick_max.y.min(max.y) - brick_min.y.max(min.y)) as u64
+                * (brick_max.z.min(max.z) - brick_min.z.max(min.z)) as u64;
+        }
+        let area = *max - *min;
+        match content {
+            None => AreaContent::Empty,
+            Some(voxel) if covered_volume == area.x as u64 * area.y as u64 * area.z as u64 => {
+                AreaContent::Solid(voxel)
+            }
+            Some(_) => AreaContent::Mixed,
+        }
+    }
+
+    /// Provides the boxes filling the tree around the given area
+    /// * `min` - the lowest corner of the area
+    /// * `max` - the highest corner of the area (exclusive)
+    /// * `size` - the size of the tree
+    fn boxes_around(min: &V3c<u32>, max: &V3c<u32>, size: u32) -> Vec<(V3c<u32>, V3c<u32>)> {
+        [
+            (V3c::new(0, 0, 0), V3c::new(min.x, size, size)),
+            (V3c::new(max.x, 0, 0), V3c::new(size, size, size)),
+            (V3c::new(min.x, 0, 0), V3c::new(max.x, min.y, size)),
+            (V3c::new(min.x, max.y, 0), V3c::new(max.x, size, size)),
+            (V3c::new(min.x, min.y, 0), V3c::new(max.x, max.y, min.z)),
+            (V3c::new(min.x, min.y, max.z), V3c::new(max.x, max.y, size)),
+        ]
+        .into_iter()
+        .filter(|(min, max)| {
+            min.x < max.x
+                && min.y < max.y
+                && min.z < max.z
+                && min.x < size
+                && min.y < size
+                && min.z < size
+        })
+        .collect()
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
     boxtree::{
         iterate::execute_for_relevant_sectants,
-        types::{BoxTreeEntry, BrickData, NodeContent, NodeData, OctreeError},
+        types::{
+            BoxTreeChangeKind, BoxTreeEntry, BrickData, NodeContent, NodeData, OctreeError,
+            PaletteIndexValues,
+        },
         BoxTree, VoxelData,
     },
     spatial::{
@@ -92,6 +95,47 @@ impl<T: VoxelData> BoxTree<T> {
             return Ok(());
         }
 
+        let target_content = self.add_to_palette(&data);
+        let captured =
+            self.journal_capture(position_u32, &(*position_u32 + V3c::unit(insert_size)));
+        self.insert_content_at_lod(
+            overwrite_if_empty,
+            position_u32,
+            insert_size,
+            target_content,
+        );
+        self.journal_record(
+            if overwrite_if_empty {
+                "insert"
+            } else {
+                "update"
+            },
+            captured,
+        );
+        self.signal_change(
+            if overwrite_if_empty {
+                BoxTreeChangeKind::Insert
+            } else {
+                BoxTreeChangeKind::Update
+            },
+            position_u32,
+            &(*position_u32 + V3c::unit(insert_size)),
+        );
+        Ok(())
+    }
+
+    /// Inserts the given palette index into the boxtree in the given lod(level of detail) based on insert_size
+    /// * `position_u32` - the position to insert the data into, expected to be contained within the tree
+    pub(crate) fn insert_content_at_lod(
+        &mut self,
+        overwrite_if_empty: bool,
+        position_u32: &V3c<u32>,
+        insert_size: u32,
+        target_content: PaletteIndexValues,
+    ) {
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let position = V3c::<f32>::from(*position_u32);
+
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(
             Self::ROOT_NODE_KEY as usize,
@@ -101,7 +145,6 @@ impl<T: VoxelData> BoxTree<T> {
         let mut modified_bottom_sectants = vec![];
         let mut actual_update_size = V3c::unit(0);
         let mut updated = false;
-        let target_content = self.add_to_palette(&data);
         loop {
             let (current_node_key, target_child_sectant) = *node_stack.last().unwrap();
             let current_bounds = bounds_stack.last().unwrap();
@@ -204,7 +247,7 @@ impl<T: VoxelData> BoxTree<T> {
                             NodeContent::UniformLeaf(brick) => match brick {
                                 BrickData::Empty => false,
                                 BrickData::Solid(voxel) => *voxel == target_content,
-                                BrickData::Parted(brick) => {
+                                brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                                     let index_in_matrix = matrix_index_for(
                                         current_bounds,
                                         &(position.into()),
@@ -216,14 +259,14 @@ impl<T: VoxelData> BoxTree<T> {
                                         index_in_matrix.z,
                                         self.brick_dim as usize,
                                     );
-                                    brick[index_in_matrix] == target_content
+                                    brick.voxel(index_in_matrix).unwrap() == target_content
                                 }
                             },
                             NodeContent::Leaf(bricks) => {
                                 match &bricks[target_child_sectant as usize] {
                                     BrickData::Empty => false,
                                     BrickData::Solid(voxel) => *voxel == target_content,
-                                    BrickData::Parted(brick) => {
+                                    brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                                         let index_in_matrix = matrix_index_for(
                                             &target_bounds,
                                             &(position.into()),
@@ -235,7 +278,7 @@ impl<T: VoxelData> BoxTree<T> {
                                             index_in_matrix.z,
                                             self.brick_dim as usize,
                                         );
-                                        brick[index_in_matrix] == target_content
+                                        brick.voxel(index_in_matrix).unwrap() == target_content
                                     }
                                 }
                             }
@@ -320,7 +363,7 @@ impl<T: VoxelData> BoxTree<T> {
 
         if !updated {
             // No need to do post-processing operations if data wasn't updated..
-            return Ok(());
+            return;
         }
 
         // post-processing operations
@@ -402,8 +445,6 @@ impl<T: VoxelData> BoxTree<T> {
         for trigger in self.update_triggers.iter() {
             trigger(node_stack_clone.clone(), modified_bottom_sectants.clone());
         }
-
-        Ok(())
     }
 
     /// Handles node post-process for connections, content, mips, occupied bits and occlusion bits
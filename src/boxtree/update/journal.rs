@@ -0,0 +1,310 @@
+use crate::{
+    boxtree::{
+        iterate::{BrickSource, BrickTraversal},
+        types::{
+            EditJournal, EditOperation, JournalEntry, JournalRegion, JournalTransaction,
+            NodeContent, PaletteIndexValues,
+        },
+        update::region::{Region, RegionShape},
+        BoxTree, VoxelData,
+    },
+    object_pool::empty_marker,
+    spatial::math::{flat_projection, vector::V3c},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Enables or disables recording updates into the edit journal
+    /// Updates made through @insert, @update, @clear, @insert_at_lod, @clear_at_lod,
+    /// @fill_box, @clear_box, @fill_sphere, @fill_shape, @paste, the boolean operations,
+    /// the region transforms and @BoxTreeEdit are recorded while enabled, to be reverted with @undo
+    /// Disabling the journal discards the recorded history
+    pub fn switch_journal(&mut self, enabled: bool) {
+        if !enabled {
+            self.journal = None;
+        } else if self.journal.is_none() {
+            self.journal = Some(EditJournal::default());
+        }
+    }
+
+    /// Returns with true if updates are recorded into the edit journal, see @switch_journal
+    pub fn journal_enabled(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Starts collecting recorded updates into a transaction with the given name
+    /// Every update until @end_transaction is undone and redone together
+    /// Updates made outside of transactions are recorded as a transaction by themselves
+    /// An already started transaction is ended before the new one begins
+    /// * `name` - The name to identify the transaction with, returned by @undo and @redo
+    pub fn begin_transaction(&mut self, name: &str) {
+        self.end_transaction();
+        if let Some(journal) = self.journal.as_mut() {
+            journal.open_transaction = Some(JournalTransaction {
+                name: name.to_string(),
+                entries: vec![],
+            });
+        }
+    }
+
+    /// Ends the transaction started with @begin_transaction
+    /// Transactions without any recorded updates are discarded
+    pub fn end_transaction(&mut self) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        if let Some(transaction) = journal.open_transaction.take()
+            && !transaction.entries.is_empty()
+        {
+            journal.undo_stack.push(transaction);
+        }
+    }
+
+    /// Returns with true if there is a recorded transaction to revert
+    pub fn can_undo(&self) -> bool {
+        self.journal.as_ref().is_some_and(|journal| {
+            !journal.undo_stack.is_empty()
+                || journal
+                    .open_transaction
+                    .as_ref()
+                    .is_some_and(|transaction| !transaction.entries.is_empty())
+        })
+    }
+
+    /// Returns with true if there is a reverted transaction to apply again
+    pub fn can_redo(&self) -> bool {
+        self.journal
+            .as_ref()
+            .is_some_and(|journal| !journal.redo_stack.is_empty())
+    }
+
+    /// Reverts the latest recorded transaction, ending the open transaction if there is one
+    /// Updates are applied through @BoxTreeEdit, so MIPs, update triggers and subscriptions are kept up to date
+    /// * Returns with the name of the reverted transaction, or None if there is nothing to revert
+    pub fn undo(&mut self) -> Option<String> {
+        self.end_transaction();
+        let transaction = self.journal.as_mut()?.undo_stack.pop()?;
+        self.replay_journal(
+            transaction
+                .entries
+                .iter()
+                .rev()
+                .map(|entry| (entry, &entry.after, &entry.before)),
+        );
+        let name = transaction.name.clone();
+        self.journal.as_mut().unwrap().redo_stack.push(transaction);
+        Some(name)
+    }
+
+    /// Applies the latest reverted transaction again
+    /// Updates are applied through @BoxTreeEdit, so MIPs, update triggers and subscriptions are kept up to date
+    /// * Returns with the name of the applied transaction, or None if there is nothing to apply
+    pub fn redo(&mut self) -> Option<String> {
+        self.end_transaction();
+        let transaction = self.journal.as_mut()?.redo_stack.pop()?;
+        self.replay_journal(
+            transaction
+                .entries
+                .iter()
+                .map(|entry| (entry, &entry.before, &entry.after)),
+        );
+        let name = transaction.name.clone();
+        self.journal.as_mut().unwrap().undo_stack.push(transaction);
+        Some(name)
+    }
+
+    /// Sets the areas of the given entries from their current to their target content,
+    /// without recording them into the journal
+    /// Areas with any current content are cleared first, then the target regions are filled in;
+    /// Regions of single voxels are updated together through @BoxTreeEdit
+    fn replay_journal<'a>(
+        &mut self,
+        entries: impl Iterator<
+            Item = (
+                &'a JournalEntry,
+                &'a Vec<JournalRegion>,
+                &'a Vec<JournalRegion>,
+            ),
+        >,
+    ) {
+        let journal = self.journal.take();
+        for (entry, current, target) in entries {
+            if !current.is_empty() {
+                self.update_region(
+                    &Region {
+                        min: entry.min,
+                        max: entry.max,
+                        shape: RegionShape::Box,
+                    },
+                    empty_marker(),
+                );
+            }
+            let mut edit = self.begin_edit();
+            for region in target.iter() {
+                if region.max - region.min == V3c::unit(1) {
+                    edit.operations.push((
+                        region.min,
+                        EditOperation::Insert {
+                            overwrite_if_empty: true,
+                            content: region.content,
+                        },
+                    ));
+                } else {
+                    edit.tree.update_region(
+                        &Region {
+                            min: region.min,
+                            max: region.max,
+                            shape: RegionShape::Box,
+                        },
+                        region.content,
+                    );
+                }
+            }
+            edit.commit();
+        }
+        self.journal = journal;
+    }
+
+    /// Collects the occupied content of the given area, one region for each solid brick,
+    /// and one for each run of the same voxel along the x axis inside other bricks
+    /// Empty nodes and bricks are skipped based on their occupancy
+    /// * `min` - the lowest corner of the area
+    /// * `max` - the highest corner of the area (exclusive), expected to be inside the tree
+    fn journal_regions(&self, min: &V3c<u32>, max: &V3c<u32>) -> Vec<JournalRegion> {
+        let mut regions: Vec<JournalRegion> = vec![];
+        let mut push_region = |position: V3c<u32>, size: u32, content: PaletteIndexValues| {
+            if NodeContent::pix_points_to_empty(
+                &content,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            ) {
+                return;
+            }
+            let region_min = V3c::new(
+                position.x.max(min.x),
+                position.y.max(min.y),
+                position.z.max(min.z),
+            );
+            let region_max = V3c::new(
+                (position.x + size).min(max.x),
+                (position.y + size).min(max.y),
+                (position.z + size).min(max.z),
+            );
+            if region_min.x >= region_max.x
+                || region_min.y >= region_max.y
+                || region_min.z >= region_max.z
+            {
+                return;
+            }
+            if let Some(last) = regions.last_mut()
+                && last.content == content
+                && last.max.x == region_min.x
+                && last.min.y == region_min.y
+                && last.min.z == region_min.z
+                && last.max.y == region_max.y
+                && last.max.z == region_max.z
+            {
+                last.max.x = region_max.x;
+                return;
+            }
+            regions.push(JournalRegion {
+                min: region_min,
+                max: region_max,
+                content,
+            });
+        };
+
+        let mut traversal = BrickTraversal::new(self, *min, *max);
+        while let Some((bounds, source)) = traversal.next_brick() {
+            let position: V3c<u32> = bounds.min_position.into();
+            let size = bounds.size as u32;
+            let (node_key, sectant) = match source {
+                BrickSource::Solid(voxel) => {
+                    push_region(position, size, voxel);
+                    continue;
+                }
+                BrickSource::Parted { node_key, sectant } => (node_key, sectant),
+            };
+
+            // One voxel inside the brick might cover multiple voxels of the tree,
+            // only the voxels overlapping with the area are collected
+            let node = self.nodes.get(node_key);
+            let brick = match (&node.content, sectant) {
+                (NodeContent::Leaf(bricks), Some(sectant)) => &bricks[sectant as usize],
+                (NodeContent::UniformLeaf(brick), None) => brick,
+                _ => unreachable!("Expected brick source to point to a brick"),
+            };
+            let brick_dim = self.brick_dim;
+            let cell_size = (size / brick_dim).max(1);
+            let cells = |start: u32, min: u32, max: u32| {
+                (min.saturating_sub(start) / cell_size)
+                    ..(max.saturating_sub(start).div_ceil(cell_size)).min(brick_dim)
+            };
+            for z in cells(position.z, min.z, max.z) {
+                for y in cells(position.y, min.y, max.y) {
+                    for x in cells(position.x, min.x, max.x) {
+                        push_region(
+                            position + V3c::new(x, y, z) * cell_size,
+                            cell_size,
+                            brick
+                                .voxel(flat_projection(
+                                    x as usize,
+                                    y as usize,
+                                    z as usize,
+                                    brick_dim as usize,
+                                ))
+                                .expect("Expected brick source to contain separate voxels"),
+                        );
+                    }
+                }
+            }
+        }
+        regions
+    }
+
+    /// Reads the current content of the given area, to be recorded after an update
+    /// * `min` - the lowest corner of the area, expected to be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the bounds of the tree
+    /// * Returns with the occupied content of the area, or None if the journal is disabled
+    pub(crate) fn journal_capture(&self, min: &V3c<u32>, max: &V3c<u32>) -> Option<JournalEntry> {
+        self.journal.as_ref()?;
+        let max = V3c::new(
+            max.x.min(self.boxtree_size),
+            max.y.min(self.boxtree_size),
+            max.z.min(self.boxtree_size),
+        );
+        Some(JournalEntry {
+            min: *min,
+            max,
+            before: self.journal_regions(min, &max),
+            after: vec![],
+        })
+    }
+
+    /// Records the update of the area captured before it into the journal
+    /// Changes are added to the open transaction, or to a new transaction with the given name
+    /// Recording a change discards the transactions available to @redo
+    /// * `name` - The name of the transaction if none is open
+    /// * `captured` - The content of the updated area before the update, see @journal_capture
+    pub(crate) fn journal_record(&mut self, name: &str, captured: Option<JournalEntry>) {
+        let Some(mut entry) = captured else {
+            return;
+        };
+        entry.after = self.journal_regions(&entry.min, &entry.max);
+        if entry.before == entry.after {
+            return;
+        }
+
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        journal.redo_stack.clear();
+        if let Some(transaction) = journal.open_transaction.as_mut() {
+            transaction.entries.push(entry);
+        } else {
+            journal.undo_stack.push(JournalTransaction {
+                name: name.to_string(),
+                entries: vec![entry],
+            });
+        }
+    }
+}
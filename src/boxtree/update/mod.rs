@@ -1,12 +1,23 @@
+mod batch;
 pub mod clear;
+mod csg;
 pub mod insert;
+mod journal;
+pub(crate) mod palette;
+mod paste;
+mod region;
+mod resize;
+mod transform;
 
 #[cfg(test)]
 mod tests;
 
 use crate::{
     boxtree::{
-        types::{BoxTreeEntry, BrickData, NodeChildren, NodeContent, PaletteIndexValues},
+        types::{
+            BoxTreeChange, BoxTreeChangeKind, BoxTreeEntry, BoxTreeUpdatedSignalParams, BrickData,
+            NodeChildren, NodeContent, PaletteIndexValues, SubscriptionHandle,
+        },
         Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
     },
     object_pool::empty_marker,
@@ -19,7 +30,13 @@ use crate::{
     },
 };
 use num_traits::Zero;
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 impl<T: VoxelData> BoxTree<T> {
     //####################################################################################
@@ -43,7 +60,7 @@ impl<T: VoxelData> BoxTree<T> {
                 if **albedo == Albedo::zero() {
                     return empty_marker();
                 }
-                let potential_new_albedo_index = self.map_to_color_index_in_palette.keys().len();
+                let potential_new_albedo_index = self.voxel_color_palette.len();
                 let albedo_index = if let std::collections::hash_map::Entry::Vacant(e) =
                     self.map_to_color_index_in_palette.entry(**albedo)
                 {
@@ -53,17 +70,18 @@ impl<T: VoxelData> BoxTree<T> {
                 } else {
                     self.map_to_color_index_in_palette[albedo]
                 };
+                self.fit_palette_index_width();
                 debug_assert!(
-                    albedo_index < u16::MAX as usize,
-                    "Albedo color palette overflow!"
+                    albedo_index < self.palette_index_width.max_colors(),
+                    "Albedo color palette overflow, see @BoxTree::set_palette_index_width!"
                 );
-                NodeContent::pix_visual(albedo_index as u16)
+                NodeContent::pix_visual(albedo_index as u32)
             }
             BoxTreeEntry::Informative(data) => {
                 if data.is_empty() {
                     return empty_marker();
                 }
-                let potential_new_data_index = self.map_to_data_index_in_palette.keys().len();
+                let potential_new_data_index = self.voxel_data_palette.len();
                 let data_index = if let std::collections::hash_map::Entry::Vacant(e) =
                     self.map_to_data_index_in_palette.entry((*data).clone())
                 {
@@ -73,11 +91,12 @@ impl<T: VoxelData> BoxTree<T> {
                 } else {
                     self.map_to_data_index_in_palette[data]
                 };
+                self.fit_palette_index_width();
                 debug_assert!(
-                    data_index < u16::MAX as usize,
-                    "Data color palette overflow!"
+                    data_index < self.palette_index_width.max_data(),
+                    "Data color palette overflow, see @BoxTree::set_palette_index_width!"
                 );
-                NodeContent::pix_informal(data_index as u16)
+                NodeContent::pix_informal(data_index as u32)
             }
             BoxTreeEntry::Complex(albedo, data) => {
                 if **albedo == Albedo::zero() {
@@ -85,7 +104,7 @@ impl<T: VoxelData> BoxTree<T> {
                 } else if data.is_empty() {
                     return self.add_to_palette(&BoxTreeEntry::Visual(albedo));
                 }
-                let potential_new_albedo_index = self.map_to_color_index_in_palette.keys().len();
+                let potential_new_albedo_index = self.voxel_color_palette.len();
                 let albedo_index = if let std::collections::hash_map::Entry::Vacant(e) =
                     self.map_to_color_index_in_palette.entry(**albedo)
                 {
@@ -95,7 +114,7 @@ impl<T: VoxelData> BoxTree<T> {
                 } else {
                     self.map_to_color_index_in_palette[albedo]
                 };
-                let potential_new_data_index = self.map_to_data_index_in_palette.keys().len();
+                let potential_new_data_index = self.voxel_data_palette.len();
                 let data_index = if let std::collections::hash_map::Entry::Vacant(e) =
                     self.map_to_data_index_in_palette.entry((*data).clone())
                 {
@@ -105,15 +124,16 @@ impl<T: VoxelData> BoxTree<T> {
                 } else {
                     self.map_to_data_index_in_palette[data]
                 };
+                self.fit_palette_index_width();
                 debug_assert!(
-                    albedo_index < u16::MAX as usize,
-                    "Albedo color palette overflow!"
+                    albedo_index < self.palette_index_width.max_colors(),
+                    "Albedo color palette overflow, see @BoxTree::set_palette_index_width!"
                 );
                 debug_assert!(
-                    data_index < u16::MAX as usize,
-                    "Data color palette overflow!"
+                    data_index < self.palette_index_width.max_data(),
+                    "Data color palette overflow, see @BoxTree::set_palette_index_width!"
                 );
-                NodeContent::pix_complex(albedo_index as u16, data_index as u16)
+                NodeContent::pix_complex(albedo_index as u32, data_index as u32)
             }
         }
         // find color in the palette is present, add if not
@@ -151,7 +171,7 @@ impl<T: VoxelData> BoxTree<T> {
     ) -> bool {
         // Update the leaf node, if it is possible as is, and if it's even needed to update
         // and decide if the node content needs to be divided into bricks, and the update function to be called again
-        let mut node = self.nodes.get_mut(node_key);
+        let node = self.nodes.get_mut(node_key);
         match &mut node.content {
             NodeContent::Leaf(bricks) => {
                 // In case brick_dimension == boxtree size, the 0 can not be a leaf...
@@ -160,10 +180,11 @@ impl<T: VoxelData> BoxTree<T> {
                     //If there is no brick in the target position of the leaf, create one
                     BrickData::Empty => {
                         // Create a new empty brick at the given sectant
-                        let mut new_brick = vec![
-                            empty_marker::<PaletteIndexValues>();
+                        let mut new_brick = BrickData::parted(vec![
+                            empty_marker::<PaletteIndexValues>(
+                            );
                             self.brick_dim.pow(3) as usize
-                        ];
+                        ]);
                         // update the new empty brick at the given position
                         Self::update_brick(
                             overwrite_if_empty,
@@ -174,7 +195,8 @@ impl<T: VoxelData> BoxTree<T> {
                             *size,
                             &target_content,
                         );
-                        bricks[target_child_sectant] = BrickData::Parted(new_brick);
+                        new_brick.narrow();
+                        bricks[target_child_sectant] = new_brick;
                         true
                     }
                     BrickData::Solid(voxel) => {
@@ -194,7 +216,8 @@ impl<T: VoxelData> BoxTree<T> {
                         ) && *voxel != target_content)
                         {
                             // create new brick and update it at the given position
-                            let mut new_brick = vec![*voxel; self.brick_dim.pow(3) as usize];
+                            let mut new_brick =
+                                BrickData::parted(vec![*voxel; self.brick_dim.pow(3) as usize]);
                             Self::update_brick(
                                 overwrite_if_empty,
                                 &mut new_brick,
@@ -204,14 +227,15 @@ impl<T: VoxelData> BoxTree<T> {
                                 *size,
                                 &target_content,
                             );
-                            bricks[target_child_sectant] = BrickData::Parted(new_brick);
+                            new_brick.narrow();
+                            bricks[target_child_sectant] = new_brick;
                             true
                         } else {
                             // Since the Voxel already equals the data to be set, no need to update anything
                             false
                         }
                     }
-                    BrickData::Parted(brick) => {
+                    brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                         // Simply update the brick at the given position
                         Self::update_brick(
                             overwrite_if_empty,
@@ -246,11 +270,11 @@ impl<T: VoxelData> BoxTree<T> {
                                     .unwrap();
 
                             // Add a brick to the target sectant and update with the given data
-                            std::mem::drop(node);
-                            let mut new_brick = vec![
-                                self.add_to_palette(&BoxTreeEntry::Empty);
-                                self.brick_dim.pow(3) as usize
-                            ];
+                            let mut new_brick =
+                                BrickData::parted(vec![
+                                    self.add_to_palette(&BoxTreeEntry::Empty);
+                                    self.brick_dim.pow(3) as usize
+                                ]);
                             Self::update_brick(
                                 overwrite_if_empty,
                                 &mut new_brick,
@@ -260,7 +284,8 @@ impl<T: VoxelData> BoxTree<T> {
                                 *size,
                                 &target_content,
                             );
-                            new_leaf_content[target_child_sectant] = BrickData::Parted(new_brick);
+                            new_brick.narrow();
+                            new_leaf_content[target_child_sectant] = new_brick;
                             self.nodes.get_mut(node_key).content =
                                 NodeContent::Leaf(new_leaf_content);
                             return true;
@@ -300,13 +325,13 @@ impl<T: VoxelData> BoxTree<T> {
                         {
                             // Data request doesn't align with the voxel data
                             // create a voxel brick and try to update with the given data
-                            *mat = BrickData::Parted(vec![
+                            *mat = BrickData::parted(vec![
                                 *voxel;
                                 (self.brick_dim * self.brick_dim * self.brick_dim)
                                     as usize
                             ]);
+                            mat.narrow();
 
-                            std::mem::drop(node);
                             return self.leaf_update(
                                 overwrite_if_empty,
                                 (node_key, node_bounds),
@@ -319,7 +344,7 @@ impl<T: VoxelData> BoxTree<T> {
                         // data request aligns with node content
                         return false;
                     }
-                    BrickData::Parted(brick) => {
+                    brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
                         // Check if the voxel at the target position matches with the data update request
                         // The target position index is to be calculated from the node bounds,
                         // instead of the target bounds because the position should cover the whole leaf
@@ -340,7 +365,7 @@ impl<T: VoxelData> BoxTree<T> {
                                         &self.voxel_data_palette,
                                     )
                                     && NodeContent::pix_points_to_empty(
-                                        &brick[mat_index],
+                                        &brick.voxel(mat_index).unwrap(),
                                         &self.voxel_color_palette,
                                         &self.voxel_data_palette
                                     )
@@ -350,7 +375,7 @@ impl<T: VoxelData> BoxTree<T> {
                                         &self.voxel_color_palette,
                                         &self.voxel_data_palette,
                                     )
-                                    && brick[mat_index] == target_content
+                                    && brick.voxel(mat_index).unwrap() == target_content
                                 )
                             )
                         {
@@ -381,10 +406,13 @@ impl<T: VoxelData> BoxTree<T> {
                                 .unwrap();
 
                         // Each brick is mapped to take up one subsection of the current data
-                        let child_bricks =
-                            Self::dilute_brick_data(std::mem::take(brick), self.brick_dim);
+                        let child_bricks = Self::dilute_brick_data(
+                            std::mem::take(brick).into_voxels().unwrap(),
+                            self.brick_dim,
+                        );
                         let mut updated = false;
-                        for (sectant, mut new_brick) in child_bricks.into_iter().enumerate() {
+                        for (sectant, new_brick) in child_bricks.into_iter().enumerate() {
+                            let mut new_brick = BrickData::parted(new_brick);
                             // Also update the brick if it is the target
                             if sectant == target_child_sectant {
                                 Self::update_brick(
@@ -398,7 +426,8 @@ impl<T: VoxelData> BoxTree<T> {
                                 );
                                 updated |= true;
                             }
-                            leaf_data[sectant] = BrickData::Parted(new_brick);
+                            new_brick.narrow();
+                            leaf_data[sectant] = new_brick;
                         }
 
                         node.content = NodeContent::Leaf(leaf_data);
@@ -406,7 +435,6 @@ impl<T: VoxelData> BoxTree<T> {
                         return updated;
                     }
                 }
-                std::mem::drop(node);
                 self.leaf_update(
                     overwrite_if_empty,
                     (node_key, node_bounds),
@@ -418,7 +446,6 @@ impl<T: VoxelData> BoxTree<T> {
             NodeContent::Internal => {
                 // Warning: Calling leaf update to an internal node might induce data loss - see #69
                 node.children = NodeChildren::NoChildren;
-                std::mem::drop(node);
                 self.nodes.get_mut(node_key).content = NodeContent::Leaf(
                     (0..BOX_NODE_CHILDREN_COUNT)
                         .map(|sectant| {
@@ -441,7 +468,6 @@ impl<T: VoxelData> BoxTree<T> {
                 // Calling leaf update on Nothing is an odd thing to do..
                 // But possible, if this call is mid-update
                 // So let's try to gather all the information possible
-                std::mem::drop(node);
                 self.nodes.get_mut(node_key).content = NodeContent::Leaf(
                     (0..BOX_NODE_CHILDREN_COUNT)
                         .map(|sectant| {
@@ -563,7 +589,7 @@ impl<T: VoxelData> BoxTree<T> {
     /// * Returns with the size of the update
     fn update_brick(
         overwrite_if_empty: bool,
-        brick: &mut [PaletteIndexValues],
+        brick: &mut BrickData<PaletteIndexValues>,
         brick_bounds: &Cube,
         brick_dim: u32,
         position: V3c<u32>,
@@ -586,16 +612,16 @@ impl<T: VoxelData> BoxTree<T> {
                 for z in mat_index.z..(mat_index.z + size.z as usize).min(brick_dim as usize) {
                     let mat_index = flat_projection(x, y, z, brick_dim as usize);
                     if overwrite_if_empty {
-                        brick[mat_index] = *data;
+                        brick.set_voxel(mat_index, *data);
                     } else {
+                        let mut voxel = brick.voxel(mat_index).unwrap();
                         if NodeContent::pix_color_is_some(data) {
-                            brick[mat_index] =
-                                NodeContent::pix_overwrite_color(brick[mat_index], data);
+                            voxel = NodeContent::pix_overwrite_color(voxel, data);
                         }
                         if NodeContent::pix_data_is_some(data) {
-                            brick[mat_index] =
-                                NodeContent::pix_overwrite_data(brick[mat_index], data);
+                            voxel = NodeContent::pix_overwrite_data(voxel, data);
                         }
+                        brick.set_voxel(mat_index, voxel);
                     }
                 }
             }
@@ -635,7 +661,7 @@ impl<T: VoxelData> BoxTree<T> {
                 }
             }
 
-            let mut node = self.nodes.get_mut(node_key);
+            let node = self.nodes.get_mut(node_key);
             match &mut node.content {
                 NodeContent::Nothing => true,
                 NodeContent::UniformLeaf(brick) => match brick {
@@ -663,7 +689,7 @@ impl<T: VoxelData> BoxTree<T> {
                             false
                         }
                     }
-                    BrickData::Parted(_brick) => {
+                    BrickData::Parted(_) | BrickData::Packed(_) => {
                         if brick.simplify(&self.voxel_color_palette, &self.voxel_data_palette) {
                             debug_assert!(
                                 node.occupied_bits == u64::MAX || node.occupied_bits == 0,
@@ -676,133 +702,20 @@ impl<T: VoxelData> BoxTree<T> {
                     }
                 },
                 NodeContent::Leaf(bricks) => {
-                    // Try to simplify bricks
-                    let mut simplified = false;
-                    let mut is_leaf_uniform_solid = true;
-                    let mut uniform_solid_value = None;
-
-                    for brick in bricks.iter_mut().take(BOX_NODE_CHILDREN_COUNT) {
-                        simplified |=
-                            brick.simplify(&self.voxel_color_palette, &self.voxel_data_palette);
-
-                        if is_leaf_uniform_solid {
-                            if let BrickData::Solid(voxel) = brick {
-                                if let Some(ref uniform_solid_value) = uniform_solid_value {
-                                    if *uniform_solid_value != voxel {
-                                        is_leaf_uniform_solid = false;
-                                    }
-                                } else {
-                                    uniform_solid_value = Some(voxel);
-                                }
-                            } else {
-                                is_leaf_uniform_solid = false;
-                            }
-                        }
-                    }
-
-                    // Try to unite bricks into a solid brick
-                    let mut unified_brick = BrickData::Empty;
-                    if is_leaf_uniform_solid {
-                        debug_assert_ne!(uniform_solid_value, None);
-                        node.content = NodeContent::UniformLeaf(BrickData::Solid(
-                            *uniform_solid_value.unwrap(),
-                        ));
-                        debug_assert_eq!(
-                            node.occupied_bits,
-                            u64::MAX,
+                    let (simplified, uniform_brick) = Self::simplify_leaf_bricks(
+                        bricks,
+                        self.brick_dim,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    );
+                    if let Some(uniform_brick) = uniform_brick {
+                        debug_assert!(
+                            !matches!(uniform_brick, BrickData::Solid(_))
+                                || node.occupied_bits == u64::MAX,
                             "Expected Leaf with uniform solid value to have u64::MAX value"
                         );
-                        return true;
-                    }
-
-                    // Do not try to unite bricks into a uniform brick
-                    // since contents are not solid, it is not unifyable
-                    // into a 1x1x1 brick ( that's equivalent to a solid brick )
-                    if self.brick_dim == 1 {
-                        return false;
+                        node.content = NodeContent::UniformLeaf(uniform_brick);
                     }
-
-                    // Try to unite bricks into a Uniform parted brick
-                    let mut unified_brick_data =
-                        vec![empty_marker::<PaletteIndexValues>(); self.brick_dim.pow(3) as usize];
-                    let mut is_leaf_uniform = true;
-                    const BRICK_CELL_SIZE: usize = BOX_NODE_DIMENSION;
-                    let superbrick_size = self.brick_dim as f32 * BOX_NODE_DIMENSION as f32;
-                    'brick_process: for x in 0..self.brick_dim {
-                        for y in 0..self.brick_dim {
-                            for z in 0..self.brick_dim {
-                                let cell_start =
-                                    V3c::new(x as f32, y as f32, z as f32) * BRICK_CELL_SIZE as f32;
-                                let ref_sectant =
-                                    offset_sectant(&cell_start, superbrick_size) as usize;
-                                let pos_in_child =
-                                    cell_start - SECTANT_OFFSET_LUT[ref_sectant] * superbrick_size;
-                                let ref_voxel = match &bricks[ref_sectant] {
-                                    BrickData::Empty => empty_marker(),
-                                    BrickData::Solid(voxel) => *voxel,
-                                    BrickData::Parted(brick) => {
-                                        brick[flat_projection(
-                                            pos_in_child.x as usize,
-                                            pos_in_child.y as usize,
-                                            pos_in_child.z as usize,
-                                            self.brick_dim as usize,
-                                        )]
-                                    }
-                                };
-
-                                for cx in 0..BRICK_CELL_SIZE {
-                                    for cy in 0..BRICK_CELL_SIZE {
-                                        for cz in 0..BRICK_CELL_SIZE {
-                                            if !is_leaf_uniform {
-                                                break 'brick_process;
-                                            }
-                                            let pos = cell_start
-                                                + V3c::new(cx as f32, cy as f32, cz as f32);
-                                            let sectant =
-                                                offset_sectant(&pos, superbrick_size) as usize;
-                                            let pos_in_child =
-                                                pos - SECTANT_OFFSET_LUT[sectant] * superbrick_size;
-
-                                            is_leaf_uniform &= match &bricks[sectant] {
-                                                BrickData::Empty => {
-                                                    ref_voxel
-                                                        == empty_marker::<PaletteIndexValues>()
-                                                }
-                                                BrickData::Solid(voxel) => ref_voxel == *voxel,
-                                                BrickData::Parted(brick) => {
-                                                    ref_voxel
-                                                        == brick[flat_projection(
-                                                            pos_in_child.x as usize,
-                                                            pos_in_child.y as usize,
-                                                            pos_in_child.z as usize,
-                                                            self.brick_dim as usize,
-                                                        )]
-                                                }
-                                            };
-                                        }
-                                    }
-                                }
-                                // All voxel are the same in this cell! set value in unified brick
-                                unified_brick_data[flat_projection(
-                                    x as usize,
-                                    y as usize,
-                                    z as usize,
-                                    self.brick_dim as usize,
-                                )] = ref_voxel;
-                            }
-                        }
-                    }
-
-                    // bricks can be represented as a uniform parted brick matrix!
-                    if is_leaf_uniform {
-                        unified_brick = BrickData::Parted(unified_brick_data);
-                        simplified = true;
-                    }
-
-                    if !matches!(unified_brick, BrickData::Empty) {
-                        node.content = NodeContent::UniformLeaf(unified_brick);
-                    }
-
                     simplified
                 }
                 NodeContent::Internal => {
@@ -823,7 +736,6 @@ impl<T: VoxelData> BoxTree<T> {
                     };
 
                     // Try to simplify each child of the node
-                    std::mem::drop(node);
                     if recursive {
                         for child_key in child_keys.iter() {
                             self.simplify(*child_key as usize, true);
@@ -865,4 +777,217 @@ impl<T: VoxelData> BoxTree<T> {
             false
         }
     }
+
+    /// Tries to simplify the bricks of a leaf node, see @simplify
+    /// * Returns with true if any of the bricks were simplified,
+    ///   and the brick the whole leaf can be replaced with, if any
+    pub(crate) fn simplify_leaf_bricks(
+        bricks: &mut [BrickData<PaletteIndexValues>; BOX_NODE_CHILDREN_COUNT],
+        brick_dim: u32,
+        color_palette: &[Albedo],
+        data_palette: &[T],
+    ) -> (bool, Option<BrickData<PaletteIndexValues>>) {
+        // Try to simplify bricks
+        let mut simplified = false;
+        let mut is_leaf_uniform_solid = true;
+        let mut uniform_solid_value = None;
+
+        for brick in bricks.iter_mut().take(BOX_NODE_CHILDREN_COUNT) {
+            simplified |= brick.simplify(color_palette, data_palette);
+
+            if is_leaf_uniform_solid {
+                if let BrickData::Solid(voxel) = brick {
+                    if let Some(ref uniform_solid_value) = uniform_solid_value {
+                        if *uniform_solid_value != voxel {
+                            is_leaf_uniform_solid = false;
+                        }
+                    } else {
+                        uniform_solid_value = Some(voxel);
+                    }
+                } else {
+                    is_leaf_uniform_solid = false;
+                }
+            }
+        }
+
+        // Try to unite bricks into a solid brick
+        let mut unified_brick = BrickData::Empty;
+        if is_leaf_uniform_solid {
+            debug_assert_ne!(uniform_solid_value, None);
+            return (true, Some(BrickData::Solid(*uniform_solid_value.unwrap())));
+        }
+
+        // Do not try to unite bricks into a uniform brick
+        // since contents are not solid, it is not unifyable
+        // into a 1x1x1 brick ( that's equivalent to a solid brick )
+        if brick_dim == 1 {
+            return (simplified, None);
+        }
+
+        // Try to unite bricks into a Uniform parted brick
+        let mut unified_brick_data =
+            vec![empty_marker::<PaletteIndexValues>(); brick_dim.pow(3) as usize];
+        let mut is_leaf_uniform = true;
+        const BRICK_CELL_SIZE: usize = BOX_NODE_DIMENSION;
+        let superbrick_size = brick_dim as f32 * BOX_NODE_DIMENSION as f32;
+        'brick_process: for x in 0..brick_dim {
+            for y in 0..brick_dim {
+                for z in 0..brick_dim {
+                    let cell_start =
+                        V3c::new(x as f32, y as f32, z as f32) * BRICK_CELL_SIZE as f32;
+                    let ref_sectant = offset_sectant(&cell_start, superbrick_size) as usize;
+                    let pos_in_child =
+                        cell_start - SECTANT_OFFSET_LUT[ref_sectant] * superbrick_size;
+                    let ref_voxel = match &bricks[ref_sectant] {
+                        BrickData::Empty => empty_marker(),
+                        BrickData::Solid(voxel) => *voxel,
+                        brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => brick
+                            .voxel(flat_projection(
+                                pos_in_child.x as usize,
+                                pos_in_child.y as usize,
+                                pos_in_child.z as usize,
+                                brick_dim as usize,
+                            ))
+                            .unwrap(),
+                    };
+
+                    for cx in 0..BRICK_CELL_SIZE {
+                        for cy in 0..BRICK_CELL_SIZE {
+                            for cz in 0..BRICK_CELL_SIZE {
+                                if !is_leaf_uniform {
+                                    break 'brick_process;
+                                }
+                                let pos = cell_start + V3c::new(cx as f32, cy as f32, cz as f32);
+                                let sectant = offset_sectant(&pos, superbrick_size) as usize;
+                                let pos_in_child =
+                                    pos - SECTANT_OFFSET_LUT[sectant] * superbrick_size;
+
+                                is_leaf_uniform &= match &bricks[sectant] {
+                                    BrickData::Empty => {
+                                        ref_voxel == empty_marker::<PaletteIndexValues>()
+                                    }
+                                    BrickData::Solid(voxel) => ref_voxel == *voxel,
+                                    brick @ (BrickData::Parted(_) | BrickData::Packed(_)) => {
+                                        ref_voxel
+                                            == brick
+                                                .voxel(flat_projection(
+                                                    pos_in_child.x as usize,
+                                                    pos_in_child.y as usize,
+                                                    pos_in_child.z as usize,
+                                                    brick_dim as usize,
+                                                ))
+                                                .unwrap()
+                                    }
+                                };
+                            }
+                        }
+                    }
+                    // All voxel are the same in this cell! set value in unified brick
+                    unified_brick_data
+                        [flat_projection(x as usize, y as usize, z as usize, brick_dim as usize)] =
+                        ref_voxel;
+                }
+            }
+        }
+
+        // bricks can be represented as a uniform parted brick matrix!
+        if is_leaf_uniform {
+            unified_brick = BrickData::parted(unified_brick_data);
+            unified_brick.narrow();
+            simplified = true;
+        }
+
+        if !matches!(unified_brick, BrickData::Empty) {
+            return (simplified, Some(unified_brick));
+        }
+
+        (simplified, None)
+    }
+
+    /// Returns with true if every node in the given access stack is still the child of the one before it
+    /// Nodes might have been merged into their parents or freed up since the stack was recorded
+    pub(crate) fn access_stack_is_valid(&self, node_stack: &[(usize, u8)]) -> bool {
+        node_stack
+            .iter()
+            .all(|(node_key, _)| self.nodes.key_is_valid(*node_key))
+            && node_stack
+                .windows(2)
+                .all(|pair| self.nodes.get(pair[0].0).child(pair[0].1) == pair[1].0)
+    }
+
+    /// Calls the update triggers once for each of the given updated nodes
+    /// Nodes which are no longer part of the tree are skipped
+    pub(crate) fn signal_updates(&self, updated_nodes: Vec<BoxTreeUpdatedSignalParams>) {
+        if self.update_triggers.is_empty() {
+            return;
+        }
+        for (node_stack, modified_sectants) in updated_nodes {
+            if !self.access_stack_is_valid(&node_stack) {
+                continue;
+            }
+
+            // Only parted bricks are uploaded separately
+            let node_key = node_stack.last().unwrap().0;
+            let modified_sectants = match &self.nodes.get(node_key).content {
+                NodeContent::Leaf(bricks) => modified_sectants
+                    .into_iter()
+                    .filter(|sectant| {
+                        matches!(
+                            bricks[*sectant as usize],
+                            BrickData::Parted(_) | BrickData::Packed(_)
+                        )
+                    })
+                    .collect(),
+                NodeContent::UniformLeaf(BrickData::Parted(_) | BrickData::Packed(_)) => vec![0],
+                _ => vec![],
+            };
+            for trigger in self.update_triggers.iter() {
+                trigger(node_stack.clone(), modified_sectants.clone());
+            }
+        }
+    }
+
+    /// Registers the given function to be called with every change made to the tree
+    /// Subscriptions are not stored with the tree, they need to be registered again after loading it
+    /// * `callback` - The function to call with the kind and the affected area of each change
+    /// * Returns with the handle to cancel the subscription with, see @unsubscribe
+    pub fn subscribe<F: Fn(&BoxTreeChange) + Send + Sync + 'static>(
+        &mut self,
+        callback: F,
+    ) -> SubscriptionHandle {
+        static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(0);
+        let handle = SubscriptionHandle(NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed));
+        self.subscriptions.push((handle, Arc::new(callback)));
+        handle
+    }
+
+    /// Cancels the subscription with the given handle
+    /// * Returns with true if the subscription was registered in the tree
+    pub fn unsubscribe(&mut self, handle: SubscriptionHandle) -> bool {
+        let subscription_count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|(subscription, _)| *subscription != handle);
+        subscription_count != self.subscriptions.len()
+    }
+
+    /// Calls every subscribed function with the given change, clipped to the bounds of the tree
+    /// * `min` - the lowest corner of the changed area
+    /// * `max` - the highest corner of the changed area (exclusive)
+    pub(crate) fn signal_change(&self, kind: BoxTreeChangeKind, min: &V3c<u32>, max: &V3c<u32>) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+        let change = BoxTreeChange {
+            kind,
+            min: *min,
+            max: V3c::new(
+                max.x.min(self.boxtree_size),
+                max.y.min(self.boxtree_size),
+                max.z.min(self.boxtree_size),
+            ),
+        };
+        for (_handle, callback) in self.subscriptions.iter() {
+            callback(&change);
+        }
+    }
 }
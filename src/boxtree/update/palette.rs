@@ -0,0 +1,426 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeChangeKind, BoxTreeNodeAccessStack, BoxTreeUpdatedSignalParams, BrickData,
+            NodeContent, NodeData, OctreeError, PaletteIndexValues, PaletteIndexWidth, PartedBrick,
+        },
+        Albedo, BoxTree, StrategyUpdater, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::math::vector::V3c,
+};
+use num_traits::Zero;
+use std::hash::Hash;
+
+/// Maps the palette indices of a tree to indices into its palettes without the unused entries
+pub(crate) struct PaletteRemap {
+    /// The new index of each color, or an empty marker for unused colors
+    colors: Vec<u32>,
+
+    /// The new index of each user data, or an empty marker for unused data
+    data: Vec<u32>,
+}
+
+impl PaletteRemap {
+    /// Creates a remap keeping every palette entry at its current index
+    fn identity(color_count: usize, data_count: usize) -> Self {
+        Self {
+            colors: (0..color_count as u32).collect(),
+            data: (0..data_count as u32).collect(),
+        }
+    }
+
+    /// Returns with true if every palette entry is kept at its current index
+    pub(crate) fn is_identity(&self) -> bool {
+        self.colors
+            .iter()
+            .chain(self.data.iter())
+            .all(|index| *index != empty_marker::<u32>())
+    }
+
+    /// Provides the palettes with only the used entries kept
+    pub(crate) fn compact<T: Clone>(&self, colors: &[Albedo], data: &[T]) -> (Vec<Albedo>, Vec<T>) {
+        (
+            colors
+                .iter()
+                .zip(self.colors.iter())
+                .filter(|(_, index)| **index != empty_marker::<u32>())
+                .map(|(color, _)| *color)
+                .collect(),
+            data.iter()
+                .zip(self.data.iter())
+                .filter(|(_, index)| **index != empty_marker::<u32>())
+                .map(|(data, _)| data.clone())
+                .collect(),
+        )
+    }
+
+    /// Provides the palette index referencing the same color and data in the compacted palettes
+    pub(crate) fn apply(&self, index: PaletteIndexValues) -> PaletteIndexValues {
+        let color = if NodeContent::pix_color_is_some(&index) {
+            self.colors
+                .get(NodeContent::pix_color_index(&index))
+                .copied()
+                .unwrap_or(empty_marker())
+        } else {
+            empty_marker()
+        };
+        let data = if NodeContent::pix_data_is_some(&index) {
+            self.data
+                .get(NodeContent::pix_data_index(&index))
+                .copied()
+                .unwrap_or(empty_marker())
+        } else {
+            empty_marker()
+        };
+        NodeContent::pix_complex(color, data)
+    }
+
+    /// Updates every palette index inside the given node, including its MIP, see @apply
+    pub(crate) fn apply_to_node(&self, node: &mut NodeData) {
+        map_node_voxels(node, |index| self.apply(*index));
+    }
+}
+
+/// Replaces every palette index value inside the given brick with the result of the given function
+pub(crate) fn map_brick_voxels(
+    brick: &mut BrickData<PaletteIndexValues>,
+    map: &impl Fn(&PaletteIndexValues) -> PaletteIndexValues,
+) {
+    match brick {
+        BrickData::Empty => {}
+        BrickData::Solid(voxel) => *voxel = map(voxel),
+        BrickData::Parted(voxels) => {
+            *voxels = PartedBrick::new(voxels.iter().map(|voxel| map(&voxel)).collect());
+        }
+        BrickData::Packed(packed) => {
+            for voxel in packed.palette.iter_mut() {
+                *voxel = map(voxel);
+            }
+        }
+    }
+}
+
+/// Replaces every palette index value inside the given node, including its MIP,
+/// with the result of the given function
+pub(crate) fn map_node_voxels(
+    node: &mut NodeData,
+    map: impl Fn(&PaletteIndexValues) -> PaletteIndexValues,
+) {
+    match &mut node.content {
+        NodeContent::Nothing | NodeContent::Internal => {}
+        NodeContent::Leaf(bricks) => {
+            for brick in bricks.iter_mut() {
+                map_brick_voxels(brick, &map);
+            }
+        }
+        NodeContent::UniformLeaf(brick) => map_brick_voxels(brick, &map),
+    }
+    map_brick_voxels(&mut node.mip, &map);
+}
+
+impl<T> BoxTree<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// Provides the width of the palette indices the tree is serialized and rendered with
+    pub fn palette_index_width(&self) -> PaletteIndexWidth {
+        self.palette_index_width
+    }
+
+    /// Sets the width of the palette indices the tree is serialized and rendered with
+    /// Trees are switched to @PaletteIndexWidth::Wide automatically once they have
+    /// more than 65535 colors or data values
+    /// * Returns with an error if the palettes have more entries than the given width can reference;
+    ///   unused entries can be removed with @compact_palettes
+    pub fn set_palette_index_width(&mut self, width: PaletteIndexWidth) -> Result<(), OctreeError> {
+        if self.voxel_color_palette.len() > width.max_colors() {
+            return Err(OctreeError::PaletteOverflow {
+                size: self.voxel_color_palette.len(),
+                limit: width.max_colors(),
+            });
+        }
+        if self.voxel_data_palette.len() > width.max_data() {
+            return Err(OctreeError::PaletteOverflow {
+                size: self.voxel_data_palette.len(),
+                limit: width.max_data(),
+            });
+        }
+        self.palette_index_width = width;
+        Ok(())
+    }
+
+    /// Switches to @PaletteIndexWidth::Wide in case the palettes outgrew the narrow indices
+    pub(crate) fn fit_palette_index_width(&mut self) {
+        if PaletteIndexWidth::Narrow == self.palette_index_width
+            && (self.voxel_color_palette.len() > PaletteIndexWidth::Narrow.max_colors()
+                || self.voxel_data_palette.len() > PaletteIndexWidth::Narrow.max_data())
+        {
+            self.palette_index_width = PaletteIndexWidth::Wide;
+        }
+    }
+
+    /// Removes every palette entry not referenced by any voxel or MIP of the tree
+    /// Entries referenced by the edit journal are kept, so recorded updates can still be reverted
+    /// Palette indices change, so trees already uploaded for rendering need to be uploaded again
+    /// Can also be done on every save instead, see @compact_palettes_on_save
+    pub fn compact_palettes(&mut self) {
+        let remap = self.palette_remap();
+        if remap.is_identity() {
+            return;
+        }
+
+        self.apply_palette_remap(&remap);
+        (self.voxel_color_palette, self.voxel_data_palette) =
+            remap.compact(&self.voxel_color_palette, &self.voxel_data_palette);
+        self.map_to_color_index_in_palette = self
+            .voxel_color_palette
+            .iter()
+            .enumerate()
+            .map(|(index, color)| (*color, index))
+            .collect();
+        self.map_to_data_index_in_palette = self
+            .voxel_data_palette
+            .iter()
+            .enumerate()
+            .map(|(index, data)| (data.clone(), index))
+            .collect();
+        self.palette_revision = self.palette_revision.wrapping_add(1);
+    }
+
+    /// Updates the palette indices inside every node and journal entry based on the given remap
+    fn apply_palette_remap(&mut self, remap: &PaletteRemap) {
+        for node_key in 0..self.nodes.len() {
+            if self.nodes.key_is_valid(node_key) {
+                remap.apply_to_node(self.nodes.get_mut(node_key));
+            }
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            for transaction in journal
+                .undo_stack
+                .iter_mut()
+                .chain(journal.redo_stack.iter_mut())
+                .chain(journal.open_transaction.iter_mut())
+            {
+                for entry in transaction.entries.iter_mut() {
+                    for region in entry.before.iter_mut().chain(entry.after.iter_mut()) {
+                        region.content = remap.apply(region.content);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects the palette entries in use, and provides their index in the compacted palettes
+    pub(crate) fn palette_remap(&self) -> PaletteRemap {
+        let mut used_colors = vec![false; self.voxel_color_palette.len()];
+        let mut used_data = vec![false; self.voxel_data_palette.len()];
+        let mut mark_used = |index: &PaletteIndexValues| {
+            if NodeContent::pix_color_is_some(index)
+                && let Some(used) = used_colors.get_mut(NodeContent::pix_color_index(index))
+            {
+                *used = true;
+            }
+            if NodeContent::pix_data_is_some(index)
+                && let Some(used) = used_data.get_mut(NodeContent::pix_data_index(index))
+            {
+                *used = true;
+            }
+        };
+        let mut mark_brick = |brick: &BrickData<PaletteIndexValues>| match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => mark_used(voxel),
+            BrickData::Parted(_) | BrickData::Packed(_) => {
+                for voxel in brick
+                    .voxels_iter()
+                    .expect("Expected brick to contain separate voxels")
+                {
+                    mark_used(&voxel);
+                }
+            }
+        };
+
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            let node = self.nodes.get(node_key);
+            match &node.content {
+                NodeContent::Nothing | NodeContent::Internal => {}
+                NodeContent::Leaf(bricks) => bricks.iter().for_each(&mut mark_brick),
+                NodeContent::UniformLeaf(brick) => mark_brick(brick),
+            }
+            mark_brick(&node.mip);
+        }
+        if let Some(journal) = self.journal.as_ref() {
+            for transaction in journal
+                .undo_stack
+                .iter()
+                .chain(journal.redo_stack.iter())
+                .chain(journal.open_transaction.iter())
+            {
+                for entry in transaction.entries.iter() {
+                    for region in entry.before.iter().chain(entry.after.iter()) {
+                        mark_brick(&BrickData::Solid(region.content));
+                    }
+                }
+            }
+        }
+
+        let new_indices = |used: Vec<bool>| {
+            let mut next_index = 0;
+            used.into_iter()
+                .map(|used| {
+                    if used {
+                        next_index += 1;
+                        next_index - 1
+                    } else {
+                        empty_marker()
+                    }
+                })
+                .collect()
+        };
+        PaletteRemap {
+            colors: new_indices(used_colors),
+            data: new_indices(used_data),
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Changes the color at the given index of the palette, recoloring every voxel using it
+    /// If the new color is already in the palette, the voxels using the given index are merged into it,
+    /// and the entry at the given index remains unused until @compact_palettes
+    /// The change is not recorded in the edit journal; reverted voxels use the new color as well
+    /// * `index` - the index of the color inside the palette
+    /// * `color` - the new color, must not be empty
+    /// * Returns with false if there is no color at the given index, or the new color is empty
+    pub fn set_palette_color(&mut self, index: usize, color: Albedo) -> bool {
+        if index >= self.voxel_color_palette.len() || color == Albedo::zero() {
+            return false;
+        }
+        let previous_color = self.voxel_color_palette[index];
+        if previous_color == color {
+            return true;
+        }
+        if self.map_to_color_index_in_palette.get(&previous_color) == Some(&index) {
+            self.map_to_color_index_in_palette.remove(&previous_color);
+        }
+
+        // The entry keeps the new color even when merged, so bricks not yet updated on the GPU stay correct
+        self.voxel_color_palette[index] = color;
+        let merged = match self.map_to_color_index_in_palette.get(&color) {
+            Some(existing_index) => {
+                let mut remap = PaletteRemap::identity(
+                    self.voxel_color_palette.len(),
+                    self.voxel_data_palette.len(),
+                );
+                remap.colors[index] = *existing_index as u32;
+                self.apply_palette_remap(&remap);
+                true
+            }
+            None => {
+                self.map_to_color_index_in_palette.insert(color, index);
+                false
+            }
+        };
+        self.palette_revision = self.palette_revision.wrapping_add(1);
+        if self.mip_map_strategy.is_enabled() {
+            StrategyUpdater(&mut *self).recalculate_mips();
+        }
+        if self.mip_map_strategy.is_enabled() || merged {
+            self.signal_updates(self.palette_user_nodes(merged));
+        }
+        self.signal_change(
+            BoxTreeChangeKind::Update,
+            &V3c::unit(0),
+            &V3c::unit(self.boxtree_size),
+        );
+        true
+    }
+
+    /// Changes the given color in the palette to the new one, recoloring every voxel using it
+    /// see @set_palette_color
+    /// * Returns with false if the given color is not in the palette, or the new color is empty
+    pub fn replace_color(&mut self, old_color: Albedo, new_color: Albedo) -> bool {
+        match self.map_to_color_index_in_palette.get(&old_color) {
+            Some(index) => self.set_palette_color(*index, new_color),
+            None => false,
+        }
+    }
+
+    /// Changes the given user data in the palette to the new one, for every voxel using it
+    /// If the new data is already in the palette, the voxels using the given data are merged into it,
+    /// and the previous entry remains unused until @compact_palettes
+    /// The change is not recorded in the edit journal; reverted voxels use the new data as well
+    /// * Returns with false if the given data is not in the palette, or the new data is empty
+    pub fn replace_data(&mut self, old_data: &T, new_data: T) -> bool {
+        if new_data.is_empty() {
+            return false;
+        }
+        let Some(index) = self.map_to_data_index_in_palette.get(old_data).copied() else {
+            return false;
+        };
+        if *old_data == new_data {
+            return true;
+        }
+        self.map_to_data_index_in_palette.remove(old_data);
+        self.voxel_data_palette[index] = new_data.clone();
+        match self.map_to_data_index_in_palette.get(&new_data) {
+            Some(existing_index) => {
+                let mut remap = PaletteRemap::identity(
+                    self.voxel_color_palette.len(),
+                    self.voxel_data_palette.len(),
+                );
+                remap.data[index] = *existing_index as u32;
+                self.apply_palette_remap(&remap);
+            }
+            None => {
+                self.map_to_data_index_in_palette.insert(new_data, index);
+            }
+        }
+        self.signal_change(
+            BoxTreeChangeKind::Update,
+            &V3c::unit(0),
+            &V3c::unit(self.boxtree_size),
+        );
+        true
+    }
+
+    /// Collects the access stack of every node without children,
+    /// so the MIPs and bricks along them are uploaded again to the GPU
+    /// * `bricks_changed` - true if the bricks of the nodes also need to be uploaded again
+    fn palette_user_nodes(&self, bricks_changed: bool) -> Vec<BoxTreeUpdatedSignalParams> {
+        if self.update_triggers.is_empty() {
+            return vec![];
+        }
+        let updated_sectants: Vec<u8> = if bricks_changed {
+            (0..BOX_NODE_CHILDREN_COUNT as u8).collect()
+        } else {
+            vec![]
+        };
+        let mut updated_nodes = vec![];
+        let mut node_stack: BoxTreeNodeAccessStack = vec![(Self::ROOT_NODE_KEY as usize, 0)];
+        while let Some((node_key, target_sectant)) = node_stack.last().copied() {
+            if BOX_NODE_CHILDREN_COUNT <= target_sectant as usize {
+                node_stack.pop();
+                if let Some(parent) = node_stack.last_mut() {
+                    parent.1 += 1;
+                }
+                continue;
+            }
+            if let NodeContent::Internal = self.nodes.get(node_key).content {
+                let child_key = self.nodes.get(node_key).child(target_sectant);
+                if self.nodes.key_is_valid(child_key) {
+                    node_stack.push((child_key, 0));
+                } else {
+                    node_stack.last_mut().unwrap().1 += 1;
+                }
+            } else {
+                updated_nodes.push((node_stack.clone(), updated_sectants.clone()));
+                node_stack.last_mut().unwrap().1 = BOX_NODE_CHILDREN_COUNT as u8;
+            }
+        }
+        updated_nodes
+    }
+}
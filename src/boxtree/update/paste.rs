@@ -0,0 +1,426 @@
+use crate::{
+    boxtree::{
+        iterate::{BrickSource, BrickTraversal},
+        types::{
+            BoxTreeChangeKind, BoxTreeEdit, BoxTreeUpdatedSignalParams, BrickData, EditOperation,
+            NodeContent, NodeData, OctreeError, PaletteIndexValues, PasteMode,
+        },
+        update::region::{Region, RegionCoverage, RegionShape},
+        BoxTree, VoxelData, BOX_NODE_DIMENSION,
+    },
+    spatial::{
+        math::{flat_projection, vector::V3c},
+        Cube,
+    },
+};
+use std::collections::HashMap;
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Copies the given area into a new tree, with the lowest corner of the area at the origin
+    /// The new tree has the same brick dimension and the smallest size the area fits into
+    /// Only the palette entries used inside the area are copied into the new tree
+    /// * `min` - the lowest corner of the area, must be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the tree bounds
+    pub fn extract(&self, min: &V3c<u32>, max: &V3c<u32>) -> Result<Self, OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*min)) {
+            return Err(OctreeError::InvalidPosition {
+                x: min.x,
+                y: min.y,
+                z: min.z,
+            });
+        }
+        let max = V3c::new(
+            max.x.min(self.boxtree_size),
+            max.y.min(self.boxtree_size),
+            max.z.min(self.boxtree_size),
+        );
+        let extent = max
+            .x
+            .saturating_sub(min.x)
+            .max(max.y.saturating_sub(min.y))
+            .max(max.z.saturating_sub(min.z));
+        let mut size = self.brick_dim * BOX_NODE_DIMENSION as u32;
+        while size < extent {
+            size *= BOX_NODE_DIMENSION as u32;
+        }
+
+        // MIPs are calculated once after every voxel is copied
+        let mut result = Self::new(size, self.brick_dim)?;
+        result.auto_simplify = self.auto_simplify;
+        result.mip_map_strategy = self.mip_map_strategy.clone();
+        result.mip_map_strategy.enabled = false;
+        result.paste_area(self, min, &max, &V3c::unit(0), PasteMode::SkipEmpty);
+        if self.mip_map_strategy.enabled {
+            result
+                .albedo_mip_map_resampling_strategy()
+                .switch_albedo_mip_maps(true);
+        }
+        Ok(result)
+    }
+
+    /// Copies the contents of the given tree into this one
+    /// Parts of the other tree not fitting inside this one are ignored
+    /// * `other` - the tree to copy the contents of
+    /// * `offset` - the position of the lowest corner of the other tree, must be contained within the tree
+    /// * `mode` - decides how the contents of the two trees are combined, see @PasteMode
+    pub fn paste(
+        &mut self,
+        other: &Self,
+        offset: &V3c<u32>,
+        mode: PasteMode,
+    ) -> Result<(), OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*offset)) {
+            return Err(OctreeError::InvalidPosition {
+                x: offset.x,
+                y: offset.y,
+                z: offset.z,
+            });
+        }
+
+        // The whole paste is recorded into the journal as one update
+        let captured = self.journal_capture(offset, &(*offset + V3c::unit(other.boxtree_size)));
+        let journal = self.journal.take();
+        self.paste_area(
+            other,
+            &V3c::unit(0),
+            &V3c::unit(other.boxtree_size),
+            offset,
+            mode,
+        );
+        self.journal = journal;
+        self.journal_record("paste", captured);
+        Ok(())
+    }
+
+    /// Copies the area `source_min..source_max` of the source tree into this tree at the given position
+    /// Solid areas are filled as a whole, aligned bricks are copied directly into empty areas,
+    /// every other voxel is copied separately together in one @BoxTreeEdit
+    /// * `target_min` - the position of `source_min` inside this tree, expected to be contained within the tree
+    pub(crate) fn paste_area(
+        &mut self,
+        source: &Self,
+        source_min: &V3c<u32>,
+        source_max: &V3c<u32>,
+        target_min: &V3c<u32>,
+        mode: PasteMode,
+    ) {
+        // Clip the copied area to the bounds of both trees
+        let source_max = V3c::new(
+            source_max
+                .x
+                .min(source.boxtree_size)
+                .min(source_min.x + self.boxtree_size - target_min.x),
+            source_max
+                .y
+                .min(source.boxtree_size)
+                .min(source_min.y + self.boxtree_size - target_min.y),
+            source_max
+                .z
+                .min(source.boxtree_size)
+                .min(source_min.z + self.boxtree_size - target_min.z),
+        );
+        if source_max.x <= source_min.x
+            || source_max.y <= source_min.y
+            || source_max.z <= source_min.z
+        {
+            return;
+        }
+        let target_max = *target_min + (source_max - *source_min);
+        if PasteMode::Overwrite == mode {
+            self.clear_box(target_min, &target_max)
+                .expect("Expected paste area to be contained within the tree");
+        }
+
+        let mut palette_map = HashMap::<PaletteIndexValues, PaletteIndexValues>::new();
+        let mut operations = vec![];
+        let mut touched_nodes = vec![];
+        let mut traversal = BrickTraversal::new(source, *source_min, source_max);
+        while let Some((bounds, brick_source)) = traversal.next_brick() {
+            let brick_min: V3c<u32> = bounds.min_position.into();
+            let brick_size = bounds.size as u32;
+            let (node_key, sectant) = match brick_source {
+                BrickSource::Solid(voxel) => {
+                    if let Some((min, max)) = Self::paste_target_area(
+                        &brick_min,
+                        brick_size,
+                        (source_min, &source_max, target_min),
+                    ) {
+                        let content = self.remap_from(source, voxel, &mut palette_map);
+                        self.paste_solid(&min, &max, content, mode, &mut operations);
+                    }
+                    continue;
+                }
+                BrickSource::Parted { node_key, sectant } => (node_key, sectant),
+            };
+
+            let voxels = {
+                let node = source.nodes.get(node_key);
+                let brick = match (&node.content, sectant) {
+                    (NodeContent::Leaf(bricks), Some(sectant)) => &bricks[sectant as usize],
+                    (NodeContent::UniformLeaf(brick), None) => brick,
+                    _ => unreachable!("Expected brick source to point to a brick"),
+                };
+                brick
+                    .voxels()
+                    .expect("Expected brick source to contain separate voxels")
+                    .into_owned()
+            };
+
+            // Bricks fully inside the area are copied directly into empty, aligned bricks of the tree
+            if source.brick_dim == self.brick_dim
+                && brick_size == self.brick_dim
+                && brick_min.x >= source_min.x
+                && brick_min.y >= source_min.y
+                && brick_min.z >= source_min.z
+                && brick_min.x + brick_size <= source_max.x
+                && brick_min.y + brick_size <= source_max.y
+                && brick_min.z + brick_size <= source_max.z
+            {
+                let brick_target = brick_min - *source_min + *target_min;
+                if brick_target.x.is_multiple_of(self.brick_dim)
+                    && brick_target.y.is_multiple_of(self.brick_dim)
+                    && brick_target.z.is_multiple_of(self.brick_dim)
+                    && (PasteMode::Overwrite == mode
+                        || self
+                            .voxels_in(&brick_target, &(brick_target + V3c::unit(brick_size)))
+                            .next()
+                            .is_none())
+                {
+                    let mut brick = BrickData::parted(
+                        voxels
+                            .iter()
+                            .map(|voxel| self.remap_from(source, *voxel, &mut palette_map))
+                            .collect(),
+                    );
+                    brick.narrow();
+                    self.place_brick(&brick_target, brick, &mut touched_nodes);
+                    continue;
+                }
+            }
+
+            // One voxel inside the brick might cover multiple voxels of the tree
+            let cell_size = (brick_size / source.brick_dim).max(1);
+            for z in 0..source.brick_dim {
+                for y in 0..source.brick_dim {
+                    for x in 0..source.brick_dim {
+                        let voxel = voxels[flat_projection(
+                            x as usize,
+                            y as usize,
+                            z as usize,
+                            source.brick_dim as usize,
+                        )];
+                        if NodeContent::pix_points_to_empty(
+                            &voxel,
+                            &source.voxel_color_palette,
+                            &source.voxel_data_palette,
+                        ) {
+                            continue;
+                        }
+                        if let Some((min, max)) = Self::paste_target_area(
+                            &(brick_min + V3c::new(x, y, z) * cell_size),
+                            cell_size,
+                            (source_min, &source_max, target_min),
+                        ) {
+                            let content = self.remap_from(source, voxel, &mut palette_map);
+                            self.paste_solid(&min, &max, content, mode, &mut operations);
+                        }
+                    }
+                }
+            }
+        }
+
+        if !touched_nodes.is_empty() {
+            self.signal_updates(touched_nodes);
+            self.signal_change(BoxTreeChangeKind::Insert, target_min, &target_max);
+        }
+        if !operations.is_empty() {
+            BoxTreeEdit {
+                tree: self,
+                operations,
+            }
+            .commit();
+        }
+    }
+
+    /// Provides the palette index inside this tree for the given palette index of the source tree
+    /// Palette entries are added to this tree if needed
+    pub(crate) fn remap_from(
+        &mut self,
+        source: &Self,
+        voxel: PaletteIndexValues,
+        palette_map: &mut HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) -> PaletteIndexValues {
+        *palette_map.entry(voxel).or_insert_with(|| {
+            self.add_to_palette(&NodeContent::pix_get_ref(
+                &voxel,
+                &source.voxel_color_palette,
+                &source.voxel_data_palette,
+            ))
+        })
+    }
+
+    /// Provides the area inside this tree an area of the source tree is copied to
+    /// * `position` - the lowest corner of the area inside the source tree
+    /// * `size` - the extent of the area in each dimension
+    /// * `(source_min, source_max, target_min)` - the copied part of the source tree, and its position inside this tree
+    /// * Returns with the lowest and the highest(exclusive) corner of the target area, or None if nothing is copied
+    fn paste_target_area(
+        position: &V3c<u32>,
+        size: u32,
+        (source_min, source_max, target_min): (&V3c<u32>, &V3c<u32>, &V3c<u32>),
+    ) -> Option<(V3c<u32>, V3c<u32>)> {
+        let min = V3c::new(
+            position.x.max(source_min.x),
+            position.y.max(source_min.y),
+            position.z.max(source_min.z),
+        );
+        let max = V3c::new(
+            (position.x + size).min(source_max.x),
+            (position.y + size).min(source_max.y),
+            (position.z + size).min(source_max.z),
+        );
+        if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+            return None;
+        }
+        Some((
+            min - *source_min + *target_min,
+            max - *source_min + *target_min,
+        ))
+    }
+
+    /// Fills the given area of the tree with the given content, based on the paste mode
+    /// Bigger areas are filled in one pass, single voxels are collected into the given operations
+    /// * `min` - the lowest corner of the area, expected to be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), expected to be contained within the tree
+    /// * `content` - the content to fill the area with, already added to the palette of this tree
+    fn paste_solid(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        content: PaletteIndexValues,
+        mode: PasteMode,
+        operations: &mut Vec<(V3c<u32>, EditOperation)>,
+    ) {
+        let single_voxel = *max - *min == V3c::unit(1);
+        if !single_voxel
+            && (PasteMode::KeepExisting != mode || self.voxels_in(min, max).next().is_none())
+        {
+            self.update_region(
+                &Region {
+                    min: *min,
+                    max: *max,
+                    shape: RegionShape::Box,
+                },
+                content,
+            );
+            return;
+        }
+
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    let position = V3c::new(x, y, z);
+                    if PasteMode::KeepExisting == mode
+                        && !NodeContent::pix_points_to_empty(
+                            &self.get_internal(
+                                Self::ROOT_NODE_KEY as usize,
+                                root_bounds,
+                                &position,
+                            ),
+                            &self.voxel_color_palette,
+                            &self.voxel_data_palette,
+                        )
+                    {
+                        continue;
+                    }
+                    operations.push((
+                        position,
+                        EditOperation::Insert {
+                            overwrite_if_empty: true,
+                            content,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Places the given brick into the tree, creating the nodes containing it if needed
+    /// * `position` - the lowest corner of the brick, expected to be aligned to the brick dimension,
+    ///   with every voxel of the tree inside the brick being empty
+    /// * `touched_nodes` - the nodes updated by the operation are collected into this
+    fn place_brick(
+        &mut self,
+        position: &V3c<u32>,
+        brick: BrickData<PaletteIndexValues>,
+        touched_nodes: &mut Vec<BoxTreeUpdatedSignalParams>,
+    ) {
+        let position_f32 = V3c::<f32>::from(*position);
+        let mut node_stack = vec![(Self::ROOT_NODE_KEY as usize, 0)];
+        let mut bounds_stack = vec![Cube::root_bounds(self.boxtree_size as f32)];
+
+        // Find the node containing the brick
+        let leaf_size = self.brick_dim * BOX_NODE_DIMENSION as u32;
+        loop {
+            let node_key = node_stack.last().unwrap().0;
+            let node_bounds = *bounds_stack.last().unwrap();
+            let sectant = node_bounds.sectant_for(&position_f32);
+            node_stack.last_mut().unwrap().1 = sectant;
+            if node_bounds.size as u32 <= leaf_size {
+                break;
+            }
+
+            if matches!(
+                self.nodes.get(node_key).content,
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
+            ) {
+                // The leaf needs to be divided into separate nodes to keep integrity
+                self.subdivide_leaf_to_nodes(node_key, sectant as usize);
+            }
+            let mut child_key = self.nodes.get(node_key).child(sectant);
+            if !self.nodes.key_is_valid(child_key) {
+                if matches!(self.nodes.get(node_key).content, NodeContent::Nothing) {
+                    self.nodes.get_mut(node_key).content = NodeContent::Internal;
+                    self.nodes.get_mut(node_key).occupied_bits = 0;
+                }
+                child_key = self.nodes.push(NodeData::empty_node());
+                *self
+                    .nodes
+                    .get_mut(node_key)
+                    .child_mut(sectant as usize)
+                    .unwrap() = child_key as u32;
+            }
+            node_stack.push((child_key, 0));
+            bounds_stack.push(node_bounds.child_bounds_for(sectant));
+        }
+
+        let (leaf_key, sectant) = *node_stack.last().unwrap();
+        self.convert_to_leaf(leaf_key);
+        match &mut self.nodes.get_mut(leaf_key).content {
+            NodeContent::Leaf(bricks) => bricks[sectant as usize] = brick,
+            _ => panic!("Expected node to be a leaf after conversion"),
+        }
+
+        // Update occupied bits, occlusion and MIPs of every node containing the brick
+        let region = Region {
+            min: *position,
+            max: *position + V3c::unit(self.brick_dim),
+            shape: RegionShape::Box,
+        };
+        while let Some((node_key, sectant)) = node_stack.last().copied() {
+            self.post_process_region_node(
+                &node_stack,
+                bounds_stack.last().unwrap(),
+                &region,
+                &[(sectant, RegionCoverage::Partial)],
+            );
+            touched_nodes.push((node_stack.clone(), vec![sectant]));
+            if self.auto_simplify {
+                self.simplify(node_key, false);
+            }
+            node_stack.pop();
+            bounds_stack.pop();
+        }
+    }
+}
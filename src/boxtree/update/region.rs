@@ -0,0 +1,708 @@
+use crate::{
+    boxtree::{
+        iterate::execute_for_relevant_sectants,
+        types::{
+            BoxTreeChangeKind, BoxTreeEntry, BoxTreeNodeAccessStack, BoxTreeUpdatedSignalParams,
+            BrickData, NodeChildren, NodeContent, NodeData, OctreeError, PaletteIndexValues,
+        },
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        math::{flat_projection, vector::V3c},
+        Cube, CubeSides,
+    },
+};
+
+/// The shape of the area affected by a region update
+pub(crate) enum RegionShape<'a> {
+    Box,
+    Sphere { center: V3c<u32>, radius: u32 },
+    Custom(&'a dyn Fn(&V3c<u32>) -> bool),
+}
+
+/// An area inside the tree to be updated in one pass
+/// Every affected voxel is inside the range `min..max`
+pub(crate) struct Region<'a> {
+    pub(crate) min: V3c<u32>,
+    pub(crate) max: V3c<u32>,
+    pub(crate) shape: RegionShape<'a>,
+}
+
+/// Describes how much of an area is covered by a region
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegionCoverage {
+    Outside,
+    Partial,
+    Full,
+}
+
+impl Region<'_> {
+    /// The size of the smallest cube containing the region from its minimum position
+    fn extent(&self) -> u32 {
+        (self.max.x - self.min.x)
+            .max(self.max.y - self.min.y)
+            .max(self.max.z - self.min.z)
+    }
+
+    /// Provides how much of the given bounds is covered by the region
+    fn coverage(&self, bounds: &Cube) -> RegionCoverage {
+        let bounds_min: V3c<u32> = bounds.min_position.into();
+        let bounds_max = bounds_min + V3c::unit(bounds.size as u32);
+        if bounds_max.x <= self.min.x
+            || bounds_max.y <= self.min.y
+            || bounds_max.z <= self.min.z
+            || bounds_min.x >= self.max.x
+            || bounds_min.y >= self.max.y
+            || bounds_min.z >= self.max.z
+        {
+            return RegionCoverage::Outside;
+        }
+
+        match &self.shape {
+            RegionShape::Box => {
+                if bounds_min.x >= self.min.x
+                    && bounds_min.y >= self.min.y
+                    && bounds_min.z >= self.min.z
+                    && bounds_max.x <= self.max.x
+                    && bounds_max.y <= self.max.y
+                    && bounds_max.z <= self.max.z
+                {
+                    RegionCoverage::Full
+                } else {
+                    RegionCoverage::Partial
+                }
+            }
+            RegionShape::Sphere { center, radius } => {
+                // Compare the closest and the farthest voxel of the bounds to the radius
+                let mut nearest_distance_squared = 0;
+                let mut farthest_distance_squared = 0;
+                for (center, low, high) in [
+                    (center.x, bounds_min.x, bounds_max.x - 1),
+                    (center.y, bounds_min.y, bounds_max.y - 1),
+                    (center.z, bounds_min.z, bounds_max.z - 1),
+                ] {
+                    let nearest =
+                        low.saturating_sub(center).max(center.saturating_sub(high)) as u64;
+                    let farthest = center.abs_diff(low).max(center.abs_diff(high)) as u64;
+                    nearest_distance_squared += nearest * nearest;
+                    farthest_distance_squared += farthest * farthest;
+                }
+                let radius_squared = *radius as u64 * *radius as u64;
+                if nearest_distance_squared > radius_squared {
+                    RegionCoverage::Outside
+                } else if farthest_distance_squared <= radius_squared {
+                    RegionCoverage::Full
+                } else {
+                    RegionCoverage::Partial
+                }
+            }
+            RegionShape::Custom(_) => RegionCoverage::Partial,
+        }
+    }
+
+    /// Returns with true if the given position is part of the region
+    /// The position is expected to be inside `min..max`
+    fn contains(&self, position: &V3c<u32>) -> bool {
+        match &self.shape {
+            RegionShape::Box => true,
+            RegionShape::Sphere { center, radius } => {
+                let distance_squared = [
+                    center.x.abs_diff(position.x) as u64,
+                    center.y.abs_diff(position.y) as u64,
+                    center.z.abs_diff(position.z) as u64,
+                ]
+                .iter()
+                .map(|d| d * d)
+                .sum::<u64>();
+                distance_squared <= *radius as u64 * *radius as u64
+            }
+            RegionShape::Custom(predicate) => predicate(position),
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Fills every voxel inside the given box with the given data, overwriting existing data
+    /// If all components are empty, this is a no-op, to erase data, please use @clear_box
+    /// * `min` - the lowest corner of the box, must be contained within the tree
+    /// * `max` - the highest corner of the box (exclusive), clipped to the tree bounds
+    /// * `data` - The data to fill the box with
+    pub fn fill_box<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        let data = data.into();
+        if let Some(region) = self.region_within(min, max, RegionShape::Box)?
+            && !data.is_none()
+        {
+            let target_content = self.add_to_palette(&data);
+            self.update_region_recorded("fill_box", &region, target_content);
+        }
+        Ok(())
+    }
+
+    /// Clears every voxel inside the given box
+    /// * `min` - the lowest corner of the box, must be contained within the tree
+    /// * `max` - the highest corner of the box (exclusive), clipped to the tree bounds
+    pub fn clear_box(&mut self, min: &V3c<u32>, max: &V3c<u32>) -> Result<(), OctreeError> {
+        if let Some(region) = self.region_within(min, max, RegionShape::Box)? {
+            self.update_region_recorded("clear_box", &region, empty_marker());
+        }
+        Ok(())
+    }
+
+    /// Fills every voxel not farther from the center, than the given radius, overwriting existing data
+    /// If all components are empty, this is a no-op, to erase data, please use @clear_box
+    /// * `center` - the center of the sphere, must be contained within the tree
+    /// * `radius` - the radius of the sphere in voxels, parts outside the tree are ignored
+    /// * `data` - The data to fill the sphere with
+    pub fn fill_sphere<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        center: &V3c<u32>,
+        radius: u32,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*center)) {
+            return Err(OctreeError::InvalidPosition {
+                x: center.x,
+                y: center.y,
+                z: center.z,
+            });
+        }
+        let data = data.into();
+        let min = V3c::new(
+            center.x.saturating_sub(radius),
+            center.y.saturating_sub(radius),
+            center.z.saturating_sub(radius),
+        );
+        let max = V3c::new(
+            center.x.saturating_add(radius).saturating_add(1),
+            center.y.saturating_add(radius).saturating_add(1),
+            center.z.saturating_add(radius).saturating_add(1),
+        );
+        let shape = RegionShape::Sphere {
+            center: *center,
+            radius,
+        };
+        if let Some(region) = self.region_within(&min, &max, shape)?
+            && !data.is_none()
+        {
+            let target_content = self.add_to_palette(&data);
+            self.update_region_recorded("fill_sphere", &region, target_content);
+        }
+        Ok(())
+    }
+
+    /// Fills every voxel inside the given box where the predicate holds, overwriting existing data
+    /// If all components are empty, this is a no-op, to erase data, please use @clear_box
+    /// * `min` - the lowest corner of the bounding box, must be contained within the tree
+    /// * `max` - the highest corner of the bounding box (exclusive), clipped to the tree bounds
+    /// * `predicate` - Decides for each voxel position inside the bounding box if it is to be filled
+    /// * `data` - The data to fill the shape with
+    pub fn fill_shape<'a, E: Into<BoxTreeEntry<'a, T>>, F: Fn(&V3c<u32>) -> bool>(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        predicate: F,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        let data = data.into();
+        if let Some(region) = self.region_within(min, max, RegionShape::Custom(&predicate))?
+            && !data.is_none()
+        {
+            let target_content = self.add_to_palette(&data);
+            self.update_region_recorded("fill_shape", &region, target_content);
+        }
+        Ok(())
+    }
+
+    /// Creates a region of the given shape, clipped to the bounds of the tree
+    /// * Returns with an error if `min` is outside the tree, and with None if the region is empty
+    fn region_within<'a>(
+        &self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        shape: RegionShape<'a>,
+    ) -> Result<Option<Region<'a>>, OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::<f32>::from(*min)) {
+            return Err(OctreeError::InvalidPosition {
+                x: min.x,
+                y: min.y,
+                z: min.z,
+            });
+        }
+        let max = V3c::new(
+            max.x.min(self.boxtree_size),
+            max.y.min(self.boxtree_size),
+            max.z.min(self.boxtree_size),
+        );
+        if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+            return Ok(None);
+        }
+        Ok(Some(Region {
+            min: *min,
+            max,
+            shape,
+        }))
+    }
+
+    /// Updates the region through @update_region, recording the update into the journal
+    /// * `name` - The name of the transaction the update is recorded as, if none is open
+    fn update_region_recorded(
+        &mut self,
+        name: &str,
+        region: &Region,
+        target_content: PaletteIndexValues,
+    ) {
+        let captured = self.journal_capture(&region.min, &region.max);
+        self.update_region(region, target_content);
+        self.journal_record(name, captured);
+    }
+
+    /// Updates every voxel of the region to the given content in one pass through the tree
+    /// Update triggers are called once for each node touched by the update
+    pub(crate) fn update_region(&mut self, region: &Region, target_content: PaletteIndexValues) {
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let mut node_stack = vec![(Self::ROOT_NODE_KEY as usize, 0)];
+        let mut touched_nodes = vec![];
+        self.update_region_in_node(
+            &mut node_stack,
+            &root_bounds,
+            region,
+            target_content,
+            &mut touched_nodes,
+        );
+
+        self.signal_updates(touched_nodes);
+        self.signal_change(
+            if target_content == empty_marker::<PaletteIndexValues>() {
+                BoxTreeChangeKind::Clear
+            } else {
+                BoxTreeChangeKind::Insert
+            },
+            &region.min,
+            &region.max,
+        );
+    }
+
+    /// Updates the part of the region inside the last node of the given stack
+    /// Fully covered children are overwritten as a whole, partially covered ones are updated recursively
+    /// * Returns with true if the node was updated
+    fn update_region_in_node(
+        &mut self,
+        node_stack: &mut BoxTreeNodeAccessStack,
+        node_bounds: &Cube,
+        region: &Region,
+        target_content: PaletteIndexValues,
+        touched_nodes: &mut Vec<BoxTreeUpdatedSignalParams>,
+    ) -> bool {
+        let node_key = node_stack.last().unwrap().0;
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+
+        // No need to continue if the content of the node already matches the request
+        let node = self.nodes.get(node_key);
+        if (clearing
+            && node
+                .content
+                .is_empty(&self.voxel_color_palette, &self.voxel_data_palette))
+            || (!clearing && node.content.is_all(&target_content))
+        {
+            return false;
+        }
+        std::mem::drop(node);
+
+        let mut relevant_sectants = vec![];
+        execute_for_relevant_sectants(
+            node_bounds,
+            &region.min,
+            region.extent(),
+            |_position_in_target, _update_size_in_target, child_sectant, child_target_bounds| {
+                let coverage = region.coverage(child_target_bounds);
+                if RegionCoverage::Outside != coverage
+                    && !relevant_sectants
+                        .iter()
+                        .any(|(sectant, _)| *sectant == child_sectant)
+                {
+                    relevant_sectants.push((child_sectant, coverage));
+                }
+            },
+        );
+
+        let mut modified_sectants = vec![];
+        let updated = if node_bounds.size / BOX_NODE_DIMENSION as f32 <= self.brick_dim as f32 {
+            self.update_region_in_bricks(
+                node_key,
+                node_bounds,
+                region,
+                target_content,
+                &relevant_sectants,
+                &mut modified_sectants,
+            )
+        } else {
+            let mut removed_children = vec![];
+            for (sectant, coverage) in relevant_sectants.iter().copied() {
+                if matches!(
+                    self.nodes.get(node_key).content,
+                    NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
+                ) {
+                    // The leaf needs to be divided into separate nodes to keep integrity
+                    self.subdivide_leaf_to_nodes(node_key, sectant as usize);
+                }
+
+                let child_bounds = node_bounds.child_bounds_for(sectant);
+                let mut child_key = self.nodes.get(node_key).child(sectant);
+                node_stack.last_mut().unwrap().1 = sectant;
+                if !self.nodes.key_is_valid(child_key) {
+                    if clearing {
+                        continue;
+                    }
+                    if matches!(self.nodes.get(node_key).content, NodeContent::Nothing) {
+                        self.nodes.get_mut(node_key).content = NodeContent::Internal;
+                        self.nodes.get_mut(node_key).occupied_bits = 0;
+                    }
+                    child_key = self.nodes.push(NodeData::empty_node());
+                    *self
+                        .nodes
+                        .get_mut(node_key)
+                        .child_mut(sectant as usize)
+                        .unwrap() = child_key as u32;
+                }
+
+                match coverage {
+                    RegionCoverage::Outside => {}
+                    RegionCoverage::Full if clearing => {
+                        removed_children.push(sectant);
+                    }
+                    RegionCoverage::Full => {
+                        if self.nodes.get(child_key).content.is_all(&target_content) {
+                            continue;
+                        }
+
+                        // Whole child node to be overwritten with data
+                        self.deallocate_children_of(child_key);
+                        let child = self.nodes.get_mut(child_key);
+                        child.children = NodeChildren::NoChildren;
+                        child.content = NodeContent::UniformLeaf(BrickData::Solid(target_content));
+                        child.mip = BrickData::Solid(target_content);
+                        child.occupied_bits = u64::MAX;
+
+                        node_stack.push((child_key, 0));
+                        self.set_sibling_occlusion(node_stack, true);
+                        node_stack.pop();
+                        touched_nodes.push((node_stack.clone(), vec![]));
+                        modified_sectants.push(sectant);
+                    }
+                    RegionCoverage::Partial => {
+                        node_stack.push((child_key, 0));
+                        let child_updated = self.update_region_in_node(
+                            node_stack,
+                            &child_bounds,
+                            region,
+                            target_content,
+                            touched_nodes,
+                        );
+                        node_stack.pop();
+                        if child_updated {
+                            modified_sectants.push(sectant);
+                        }
+                        if matches!(self.nodes.get(child_key).content, NodeContent::Nothing) {
+                            removed_children.push(sectant);
+                        }
+                    }
+                }
+            }
+
+            // Children cleared during this operation need to be freed up
+            for sectant in removed_children {
+                let child_key = self.nodes.get(node_key).child(sectant);
+                if self.nodes.get(child_key).occupied_bits == u64::MAX {
+                    node_stack.last_mut().unwrap().1 = sectant;
+                    node_stack.push((child_key, 0));
+                    self.set_sibling_occlusion(node_stack, false);
+                    node_stack.pop();
+                }
+                self.deallocate_children_of(child_key);
+                self.nodes.free(child_key);
+                self.nodes.get_mut(node_key).clear_child(sectant as usize);
+                if !modified_sectants.contains(&sectant) {
+                    modified_sectants.push(sectant);
+                }
+            }
+            !modified_sectants.is_empty()
+        };
+
+        if !updated {
+            return false;
+        }
+
+        node_stack.last_mut().unwrap().1 = modified_sectants.first().copied().unwrap_or(0);
+        self.post_process_region_node(node_stack, node_bounds, region, &relevant_sectants);
+        touched_nodes.push((node_stack.clone(), modified_sectants));
+
+        if self.auto_simplify {
+            self.simplify(node_key, false);
+        }
+        true
+    }
+
+    /// Updates the bricks of the given node inside the region, converting the node to a leaf if needed
+    /// * Returns with true if the node was updated
+    fn update_region_in_bricks(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &Region,
+        target_content: PaletteIndexValues,
+        relevant_sectants: &[(u8, RegionCoverage)],
+        modified_sectants: &mut Vec<u8>,
+    ) -> bool {
+        let converted = self.convert_to_leaf(node_key);
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        let brick_dim = self.brick_dim;
+        let node = self.nodes.get_mut(node_key);
+        let NodeContent::Leaf(bricks) = &mut node.content else {
+            panic!("Expected node to be a leaf after conversion");
+        };
+        for (sectant, coverage) in relevant_sectants.iter().copied() {
+            let brick = &mut bricks[sectant as usize];
+            let brick_updated = match coverage {
+                RegionCoverage::Outside => false,
+                RegionCoverage::Full => {
+                    let new_brick = if clearing {
+                        BrickData::Empty
+                    } else {
+                        BrickData::Solid(target_content)
+                    };
+                    if *brick != new_brick {
+                        *brick = new_brick;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                RegionCoverage::Partial => Self::update_brick_in_region(
+                    brick,
+                    &node_bounds.child_bounds_for(sectant),
+                    brick_dim,
+                    region,
+                    target_content,
+                    clearing,
+                ),
+            };
+            if brick_updated {
+                modified_sectants.push(sectant);
+            }
+        }
+        converted || !modified_sectants.is_empty()
+    }
+
+    /// Updates every voxel of the brick which is part of the region
+    /// * Returns with true if the brick was updated
+    fn update_brick_in_region(
+        brick: &mut BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        brick_dim: u32,
+        region: &Region,
+        target_content: PaletteIndexValues,
+        clearing: bool,
+    ) -> bool {
+        let brick_min: V3c<u32> = brick_bounds.min_position.into();
+        let start = V3c::new(
+            region.min.x.max(brick_min.x),
+            region.min.y.max(brick_min.y),
+            region.min.z.max(brick_min.z),
+        );
+        let end = V3c::new(
+            region.max.x.min(brick_min.x + brick_dim),
+            region.max.y.min(brick_min.y + brick_dim),
+            region.max.z.min(brick_min.z + brick_dim),
+        );
+        let mut updated = false;
+        for x in start.x..end.x {
+            for y in start.y..end.y {
+                for z in start.z..end.z {
+                    if !region.contains(&V3c::new(x, y, z)) {
+                        continue;
+                    }
+                    let index = flat_projection(
+                        (x - brick_min.x) as usize,
+                        (y - brick_min.y) as usize,
+                        (z - brick_min.z) as usize,
+                        brick_dim as usize,
+                    );
+                    match brick {
+                        BrickData::Empty if clearing => continue,
+                        BrickData::Empty => {
+                            *brick = BrickData::parted(vec![
+                                empty_marker::<PaletteIndexValues>();
+                                brick_dim.pow(3) as usize
+                            ]);
+                        }
+                        BrickData::Solid(voxel) if *voxel == target_content => continue,
+                        BrickData::Solid(voxel) => {
+                            *brick = BrickData::parted(vec![*voxel; brick_dim.pow(3) as usize]);
+                        }
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
+                            if brick.voxel(index) == Some(target_content) {
+                                continue;
+                            }
+                        }
+                    }
+                    brick.set_voxel(index, target_content);
+                    updated = true;
+                }
+            }
+        }
+        if updated {
+            brick.narrow();
+        }
+        updated
+    }
+
+    /// Converts the given node into a leaf with equivalent content
+    /// * Returns with true if the node needed to be converted
+    pub(crate) fn convert_to_leaf(&mut self, node_key: usize) -> bool {
+        let bricks: [BrickData<PaletteIndexValues>; BOX_NODE_CHILDREN_COUNT] =
+            match &self.nodes.get(node_key).content {
+                NodeContent::Leaf(_) => return false,
+                NodeContent::UniformLeaf(BrickData::Empty) => {
+                    vec![BrickData::Empty; BOX_NODE_CHILDREN_COUNT]
+                        .try_into()
+                        .unwrap()
+                }
+                NodeContent::UniformLeaf(BrickData::Solid(voxel)) => {
+                    vec![BrickData::Solid(*voxel); BOX_NODE_CHILDREN_COUNT]
+                        .try_into()
+                        .unwrap()
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    // Each brick is mapped to take up one subsection of the current data
+                    Self::dilute_brick_data(brick.voxels().unwrap().into_owned(), self.brick_dim)
+                        .map(|voxels| {
+                            let mut brick = BrickData::parted(voxels);
+                            brick.narrow();
+                            brick
+                        })
+                }
+                NodeContent::Nothing | NodeContent::Internal => (0..BOX_NODE_CHILDREN_COUNT)
+                    .map(|sectant| {
+                        self.try_brick_from_node(self.nodes.get(node_key).child(sectant as u8))
+                    })
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            };
+        self.deallocate_children_of(node_key);
+        let node = self.nodes.get_mut(node_key);
+        node.children = NodeChildren::NoChildren;
+        node.content = NodeContent::Leaf(bricks);
+        true
+    }
+
+    /// Handles node post-process for content, mips, occupied bits and occlusion bits
+    /// after a region update
+    pub(crate) fn post_process_region_node(
+        &mut self,
+        node_stack: &[(usize, u8)],
+        node_bounds: &Cube,
+        region: &Region,
+        relevant_sectants: &[(u8, RegionCoverage)],
+    ) {
+        let node_key = node_stack.last().unwrap().0;
+        let old_occupied_bits = self.nodes.get(node_key).occupied_bits;
+        let mut new_occupied_bits = old_occupied_bits;
+        for (sectant, _) in relevant_sectants.iter() {
+            if self.node_empty_at(node_key, *sectant) {
+                new_occupied_bits &= !(0x01 << sectant);
+            } else {
+                new_occupied_bits |= 0x01 << sectant;
+            }
+        }
+
+        // If Occupied bits depleted, deallocate children and unset node
+        if 0 == new_occupied_bits {
+            self.deallocate_children_of(node_key);
+            self.nodes.get_mut(node_key).children = NodeChildren::NoChildren;
+            self.nodes.get_mut(node_key).content = NodeContent::Nothing;
+        }
+
+        if old_occupied_bits == u64::MAX && new_occupied_bits != u64::MAX {
+            self.set_sibling_occlusion(node_stack, false);
+        } else if old_occupied_bits != u64::MAX && new_occupied_bits == u64::MAX {
+            self.set_sibling_occlusion(node_stack, true);
+        }
+        self.nodes.get_mut(node_key).occupied_bits = new_occupied_bits;
+
+        // Update every MIP cell the region intersects
+        if !self.mip_map_strategy.enabled {
+            return;
+        }
+        let cell_size = node_bounds.size as u32 / self.brick_dim;
+        let node_min: V3c<u32> = node_bounds.min_position.into();
+        let node_max = node_min + V3c::unit(node_bounds.size as u32);
+        let start = V3c::new(
+            region.min.x.max(node_min.x) - node_min.x,
+            region.min.y.max(node_min.y) - node_min.y,
+            region.min.z.max(node_min.z) - node_min.z,
+        ) / cell_size;
+        let end = (V3c::new(
+            region.max.x.min(node_max.x) - node_min.x,
+            region.max.y.min(node_max.y) - node_min.y,
+            region.max.z.min(node_max.z) - node_min.z,
+        ) + V3c::unit(cell_size - 1))
+            / cell_size;
+        for x in start.x..end.x {
+            for y in start.y..end.y {
+                for z in start.z..end.z {
+                    self.update_mip(
+                        node_key,
+                        node_bounds,
+                        &(node_min + V3c::new(x, y, z) * cell_size),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Sets the occlusion bits of the siblings of the node at the end of the given stack
+    fn set_sibling_occlusion(&mut self, node_stack: &[(usize, u8)], occluded: bool) {
+        for (direction, side) in [
+            (V3c::new(-1., 0., 0.), CubeSides::Right),
+            (V3c::new(1., 0., 0.), CubeSides::Left),
+            (V3c::new(0., -1., 0.), CubeSides::Top),
+            (V3c::new(0., 1., 0.), CubeSides::Bottom),
+            (V3c::new(0., 0., -1.), CubeSides::Front),
+            (V3c::new(0., 0., 1.), CubeSides::Back),
+        ]
+        .iter()
+        {
+            if let Some((sibling_node, _sibling_sectant)) =
+                self.get_sibling_by_stack(*direction, node_stack)
+            {
+                self.nodes
+                    .get_mut(sibling_node)
+                    .set_occlusion(*side, occluded);
+            }
+        }
+    }
+}
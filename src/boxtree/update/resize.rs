@@ -0,0 +1,190 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeChangeKind, BoxTreeNodeAccessStack, BoxTreeUpdatedSignalParams, EditJournal,
+            NodeChildren, NodeContent, NodeData, OctreeError,
+        },
+        BoxTree, StrategyUpdater, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        math::{flat_projection, vector::V3c},
+        Cube,
+    },
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Extends the tree to the given size, placing the current contents at the given position
+    /// The current root is placed under new parent nodes, so no voxel data is copied,
+    /// and the keys and MIPs of every existing node other than the root stay valid
+    /// The journal history is discarded if the contents are moved, see @switch_journal
+    /// * `new_size` - must be `size * (4^x)`, where size is the current size of the tree
+    /// * `anchor` - the position of the current contents in the extended tree,
+    ///   must be a multiple of the current size, with the contents fitting inside the extended tree
+    pub fn grow_to(&mut self, new_size: u32, anchor: &V3c<u32>) -> Result<(), OctreeError> {
+        let growth = new_size / self.boxtree_size;
+        if new_size < self.boxtree_size
+            || !new_size.is_multiple_of(self.boxtree_size)
+            || !growth.is_power_of_two()
+            || !growth.trailing_zeros().is_multiple_of(2)
+        {
+            return Err(OctreeError::InvalidSize(new_size));
+        }
+        if !anchor.x.is_multiple_of(self.boxtree_size)
+            || !anchor.y.is_multiple_of(self.boxtree_size)
+            || !anchor.z.is_multiple_of(self.boxtree_size)
+            || anchor.x >= new_size
+            || anchor.y >= new_size
+            || anchor.z >= new_size
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: anchor.x,
+                y: anchor.y,
+                z: anchor.z,
+            });
+        }
+
+        let original_size = self.boxtree_size;
+        let tree_empty = 0 == self.nodes.get(Self::ROOT_NODE_KEY as usize).occupied_bits;
+        if tree_empty {
+            self.deallocate_children_of(Self::ROOT_NODE_KEY as usize);
+            *self.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = NodeData::empty_node();
+        } else {
+            // Place the root under a new root node for each new level, starting with the lowest one
+            let mut level_size = self.boxtree_size;
+            while level_size < new_size {
+                let sectant = flat_projection(
+                    ((anchor.x / level_size) % BOX_NODE_DIMENSION as u32) as usize,
+                    ((anchor.y / level_size) % BOX_NODE_DIMENSION as u32) as usize,
+                    ((anchor.z / level_size) % BOX_NODE_DIMENSION as u32) as usize,
+                    BOX_NODE_DIMENSION,
+                );
+                let previous_root = std::mem::replace(
+                    self.nodes.get_mut(Self::ROOT_NODE_KEY as usize),
+                    NodeData::empty_node(),
+                );
+                let previous_root_key = self.nodes.push(previous_root);
+                let mut children = [empty_marker(); BOX_NODE_CHILDREN_COUNT];
+                children[sectant] = previous_root_key as u32;
+                let root = self.nodes.get_mut(Self::ROOT_NODE_KEY as usize);
+                root.content = NodeContent::Internal;
+                root.children = NodeChildren::Children(children);
+                root.occupied_bits = 0x01 << sectant;
+                level_size *= BOX_NODE_DIMENSION as u32;
+            }
+        }
+        self.boxtree_size = new_size;
+        if tree_empty {
+            return Ok(());
+        }
+
+        // Collect the new nodes, to update their MIPs bottom up
+        let mut node_stack: BoxTreeNodeAccessStack = vec![];
+        let mut node_key = Self::ROOT_NODE_KEY as usize;
+        let mut node_bounds = Cube::root_bounds(new_size as f32);
+        let mut new_nodes = vec![];
+        while node_bounds.size as u32 > original_size {
+            let sectant = self.nodes.get(node_key).occupied_bits.trailing_zeros() as u8;
+            node_stack.push((node_key, sectant));
+            new_nodes.push((node_key, node_bounds));
+            node_key = self.nodes.get(node_key).child(sectant);
+            node_bounds = node_bounds.child_bounds_for(sectant);
+        }
+        for (node_key, node_bounds) in new_nodes.iter().rev() {
+            StrategyUpdater(&mut *self).recalculate_mip(*node_key, node_bounds);
+        }
+
+        let mut updated_nodes: Vec<BoxTreeUpdatedSignalParams> = vec![];
+        for depth in 1..=node_stack.len() {
+            updated_nodes.push((node_stack[..depth].to_vec(), vec![node_stack[depth - 1].1]));
+        }
+        self.signal_updates(updated_nodes);
+        if V3c::unit(0) != *anchor {
+            if let Some(journal) = self.journal.as_mut() {
+                *journal = EditJournal::default();
+            }
+            self.signal_change(
+                BoxTreeChangeKind::Clear,
+                &V3c::unit(0),
+                &V3c::unit(original_size),
+            );
+            self.signal_change(
+                BoxTreeChangeKind::Insert,
+                anchor,
+                &(*anchor + V3c::unit(original_size)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Removes the outer levels of the tree which have only one occupied child,
+    /// keeping the nodes of the occupied child as they are
+    /// An empty tree is reduced to the smallest valid size
+    /// The journal history is discarded if the contents are moved, see @switch_journal
+    /// * Returns with the position of the kept contents in the tree before shrinking;
+    ///   every voxel is moved by the negative of it
+    pub fn shrink_to_fit(&mut self) -> V3c<u32> {
+        let original_size = self.boxtree_size;
+        let minimum_size = self.brick_dim * BOX_NODE_DIMENSION as u32;
+        let mut offset = V3c::unit(0);
+        while self.boxtree_size > minimum_size {
+            let (content_is_internal, occupied_bits) = {
+                let root = self.nodes.get(Self::ROOT_NODE_KEY as usize);
+                (
+                    matches!(root.content, NodeContent::Internal | NodeContent::Nothing),
+                    root.occupied_bits,
+                )
+            };
+            if !content_is_internal || 1 < occupied_bits.count_ones() {
+                break;
+            }
+            if 0 == occupied_bits {
+                // The tree is empty, every level can be dropped
+                self.deallocate_children_of(Self::ROOT_NODE_KEY as usize);
+                *self.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = NodeData::empty_node();
+                self.boxtree_size = minimum_size;
+                break;
+            }
+
+            // Keep only the occupied child, and make it the new root
+            let sectant = occupied_bits.trailing_zeros() as u8;
+            let child_key = self.nodes.get(Self::ROOT_NODE_KEY as usize).child(sectant);
+            if !self.nodes.key_is_valid(child_key) {
+                break;
+            }
+            *self
+                .nodes
+                .get_mut(Self::ROOT_NODE_KEY as usize)
+                .child_mut(sectant as usize)
+                .unwrap() = empty_marker();
+            self.deallocate_children_of(Self::ROOT_NODE_KEY as usize);
+            let mut new_root = self.nodes.pop(child_key);
+            new_root.occlusion_bits = 0;
+            *self.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = new_root;
+            self.boxtree_size /= BOX_NODE_DIMENSION as u32;
+            offset += V3c::new(
+                sectant as u32 % BOX_NODE_DIMENSION as u32,
+                (sectant as u32 / BOX_NODE_DIMENSION as u32) % BOX_NODE_DIMENSION as u32,
+                sectant as u32 / (BOX_NODE_DIMENSION * BOX_NODE_DIMENSION) as u32,
+            ) * self.boxtree_size;
+        }
+
+        if original_size != self.boxtree_size {
+            if V3c::unit(0) != offset
+                && let Some(journal) = self.journal.as_mut()
+            {
+                *journal = EditJournal::default();
+            }
+            self.signal_updates(vec![(
+                vec![(Self::ROOT_NODE_KEY as usize, 0)],
+                (0..BOX_NODE_CHILDREN_COUNT as u8).collect(),
+            )]);
+            self.signal_change(
+                BoxTreeChangeKind::Insert,
+                &V3c::unit(0),
+                &V3c::unit(self.boxtree_size),
+            );
+        }
+        offset
+    }
+}
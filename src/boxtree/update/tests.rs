@@ -1,9 +1,17 @@
 use crate::{
-    boxtree::{Albedo, BoxTree, BoxTreeEntry, BOX_NODE_CHILDREN_COUNT},
+    boxtree::{
+        types::{BrickData, NodeContent},
+        Albedo, BoxTree, BoxTreeAxis, BoxTreeChange, BoxTreeChangeKind, BoxTreeEntry, PasteMode,
+        SharedBoxTree, ValidationError, BOX_NODE_CHILDREN_COUNT,
+    },
     spatial::{lut::SECTANT_OFFSET_LUT, math::vector::V3c, Cube},
     voxel_data,
 };
 use num_traits::Zero;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 
 #[test]
 fn test_simplest_insert_and_get() {
@@ -1838,3 +1846,1411 @@ fn test_occlusion_bits() {
     assert_eq!(tree.nodes.get(center_node).occlusion_bits, 0x3D);
     assert_eq!(tree.nodes.get(center_node).is_occluded(), false);
 }
+
+#[test]
+fn test_fill_box_and_get() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    let min = V3c::new(3, 5, 7);
+    let max = V3c::new(40, 33, 20);
+    tree.fill_box(&min, &max, &red).expect("boxtree fill box");
+
+    for x in 0..48 {
+        for y in 0..48 {
+            for z in 0..48 {
+                let inside = (min.x..max.x).contains(&x)
+                    && (min.y..max.y).contains(&y)
+                    && (min.z..max.z).contains(&z);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if inside {
+                    assert!(hit == (&red).into(), "Expected hit at {:?}", (x, y, z));
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Unexpected hit at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fill_box_overwrites_and_clips_to_bounds() {
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    tree.insert(&V3c::new(10, 10, 10), &red).ok().unwrap();
+    tree.insert(&V3c::new(2, 2, 2), &red).ok().unwrap();
+    tree.fill_box(&V3c::new(8, 8, 8), &V3c::new(100, 100, 100), &blue)
+        .expect("boxtree fill box");
+
+    assert!(tree.get(&V3c::new(10, 10, 10)) == (&blue).into());
+    assert!(tree.get(&V3c::new(15, 15, 15)) == (&blue).into());
+    assert!(tree.get(&V3c::new(2, 2, 2)) == (&red).into());
+    assert!(tree.get(&V3c::new(7, 8, 8)) == BoxTreeEntry::Empty);
+    assert!(tree
+        .fill_box(&V3c::new(16, 0, 0), &V3c::new(20, 4, 4), &blue)
+        .is_err());
+}
+
+#[test]
+fn test_fill_box_writes_solid_content() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.auto_simplify = false;
+
+    // The first child of the root node is fully covered
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+        .expect("boxtree fill box");
+    let child_key = tree
+        .nodes
+        .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+        .child(0);
+    assert!(matches!(
+        tree.nodes.get(child_key).content,
+        NodeContent::UniformLeaf(BrickData::Solid(_))
+    ));
+
+    // The first brick of the second child of the root node is fully covered
+    tree.fill_box(&V3c::new(16, 0, 0), &V3c::new(20, 4, 4), &red)
+        .expect("boxtree fill box");
+    let child_key = tree
+        .nodes
+        .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+        .child(1);
+    match &tree.nodes.get(child_key).content {
+        NodeContent::Leaf(bricks) => {
+            assert!(matches!(bricks[0], BrickData::Solid(_)));
+            assert!(matches!(bricks[1], BrickData::Empty));
+        }
+        content => panic!("Expected Leaf node instead of {content:?}"),
+    }
+}
+
+#[test]
+fn test_clear_box() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &red)
+        .expect("boxtree fill box");
+    tree.clear_box(&V3c::new(5, 0, 9), &V3c::new(23, 17, 32))
+        .expect("boxtree clear box");
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let cleared = (5..23).contains(&x) && (0..17).contains(&y) && (9..32).contains(&z);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if cleared {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Unexpected hit at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert!(hit == (&red).into(), "Expected hit at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    tree.clear_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+        .expect("boxtree clear box");
+    assert!(matches!(
+        tree.nodes
+            .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+            .content,
+        NodeContent::Nothing
+    ));
+    assert_eq!(
+        0,
+        tree.nodes
+            .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+            .occupied_bits
+    );
+}
+
+#[test]
+fn test_fill_sphere() {
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(32, 4, 32), &blue)
+        .expect("boxtree fill box");
+    let center = V3c::new(10, 3, 14);
+    let radius = 7;
+    tree.fill_sphere(&center, radius, &red)
+        .expect("boxtree fill sphere");
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let distance_squared = (x as i32 - center.x as i32).pow(2)
+                    + (y as i32 - center.y as i32).pow(2)
+                    + (z as i32 - center.z as i32).pow(2);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if distance_squared <= (radius * radius) as i32 {
+                    assert!(hit == (&red).into(), "Expected hit at {:?}", (x, y, z));
+                } else if y < 4 {
+                    assert!(hit == (&blue).into(), "Expected hit at {:?}", (x, y, z));
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Unexpected hit at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fill_shape() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    let predicate = |position: &V3c<u32>| (position.x + position.y + position.z).is_multiple_of(3);
+    tree.fill_shape(&V3c::new(1, 2, 3), &V3c::new(13, 14, 15), predicate, &red)
+        .expect("boxtree fill shape");
+
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let inside = (1..13).contains(&x) && (2..14).contains(&y) && (3..15).contains(&z);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if inside && predicate(&V3c::new(x, y, z)) {
+                    assert!(hit == (&red).into(), "Expected hit at {:?}", (x, y, z));
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Unexpected hit at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fill_box_triggers_once_per_node() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.auto_simplify = false;
+    let trigger_count = Arc::new(AtomicUsize::new(0));
+    let trigger_count_clone = trigger_count.clone();
+    tree.update_triggers
+        .push(Arc::new(move |_node_stack, _sectants| {
+            trigger_count_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+
+    // The root node and its 8 fully covered children are touched
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &red)
+        .expect("boxtree fill box");
+    assert_eq!(9, trigger_count.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_batch_matches_sequential_updates() {
+    let colors: Vec<Albedo> = (1..5).map(|i| Albedo::from(0x110000FF * i)).collect();
+    let mut sequential_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let mut batched_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    sequential_tree
+        .albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    batched_tree
+        .albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+
+    let positions = (0..2000u32)
+        .map(|i| V3c::new((i * 7) % 32, (i * 13) % 29, (i * 31) % 23))
+        .collect::<Vec<_>>();
+    for (i, position) in positions.iter().enumerate() {
+        if i % 5 == 4 {
+            sequential_tree.clear(position).ok().unwrap();
+        } else {
+            sequential_tree
+                .insert(position, &colors[i % colors.len()])
+                .ok()
+                .unwrap();
+        }
+    }
+    batched_tree.batch(|edit| {
+        for (i, position) in positions.iter().enumerate() {
+            if i % 5 == 4 {
+                edit.clear(position).ok().unwrap();
+            } else {
+                edit.insert(position, &colors[i % colors.len()])
+                    .ok()
+                    .unwrap();
+            }
+        }
+    });
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(
+                    sequential_tree.get(&position) == batched_tree.get(&position),
+                    "Mismatch at {:?}: {:?} <> {:?}",
+                    position,
+                    sequential_tree.get(&position),
+                    batched_tree.get(&position)
+                );
+            }
+        }
+    }
+    assert!(
+        sequential_tree
+            .nodes
+            .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+            .mip
+            == batched_tree
+                .nodes
+                .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+                .mip
+    );
+}
+
+#[test]
+fn test_batch_simplifies_on_commit() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    let mut edit = tree.begin_edit();
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                edit.insert(&V3c::new(x, y, z), &red).ok().unwrap();
+            }
+        }
+    }
+    assert_eq!(16 * 16 * 16, edit.len());
+    edit.commit();
+
+    assert!(matches!(
+        tree.nodes
+            .get(BoxTree::<u32>::ROOT_NODE_KEY as usize)
+            .content,
+        NodeContent::UniformLeaf(BrickData::Solid(_))
+    ));
+    assert!(tree.get(&V3c::new(15, 15, 15)) == (&red).into());
+}
+
+#[test]
+fn test_batch_triggers_once_per_node() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    tree.auto_simplify = false;
+    let trigger_count = Arc::new(AtomicUsize::new(0));
+    let trigger_count_clone = trigger_count.clone();
+    tree.update_triggers
+        .push(Arc::new(move |_node_stack, _sectants| {
+            trigger_count_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+
+    // Every update is inside the root node, which is a leaf
+    tree.batch(|edit| {
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    edit.insert(&V3c::new(x, y, z), &red).ok().unwrap();
+                }
+            }
+        }
+    });
+    assert_eq!(1, trigger_count.load(Ordering::Relaxed));
+    assert!(tree.get(&V3c::new(3, 3, 3)) == (&red).into());
+}
+
+#[test]
+fn test_batch_rejects_invalid_position() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    tree.batch(|edit| {
+        assert!(edit.insert(&V3c::new(16, 0, 0), &red).is_err());
+        assert!(edit.clear(&V3c::new(0, 0, 16)).is_err());
+        assert!(edit.is_empty());
+    });
+}
+
+/// Subscribes to the changes of the given tree, collecting them into the returned container
+fn collect_changes(tree: &mut BoxTree) -> Arc<Mutex<Vec<BoxTreeChange>>> {
+    let changes: Arc<Mutex<Vec<BoxTreeChange>>> = Arc::default();
+    let changes_clone = changes.clone();
+    tree.subscribe(move |change| changes_clone.lock().unwrap().push(*change));
+    changes
+}
+
+#[test]
+fn test_subscription_receives_changes() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let changes = collect_changes(&mut tree);
+
+    tree.insert(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    tree.update(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    tree.clear_at_lod(&V3c::new(0, 0, 0), 4).ok().unwrap();
+    tree.fill_box(&V3c::new(2, 2, 2), &V3c::new(5, 6, 7), &red)
+        .ok()
+        .unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+
+    // Nothing to clear, so no changes are made
+    tree.clear(&V3c::new(31, 31, 31)).ok().unwrap();
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Insert,
+                min: V3c::new(1, 2, 3),
+                max: V3c::new(2, 3, 4),
+            },
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Update,
+                min: V3c::new(1, 2, 3),
+                max: V3c::new(2, 3, 4),
+            },
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Clear,
+                min: V3c::new(0, 0, 0),
+                max: V3c::new(4, 4, 4),
+            },
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Insert,
+                min: V3c::new(2, 2, 2),
+                max: V3c::new(5, 6, 7),
+            },
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::MIP,
+                min: V3c::new(0, 0, 0),
+                max: V3c::new(32, 32, 32),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_unsubscribe() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let change_count = Arc::new(AtomicUsize::new(0));
+    let change_count_clone = change_count.clone();
+    let handle = tree.subscribe(move |_change| {
+        change_count_clone.fetch_add(1, Ordering::Relaxed);
+    });
+
+    tree.insert(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    assert_eq!(1, change_count.load(Ordering::Relaxed));
+
+    assert!(tree.unsubscribe(handle));
+    assert!(!tree.unsubscribe(handle));
+    tree.insert(&V3c::new(3, 2, 1), &red).ok().unwrap();
+    assert_eq!(1, change_count.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_batch_changes_are_grouped_by_kind() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let changes = collect_changes(&mut tree);
+
+    tree.batch(|edit| {
+        for x in 0..4 {
+            edit.insert(&V3c::new(x, 1, 1), &red).ok().unwrap();
+        }
+        edit.clear(&V3c::new(2, 2, 2)).ok().unwrap();
+        edit.insert(&V3c::new(20, 20, 20), &red).ok().unwrap();
+    });
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Insert,
+                min: V3c::new(0, 1, 1),
+                max: V3c::new(21, 21, 21),
+            },
+            BoxTreeChange {
+                kind: BoxTreeChangeKind::Clear,
+                min: V3c::new(2, 2, 2),
+                max: V3c::new(3, 3, 3),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_journal_undo_redo() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &red).ok().unwrap();
+    tree.switch_journal(true);
+    assert!(tree.journal_enabled());
+    assert!(!tree.can_undo());
+
+    tree.insert(&V3c::new(1, 1, 1), &red).ok().unwrap();
+    tree.update(&V3c::new(1, 1, 1), voxel_data!(&3))
+        .ok()
+        .unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &green).ok().unwrap();
+    tree.clear(&V3c::new(1, 1, 1)).ok().unwrap();
+    assert!(tree.get(&V3c::new(1, 1, 1)).is_none());
+
+    assert_eq!(Some("clear".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(1, 1, 1)) == BoxTreeEntry::Complex(&red, &3));
+    assert_eq!(Some("insert".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(0, 0, 0)) == (&red).into());
+    assert_eq!(Some("update".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&red).into());
+    assert_eq!(Some("insert".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(1, 1, 1)).is_none());
+    assert!(!tree.can_undo());
+    assert_eq!(None, tree.undo());
+
+    // Edits before the journal was enabled are untouched
+    assert!(tree.get(&V3c::new(0, 0, 0)) == (&red).into());
+
+    assert_eq!(Some("insert".to_string()), tree.redo());
+    assert_eq!(Some("update".to_string()), tree.redo());
+    assert!(tree.get(&V3c::new(1, 1, 1)) == BoxTreeEntry::Complex(&red, &3));
+
+    // A new edit discards the undone transactions
+    tree.insert(&V3c::new(5, 5, 5), &green).ok().unwrap();
+    assert!(!tree.can_redo());
+    assert_eq!(None, tree.redo());
+    assert!(tree.get(&V3c::new(0, 0, 0)) == (&red).into());
+}
+
+#[test]
+fn test_journal_transactions() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.switch_journal(true);
+
+    tree.begin_transaction("wall");
+    for y in 0..8 {
+        tree.insert(&V3c::new(3, y, 3), &red).ok().unwrap();
+    }
+    tree.end_transaction();
+    tree.batch(|edit| {
+        edit.insert(&V3c::new(10, 10, 10), &red).ok().unwrap();
+        edit.clear(&V3c::new(3, 0, 3)).ok().unwrap();
+    });
+    tree.insert_at_lod(&V3c::new(16, 16, 16), 8, &red)
+        .ok()
+        .unwrap();
+    tree.clear_at_lod(&V3c::new(16, 16, 16), 4).ok().unwrap();
+    assert!(tree.get(&V3c::new(17, 17, 17)).is_none());
+    assert!(tree.get(&V3c::new(21, 21, 21)) == (&red).into());
+
+    assert_eq!(Some("clear".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(17, 17, 17)) == (&red).into());
+    assert_eq!(Some("insert".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(21, 21, 21)).is_none());
+    assert_eq!(Some("batch".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(10, 10, 10)).is_none());
+    assert!(tree.get(&V3c::new(3, 0, 3)) == (&red).into());
+    assert_eq!(Some("wall".to_string()), tree.undo());
+    for y in 0..8 {
+        assert!(tree.get(&V3c::new(3, y, 3)).is_none());
+    }
+    assert!(tree.get(&V3c::new(0, 0, 0)).is_none());
+    assert!(!tree.can_undo());
+
+    assert_eq!(Some("wall".to_string()), tree.redo());
+    for y in 0..8 {
+        assert!(tree.get(&V3c::new(3, y, 3)) == (&red).into());
+    }
+
+    // Disabling the journal discards history
+    tree.switch_journal(false);
+    assert!(!tree.can_redo());
+    assert_eq!(None, tree.undo());
+}
+
+#[test]
+fn test_journal_records_region_fills_by_brick() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.insert(&V3c::new(40, 40, 40), &green).ok().unwrap();
+    tree.switch_journal(true);
+
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &red)
+        .ok()
+        .unwrap();
+    tree.fill_sphere(&V3c::new(40, 40, 40), 3, &red)
+        .ok()
+        .unwrap();
+    tree.clear_box(&V3c::new(0, 0, 0), &V3c::new(16, 64, 64))
+        .ok()
+        .unwrap();
+
+    // Solid areas are recorded as a whole instead of voxel by voxel
+    let journal = tree.journal.as_ref().unwrap();
+    let fill = &journal.undo_stack[0].entries[0];
+    assert!(fill.before.is_empty());
+    assert!(fill.after.len() < 16);
+
+    assert_eq!(Some("clear_box".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(3, 3, 3)) == (&red).into());
+    assert_eq!(Some("fill_sphere".to_string()), tree.undo());
+    assert!(tree.get(&V3c::new(40, 40, 40)) == (&green).into());
+    assert!(tree.get(&V3c::new(41, 40, 40)).is_none());
+    assert_eq!(Some("fill_box".to_string()), tree.undo());
+    assert_eq!(1, tree.voxels().count());
+
+    assert_eq!(Some("fill_box".to_string()), tree.redo());
+    assert_eq!(32 * 32 * 32 + 1, tree.voxels().count());
+}
+
+#[test]
+fn test_journal_undo_notifies_and_updates_mips() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.switch_journal(true);
+    tree.insert(&V3c::new(1, 1, 1), &red).ok().unwrap();
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(0, &V3c::new(0, 0, 0))
+        .is_some());
+
+    let changes = collect_changes(&mut tree);
+    tree.undo();
+    assert!(tree.get(&V3c::new(1, 1, 1)).is_none());
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(0, &V3c::new(0, 0, 0))
+        .is_none());
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![BoxTreeChange {
+            kind: BoxTreeChangeKind::Clear,
+            min: V3c::new(1, 1, 1),
+            max: V3c::new(2, 2, 2),
+        }]
+    );
+}
+
+#[test]
+fn test_clearing_node_keeps_siblings() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert(&V3c::new(3, 3, 3), &red).ok().unwrap();
+    tree.insert(&V3c::new(16, 16, 16), &red).ok().unwrap();
+
+    // Erasing the node at (16,16,16) should not erase the sibling under the sectant of its position
+    tree.clear(&V3c::new(16, 16, 16)).ok().unwrap();
+    assert!(tree.get(&V3c::new(16, 16, 16)).is_none());
+    assert!(tree.get(&V3c::new(3, 3, 3)) == (&red).into());
+}
+
+/// Fills the given tree with a pattern of different colors, data and solid areas
+fn fill_paste_pattern(tree: &mut BoxTree, offset: u32) {
+    let red: Albedo = 0xFF0000FF.into();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+        .ok()
+        .unwrap();
+    for x in (0..tree.get_size()).step_by(2) {
+        for y in (0..tree.get_size()).step_by(3) {
+            for z in (0..tree.get_size()).step_by(5) {
+                let color: Albedo = (0x000000FF | ((x + y + z + offset) % 7) << 8).into();
+                if 0 == (x + z) % 4 {
+                    tree.clear(&V3c::new(x, y, z)).ok().unwrap();
+                } else if 0 == (x + y) % 3 {
+                    tree.insert(&V3c::new(x, y, z), voxel_data!(&(x + offset)))
+                        .ok()
+                        .unwrap();
+                } else {
+                    tree.insert(&V3c::new(x, y, z), &color).ok().unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_extract() {
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    fill_paste_pattern(&mut tree, 0);
+
+    let min = V3c::new(3, 4, 5);
+    let max = V3c::new(41, 30, 22);
+    let extracted = tree.extract(&min, &max).ok().unwrap();
+    assert_eq!(64, extracted.get_size());
+    for x in 0..64 {
+        for y in 0..64 {
+            for z in 0..64 {
+                let position = V3c::new(x, y, z);
+                if x < max.x - min.x && y < max.y - min.y && z < max.z - min.z {
+                    assert!(
+                        extracted.get(&position) == tree.get(&(position + min)),
+                        "Expected extracted voxel at {:?} to match the source",
+                        position
+                    );
+                } else {
+                    assert!(extracted.get(&position).is_none());
+                }
+            }
+        }
+    }
+
+    // Only the used palette entries are copied
+    let small = tree
+        .extract(&V3c::new(0, 1, 1), &V3c::new(16, 3, 3))
+        .ok()
+        .unwrap();
+    assert_eq!(16, small.get_size());
+    assert_eq!(1, small.voxel_color_palette.len());
+    assert!(small.voxel_data_palette.is_empty());
+    assert!(tree.extract(&V3c::new(64, 0, 0), &max).is_err());
+}
+
+#[test]
+fn test_paste_modes() {
+    let mut source: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    let mut target: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    fill_paste_pattern(&mut source, 0);
+    fill_paste_pattern(&mut target, 2);
+
+    for offset in [V3c::new(16, 32, 0), V3c::new(5, 3, 57)] {
+        for mode in [
+            PasteMode::Overwrite,
+            PasteMode::KeepExisting,
+            PasteMode::SkipEmpty,
+        ] {
+            let mut pasted: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+            fill_paste_pattern(&mut pasted, 2);
+            pasted.paste(&source, &offset, mode).ok().unwrap();
+            // Only the pasted area and its surroundings are compared
+            for x in offset.x.saturating_sub(2)..(offset.x + 18).min(64) {
+                for y in offset.y.saturating_sub(2)..(offset.y + 18).min(64) {
+                    for z in offset.z.saturating_sub(2)..(offset.z + 18).min(64) {
+                        let position = V3c::new(x, y, z);
+                        let original = target.get(&position);
+                        if x < offset.x
+                            || y < offset.y
+                            || z < offset.z
+                            || x >= offset.x + 16
+                            || y >= offset.y + 16
+                            || z >= offset.z + 16
+                        {
+                            assert!(pasted.get(&position) == original);
+                            continue;
+                        }
+                        let copied = source.get(&(position - offset));
+                        let expected = match mode {
+                            PasteMode::Overwrite => copied,
+                            PasteMode::KeepExisting if original.is_some() => original,
+                            PasteMode::SkipEmpty if copied.is_none() => original,
+                            _ => copied,
+                        };
+                        assert!(
+                            pasted.get(&position) == expected,
+                            "Mismatch at {:?} pasting at {:?} with {:?}",
+                            position,
+                            offset,
+                            mode
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_paste_copies_aligned_bricks() {
+    let green: Albedo = 0x00FF00FF.into();
+    let mut source: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    source.insert(&V3c::new(1, 2, 3), &green).ok().unwrap();
+    source
+        .insert(&V3c::new(14, 13, 12), voxel_data!(&5))
+        .ok()
+        .unwrap();
+
+    let mut target: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    target.switch_journal(true);
+    let changes = collect_changes(&mut target);
+    target
+        .paste(&source, &V3c::new(32, 16, 48), PasteMode::SkipEmpty)
+        .ok()
+        .unwrap();
+    assert!(target.get(&V3c::new(33, 18, 51)) == (&green).into());
+    assert!(target.get(&V3c::new(46, 29, 60)) == voxel_data!(&5));
+    assert_eq!(2, target.voxels().count());
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![BoxTreeChange {
+            kind: BoxTreeChangeKind::Insert,
+            min: V3c::new(32, 16, 48),
+            max: V3c::new(48, 32, 64),
+        }]
+    );
+
+    // The whole paste is undone together
+    assert_eq!(Some("paste".to_string()), target.undo());
+    assert_eq!(0, target.voxels().count());
+    assert!(target
+        .paste(&source, &V3c::new(64, 0, 0), PasteMode::Overwrite)
+        .is_err());
+}
+
+#[test]
+fn test_rotate_and_mirror() {
+    // A transform with the position it moves the given voxel to, inside a tree with the given last index
+    type TransformCase = (fn(&mut BoxTree), fn(u32, u32, u32, u32) -> V3c<u32>);
+    for (size, brick_dimension) in [(16, 4), (32, 2)] {
+        let mut original: BoxTree = BoxTree::new(size, brick_dimension).ok().unwrap();
+        fill_paste_pattern(&mut original, 1);
+        let transforms: [TransformCase; 4] = [
+            (
+                |tree| tree.rotate(BoxTreeAxis::X, 1),
+                |last, x, y, z| V3c::new(x, last - z, y),
+            ),
+            (
+                |tree| tree.rotate(BoxTreeAxis::Y, 3),
+                |last, x, y, z| V3c::new(last - z, y, x),
+            ),
+            (
+                |tree| tree.rotate(BoxTreeAxis::Z, 2),
+                |last, x, y, z| V3c::new(last - x, last - y, z),
+            ),
+            (
+                |tree| tree.mirror(BoxTreeAxis::Y),
+                |last, x, y, z| V3c::new(x, last - y, z),
+            ),
+        ];
+        for (transform, expected_position) in transforms {
+            let mut tree: BoxTree = BoxTree::new(size, brick_dimension).ok().unwrap();
+            fill_paste_pattern(&mut tree, 1);
+            transform(&mut tree);
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        assert!(
+                            tree.get(&expected_position(size - 1, x, y, z))
+                                == original.get(&V3c::new(x, y, z)),
+                            "Mismatch for voxel at {:?} in tree of size {}",
+                            V3c::new(x, y, z),
+                            size
+                        );
+                    }
+                }
+            }
+        }
+
+        // Four quarter turns, or mirroring twice restores the original tree
+        let mut tree: BoxTree = BoxTree::new(size, brick_dimension).ok().unwrap();
+        fill_paste_pattern(&mut tree, 1);
+        for _ in 0..4 {
+            tree.rotate(BoxTreeAxis::Z, 1);
+        }
+        tree.rotate(BoxTreeAxis::X, 2);
+        tree.mirror(BoxTreeAxis::Y);
+        tree.mirror(BoxTreeAxis::Z);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let position = V3c::new(x, y, z);
+                    assert!(tree.get(&position) == original.get(&position));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transform_regions() {
+    let mut original: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut original, 3);
+
+    // Rotating around Y moves local (x, y, z) to (z, y, extent.x - 1 - x)
+    let min = V3c::new(3, 2, 6);
+    let max = V3c::new(13, 7, 10);
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut tree, 3);
+    tree.rotate_region(&min, &max, BoxTreeAxis::Y, 1)
+        .ok()
+        .unwrap();
+    let rotated_max = min + V3c::new(max.z - min.z, max.y - min.y, max.x - min.x);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let inside = |min: &V3c<u32>, max: &V3c<u32>| {
+                    x >= min.x && y >= min.y && z >= min.z && x < max.x && y < max.y && z < max.z
+                };
+                let expected = if inside(&min, &rotated_max) {
+                    let local = position - min;
+                    original.get(&(min + V3c::new(max.x - min.x - 1 - local.z, local.y, local.x)))
+                } else if inside(&min, &max) {
+                    BoxTreeEntry::Empty
+                } else {
+                    original.get(&position)
+                };
+                assert!(
+                    tree.get(&position) == expected,
+                    "Mismatch at {:?}",
+                    position
+                );
+            }
+        }
+    }
+
+    // Mirroring a region keeps it in place
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut tree, 3);
+    tree.mirror_region(&min, &max, BoxTreeAxis::X).ok().unwrap();
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let source = if x >= min.x
+                    && y >= min.y
+                    && z >= min.z
+                    && x < max.x
+                    && y < max.y
+                    && z < max.z
+                {
+                    V3c::new(max.x - 1 - (x - min.x), y, z)
+                } else {
+                    position
+                };
+                assert!(tree.get(&position) == original.get(&source));
+            }
+        }
+    }
+
+    // Invalid regions are rejected
+    assert!(tree
+        .rotate_region(&V3c::new(32, 0, 0), &V3c::new(40, 8, 8), BoxTreeAxis::Z, 1)
+        .is_err());
+}
+
+#[test]
+fn test_translate() {
+    let mut original: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut original, 4);
+    let offset = V3c::new(5, -3, 0);
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut tree, 4);
+    tree.translate(&offset);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if x < 5 || y >= 29 {
+                    BoxTreeEntry::Empty
+                } else {
+                    original.get(&V3c::new(x - 5, y + 3, z))
+                };
+                assert!(
+                    tree.get(&position) == expected,
+                    "Mismatch at {:?}",
+                    position
+                );
+            }
+        }
+    }
+
+    // Moved regions replace what they are placed over, and are undone together
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut tree, 4);
+    tree.switch_journal(true);
+    let min = V3c::new(2, 4, 6);
+    let max = V3c::new(10, 12, 14);
+    tree.translate_region(&min, &max, &V3c::new(-4, 3, 20))
+        .ok()
+        .unwrap();
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if x < 6 && (7..15).contains(&y) && (26..32).contains(&z) {
+                    original.get(&V3c::new(x + 4, y - 3, z - 20))
+                } else if x >= min.x
+                    && y >= min.y
+                    && z >= min.z
+                    && x < max.x
+                    && y < max.y
+                    && z < max.z
+                {
+                    BoxTreeEntry::Empty
+                } else {
+                    original.get(&position)
+                };
+                assert!(
+                    tree.get(&position) == expected,
+                    "Mismatch at {:?}",
+                    position
+                );
+            }
+        }
+    }
+    assert_eq!(Some("translate".to_string()), tree.undo());
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(tree.get(&position) == original.get(&position));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_grow_and_shrink() {
+    let mut original: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut original, 5);
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    fill_paste_pattern(&mut tree, 5);
+
+    assert!(tree.grow_to(64, &V3c::unit(0)).is_err());
+    assert!(tree.grow_to(512, &V3c::new(16, 0, 0)).is_err());
+    assert!(tree.grow_to(512, &V3c::new(512, 0, 0)).is_err());
+
+    let anchor = V3c::new(32, 0, 480);
+    tree.grow_to(512, &anchor).ok().unwrap();
+    assert_eq!(512, tree.get_size());
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(tree.get(&(anchor + position)) == original.get(&position));
+            }
+        }
+    }
+    assert!(tree.get(&V3c::new(0, 0, 0)) == BoxTreeEntry::Empty);
+    assert!(tree.get(&V3c::new(64, 32, 32)) == BoxTreeEntry::Empty);
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(BOX_NODE_CHILDREN_COUNT as u8, &V3c::new(0, 0, 1))
+        .albedo()
+        .is_some());
+
+    // The extended area can be updated
+    let green: Albedo = 0x00FF00FF.into();
+    tree.insert(&V3c::new(500, 400, 300), &green).ok().unwrap();
+    assert!(tree.get(&V3c::new(500, 400, 300)) == (&green).into());
+    tree.clear(&V3c::new(500, 400, 300)).ok().unwrap();
+
+    // Shrinking restores the original tree
+    assert_eq!(anchor, tree.shrink_to_fit());
+    assert_eq!(32, tree.get_size());
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(tree.get(&position) == original.get(&position));
+            }
+        }
+    }
+
+    // Trees with contents in multiple children of the root are kept as they are
+    assert_eq!(V3c::unit(0), tree.shrink_to_fit());
+    assert_eq!(32, tree.get_size());
+
+    // Empty trees are reduced to the smallest size
+    let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+    tree.insert(&V3c::new(100, 3, 70), &green).ok().unwrap();
+    tree.clear(&V3c::new(100, 3, 70)).ok().unwrap();
+    assert_eq!(V3c::unit(0), tree.shrink_to_fit());
+    assert_eq!(8, tree.get_size());
+}
+
+#[test]
+fn test_csg_operations() {
+    let mut original: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut original, 1);
+    let mut other: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut other, 2);
+    other
+        .fill_box(&V3c::new(20, 0, 8), &V3c::new(28, 32, 16), voxel_data!(&7))
+        .ok()
+        .unwrap();
+    let offset = V3c::new(8, 4, 0);
+    let other_at = |position: &V3c<u32>| {
+        if position.x < offset.x || position.y < offset.y || position.z < offset.z {
+            BoxTreeEntry::Empty
+        } else {
+            other.get(&(*position - offset))
+        }
+    };
+    // Keeps the color of this tree, and the data of the other tree if any
+    fn merge<'a>(
+        own: BoxTreeEntry<'a, u32>,
+        theirs: BoxTreeEntry<'a, u32>,
+    ) -> BoxTreeEntry<'a, u32> {
+        match (own.albedo(), theirs.data().or(own.data())) {
+            (Some(albedo), Some(data)) => BoxTreeEntry::Complex(albedo, data),
+            (Some(albedo), None) => BoxTreeEntry::Visual(albedo),
+            (None, Some(data)) => BoxTreeEntry::Informative(data),
+            (None, None) => BoxTreeEntry::Empty,
+        }
+    }
+
+    for operation in ["union", "intersection", "difference", "xor"] {
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        fill_paste_pattern(&mut tree, 1);
+        match operation {
+            "union" => tree.union(&other, &offset, merge),
+            "intersection" => tree.intersection(&other, &offset, merge),
+            "difference" => tree.difference(&other, &offset),
+            _ => tree.xor(&other, &offset),
+        }
+        .ok()
+        .unwrap();
+
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    let own = original.get(&position);
+                    let theirs = other_at(&position);
+                    let expected = match (operation, own.is_some(), theirs.is_some()) {
+                        ("union" | "intersection", true, true) => merge(own, theirs),
+                        ("union" | "xor", false, true) => theirs,
+                        ("union" | "difference" | "xor", true, false) => own,
+                        _ => BoxTreeEntry::Empty,
+                    };
+                    assert!(
+                        tree.get(&position) == expected,
+                        "Mismatch at {:?} after {}",
+                        position,
+                        operation
+                    );
+                }
+            }
+        }
+    }
+
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let other: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+    assert!(tree.difference(&other, &V3c::unit(0)).is_err());
+    let other: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    assert!(tree.xor(&other, &V3c::new(0, 32, 0)).is_err());
+}
+
+#[test]
+fn test_compact_palettes() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    fill_paste_pattern(&mut tree, 6);
+
+    // Overwrite most of the colors and data, so their palette entries are unused
+    let blue: Albedo = 0x0000FFFF.into();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(24, 32, 32), &blue)
+        .ok()
+        .unwrap();
+    tree.switch_journal(true);
+    tree.insert(&V3c::new(31, 31, 31), voxel_data!(&500))
+        .ok()
+        .unwrap();
+    tree.clear(&V3c::new(31, 31, 31)).ok().unwrap();
+    let mut expected: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    fill_paste_pattern(&mut expected, 6);
+    expected
+        .fill_box(&V3c::new(0, 0, 0), &V3c::new(24, 32, 32), &blue)
+        .ok()
+        .unwrap();
+
+    let color_count = tree.voxel_color_palette.len();
+    let data_count = tree.voxel_data_palette.len();
+    tree.compact_palettes();
+    assert!(tree.voxel_color_palette.len() < color_count);
+    assert!(tree.voxel_data_palette.len() < data_count);
+    assert_eq!(
+        tree.voxel_color_palette.len(),
+        tree.map_to_color_index_in_palette.len()
+    );
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(tree.get(&position) == expected.get(&position));
+            }
+        }
+    }
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(0, &V3c::new(0, 0, 0))
+        .albedo()
+        .is_some_and(|albedo| *albedo == blue));
+
+    // Data referenced only by the journal is kept
+    assert!(tree.voxel_data_palette.contains(&500));
+    tree.undo();
+    assert!(tree.get(&V3c::new(31, 31, 31)) == voxel_data!(&500));
+
+    // Compacted palettes are extended the same way
+    let green: Albedo = 0x00FF00FF.into();
+    tree.insert(&V3c::new(1, 1, 1), &green).ok().unwrap();
+    tree.insert(&V3c::new(2, 1, 1), &blue).ok().unwrap();
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&green).into());
+    assert_eq!(
+        tree.voxel_color_palette.len(),
+        tree.map_to_color_index_in_palette.len()
+    );
+}
+
+#[test]
+fn test_palette_editing() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    let green: Albedo = 0x00FF00FF.into();
+    let brown: Albedo = 0x8B4513FF.into();
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(16, 32, 32), &green)
+        .ok()
+        .unwrap();
+    tree.fill_box(&V3c::new(16, 0, 0), &V3c::new(32, 32, 32), &red)
+        .ok()
+        .unwrap();
+    tree.insert(&V3c::new(1, 1, 1), (&green, &5)).ok().unwrap();
+    tree.insert(&V3c::new(2, 1, 1), (&green, &6)).ok().unwrap();
+
+    // Recoloring an entry changes every voxel and MIP using it
+    let green_index = tree.map_to_color_index_in_palette[&green];
+    assert!(tree.set_palette_color(green_index, brown));
+    assert!(!tree.set_palette_color(tree.voxel_color_palette.len(), brown));
+    assert!(!tree.set_palette_color(green_index, Albedo::zero()));
+    assert!(tree.get(&V3c::new(0, 0, 0)) == (&brown).into());
+    assert!(tree.get(&V3c::new(20, 0, 0)) == (&red).into());
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&brown, &5).into());
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(0, &V3c::new(0, 0, 0))
+        .albedo()
+        .is_some_and(|albedo| *albedo == brown));
+    assert!(!tree.replace_color(green, red));
+
+    // Replacing a color with one already in the palette merges them
+    assert!(tree.replace_color(brown, red));
+    assert!(!tree.map_to_color_index_in_palette.contains_key(&brown));
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let entry = tree.get(&V3c::new(x, y, z));
+                assert!(entry.albedo().is_some_and(|albedo| *albedo == red));
+            }
+        }
+    }
+    assert!(tree
+        .albedo_mip_map_resampling_strategy()
+        .sample_root_mip(0, &V3c::new(0, 0, 0))
+        .albedo()
+        .is_some_and(|albedo| *albedo == red));
+    tree.insert(&V3c::new(3, 3, 3), &blue).ok().unwrap();
+    assert!(tree.get(&V3c::new(3, 3, 3)) == (&blue).into());
+    tree.compact_palettes();
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&red, &5).into());
+    assert!(tree.get(&V3c::new(3, 3, 3)) == (&blue).into());
+
+    // User data is replaced and merged the same way
+    assert!(tree.replace_data(&5, 7));
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&red, &7).into());
+    assert!(!tree.replace_data(&5, 8));
+    assert!(!tree.replace_data(&7, 0));
+    assert!(tree.replace_data(&7, 6));
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&red, &6).into());
+    assert!(tree.get(&V3c::new(2, 1, 1)) == (&red, &6).into());
+    assert!(!tree.map_to_data_index_in_palette.contains_key(&7));
+}
+
+#[test]
+fn test_validate_after_edits() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    assert_eq!(tree.validate(), Ok(()));
+
+    tree.insert(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    tree.insert_at_lod(&V3c::new(16, 16, 16), 8, &green)
+        .ok()
+        .unwrap();
+    tree.fill_box(&V3c::new(30, 2, 2), &V3c::new(50, 20, 9), &red)
+        .ok()
+        .unwrap();
+    assert_eq!(tree.validate(), Ok(()));
+
+    tree.clear(&V3c::new(1, 2, 3)).ok().unwrap();
+    tree.clear_at_lod(&V3c::new(16, 16, 16), 4).ok().unwrap();
+    for i in 0..20 {
+        tree.insert(&V3c::new(i * 3, i * 2, i), &green)
+            .ok()
+            .unwrap();
+    }
+    assert_eq!(tree.validate(), Ok(()));
+}
+
+#[test]
+fn test_validate_reports_inconsistencies() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    tree.insert(&V3c::new(9, 9, 9), &red).ok().unwrap();
+    assert_eq!(tree.validate(), Ok(()));
+
+    let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+    let child_key = tree.nodes.get(root_key).child(0);
+
+    // Wrong occupied bits
+    let occupied_bits = tree.nodes.get(root_key).occupied_bits;
+    tree.nodes.get_mut(root_key).occupied_bits = u64::MAX;
+    assert_eq!(
+        tree.validate(),
+        Err(vec![ValidationError::OccupiedBitsMismatch {
+            node_key: root_key,
+            stored: u64::MAX,
+            expected: occupied_bits,
+        },])
+    );
+    tree.nodes.get_mut(root_key).occupied_bits = occupied_bits;
+
+    // Outdated MIP
+    let mip = tree.nodes.get(child_key).mip.clone();
+    tree.nodes.get_mut(child_key).mip = BrickData::Empty;
+    assert_eq!(
+        tree.validate(),
+        Err(vec![ValidationError::OutdatedMip {
+            node_key: child_key
+        }])
+    );
+    tree.nodes.get_mut(child_key).mip = mip;
+
+    // Palette index out of range
+    let mip = tree.nodes.get(root_key).mip.clone();
+    tree.nodes.get_mut(root_key).mip = BrickData::Solid(NodeContent::pix_visual(100));
+    assert_eq!(
+        tree.validate(),
+        Err(vec![ValidationError::PaletteIndexOutOfRange {
+            node_key: root_key,
+            index: 100,
+            palette_size: tree.voxel_color_palette.len(),
+        }])
+    );
+    tree.nodes.get_mut(root_key).mip = mip;
+
+    // Invalid child key
+    *tree.nodes.get_mut(root_key).child_mut(63).unwrap() = 1000;
+    assert_eq!(
+        tree.validate(),
+        Err(vec![ValidationError::InvalidChild {
+            node_key: root_key,
+            sectant: 63,
+            child_key: 1000,
+        }])
+    );
+    tree.nodes.get_mut(root_key).clear_child(63);
+    assert_eq!(tree.validate(), Ok(()));
+}
+
+#[test]
+fn test_snapshot_is_not_affected_by_edits() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.fill_box(&V3c::new(10, 10, 10), &V3c::new(40, 30, 20), &red)
+        .ok()
+        .unwrap();
+
+    let snapshot = tree.snapshot();
+    tree.insert(&V3c::new(100, 100, 100), &green).ok().unwrap();
+    tree.clear(&V3c::new(15, 15, 15)).ok().unwrap();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(5, 5, 5), &green)
+        .ok()
+        .unwrap();
+
+    assert_eq!(tree.get(&V3c::new(100, 100, 100)), (&green).into());
+    assert!(tree.get(&V3c::new(15, 15, 15)).is_none());
+    assert_eq!(snapshot.get(&V3c::new(15, 15, 15)), (&red).into());
+    assert!(snapshot.get(&V3c::new(100, 100, 100)).is_none());
+    assert!(snapshot.get(&V3c::new(2, 2, 2)).is_none());
+    assert_eq!(snapshot.validate(), Ok(()));
+    assert_eq!(tree.validate(), Ok(()));
+
+    // A tree restored from the snapshot can be edited separately
+    let mut restored = snapshot.to_tree();
+    restored.clear(&V3c::new(20, 20, 12)).ok().unwrap();
+    assert!(restored.get(&V3c::new(20, 20, 12)).is_none());
+    assert_eq!(snapshot.get(&V3c::new(20, 20, 12)), (&red).into());
+    assert_eq!(tree.get(&V3c::new(20, 20, 12)), (&red).into());
+}
+
+#[test]
+fn test_snapshot_read_while_editing() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.fill_box(&V3c::new(0, 0, 0), &V3c::new(32, 16, 32), &red)
+        .ok()
+        .unwrap();
+
+    let snapshot = tree.snapshot();
+    let reader = std::thread::spawn(move || {
+        for _ in 0..10 {
+            for x in 0..32 {
+                assert_eq!(snapshot.get(&V3c::new(x, 8, x)), (&red).into());
+                assert!(snapshot.get(&V3c::new(x, 24, x)).is_none());
+            }
+        }
+    });
+    for x in 0..32 {
+        tree.insert(&V3c::new(x, 24, x), &green).ok().unwrap();
+        tree.clear(&V3c::new(x, 8, x)).ok().unwrap();
+    }
+    reader.join().unwrap();
+    assert_eq!(tree.get(&V3c::new(3, 24, 3)), (&green).into());
+}
+
+#[test]
+fn test_shared_tree_read_while_editing() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let shared: SharedBoxTree = SharedBoxTree::new(BoxTree::new(32, 2).ok().unwrap());
+
+    let reader_shared = shared.clone();
+    let reader = std::thread::spawn(move || {
+        // Every published state contains every voxel of the finished edits, in order
+        let mut inserted_count = 0;
+        while inserted_count < 32 {
+            let tree = reader_shared.read();
+            let visible = (0..32)
+                .take_while(|x| tree.get(&V3c::new(*x, 24, *x)) == (&green).into())
+                .count() as u32;
+            assert!(inserted_count <= visible);
+            for x in visible..32 {
+                assert!(tree.get(&V3c::new(x, 24, x)).is_none());
+            }
+            inserted_count = visible;
+        }
+    });
+    for x in 0..32 {
+        shared.edit(|tree| {
+            tree.insert(&V3c::new(x, 24, x), &green).ok().unwrap();
+            tree.insert(&V3c::new(x, 8, x), &red).ok().unwrap();
+        });
+    }
+    reader.join().unwrap();
+    assert_eq!(shared.read().get(&V3c::new(3, 8, 3)), (&red).into());
+}
@@ -0,0 +1,405 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeAxis, BoxTreeChangeKind, BoxTreeNodeAccessStack, BoxTreeUpdatedSignalParams,
+            BrickData, EditJournal, NodeChildren, NodeContent, OctreeError, PaletteIndexValues,
+            PasteMode,
+        },
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::math::{flat_projection, vector::V3c},
+};
+
+/// Maps positions inside a cube onto their rotated or mirrored positions inside the same cube
+/// Each component of the result is a component of the source position, optionally flipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CubeTransform {
+    /// The component of the source position each component of the result is taken from
+    source_axis: [usize; 3],
+
+    /// Set for components of the result which are flipped inside the cube
+    flipped: [bool; 3],
+}
+
+impl CubeTransform {
+    const IDENTITY: Self = Self {
+        source_axis: [0, 1, 2],
+        flipped: [false, false, false],
+    };
+
+    /// Rotation by 90° around the given axis, counter-clockwise when looking from the positive end of the axis
+    fn quarter_turn(axis: BoxTreeAxis) -> Self {
+        match axis {
+            BoxTreeAxis::X => Self {
+                source_axis: [0, 2, 1],
+                flipped: [false, true, false],
+            },
+            BoxTreeAxis::Y => Self {
+                source_axis: [2, 1, 0],
+                flipped: [false, false, true],
+            },
+            BoxTreeAxis::Z => Self {
+                source_axis: [1, 0, 2],
+                flipped: [true, false, false],
+            },
+        }
+    }
+
+    /// Rotation by the given number of quarter turns around the given axis, see @quarter_turn
+    fn rotation(axis: BoxTreeAxis, quarter_turns: u32) -> Self {
+        (0..quarter_turns % 4).fold(Self::IDENTITY, |transform, _| {
+            transform.then(&Self::quarter_turn(axis))
+        })
+    }
+
+    /// Mirroring along the given axis
+    fn mirror(axis: BoxTreeAxis) -> Self {
+        let mut transform = Self::IDENTITY;
+        transform.flipped[axis as usize] = true;
+        transform
+    }
+
+    /// Provides the transform applying this one, then the given one
+    fn then(&self, other: &Self) -> Self {
+        let mut result = Self::IDENTITY;
+        for axis in 0..3 {
+            result.source_axis[axis] = self.source_axis[other.source_axis[axis]];
+            result.flipped[axis] = other.flipped[axis] ^ self.flipped[other.source_axis[axis]];
+        }
+        result
+    }
+
+    /// Provides the transformed position of the given position inside a cube of the given size
+    fn apply(&self, position: [u32; 3], size: u32) -> [u32; 3] {
+        let mut result = [0; 3];
+        for axis in 0..3 {
+            let component = position[self.source_axis[axis]];
+            result[axis] = if self.flipped[axis] {
+                size - 1 - component
+            } else {
+                component
+            };
+        }
+        result
+    }
+
+    /// Provides the sectant the given sectant is moved to
+    fn apply_sectant(&self, sectant: u8) -> u8 {
+        let dim = BOX_NODE_DIMENSION as u32;
+        let sectant = sectant as u32;
+        let [x, y, z] = self.apply(
+            [sectant % dim, (sectant / dim) % dim, sectant / (dim * dim)],
+            dim,
+        );
+        flat_projection(x as usize, y as usize, z as usize, BOX_NODE_DIMENSION) as u8
+    }
+
+    /// Moves each bit of the given occupancy bitmap to the bit of the transformed sectant
+    fn apply_occupied_bits(&self, occupied_bits: u64) -> u64 {
+        (0..BOX_NODE_CHILDREN_COUNT as u8)
+            .filter(|sectant| 0 != (occupied_bits & (0x01 << sectant)))
+            .fold(0, |result, sectant| {
+                result | (0x01 << self.apply_sectant(sectant))
+            })
+    }
+
+    /// Moves each bit of the given occlusion bits to the bit of the transformed side, see @NodeData::occlusion_bits
+    fn apply_occlusion_bits(&self, occlusion_bits: u8) -> u8 {
+        // Bit position of the negative and the positive side along each axis
+        const SIDE_BITS: [(u8, u8); 3] = [(4, 5), (3, 2), (0, 1)];
+        let mut result = occlusion_bits & !0x3F;
+        for (axis, (negative_bit, positive_bit)) in SIDE_BITS.iter().enumerate() {
+            let source_axis = self.source_axis[axis];
+            let (mut source_negative, mut source_positive) = SIDE_BITS[source_axis];
+            if self.flipped[axis] {
+                std::mem::swap(&mut source_negative, &mut source_positive);
+            }
+            result |= ((occlusion_bits >> source_negative) & 0x01) << negative_bit;
+            result |= ((occlusion_bits >> source_positive) & 0x01) << positive_bit;
+        }
+        result
+    }
+
+    /// Provides the given brick with its voxels moved to their transformed positions
+    fn apply_brick(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_dim: u32,
+    ) -> BrickData<PaletteIndexValues> {
+        let Some(voxels) = brick.voxels() else {
+            return brick.clone();
+        };
+        let mut transformed = voxels.to_vec();
+        for z in 0..brick_dim {
+            for y in 0..brick_dim {
+                for x in 0..brick_dim {
+                    let [tx, ty, tz] = self.apply([x, y, z], brick_dim);
+                    transformed[flat_projection(
+                        tx as usize,
+                        ty as usize,
+                        tz as usize,
+                        brick_dim as usize,
+                    )] = voxels
+                        [flat_projection(x as usize, y as usize, z as usize, brick_dim as usize)];
+                }
+            }
+        }
+        let mut brick = BrickData::parted(transformed);
+        brick.narrow();
+        brick
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Rotates the contents of the whole tree by 90° the given number of times
+    /// The rotation is counter-clockwise when looking from the positive end of the axis towards the origin
+    /// The journal history is discarded, as recorded positions are no longer valid, see @switch_journal
+    /// * `axis` - The axis to rotate around
+    /// * `quarter_turns` - The number of 90° rotations
+    pub fn rotate(&mut self, axis: BoxTreeAxis, quarter_turns: u32) {
+        self.transform_nodes(&CubeTransform::rotation(axis, quarter_turns));
+    }
+
+    /// Mirrors the contents of the whole tree along the given axis
+    /// The journal history is discarded, as recorded positions are no longer valid, see @switch_journal
+    pub fn mirror(&mut self, axis: BoxTreeAxis) {
+        self.transform_nodes(&CubeTransform::mirror(axis));
+    }
+
+    /// Moves the contents of the whole tree by the given offset
+    /// Parts moved outside the tree are erased
+    /// The journal history is discarded, as recorded positions are no longer valid, see @switch_journal
+    pub fn translate(&mut self, offset: &V3c<i32>) {
+        if *offset == V3c::unit(0) {
+            return;
+        }
+        let journal = self.journal.take();
+        let size = V3c::unit(self.boxtree_size);
+        self.transform_area(&V3c::unit(0), &size, &CubeTransform::IDENTITY, offset)
+            .expect("Expected the whole tree to be a valid area");
+        self.journal = journal.map(|_| EditJournal::default());
+    }
+
+    /// Rotates the contents of the given area by 90° the given number of times
+    /// The rotated area keeps its lowest corner, and replaces the contents it is placed over
+    /// Parts of the rotated area outside the tree are erased
+    /// * `min` - the lowest corner of the area, must be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the tree bounds
+    /// * `axis` - The axis to rotate around, see @rotate
+    /// * `quarter_turns` - The number of 90° rotations
+    pub fn rotate_region(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        axis: BoxTreeAxis,
+        quarter_turns: u32,
+    ) -> Result<(), OctreeError> {
+        self.transform_region(
+            min,
+            max,
+            &CubeTransform::rotation(axis, quarter_turns),
+            &V3c::unit(0),
+            "rotate",
+        )
+    }
+
+    /// Mirrors the contents of the given area along the given axis
+    /// * `min` - the lowest corner of the area, must be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the tree bounds
+    pub fn mirror_region(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        axis: BoxTreeAxis,
+    ) -> Result<(), OctreeError> {
+        self.transform_region(
+            min,
+            max,
+            &CubeTransform::mirror(axis),
+            &V3c::unit(0),
+            "mirror",
+        )
+    }
+
+    /// Moves the contents of the given area by the given offset
+    /// The moved area replaces the contents it is placed over, parts moved outside the tree are erased
+    /// * `min` - the lowest corner of the area, must be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the tree bounds
+    pub fn translate_region(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        offset: &V3c<i32>,
+    ) -> Result<(), OctreeError> {
+        self.transform_region(min, max, &CubeTransform::IDENTITY, offset, "translate")
+    }
+
+    /// Transforms the given area, recording the update into the journal as one transaction
+    fn transform_region(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        transform: &CubeTransform,
+        offset: &V3c<i32>,
+        name: &str,
+    ) -> Result<(), OctreeError> {
+        if self.journal.is_none() {
+            return self.transform_area(min, max, transform, offset);
+        }
+
+        // The area is only updated around the original and the moved area
+        let journal_min = V3c::new(
+            (min.x as i64 + offset.x.min(0) as i64).max(0) as u32,
+            (min.y as i64 + offset.y.min(0) as i64).max(0) as u32,
+            (min.z as i64 + offset.z.min(0) as i64).max(0) as u32,
+        );
+        let extent = max
+            .x
+            .min(self.boxtree_size)
+            .saturating_sub(min.x)
+            .max(max.y.min(self.boxtree_size).saturating_sub(min.y))
+            .max(max.z.min(self.boxtree_size).saturating_sub(min.z));
+        let journal_max = V3c::new(
+            (min.x as i64 + extent as i64 + offset.x.max(0) as i64).max(0) as u32,
+            (min.y as i64 + extent as i64 + offset.y.max(0) as i64).max(0) as u32,
+            (min.z as i64 + extent as i64 + offset.z.max(0) as i64).max(0) as u32,
+        );
+        let captured = self.journal_capture(&journal_min, &journal_max);
+        let journal = self.journal.take();
+        let result = self.transform_area(min, max, transform, offset);
+        self.journal = journal;
+        self.journal_record(name, captured);
+        result
+    }
+
+    /// Copies the given area out of the tree, transforms it, then places it back moved by the given offset
+    /// * `min` - the lowest corner of the area, must be contained within the tree
+    /// * `max` - the highest corner of the area (exclusive), clipped to the tree bounds
+    fn transform_area(
+        &mut self,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        transform: &CubeTransform,
+        offset: &V3c<i32>,
+    ) -> Result<(), OctreeError> {
+        let mut area = self.extract(min, max)?;
+        let max = V3c::new(
+            max.x.min(self.boxtree_size),
+            max.y.min(self.boxtree_size),
+            max.z.min(self.boxtree_size),
+        );
+        if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+            return Ok(());
+        }
+        area.transform_nodes(transform);
+
+        // Find the transformed area inside the extracted tree, and where to place it
+        let extent = [max.x - min.x, max.y - min.y, max.z - min.z];
+        let min = [min.x, min.y, min.z];
+        let offset = [offset.x, offset.y, offset.z];
+        let mut source_min = [0; 3];
+        let mut source_max = [0; 3];
+        let mut target_min = [0; 3];
+        for axis in 0..3 {
+            let transformed_extent = extent[transform.source_axis[axis]];
+            source_min[axis] = if transform.flipped[axis] {
+                area.boxtree_size - transformed_extent
+            } else {
+                0
+            };
+            source_max[axis] = source_min[axis] + transformed_extent;
+
+            // Parts moved below the origin are not placed back
+            let target = min[axis] as i64 + offset[axis] as i64;
+            if target < 0 {
+                source_min[axis] =
+                    (source_min[axis] as i64 - target).min(source_max[axis] as i64) as u32;
+            }
+            target_min[axis] = target.clamp(0, self.boxtree_size as i64) as u32;
+        }
+
+        self.clear_box(&V3c::new(min[0], min[1], min[2]), &max)?;
+        if source_min
+            .iter()
+            .zip(source_max.iter())
+            .all(|(low, high)| low < high)
+            && target_min.iter().all(|target| *target < self.boxtree_size)
+        {
+            self.paste_area(
+                &area,
+                &V3c::new(source_min[0], source_min[1], source_min[2]),
+                &V3c::new(source_max[0], source_max[1], source_max[2]),
+                &V3c::new(target_min[0], target_min[1], target_min[2]),
+                PasteMode::Overwrite,
+            );
+        }
+        Ok(())
+    }
+
+    /// Applies the given transform to the structure of every node in the tree
+    /// Children, bricks and MIPs are moved to their transformed positions without copying any voxel data
+    fn transform_nodes(&mut self, transform: &CubeTransform) {
+        if CubeTransform::IDENTITY == *transform {
+            return;
+        }
+
+        let mut updated_nodes: Vec<BoxTreeUpdatedSignalParams> = vec![];
+        let mut node_stack: Vec<BoxTreeNodeAccessStack> =
+            vec![vec![(Self::ROOT_NODE_KEY as usize, 0)]];
+        while let Some(access_stack) = node_stack.pop() {
+            let node_key = access_stack.last().unwrap().0;
+            let node = self.nodes.get_mut(node_key);
+            node.content = match &node.content {
+                NodeContent::Nothing => NodeContent::Nothing,
+                NodeContent::Internal => NodeContent::Internal,
+                NodeContent::UniformLeaf(brick) => {
+                    NodeContent::UniformLeaf(transform.apply_brick(brick, self.brick_dim))
+                }
+                NodeContent::Leaf(bricks) => {
+                    let mut transformed = bricks.clone();
+                    for (sectant, brick) in bricks.iter().enumerate() {
+                        transformed[transform.apply_sectant(sectant as u8) as usize] =
+                            transform.apply_brick(brick, self.brick_dim);
+                    }
+                    NodeContent::Leaf(transformed)
+                }
+            };
+            node.mip = transform.apply_brick(&node.mip, self.brick_dim);
+            node.occupied_bits = transform.apply_occupied_bits(node.occupied_bits);
+            node.occlusion_bits = transform.apply_occlusion_bits(node.occlusion_bits);
+            let children = match node.children {
+                NodeChildren::Children(children) => {
+                    let mut transformed = children;
+                    for (sectant, child) in children.iter().enumerate() {
+                        transformed[transform.apply_sectant(sectant as u8) as usize] = *child;
+                    }
+                    node.children = NodeChildren::Children(transformed);
+                    transformed
+                }
+                NodeChildren::NoChildren => [empty_marker(); BOX_NODE_CHILDREN_COUNT],
+            };
+            for (sectant, child) in children.iter().enumerate() {
+                if self.nodes.key_is_valid(*child as usize) {
+                    let mut child_stack = access_stack.clone();
+                    child_stack.last_mut().unwrap().1 = sectant as u8;
+                    child_stack.push((*child as usize, 0));
+                    node_stack.push(child_stack);
+                }
+            }
+            if !self.update_triggers.is_empty() {
+                updated_nodes.push((access_stack, (0..BOX_NODE_CHILDREN_COUNT as u8).collect()));
+            }
+        }
+
+        if let Some(journal) = self.journal.as_mut() {
+            *journal = EditJournal::default();
+        }
+        self.signal_updates(updated_nodes);
+        self.signal_change(
+            BoxTreeChangeKind::Insert,
+            &V3c::unit(0),
+            &V3c::unit(self.boxtree_size),
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeChildren, NodeContent, NodeData, PaletteIndexValues},
+        BoxTree, StrategyUpdater, ValidationError, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube, CubeSides},
+};
+use std::borrow::Cow;
+
+/// A node reached during validation
+/// * The key of the node
+/// * The bounds of the node
+/// * The access path of the node: the key of each parent along with the sectant of the path
+type ValidatedNode = (usize, Cube, Vec<(usize, u8)>);
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Walks every node of the tree, and reports each inconsistency found in the structure
+    /// Child keys, palette indices and node contents are checked first; occupied bits,
+    /// occlusion bits and MIPs are only checked if these are consistent
+    /// The check resamples every MIP, so it is intended for debugging, e.g. after edits or loading
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        if !self.nodes.key_is_valid(Self::ROOT_NODE_KEY as usize) {
+            return Err(vec![ValidationError::MissingRoot]);
+        }
+
+        // Collect the nodes reachable from the root, parents are collected before their children
+        let mut errors = vec![];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut nodes: Vec<ValidatedNode> = vec![];
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+            vec![],
+        )];
+        while let Some((node_key, node_bounds, access_stack)) = node_stack.pop() {
+            if visited[node_key] {
+                errors.push(ValidationError::SharedNode { node_key });
+                continue;
+            }
+            visited[node_key] = true;
+
+            let node = self.nodes.get(node_key);
+            self.validate_node_structure(node_key, &node, &mut errors);
+            if let NodeChildren::Children(children) = node.children {
+                for (sectant, child_key) in children.iter().enumerate() {
+                    let child_key = *child_key as usize;
+                    if child_key == empty_marker::<u32>() as usize {
+                        continue;
+                    }
+                    if !self.nodes.key_is_valid(child_key) {
+                        errors.push(ValidationError::InvalidChild {
+                            node_key,
+                            sectant: sectant as u8,
+                            child_key,
+                        });
+                        continue;
+                    }
+                    let mut child_access_stack = access_stack.clone();
+                    child_access_stack.push((node_key, sectant as u8));
+                    node_stack.push((
+                        child_key,
+                        node_bounds.child_bounds_for(sectant as u8),
+                        child_access_stack,
+                    ));
+                }
+            }
+            nodes.push((node_key, node_bounds, access_stack));
+        }
+
+        // Content dependent checks would access invalid nodes or palette entries otherwise
+        if errors.is_empty() {
+            self.validate_occupied_bits(&nodes, &mut errors);
+            self.validate_occlusion_bits(&nodes, &mut errors);
+            self.validate_mips(&nodes, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks the content, the bricks and the palette references of a single node
+    fn validate_node_structure(
+        &self,
+        node_key: usize,
+        node: &NodeData,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        let has_children = matches!(node.children, NodeChildren::Children(_));
+        let bricks: &[BrickData<PaletteIndexValues>] = match &node.content {
+            NodeContent::Internal => &[],
+            NodeContent::Nothing => {
+                if has_children {
+                    errors.push(ValidationError::InvalidContent {
+                        node_key,
+                        reason: "Empty node has children".to_string(),
+                    });
+                }
+                &[]
+            }
+            NodeContent::UniformLeaf(brick) => {
+                if has_children {
+                    errors.push(ValidationError::InvalidContent {
+                        node_key,
+                        reason: "Uniform leaf node has children".to_string(),
+                    });
+                }
+                std::slice::from_ref(brick)
+            }
+            NodeContent::Leaf(bricks) => {
+                if has_children {
+                    errors.push(ValidationError::InvalidContent {
+                        node_key,
+                        reason: "Leaf node has children".to_string(),
+                    });
+                }
+                bricks
+            }
+        };
+
+        let brick_size = self.brick_dim.pow(3) as usize;
+        for brick in bricks.iter().chain(std::iter::once(&node.mip)) {
+            let voxels: Cow<[PaletteIndexValues]> = match brick {
+                BrickData::Empty => Cow::Borrowed(&[]),
+                BrickData::Solid(voxel) => Cow::Borrowed(std::slice::from_ref(voxel)),
+                BrickData::Parted(voxels) => voxels.as_slice(),
+                BrickData::Packed(packed) => Cow::Borrowed(&packed.palette),
+            };
+            if let BrickData::Parted(_) | BrickData::Packed(_) = brick {
+                let voxel_count = match brick {
+                    BrickData::Packed(packed) => packed.len(),
+                    _ => voxels.len(),
+                };
+                if voxel_count != brick_size {
+                    errors.push(ValidationError::InvalidContent {
+                        node_key,
+                        reason: format!("Brick of {voxel_count} voxels, instead of {brick_size}"),
+                    });
+                }
+            }
+            for voxel in voxels.iter() {
+                if NodeContent::pix_color_is_some(voxel)
+                    && NodeContent::pix_color_index(voxel) >= self.voxel_color_palette.len()
+                {
+                    errors.push(ValidationError::PaletteIndexOutOfRange {
+                        node_key,
+                        index: NodeContent::pix_color_index(voxel),
+                        palette_size: self.voxel_color_palette.len(),
+                    });
+                }
+                if NodeContent::pix_data_is_some(voxel)
+                    && NodeContent::pix_data_index(voxel) >= self.voxel_data_palette.len()
+                {
+                    errors.push(ValidationError::PaletteIndexOutOfRange {
+                        node_key,
+                        index: NodeContent::pix_data_index(voxel),
+                        palette_size: self.voxel_data_palette.len(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Checks if the occupied bits of each node agree with the content below it
+    fn validate_occupied_bits(&self, nodes: &[ValidatedNode], errors: &mut Vec<ValidationError>) {
+        for (node_key, _, _) in nodes.iter() {
+            let mut expected = 0;
+            for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                if !self.node_empty_at(*node_key, sectant) {
+                    expected |= 0x01 << sectant;
+                }
+            }
+            let stored = self.nodes.get(*node_key).occupied_bits;
+            if stored != expected {
+                errors.push(ValidationError::OccupiedBitsMismatch {
+                    node_key: *node_key,
+                    stored,
+                    expected,
+                });
+            }
+        }
+    }
+
+    /// Checks if each node marked occluded from every side has a full node next to it on each side
+    /// Only these nodes are checked, as they are skipped while streaming the content of the tree;
+    /// Partial occlusion and missing occlusion are not reported, as they only affect performance
+    fn validate_occlusion_bits(&self, nodes: &[ValidatedNode], errors: &mut Vec<ValidationError>) {
+        // Full nodes mark their siblings occluded, which might be larger nodes on a higher level
+        let mut expected_occlusion = vec![0u8; self.nodes.len()];
+        for (node_key, _, access_stack) in nodes.iter() {
+            if access_stack.is_empty() || u64::MAX != self.nodes.get(*node_key).occupied_bits {
+                continue;
+            }
+            for (direction, side) in [
+                (V3c::new(-1., 0., 0.), CubeSides::Right),
+                (V3c::new(1., 0., 0.), CubeSides::Left),
+                (V3c::new(0., -1., 0.), CubeSides::Top),
+                (V3c::new(0., 1., 0.), CubeSides::Bottom),
+                (V3c::new(0., 0., -1.), CubeSides::Front),
+                (V3c::new(0., 0., 1.), CubeSides::Back),
+            ]
+            .iter()
+            {
+                if let Some((sibling_node, _sibling_sectant)) =
+                    self.get_sibling_by_stack(*direction, access_stack)
+                {
+                    expected_occlusion[sibling_node] |= 0x01 << (*side as u8);
+                }
+            }
+        }
+
+        // Nodes are also occluded by the full area of the same size next to them
+        for (node_key, node_bounds, _) in nodes.iter() {
+            let node = self.nodes.get(*node_key);
+            if !node.is_occluded() || matches!(node.children, NodeChildren::NoChildren) {
+                continue;
+            }
+            let stored = node.occlusion_bits & 0x3F;
+            std::mem::drop(node);
+            for (direction, side) in [
+                (V3c::new(1., 0., 0.), CubeSides::Right),
+                (V3c::new(-1., 0., 0.), CubeSides::Left),
+                (V3c::new(0., 1., 0.), CubeSides::Top),
+                (V3c::new(0., -1., 0.), CubeSides::Bottom),
+                (V3c::new(0., 0., 1.), CubeSides::Front),
+                (V3c::new(0., 0., -1.), CubeSides::Back),
+            ]
+            .iter()
+            {
+                let side_bit = 0x01 << (*side as u8);
+                if 0 == expected_occlusion[*node_key] & side_bit
+                    && self.bounds_are_full(&Cube {
+                        min_position: node_bounds.min_position + *direction * node_bounds.size,
+                        size: node_bounds.size,
+                    })
+                {
+                    expected_occlusion[*node_key] |= side_bit;
+                }
+            }
+
+            let expected = expected_occlusion[*node_key];
+            if stored != expected {
+                errors.push(ValidationError::OcclusionBitsMismatch {
+                    node_key: *node_key,
+                    stored,
+                    expected,
+                });
+            }
+        }
+    }
+
+    /// Checks if the area inside the given bounds is full, based on the occupied bits of the nodes
+    /// * `bounds` - The area to check, expected to be aligned with the nodes of the same size
+    fn bounds_are_full(&self, bounds: &Cube) -> bool {
+        let mut node_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        if !node_bounds.contains(&bounds.min_position) {
+            return false;
+        }
+
+        let mut node_key = Self::ROOT_NODE_KEY as usize;
+        loop {
+            let node = self.nodes.get(node_key);
+            if node_bounds.size <= bounds.size {
+                return u64::MAX == node.occupied_bits;
+            }
+            let sectant = node_bounds.sectant_for(&bounds.min_position);
+            match &node.content {
+                NodeContent::Nothing => return false,
+                NodeContent::UniformLeaf(_) => return u64::MAX == node.occupied_bits,
+                NodeContent::Leaf(bricks) => {
+                    return match &bricks[sectant as usize] {
+                        BrickData::Solid(voxel) => !NodeContent::pix_points_to_empty(
+                            voxel,
+                            &self.voxel_color_palette,
+                            &self.voxel_data_palette,
+                        ),
+                        _ => false,
+                    };
+                }
+                NodeContent::Internal => {
+                    let child_key = node.child(sectant);
+                    if !self.nodes.key_is_valid(child_key) {
+                        return false;
+                    }
+                    node_key = child_key;
+                    node_bounds = node_bounds.child_bounds_for(sectant);
+                }
+            }
+        }
+    }
+
+    /// Checks if the MIP of each node matches the one resampled from its current content
+    /// Colors matched to the palette might differ within the similarity threshold of the MIP level
+    fn validate_mips(&self, nodes: &[ValidatedNode], errors: &mut Vec<ValidationError>) {
+        if !self.mip_map_strategy.enabled {
+            return;
+        }
+
+        // MIPs are resampled inside a copy of the tree, parents first,
+        // so every MIP is resampled from the original MIPs of its children
+        let mut resampled = self.clone();
+        resampled.update_triggers.clear();
+        resampled.subscriptions.clear();
+        resampled.journal = None;
+
+        for (node_key, node_bounds, _) in nodes.iter() {
+            // The content of uniform leaf nodes is equivalent with their MIP, so it might be omitted
+            if let NodeContent::UniformLeaf(brick) = &self.nodes.get(*node_key).content {
+                let mip = &self.nodes.get(*node_key).mip;
+                if !matches!(mip, BrickData::Empty)
+                    && (0..self.brick_dim.pow(3) as usize)
+                        .any(|index| mip.voxel(index) != brick.voxel(index))
+                {
+                    errors.push(ValidationError::OutdatedMip {
+                        node_key: *node_key,
+                    });
+                }
+                continue;
+            }
+
+            StrategyUpdater(&mut resampled).recalculate_mip(*node_key, node_bounds);
+
+            let mip_level = (node_bounds.size / self.brick_dim as f32).log2() as usize;
+            let color_tolerance = self
+                .mip_map_strategy
+                .resampling_color_matching_thresholds
+                .get(&mip_level)
+                .map_or(0., |threshold| 2. * threshold * 255.);
+            let stored_mip = &self.nodes.get(*node_key).mip;
+            let resampled_mip = &resampled.nodes.get(*node_key).mip;
+            let up_to_date = (0..self.brick_dim.pow(3) as usize).all(|index| {
+                let stored = stored_mip.voxel(index).and_then(|voxel| {
+                    NodeContent::pix_get_ref(
+                        &voxel,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    )
+                    .albedo()
+                    .copied()
+                });
+                let expected = resampled_mip.voxel(index).and_then(|voxel| {
+                    NodeContent::pix_get_ref(
+                        &voxel,
+                        &resampled.voxel_color_palette,
+                        &resampled.voxel_data_palette,
+                    )
+                    .albedo()
+                    .copied()
+                });
+                match (stored, expected) {
+                    (None, None) => true,
+                    (Some(stored), Some(expected)) => {
+                        stored == expected || stored.distance_from(&expected) < color_tolerance
+                    }
+                    _ => false,
+                }
+            });
+            if !up_to_date {
+                errors.push(ValidationError::OutdatedMip {
+                    node_key: *node_key,
+                });
+            }
+        }
+    }
+}
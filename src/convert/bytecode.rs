@@ -1,19 +1,20 @@
 use crate::{
     boxtree::{
         types::{
-            BrickData, MIPMapStrategy, MIPResamplingMethods, NodeChildren, NodeContent, NodeData,
-            PaletteIndexValues,
+            BrickData, CompactVoxel, MIPMapStrategy, MIPResamplingMethods, NodeChildren,
+            NodeContent, NodeData, PaletteIndexValues, PaletteIndexWidth, SerializationError,
         },
-        Albedo, BoxTree, BOX_NODE_CHILDREN_COUNT,
+        update::palette::{map_node_voxels, PaletteRemap},
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
     },
-    object_pool::ObjectPool,
+    object_pool::{empty_marker, ObjectPool},
     Version,
 };
 use bendy::{
     decoding::{Error, FromBencode, Object},
     encoding::{Error as BencodeError, SingleItemEncoder, ToBencode},
 };
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, hash::Hash};
 
 impl ToBencode for Version {
     const MAX_DEPTH: usize = 2;
@@ -30,21 +31,30 @@ impl FromBencode for Version {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let major = match list.next_object()?.expect("Expected Major version string") {
+                let major = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("library major version"))?
+                {
                     Object::Integer(i) => Ok(i.parse::<u32>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field library major version",
                         "Something else",
                     )),
                 }?;
-                let minor = match list.next_object()?.expect("Expected Minor version string") {
+                let minor = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("library minor version"))?
+                {
                     Object::Integer(i) => Ok(i.parse::<u32>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field library major version",
                         "Something else",
                     )),
                 }?;
-                let patch = match list.next_object()?.expect("Expected Patch version string") {
+                let patch = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("library patch version"))?
+                {
                     Object::Integer(i) => Ok(i.parse::<u32>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field library major version",
@@ -96,28 +106,40 @@ impl FromBencode for Albedo {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let r = match list.next_object()?.unwrap() {
+                let r = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("red color component"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field red color component",
                         "Something else",
                     )),
                 }?;
-                let g = match list.next_object()?.unwrap() {
+                let g = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("green color component"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field green color component",
                         "Something else",
                     )),
                 }?;
-                let b = match list.next_object()?.unwrap() {
+                let b = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("blue color component"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field blue color component",
                         "Something else",
                     )),
                 }?;
-                let a = match list.next_object()?.unwrap() {
+                let a = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("alpha color component"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field alpha color component",
@@ -148,9 +170,106 @@ impl FromBencode for Albedo {
 //  ░███    ███  ░███    ░███     ░███     ░███    ░███
 //  ██████████   █████   █████    █████    █████   █████
 //####################################################################################
+/// Serializes the wrapped data with compressed bricks, see `BoxTree::to_bytes_compressed`
+pub(crate) struct Compressed<'a, T>(pub(crate) &'a T);
+
+/// The number of bits required to store every value up to the given one
+fn bits_for(max_value: usize) -> u32 {
+    usize::BITS - max_value.leading_zeros()
+}
+
+/// Appends the lowest `bits` of the given value into a little endian bit stream
+fn push_bits(bytes: &mut Vec<u8>, bit_count: &mut usize, value: u64, bits: u32) {
+    for bit in 0..bits as usize {
+        if bit_count.is_multiple_of(8) {
+            bytes.push(0);
+        }
+        if 0 != (value >> bit) & 1 {
+            *bytes.last_mut().unwrap() |= 1 << (*bit_count % 8);
+        }
+        *bit_count += 1;
+    }
+}
+
+/// Reads `bits` number of bits from the given little endian bit stream
+fn read_bits(bytes: &[u8], bit_position: &mut usize, bits: u32) -> Option<u64> {
+    let mut value = 0;
+    for bit in 0..bits as usize {
+        let byte = bytes.get(*bit_position / 8)?;
+        if 0 != (byte >> (*bit_position % 8)) & 1 {
+            value |= 1 << bit;
+        }
+        *bit_position += 1;
+    }
+    Some(value)
+}
+
+impl ToBencode for Compressed<'_, BrickData<PaletteIndexValues>> {
+    const MAX_DEPTH: usize = 3;
+
+    /// Parted bricks are stored with a palette local to the brick, and
+    /// runs of the same voxel along the flat projection order are stored as (palette index, run length)
+    /// Both values of a run are bit packed, using the bits required for the largest value in the brick
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        let Some(voxels) = self.0.voxels_iter() else {
+            return self.0.encode(encoder);
+        };
+
+        let mut palette = Vec::new();
+        let mut palette_index_for = HashMap::new();
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        let mut brick_len = 0;
+        for voxel in voxels {
+            brick_len += 1;
+            let palette_index = *palette_index_for.entry(voxel).or_insert_with(|| {
+                palette.push(voxel);
+                palette.len() - 1
+            });
+            match runs.last_mut() {
+                Some((run_index, run_length)) if *run_index == palette_index => *run_length += 1,
+                _ => runs.push((palette_index, 1)),
+            }
+        }
+
+        let index_bits = bits_for(palette.len().saturating_sub(1));
+        let length_bits = bits_for(
+            runs.iter()
+                .map(|(_, run_length)| run_length - 1)
+                .max()
+                .unwrap_or(0),
+        );
+        let mut packed = Vec::new();
+        let mut bit_count = 0;
+        for (palette_index, run_length) in runs.iter() {
+            push_bits(
+                &mut packed,
+                &mut bit_count,
+                *palette_index as u64,
+                index_bits,
+            );
+            push_bits(
+                &mut packed,
+                &mut bit_count,
+                (*run_length - 1) as u64,
+                length_bits,
+            );
+        }
+
+        encoder.emit_list(|e| {
+            e.emit_str("#zb#")?;
+            e.emit_int(brick_len)?;
+            e.emit(&palette)?;
+            e.emit_int(index_bits)?;
+            e.emit_int(length_bits)?;
+            e.emit_int(runs.len())?;
+            e.emit_bytes(&packed)
+        })
+    }
+}
+
 impl<T> ToBencode for BrickData<T>
 where
-    T: ToBencode + Default + Clone + PartialEq,
+    T: ToBencode + Default + Clone + PartialEq + CompactVoxel,
 {
     const MAX_DEPTH: usize = 3;
 
@@ -161,11 +280,15 @@ where
                 e.emit_str("#b#")?;
                 e.emit(voxel)
             }),
-            BrickData::Parted(brick) => encoder.emit_list(|e| {
+            // Packed bricks are stored the same way as Parted bricks
+            BrickData::Parted(_) | BrickData::Packed(_) => encoder.emit_list(|e| {
+                let voxels = self
+                    .voxels_iter()
+                    .expect("Expected Parted and Packed bricks to have voxels");
                 e.emit_str("##b#")?;
-                e.emit_int(brick.len())?;
-                for voxel in brick.iter() {
-                    e.emit(voxel)?;
+                e.emit_int(voxels.len())?;
+                for voxel in voxels {
+                    e.emit(&voxel)?;
                 }
                 e.emit_str("#")?;
                 Ok(())
@@ -176,30 +299,37 @@ where
 
 impl<T> FromBencode for BrickData<T>
 where
-    T: FromBencode + Clone + PartialEq,
+    T: FromBencode + Clone + Eq + Hash + CompactVoxel,
 {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::Bytes(b) => {
-                debug_assert_eq!(
-                    String::from_utf8(b.to_vec())
-                        .unwrap_or("".to_string())
-                        .as_str(),
-                    "#b"
-                );
-                Ok(BrickData::Empty)
+                match String::from_utf8(b.to_vec())
+                    .unwrap_or("".to_string())
+                    .as_str()
+                {
+                    "#b" => Ok(BrickData::Empty),
+                    misc => Err(bendy::decoding::Error::unexpected_token(
+                        "An empty BrickData identifier #b",
+                        "The string ".to_owned() + misc,
+                    )),
+                }
             }
             Object::List(mut list) => {
-                let is_solid = match list.next_object()?.unwrap() {
+                let (is_solid, is_compressed) = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("BrickData identifier"))?
+                {
                     Object::Bytes(b) => {
                         match String::from_utf8(b.to_vec())
                             .unwrap_or("".to_string())
                             .as_str()
                         {
-                            "#b#" => Ok(true),   // The content is a single voxel
-                            "##b#" => Ok(false), // The content is a brick of voxels
+                            "#b#" => Ok((true, false)),   // The content is a single voxel
+                            "##b#" => Ok((false, false)), // The content is a brick of voxels
+                            "#zb#" => Ok((false, true)), // The content is a compressed brick of voxels
                             misc => Err(bendy::decoding::Error::unexpected_token(
-                                "A NodeContent Identifier string, which is either # or ##",
+                                "A BrickData Identifier string, which is either #b#, ##b# or #zb#",
                                 "The string ".to_owned() + misc,
                             )),
                         }
@@ -211,22 +341,111 @@ where
                 }?;
                 if is_solid {
                     Ok(BrickData::Solid(T::decode_bencode_object(
-                        list.next_object()?.unwrap(),
+                        list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("solid brick voxel")
+                        })?,
                     )?))
                 } else {
-                    let len = match list.next_object()?.unwrap() {
-                        Object::Integer(i) => Ok(i.parse()?),
+                    let len = match list
+                        .next_object()?
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("brick length"))?
+                    {
+                        Object::Integer(i) => Ok(i.parse::<usize>()?),
                         _ => Err(bendy::decoding::Error::unexpected_token(
                             "int field brick length",
                             "Something else",
                         )),
                     }?;
-                    debug_assert!(0 < len, "Expected brick to be of non-zero length!");
-                    let mut brick_data = Vec::with_capacity(len as usize);
+                    if 0 == len {
+                        return Err(bendy::decoding::Error::unexpected_token(
+                            "non-zero brick length",
+                            "0",
+                        ));
+                    }
+                    // Length is not trusted until the voxels are actually read
+                    let mut brick_data = Vec::new();
+                    if is_compressed {
+                        let palette = Vec::<T>::decode_bencode_object(
+                            list.next_object()?.ok_or_else(|| {
+                                bendy::decoding::Error::missing_field("compressed brick palette")
+                            })?,
+                        )?;
+                        let index_bits =
+                            u32::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                                bendy::decoding::Error::missing_field("compressed brick index bits")
+                            })?)?;
+                        let length_bits =
+                            u32::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                                bendy::decoding::Error::missing_field(
+                                    "compressed brick length bits",
+                                )
+                            })?)?;
+                        let run_count = usize::decode_bencode_object(
+                            list.next_object()?.ok_or_else(|| {
+                                bendy::decoding::Error::missing_field("compressed brick run count")
+                            })?,
+                        )?;
+                        let packed = match list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("compressed brick runs")
+                        })? {
+                            Object::Bytes(b) => Ok(b),
+                            _ => Err(bendy::decoding::Error::unexpected_token(
+                                "bytes field compressed brick runs",
+                                "Something else",
+                            )),
+                        }?;
+                        if index_bits > u32::BITS || length_bits > u32::BITS {
+                            return Err(bendy::decoding::Error::unexpected_token(
+                                "at most 32 bits for compressed brick runs",
+                                format!("{index_bits} and {length_bits} bits"),
+                            ));
+                        }
+
+                        let mut bit_position = 0;
+                        for _ in 0..run_count {
+                            let (Some(palette_index), Some(run_length)) = (
+                                read_bits(packed, &mut bit_position, index_bits),
+                                read_bits(packed, &mut bit_position, length_bits),
+                            ) else {
+                                return Err(bendy::decoding::Error::missing_field(
+                                    "compressed brick run",
+                                ));
+                            };
+                            let run_length = run_length as usize + 1;
+                            let voxel = palette.get(palette_index as usize).ok_or_else(|| {
+                                bendy::decoding::Error::unexpected_token(
+                                    format!("brick palette index below {}", palette.len()),
+                                    palette_index,
+                                )
+                            })?;
+                            if brick_data.len() + run_length > len {
+                                return Err(bendy::decoding::Error::unexpected_token(
+                                    format!("compressed brick of {len} voxels"),
+                                    "more voxels",
+                                ));
+                            }
+                            brick_data.extend(std::iter::repeat_n(voxel.clone(), run_length));
+                        }
+                        if brick_data.len() != len {
+                            return Err(bendy::decoding::Error::unexpected_token(
+                                format!("compressed brick of {len} voxels"),
+                                brick_data.len(),
+                            ));
+                        }
+                        let mut brick = BrickData::parted(brick_data);
+                        brick.narrow();
+                        return Ok(brick);
+                    }
                     for _ in 0..len {
-                        brick_data.push(T::decode_bencode_object(list.next_object()?.unwrap())?);
+                        brick_data.push(T::decode_bencode_object(
+                            list.next_object()?.ok_or_else(|| {
+                                bendy::decoding::Error::missing_field("brick voxel")
+                            })?,
+                        )?);
                     }
-                    Ok(BrickData::Parted(brick_data))
+                    let mut brick = BrickData::parted(brick_data);
+                    brick.narrow();
+                    Ok(brick)
                 }
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
@@ -267,30 +486,43 @@ impl ToBencode for NodeData {
     }
 }
 
+impl ToBencode for Compressed<'_, NodeData> {
+    const MAX_DEPTH: usize = SERIALIZE_MAX_DEPTH;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        encoder.emit_list(|e| {
+            e.emit(Compressed(&self.0.content))?;
+            e.emit(self.0.children)?;
+            e.emit(Compressed(&self.0.mip))?;
+            e.emit(self.0.occupied_bits)?;
+            e.emit(self.0.occlusion_bits)
+        })
+    }
+}
+
 impl FromBencode for NodeData {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let content = NodeContent::<u32>::decode_bencode_object(
+                let content = NodeContent::<PaletteIndexValues>::decode_bencode_object(
                     list.next_object()?
-                        .expect("Expected Node children from byte stream!"),
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("Node content"))?,
                 )?;
                 let children = NodeChildren::decode_bencode_object(
                     list.next_object()?
-                        .expect("Expected Node contents from byte stream!"),
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("Node children"))?,
                 )?;
                 let mip = BrickData::<PaletteIndexValues>::decode_bencode_object(
                     list.next_object()?
-                        .expect("Expected Node mip from byte stream!"),
-                )?;
-                let occupied_bits = u64::decode_bencode_object(
-                    list.next_object()?
-                        .expect("Expected Node occupied bits from byte stream!"),
-                )?;
-                let occlusion_bits = u8::decode_bencode_object(
-                    list.next_object()?
-                        .expect("Expected Node occlusion bits from byte stream!"),
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("Node mip"))?,
                 )?;
+                let occupied_bits =
+                    u64::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("Node occupied bits")
+                    })?)?;
+                let occlusion_bits =
+                    u8::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("Node occlusion bits")
+                    })?)?;
                 Ok(Self {
                     content,
                     children,
@@ -306,7 +538,7 @@ impl FromBencode for NodeData {
 
 impl<T> ToBencode for NodeContent<T>
 where
-    T: ToBencode + Debug + Default + Clone + PartialEq,
+    T: ToBencode + Debug + Default + Clone + PartialEq + CompactVoxel,
 {
     const MAX_DEPTH: usize = 8;
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
@@ -328,33 +560,51 @@ where
     }
 }
 
+impl ToBencode for Compressed<'_, NodeContent<PaletteIndexValues>> {
+    const MAX_DEPTH: usize = 8;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        match self.0 {
+            NodeContent::Leaf(bricks) => encoder.emit_list(|e| {
+                e.emit_str("###")?;
+                for brick in bricks.iter().take(BOX_NODE_CHILDREN_COUNT) {
+                    e.emit(Compressed(brick))?;
+                }
+                Ok(())
+            }),
+            NodeContent::UniformLeaf(brick) => encoder.emit_list(|e| {
+                e.emit_str("##u#")?;
+                e.emit(Compressed(brick))
+            }),
+            content => content.encode(encoder),
+        }
+    }
+}
+
 impl<T> FromBencode for NodeContent<T>
 where
-    T: FromBencode + Debug + Clone + PartialEq,
+    T: FromBencode + Debug + Clone + Eq + Hash + CompactVoxel,
 {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let (is_leaf, is_uniform) = match list.next_object()?.unwrap() {
+                let is_uniform = match list.next_object()?.ok_or_else(|| {
+                    bendy::decoding::Error::missing_field("NodeContent identifier")
+                })? {
                     Object::Bytes(b) => {
                         match String::from_utf8(b.to_vec())
                             .unwrap_or("".to_string())
                             .as_str()
                         {
-                            "##" => {
-                                // The content is an internal Node
-                                Ok((false, false))
-                            }
                             "###" => {
                                 // The content is a leaf
-                                Ok((true, false))
+                                Ok(false)
                             }
                             "##u#" => {
                                 // The content is a uniform leaf
-                                Ok((true, true))
+                                Ok(true)
                             }
                             misc => Err(bendy::decoding::Error::unexpected_token(
-                                "A NodeContent Identifier string, which is either # or ##",
+                                "A leaf NodeContent Identifier string, which is either ### or ##u#",
                                 "The string ".to_owned() + misc,
                             )),
                         }
@@ -365,37 +615,28 @@ where
                     )),
                 }?;
 
-                debug_assert!(
-                    is_leaf || is_uniform,
-                    "Expected NodeContent list to be either leaf, uniform or both!"
-                );
-
-                if is_leaf && !is_uniform {
-                    let leaf_data: [BrickData<T>; BOX_NODE_CHILDREN_COUNT] = (0
-                        ..BOX_NODE_CHILDREN_COUNT)
-                        .map(|_sectant| {
-                            BrickData::decode_bencode_object(
-                                list.next_object()
-                                    .expect("Expected BrickData object:")
-                                    .unwrap(),
-                            )
-                            .expect("Expected to decode BrickData:")
-                        })
-                        .collect::<Vec<_>>()
-                        .try_into()
-                        .unwrap();
-
-                    return Ok(NodeContent::Leaf(leaf_data));
-                }
-
-                if is_leaf && is_uniform {
+                if is_uniform {
                     return Ok(NodeContent::UniformLeaf(BrickData::decode_bencode_object(
-                        list.next_object()?.unwrap(),
+                        list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("uniform leaf brick")
+                        })?,
                     )?));
                 }
-                panic!(
-                    "The logical combination of !is_leaf and is_uniform should never be reached"
-                );
+
+                let mut leaf_data = Vec::with_capacity(BOX_NODE_CHILDREN_COUNT);
+                for _ in 0..BOX_NODE_CHILDREN_COUNT {
+                    leaf_data.push(BrickData::decode_bencode_object(
+                        list.next_object()?
+                            .ok_or_else(|| bendy::decoding::Error::missing_field("leaf brick"))?,
+                    )?);
+                }
+                match leaf_data.try_into() {
+                    Ok(leaf_data) => Ok(NodeContent::Leaf(leaf_data)),
+                    Err(_) => Err(bendy::decoding::Error::unexpected_token(
+                        "A BrickData for each sectant of the leaf",
+                        "Less BrickData",
+                    )),
+                }
             }
             Object::Bytes(b) => {
                 // NodeContent is either Internal or Nothing
@@ -460,34 +701,37 @@ impl FromBencode for NodeChildren<u32> {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let marker = String::decode_bencode_object(list.next_object()?.unwrap())?;
+                let marker =
+                    String::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("NodeChildren marker")
+                    })?)?;
                 match marker.as_str() {
                     "##c##" => {
-                        let mut c = Vec::new();
-                        for _ in 0..BOX_NODE_CHILDREN_COUNT {
-                            c.push(
-                                u32::decode_bencode_object(list.next_object()?.unwrap())
-                                    .ok()
-                                    .unwrap(),
-                            );
+                        let mut c = [0; BOX_NODE_CHILDREN_COUNT];
+                        for child in c.iter_mut() {
+                            *child = u32::decode_bencode_object(list.next_object()?.ok_or_else(
+                                || bendy::decoding::Error::missing_field("child key"),
+                            )?)?;
                         }
-                        Ok(NodeChildren::Children(c.try_into().ok().unwrap()))
+                        Ok(NodeChildren::Children(c))
                     }
-                    "##x##" => todo!(),
                     s => Err(bendy::decoding::Error::unexpected_token(
-                        "A NodeChildren marker, either ##b## or ##c##",
+                        "A NodeChildren list marker ##c##",
                         s,
                     )),
                 }
             }
             Object::Bytes(b) => {
-                debug_assert_eq!(
-                    String::from_utf8(b.to_vec())
-                        .unwrap_or("".to_string())
-                        .as_str(),
-                    "##x##"
-                );
-                Ok(NodeChildren::default())
+                match String::from_utf8(b.to_vec())
+                    .unwrap_or("".to_string())
+                    .as_str()
+                {
+                    "##x##" => Ok(NodeChildren::default()),
+                    s => Err(bendy::decoding::Error::unexpected_token(
+                        "A NodeChildren marker ##x##",
+                        s,
+                    )),
+                }
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "A NodeChildren Object, Either a List or a ByteString",
@@ -540,7 +784,10 @@ impl FromBencode for MIPMapStrategy {
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let enabled = match list.next_object()?.unwrap() {
+                let enabled = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("MIP maps enabled"))?
+                {
                     Object::Integer("0") => Ok(false),
                     Object::Integer("1") => Ok(true),
                     Object::Integer(i) => Err(bendy::decoding::Error::unexpected_token(
@@ -553,8 +800,10 @@ impl FromBencode for MIPMapStrategy {
                     )),
                 }?;
 
-                let resampling_strategy_len = match list.next_object()?.unwrap() {
-                    Object::Integer(i) => Ok(i.parse()?),
+                let resampling_strategy_len = match list.next_object()?.ok_or_else(|| {
+                    bendy::decoding::Error::missing_field("MIP resampling strategy length")
+                })? {
+                    Object::Integer(i) => Ok(i.parse::<usize>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field MIP resampling strategy length",
                         "Something else",
@@ -562,14 +811,21 @@ impl FromBencode for MIPMapStrategy {
                 }?;
                 let mut resampling_methods = HashMap::new();
                 for _ in 0..resampling_strategy_len {
-                    let key = usize::decode_bencode_object(list.next_object()?.unwrap()).unwrap();
-                    let value =
-                        MIPResamplingMethods::decode_bencode_object(list.next_object()?.unwrap())
-                            .unwrap();
+                    let key =
+                        usize::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("MIP resampling strategy level")
+                        })?)?;
+                    let value = MIPResamplingMethods::decode_bencode_object(
+                        list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("MIP resampling method")
+                        })?,
+                    )?;
                     resampling_methods.insert(key, value);
                 }
 
-                let resampling_strategy_len = match list.next_object()?.unwrap() {
+                let resampling_strategy_len = match list.next_object()?.ok_or_else(|| {
+                    bendy::decoding::Error::missing_field("MIP color matching strategy length")
+                })? {
                     Object::Integer(i) => Ok(i.parse::<usize>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field MIP color matching strategy length",
@@ -578,8 +834,13 @@ impl FromBencode for MIPMapStrategy {
                 }?;
                 let mut resampling_color_matching_thresholds = HashMap::new();
                 for _ in 0..resampling_strategy_len {
-                    let key = usize::decode_bencode_object(list.next_object()?.unwrap()).unwrap();
-                    let value = match list.next_object()?.unwrap() {
+                    let key =
+                        usize::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                            bendy::decoding::Error::missing_field("MIP color matching level")
+                        })?)?;
+                    let value = match list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("MIP color matching threshold")
+                    })? {
                         Object::Integer(i) => Ok(i.parse::<u32>()?),
                         _ => Err(bendy::decoding::Error::unexpected_token(
                             "int field MIP color matching strategy length",
@@ -623,10 +884,7 @@ impl FromBencode for MIPResamplingMethods {
             Object::Integer("0") => Ok(MIPResamplingMethods::BoxFilter),
             Object::Integer("1") => Ok(MIPResamplingMethods::PointFilter),
             Object::Integer("2") => Ok(MIPResamplingMethods::PointFilterBD),
-            Object::Integer(int) => match int
-                .parse::<u32>()
-                .unwrap_or_else(|_| panic!("Expected to be able to parse: {int:?} as u32"))
-            {
+            Object::Integer(int) => match int.parse::<u32>()? {
                 thr if (3..1002).contains(&thr) => {
                     Ok(MIPResamplingMethods::Posterize((thr as f32 - 3.) / 1000.))
                 }
@@ -672,34 +930,219 @@ where
         match bendy::decoding::Decoder::new(bytes)
             .with_max_depth(SERIALIZE_MAX_DEPTH)
             .next_object()?
-            .expect("Expected BoxTree object list")
+            .ok_or_else(|| bendy::decoding::Error::missing_field("BoxTree object list"))?
         {
             Object::List(mut list) => crate::Version::decode_bencode_object(
-                list.next_object()?.expect("Expected Version object bytes"),
+                list.next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("BoxTree version"))?,
             ),
             _ => Err(bendy::decoding::Error::unexpected_token("List", "not List")),
         }
     }
 }
 
+impl<T: VoxelData> BoxTree<T> {
+    /// Checks the consistency of a freshly decoded structure, so no invalid
+    /// node keys or palette indices are accessed during later usage
+    pub(crate) fn check_loaded_structure(&self) -> Result<(), SerializationError> {
+        if 0 == self.brick_dim
+            || 0 == self.boxtree_size
+            || self.boxtree_size < self.brick_dim * BOX_NODE_DIMENSION as u32
+        {
+            return Err(SerializationError::Malformed(format!(
+                "Invalid boxtree size({}) or brick dimension({})",
+                self.boxtree_size, self.brick_dim
+            )));
+        }
+        if !self.nodes.key_is_valid(Self::ROOT_NODE_KEY as usize) {
+            return Err(SerializationError::Malformed(
+                "Root node is missing".to_string(),
+            ));
+        }
+
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            check_loaded_node(
+                node_key,
+                &self.nodes.get(node_key),
+                self.brick_dim,
+                self.voxel_color_palette.len(),
+                self.voxel_data_palette.len(),
+                |key| self.nodes.key_is_valid(key),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the consistency of a freshly decoded node, so no invalid
+/// node keys or palette indices are accessed during later usage
+pub(crate) fn check_loaded_node(
+    node_key: usize,
+    node: &NodeData,
+    brick_dim: u32,
+    color_palette_size: usize,
+    data_palette_size: usize,
+    key_is_valid: impl Fn(usize) -> bool,
+) -> Result<(), SerializationError> {
+    let brick_size = brick_dim.pow(3) as usize;
+    if let NodeChildren::Children(children) = node.children {
+        for child_key in children.iter() {
+            if *child_key != empty_marker::<u32>() && !key_is_valid(*child_key as usize) {
+                return Err(SerializationError::MalformedNode {
+                    node_key,
+                    reason: format!("Child key {child_key} is not a valid node"),
+                });
+            }
+        }
+    }
+
+    let bricks: &[BrickData<PaletteIndexValues>] = match &node.content {
+        NodeContent::Nothing | NodeContent::Internal => &[],
+        NodeContent::UniformLeaf(brick) => std::slice::from_ref(brick),
+        NodeContent::Leaf(bricks) => bricks,
+    };
+    for brick in bricks.iter().chain(std::iter::once(&node.mip)) {
+        let voxels: Cow<[PaletteIndexValues]> = match brick {
+            BrickData::Empty => Cow::Borrowed(&[]),
+            BrickData::Solid(voxel) => Cow::Borrowed(std::slice::from_ref(voxel)),
+            BrickData::Packed(brick) => {
+                if brick.len() != brick_size {
+                    return Err(SerializationError::MalformedNode {
+                        node_key,
+                        reason: format!("Brick of {} voxels, instead of {brick_size}", brick.len()),
+                    });
+                }
+                Cow::Borrowed(&brick.palette)
+            }
+            BrickData::Parted(voxels) => {
+                if voxels.len() != brick_size {
+                    return Err(SerializationError::MalformedNode {
+                        node_key,
+                        reason: format!(
+                            "Brick of {} voxels, instead of {brick_size}",
+                            voxels.len()
+                        ),
+                    });
+                }
+                voxels.as_slice()
+            }
+        };
+        for voxel in voxels.iter() {
+            if NodeContent::pix_color_is_some(voxel)
+                && NodeContent::pix_color_index(voxel) >= color_palette_size
+            {
+                return Err(SerializationError::PaletteIndexOutOfRange {
+                    node_key,
+                    index: NodeContent::pix_color_index(voxel),
+                    palette_size: color_palette_size,
+                });
+            }
+            if NodeContent::pix_data_is_some(voxel)
+                && NodeContent::pix_data_index(voxel) >= data_palette_size
+            {
+                return Err(SerializationError::PaletteIndexOutOfRange {
+                    node_key,
+                    index: NodeContent::pix_data_index(voxel),
+                    palette_size: data_palette_size,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<T> ToBencode for BoxTree<T>
 where
     T: ToBencode + Default + Clone + Eq + Hash,
 {
     const MAX_DEPTH: usize = SERIALIZE_MAX_DEPTH;
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        self.encode_with_bricks(encoder, false)
+    }
+}
+
+impl<T> ToBencode for Compressed<'_, BoxTree<T>>
+where
+    T: ToBencode + Default + Clone + Eq + Hash,
+{
+    const MAX_DEPTH: usize = SERIALIZE_MAX_DEPTH;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        self.0.encode_with_bricks(encoder, true)
+    }
+}
+
+impl<T> BoxTree<T>
+where
+    T: ToBencode + Default + Clone + Eq + Hash,
+{
+    /// Serializes the boxtree, optionally with compressed bricks inside the nodes
+    fn encode_with_bricks(
+        &self,
+        encoder: SingleItemEncoder,
+        compress_bricks: bool,
+    ) -> Result<(), BencodeError> {
+        // Unused palette entries are left out by remapping every node while it is written
+        let remap = self
+            .compact_palettes_on_save
+            .then(|| self.palette_remap())
+            .filter(|remap| !remap.is_identity());
         encoder.emit_list(|e| {
             e.emit(crate::version())?;
             e.emit_int(self.auto_simplify as u8)?;
             e.emit_int(self.boxtree_size)?;
             e.emit_int(self.brick_dim)?;
-            e.emit(&self.nodes)?;
-            e.emit(&self.voxel_color_palette)?;
-            e.emit(&self.voxel_data_palette)?;
+            e.emit_with(|node_encoder| {
+                self.nodes.encode_with(node_encoder, |node, e| {
+                    let converted_node = self.converted_for_save(node, remap.as_ref());
+                    let node = converted_node.as_ref().unwrap_or(node);
+                    if compress_bricks {
+                        e.emit(Compressed(node))
+                    } else {
+                        e.emit(node)
+                    }
+                })
+            })?;
+            if let Some(remap) = remap.as_ref() {
+                let (color_palette, data_palette) =
+                    remap.compact(&self.voxel_color_palette, &self.voxel_data_palette);
+                e.emit(&color_palette)?;
+                e.emit(&data_palette)?;
+            } else {
+                e.emit(&self.voxel_color_palette)?;
+                e.emit(&self.voxel_data_palette)?;
+            }
             e.emit(&self.mip_map_strategy)?;
+            e.emit_int(match self.palette_index_width {
+                PaletteIndexWidth::Narrow => 0,
+                PaletteIndexWidth::Wide => 1,
+            })?;
             Ok(())
         })
     }
+
+    /// Provides the node as it is to be serialized, if it differs from the one stored in the tree:
+    /// remapped to the compacted palettes if needed, and in the layout of the palette index width
+    pub(crate) fn converted_for_save(
+        &self,
+        node: &NodeData,
+        remap: Option<&PaletteRemap>,
+    ) -> Option<NodeData> {
+        let narrow = PaletteIndexWidth::Narrow == self.palette_index_width;
+        if remap.is_none() && !narrow {
+            return None;
+        }
+        let mut node = node.clone();
+        if let Some(remap) = remap {
+            remap.apply_to_node(&mut node);
+        }
+        if narrow {
+            map_node_voxels(&mut node, NodeContent::pix_narrowed);
+        }
+        Some(node)
+    }
 }
 
 impl<T> FromBencode for BoxTree<T>
@@ -709,8 +1152,12 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                list.next_object()?.expect("Expected Version object string");
-                let auto_simplify = match list.next_object()?.unwrap() {
+                list.next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("BoxTree version"))?;
+                let auto_simplify = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("auto_simplify"))?
+                {
                     Object::Integer("0") => Ok(false),
                     Object::Integer("1") => Ok(true),
                     Object::Integer(i) => Err(bendy::decoding::Error::unexpected_token(
@@ -723,7 +1170,10 @@ where
                     )),
                 }?;
 
-                let boxtree_size = match list.next_object()?.unwrap() {
+                let boxtree_size = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("boxtree_size"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "int field boxtree_size",
@@ -731,35 +1181,69 @@ where
                     )),
                 }?;
 
-                let brick_dim = match list.next_object()?.unwrap() {
+                let brick_dim = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("brick_dim"))?
+                {
                     Object::Integer(i) => Ok(i.parse()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
-                        "int field boxtree_size",
+                        "int field brick_dim",
                         "Something else",
                     )),
                 }?;
 
-                let nodes = ObjectPool::decode_bencode_object(list.next_object()?.unwrap())?;
+                let mut nodes = ObjectPool::decode_bencode_object(
+                    list.next_object()?
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("nodes"))?,
+                )?;
 
                 let voxel_color_palette =
-                    Vec::<Albedo>::decode_bencode_object(list.next_object()?.unwrap())?;
+                    Vec::<Albedo>::decode_bencode_object(list.next_object()?.ok_or_else(
+                        || bendy::decoding::Error::missing_field("voxel_color_palette"),
+                    )?)?;
                 let mut map_to_color_index_in_palette = HashMap::new();
                 for (i, voxel_color) in voxel_color_palette.iter().enumerate() {
                     map_to_color_index_in_palette.insert(*voxel_color, i);
                 }
 
                 let voxel_data_palette =
-                    Vec::<T>::decode_bencode_object(list.next_object()?.unwrap())?;
+                    Vec::<T>::decode_bencode_object(list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("voxel_data_palette")
+                    })?)?;
                 let mut map_to_data_index_in_palette = HashMap::new();
                 for (i, voxel_data) in voxel_data_palette.iter().enumerate() {
                     map_to_data_index_in_palette.insert(voxel_data.clone(), i);
                 }
 
-                let mip_map_strategy =
-                    MIPMapStrategy::decode_bencode_object(list.next_object()?.unwrap())?;
+                let mip_map_strategy = MIPMapStrategy::decode_bencode_object(
+                    list.next_object()?
+                        .ok_or_else(|| bendy::decoding::Error::missing_field("mip_map_strategy"))?,
+                )?;
+
+                // Trees stored before wide palette indices were introduced use narrow ones
+                let palette_index_width = match list.next_object()? {
+                    None | Some(Object::Integer("0")) => Ok(PaletteIndexWidth::Narrow),
+                    Some(Object::Integer("1")) => Ok(PaletteIndexWidth::Wide),
+                    Some(Object::Integer(i)) => Err(bendy::decoding::Error::unexpected_token(
+                        "palette index width of 0 or 1",
+                        format!("the number: {i}"),
+                    )),
+                    Some(_) => Err(bendy::decoding::Error::unexpected_token(
+                        "int field palette_index_width",
+                        "Something else",
+                    )),
+                }?;
+                if PaletteIndexWidth::Narrow == palette_index_width {
+                    for node_key in 0..nodes.len() {
+                        if nodes.key_is_valid(node_key) {
+                            map_node_voxels(nodes.get_mut(node_key), NodeContent::pix_widened);
+                        }
+                    }
+                }
 
                 Ok(Self {
                     auto_simplify,
+                    compact_palettes_on_save: false,
                     boxtree_size,
                     brick_dim,
                     nodes,
@@ -767,8 +1251,12 @@ where
                     voxel_data_palette,
                     map_to_color_index_in_palette,
                     map_to_data_index_in_palette,
+                    palette_index_width,
+                    palette_revision: 0,
                     mip_map_strategy,
                     update_triggers: vec![], // Cannot serialize output triggers
+                    subscriptions: vec![],
+                    journal: None,
                 })
             }
             _ => Err(bendy::decoding::Error::unexpected_token("List", "not List")),
@@ -1,14 +1,77 @@
 use crate::{
     boxtree::{
         types::{MIPMapStrategy, OctreeError},
-        Albedo, BoxTree, BoxTreeEntry, VoxelData, V3c,
+        Albedo, BoxTree, BoxTreeEntry, VoxelData, V3c, BOX_NODE_DIMENSION,
     },
     spatial::math::{convert_coordinate, CoordinateSystemType},
 };
-use dot_vox::{Color, DotVoxData, Model, SceneNode, Size, Voxel};
+use dot_vox::{Color, Dict, DotVoxData, Material, Model, SceneNode, Size, Voxel};
 use nalgebra::Matrix3;
 use num_traits::Num;
-use std::{convert::From, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::From,
+    error::Error,
+    fmt,
+    io::ErrorKind,
+    path::Path,
+};
+
+/// error types during MagicaVoxel import and export
+#[derive(Debug)]
+pub enum MagicaVoxelError {
+    /// The given .vox file does not exist
+    FileNotFound(String),
+
+    /// The given .vox file could not be read
+    Io(std::io::Error),
+
+    /// The given data could not be parsed as .vox data ( refer to reason )
+    ParseError(String),
+
+    /// The extent of the models in the scene is larger, than the largest possible boxtree
+    ModelTooLarge(V3c<i32>),
+
+    /// The .vox data contains a chunk which is not supported ( refer to reason )
+    UnsupportedChunk(String),
+
+    /// The boxtree could not be constructed from the parsed data
+    InvalidTree(OctreeError),
+}
+
+impl fmt::Display for MagicaVoxelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MagicaVoxelError::FileNotFound(path) => write!(f, "File not found: {path}"),
+            MagicaVoxelError::Io(err) => write!(f, "I/O error: {err}"),
+            MagicaVoxelError::ParseError(reason) => write!(f, "Invalid .vox data: {reason}"),
+            MagicaVoxelError::ModelTooLarge(size) => write!(
+                f,
+                "Model of size {}x{}x{} does not fit into a boxtree",
+                size.x, size.y, size.z
+            ),
+            MagicaVoxelError::UnsupportedChunk(reason) => {
+                write!(f, "Unsupported .vox chunk: {reason}")
+            }
+            MagicaVoxelError::InvalidTree(err) => write!(f, "Unable to build boxtree: {err:?}"),
+        }
+    }
+}
+
+impl Error for MagicaVoxelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MagicaVoxelError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<OctreeError> for MagicaVoxelError {
+    fn from(err: OctreeError) -> Self {
+        MagicaVoxelError::InvalidTree(err)
+    }
+}
 
 impl From<Albedo> for Color {
     fn from(color: Albedo) -> Self {
@@ -52,11 +115,127 @@ impl From<Size> for V3c<i32> {
     }
 }
 
+/// Material properties of a MagicaVoxel palette entry
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VoxMaterial {
+    /// The type of the material, e.g. "_diffuse", "_metal", "_glass", "_emit"
+    pub material_type: Option<String>,
+
+    /// Emission strength of the material
+    pub emission: Option<f32>,
+
+    /// Emission power of the material
+    pub flux: Option<f32>,
+
+    /// Metalness of the material
+    pub metalness: Option<f32>,
+
+    /// Roughness of the material
+    pub roughness: Option<f32>,
+
+    /// Index of refraction of the material
+    pub ior: Option<f32>,
+
+    /// Transparency of the material
+    pub transparency: Option<f32>,
+}
+
+impl VoxMaterial {
+    fn from_properties(properties: &Dict) -> Result<Self, MagicaVoxelError> {
+        let value = |key: &str| {
+            properties
+                .get(key)
+                .map(|value| {
+                    value.parse::<f32>().map_err(|_| {
+                        MagicaVoxelError::ParseError(format!(
+                            "Invalid material property {key}: {value:?}"
+                        ))
+                    })
+                })
+                .transpose()
+        };
+        Ok(Self {
+            material_type: properties.get("_type").cloned(),
+            emission: value("_emit")?,
+            flux: value("_flux")?,
+            metalness: value("_metal")?,
+            roughness: value("_rough")?,
+            ior: value("_ior")?,
+            transparency: value("_trans")?,
+        })
+    }
+}
+
+/// A layer of a MagicaVoxel scene
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VoxLayer {
+    /// The identifier of the layer, referenced by the scene graph
+    pub id: u32,
+
+    /// The name of the layer, if any
+    pub name: Option<String>,
+
+    /// True if the layer is hidden in the scene
+    pub hidden: bool,
+}
+
+impl VoxLayer {
+    /// Collects the layers of the given .vox data
+    fn layers_of(vox_tree: &DotVoxData) -> Vec<Self> {
+        vox_tree
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(id, layer)| Self {
+                id: id as u32,
+                name: layer.attributes.get("_name").cloned(),
+                hidden: layer
+                    .attributes
+                    .get("_hidden")
+                    .is_some_and(|hidden| "1" == hidden),
+            })
+            .collect()
+    }
+}
+
+/// A voxel being imported from a .vox file, given to the mapping of `BoxTree::load_vox_file_with`
+#[derive(Debug, Clone, Copy)]
+pub struct VoxVoxel<'a> {
+    /// The position of the voxel inside the boxtree
+    pub position: V3c<u32>,
+
+    /// The color of the voxel
+    pub color: Albedo,
+
+    /// The index of the voxel color inside the .vox palette
+    pub palette_index: u8,
+
+    /// The material of the voxel color, if the file defines any
+    pub material: Option<&'a VoxMaterial>,
+
+    /// The layer containing the voxel, if any
+    pub layer: Option<&'a VoxLayer>,
+
+    /// The name of the closest named scene node above the voxel, if any
+    pub node_name: Option<&'a str>,
+}
+
 /// Gives the size of a tree which fits the given model size
-pub fn model_size_to_tree_size(model_size: &V3c<i32>, brick_dimension: u32) -> u32 {
+/// * `returns` - None if no boxtree of `u32` size can contain the model
+pub fn model_size_to_tree_size(model_size: &V3c<i32>, brick_dimension: u32) -> Option<u32> {
     let model_size = model_size.x.max(model_size.y).max(model_size.z);
-    let tree_size = (model_size as f32 / brick_dimension as f32).log(4.).ceil() as u32;
-    4_u32.pow(tree_size) * brick_dimension
+    // A tree needs at least one level of nodes above the bricks
+    let tree_size = ((model_size as f32 / brick_dimension as f32).log(4.).ceil() as u32).max(1);
+    (BOX_NODE_DIMENSION as u32)
+        .checked_pow(tree_size)?
+        .checked_mul(brick_dimension)
+}
+
+/// True if the given byte encodes a valid rotation matrix, see `parse_rotation_matrix`
+fn is_valid_rotation(b: u8) -> bool {
+    let index_in_first_row = b & 0x3;
+    let index_in_second_row = (b >> 2) & 0x3;
+    index_in_first_row < 3 && index_in_second_row < 3 && index_in_first_row != index_in_second_row
 }
 
 /// Converts the given byte value to a rotation matrix
@@ -102,61 +281,153 @@ where
     }
 }
 
+/// The placement of a model inside the scene graph of a .vox file
+#[derive(Clone, Copy)]
+struct VoxModelPlacement<'a> {
+    /// The layer of the closest transform above the model which has a layer
+    layer_id: Option<u32>,
+
+    /// The name of the closest transform above the model which has a name
+    node_name: Option<&'a str>,
+}
+
+impl<'a> VoxModelPlacement<'a> {
+    /// Updates the placement with the layer and name of the given transform node
+    fn under_transform(self, attributes: &'a Dict, layer_id: u32) -> Self {
+        Self {
+            // Layer id of -1 is stored as u32::MAX, which means the node is not in any layer
+            layer_id: if u32::MAX == layer_id {
+                self.layer_id
+            } else {
+                Some(layer_id)
+            },
+            node_name: attributes
+                .get("_name")
+                .map(|name| name.as_str())
+                .or(self.node_name),
+        }
+    }
+}
+
+/// Makes sure the given scene graph node is not on the path leading to it, starting from the root node
+fn check_scene_node_not_on_path(
+    mut path: impl Iterator<Item = u32>,
+    node: u32,
+) -> Result<(), MagicaVoxelError> {
+    if 0 == node || path.any(|visited| visited == node) {
+        return Err(MagicaVoxelError::UnsupportedChunk(format!(
+            "Scene graph contains a cycle through node {node}"
+        )));
+    }
+    Ok(())
+}
+
 /// Iterates the given dot_vox data and calls the given function on every model in the scene
-fn iterate_vox_tree<F: FnMut(&Model, &V3c<i32>, &Matrix3<i8>)>(
-    vox_tree: &DotVoxData,
+/// Files without a scene graph have every model placed at the origin
+fn iterate_vox_tree<'a, F>(
+    vox_tree: &'a DotVoxData,
     frame: usize,
     mut fun: F,
-) {
-    let mut node_stack: Vec<(u32, V3c<i32>, Matrix3<i8>, u32)> = Vec::new();
+) -> Result<(), MagicaVoxelError>
+where
+    F: FnMut(
+        &Model,
+        &V3c<i32>,
+        &Matrix3<i8>,
+        &VoxModelPlacement<'a>,
+    ) -> Result<(), MagicaVoxelError>,
+{
+    // (node, translation, rotation, placement, index of the next child to visit)
+    let mut node_stack = Vec::new();
+    let unplaced = VoxModelPlacement {
+        layer_id: None,
+        node_name: None,
+    };
 
-    match &vox_tree.scenes[0] {
-        SceneNode::Transform {
-            attributes: _,
+    match vox_tree.scenes.first() {
+        Some(SceneNode::Transform {
+            attributes,
             frames: _,
             child,
-            layer_id: _,
-        } => {
-            node_stack.push((*child, V3c::unit(0), Matrix3::identity(), 0));
+            layer_id,
+        }) => {
+            check_scene_node_not_on_path(std::iter::empty(), *child)?;
+            node_stack.push((
+                *child,
+                V3c::unit(0),
+                Matrix3::identity(),
+                unplaced.under_transform(attributes, *layer_id),
+                0,
+            ));
+        }
+        Some(_) => {
+            return Err(MagicaVoxelError::UnsupportedChunk(
+                "The root node of the scene graph should be a transform".to_string(),
+            ));
         }
-        _ => {
-            panic!("The root node for a magicka voxel DAG should be a translation")
+        None => {
+            for model in vox_tree.models.iter() {
+                fun(model, &V3c::unit(0), &Matrix3::identity(), &unplaced)?;
+            }
+            return Ok(());
         }
     }
 
-    while !node_stack.is_empty() {
-        let (current_node, translation, rotation, index) = *node_stack.last().unwrap();
-        match &vox_tree.scenes[current_node as usize] {
+    while let Some((current_node, translation, rotation, placement, index)) =
+        node_stack.last().copied()
+    {
+        let Some(scene_node) = vox_tree.scenes.get(current_node as usize) else {
+            return Err(MagicaVoxelError::UnsupportedChunk(format!(
+                "Scene graph references missing node {current_node}"
+            )));
+        };
+        match scene_node {
             SceneNode::Transform {
-                attributes: _,
+                attributes,
                 frames,
                 child,
-                layer_id: _,
+                layer_id,
             } => {
                 let used_frame = if frame < frames.len() { frame } else { 0 };
-                let translation = if let Some(t) = frames[used_frame].attributes.get("_t") {
-                    translation
-                        + t.split(" ")
-                            .map(|x| x.parse().expect("Not an integer!"))
-                            .collect::<Vec<i32>>()
-                            .into()
+                let frame_attributes = frames.get(used_frame).map(|frame| &frame.attributes);
+                let translation = if let Some(t) = frame_attributes.and_then(|a| a.get("_t")) {
+                    let t = t
+                        .split(" ")
+                        .map(|x| x.parse::<i32>())
+                        .collect::<Result<Vec<i32>, _>>()
+                        .ok()
+                        .filter(|t| 3 == t.len())
+                        .ok_or_else(|| {
+                            MagicaVoxelError::ParseError(format!("Invalid translation: {t:?}"))
+                        })?;
+                    translation + t.into()
                 } else {
                     translation
                 };
-                let orientation = if let Some(r) = frames[used_frame].attributes.get("_r") {
-                    rotation
-                        * parse_rotation_matrix(
-                            r.parse()
-                                .expect("Expected valid u8 byte to parse rotation matrix"),
-                        )
+                let orientation = if let Some(r) = frame_attributes.and_then(|a| a.get("_r")) {
+                    let r = r
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|r| is_valid_rotation(*r))
+                        .ok_or_else(|| {
+                            MagicaVoxelError::ParseError(format!("Invalid rotation: {r:?}"))
+                        })?;
+                    rotation * parse_rotation_matrix(r)
                 } else {
                     Matrix3::identity()
                 };
                 // the index variable for a Transform stores whether to go above or below a level next
                 if 0 == index {
                     // 0 == index ==> iterate into the child of the translation
-                    node_stack.last_mut().unwrap().3 += 1;
-                    node_stack.push((*child, translation, orientation, 0));
+                    check_scene_node_not_on_path(node_stack.iter().map(|entry| entry.0), *child)?;
+                    node_stack.last_mut().unwrap().4 += 1;
+                    node_stack.push((
+                        *child,
+                        translation,
+                        orientation,
+                        placement.under_transform(attributes, *layer_id),
+                        0,
+                    ));
                 } else {
                     // 0 != index ==> remove translation and iterate into parent
                     node_stack.pop();
@@ -167,8 +438,18 @@ fn iterate_vox_tree<F: FnMut(&Model, &V3c<i32>, &Matrix3<i8>)>(
                 children,
             } => {
                 if (index as usize) < children.len() {
-                    node_stack.last_mut().unwrap().3 += 1;
-                    node_stack.push((children[index as usize], translation, rotation, 0));
+                    check_scene_node_not_on_path(
+                        node_stack.iter().map(|entry| entry.0),
+                        children[index as usize],
+                    )?;
+                    node_stack.last_mut().unwrap().4 += 1;
+                    node_stack.push((
+                        children[index as usize],
+                        translation,
+                        rotation,
+                        placement,
+                        0,
+                    ));
                 } else {
                     node_stack.pop();
                 }
@@ -178,47 +459,68 @@ fn iterate_vox_tree<F: FnMut(&Model, &V3c<i32>, &Matrix3<i8>)>(
                 models,
             } => {
                 for model in models {
-                    if model
-                        .attributes
-                        .get("_f")
-                        .unwrap_or(&"0".to_string())
-                        .parse::<usize>()
-                        .expect("Expected frame attribute of Voxel Model to be a parsable integer")
-                        == frame
-                    {
+                    let model_frame = match model.attributes.get("_f") {
+                        Some(f) => f.parse::<usize>().map_err(|_| {
+                            MagicaVoxelError::ParseError(format!("Invalid model frame: {f:?}"))
+                        })?,
+                        None => 0,
+                    };
+                    if model_frame == frame {
                         fun(
-                            &vox_tree.models[model.model_id as usize],
+                            vox_tree
+                                .models
+                                .get(model.model_id as usize)
+                                .ok_or_else(|| {
+                                    MagicaVoxelError::UnsupportedChunk(format!(
+                                        "Shape references missing model {}",
+                                        model.model_id
+                                    ))
+                                })?,
                             &translation,
                             &rotation,
-                        );
+                            &placement,
+                        )?;
                     }
                 }
                 node_stack.pop();
                 if let Some(parent) = node_stack.last_mut() {
-                    parent.3 += 1;
+                    parent.4 += 1;
                 }
             }
         }
     }
+    Ok(())
 }
 
 impl MIPMapStrategy {
+    /// Loads the .vox file under the given path into a boxtree using this MIP map strategy
     pub fn load_vox_file<P: AsRef<Path>, T: VoxelData>(
         self,
         brick_dimension: u32,
-        filename: &str,
-    ) -> Result<BoxTree<T>, &'static str> {
-        let (vox_data, min_position, mut max_position) =
-            BoxTree::<T>::load_vox_file_internal(filename);
-        max_position -= min_position;
-        let tree_size = model_size_to_tree_size(&max_position, brick_dimension);
-
-        let mut shocovox_boxtree =
-            BoxTree::<T>::new(tree_size, brick_dimension).unwrap_or_else(|err| {
-                panic!(
-                    "Expected to build a valid boxtree with dimension {tree_size:?} and brick dimension {brick_dimension:?}; Instead: {err:?}"
-                )
-            });
+        filename: P,
+    ) -> Result<BoxTree<T>, MagicaVoxelError> {
+        let vox_data = read_vox_file(filename)?;
+        self.load_vox_data(brick_dimension, &vox_data)
+    }
+
+    /// Loads the given in-memory .vox file contents into a boxtree using this MIP map strategy
+    pub fn load_vox_bytes<T: VoxelData>(
+        self,
+        brick_dimension: u32,
+        bytes: &[u8],
+    ) -> Result<BoxTree<T>, MagicaVoxelError> {
+        let vox_data = dot_vox::load_bytes(bytes)
+            .map_err(|err| MagicaVoxelError::ParseError(err.to_string()))?;
+        self.load_vox_data(brick_dimension, &vox_data)
+    }
+
+    fn load_vox_data<T: VoxelData>(
+        self,
+        brick_dimension: u32,
+        vox_data: &DotVoxData,
+    ) -> Result<BoxTree<T>, MagicaVoxelError> {
+        let (min_position, tree_size) = BoxTree::<T>::vox_data_extent(vox_data, brick_dimension)?;
+        let mut shocovox_boxtree = BoxTree::<T>::new(tree_size, brick_dimension)?;
 
         shocovox_boxtree.mip_map_strategy.enabled = self.enabled;
         shocovox_boxtree.mip_map_strategy.resampling_methods = self.resampling_methods.clone();
@@ -226,57 +528,110 @@ impl MIPMapStrategy {
             .mip_map_strategy
             .resampling_color_matching_thresholds =
             self.resampling_color_matching_thresholds.clone();
-        shocovox_boxtree.load_vox_data_internal(&vox_data, &min_position);
+        shocovox_boxtree.load_vox_data_internal(vox_data, &min_position)?;
         Ok(shocovox_boxtree)
     }
 }
 
+/// Reads and parses the .vox file under the given path
+fn read_vox_file<P: AsRef<Path>>(filename: P) -> Result<DotVoxData, MagicaVoxelError> {
+    let bytes = std::fs::read(filename.as_ref()).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => {
+            MagicaVoxelError::FileNotFound(filename.as_ref().to_string_lossy().to_string())
+        }
+        _ => MagicaVoxelError::Io(err),
+    })?;
+    dot_vox::load_bytes(&bytes).map_err(|err| MagicaVoxelError::ParseError(err.to_string()))
+}
+
 impl<T: VoxelData> BoxTree<T>
 {
-    pub fn load_vox_file(filename: &str, brick_dimension: u32) -> Result<Self, &'static str> {
-        let (vox_data, min_position, mut max_position) = Self::load_vox_file_internal(filename);
-        max_position -= min_position;
-        let tree_size = model_size_to_tree_size(&max_position, brick_dimension);
-
-        let mut shocovox_boxtree =
-            BoxTree::<T>::new(tree_size, brick_dimension).unwrap_or_else(|err| {
-                panic!(
-                    "Expected to build a valid boxtree with dimension {:?} and brick dimension {:?}; Instead: {:?}",
-                    tree_size,
-                    brick_dimension.to_owned(),
-                    err
-                )
-            });
-
-        shocovox_boxtree.load_vox_data_internal(&vox_data, &min_position);
-        Ok(shocovox_boxtree)
+    /// Loads the .vox file under the given path into a new boxtree
+    pub fn load_vox_file<P: AsRef<Path>>(
+        filename: P,
+        brick_dimension: u32,
+    ) -> Result<Self, MagicaVoxelError> {
+        Self::load_vox_data(&read_vox_file(filename)?, brick_dimension)
+    }
+
+    /// Loads the given in-memory .vox file contents into a new boxtree
+    pub fn load_vox_bytes(bytes: &[u8], brick_dimension: u32) -> Result<Self, MagicaVoxelError> {
+        let vox_data = dot_vox::load_bytes(bytes)
+            .map_err(|err| MagicaVoxelError::ParseError(err.to_string()))?;
+        Self::load_vox_data(&vox_data, brick_dimension)
     }
 
-    /// Loads data from the given filename
-    /// * `returns` - (file_data, voxel_minimum_position_lyup, voxel_maximum_position_lyup)
-    pub(crate) fn load_vox_file_internal<P: AsRef<Path>>(
+    /// Loads the .vox file under the given path into a new boxtree,
+    /// with user data provided by the given mapping for each voxel
+    /// Positions are the same as in `load_vox_file`, even if hidden layers are skipped
+    /// * `skip_hidden_layers` - If true, voxels inside hidden layers are not imported
+    /// * `data_for` - Provides the user data to store alongside the color of the given voxel
+    /// * `returns` - The boxtree and the layers of the scene
+    pub fn load_vox_file_with<P: AsRef<Path>, F: FnMut(&VoxVoxel) -> Option<T>>(
         filename: P,
-    ) -> (DotVoxData, V3c<i32>, V3c<i32>) {
-        let vox_tree = dot_vox::load(
-            filename
-                .as_ref()
-                .to_str()
-                .expect("Expected {filename} to be interpreted as `&str`!"),
+        brick_dimension: u32,
+        skip_hidden_layers: bool,
+        data_for: F,
+    ) -> Result<(Self, Vec<VoxLayer>), MagicaVoxelError> {
+        Self::load_vox_data_with(
+            &read_vox_file(filename)?,
+            brick_dimension,
+            skip_hidden_layers,
+            data_for,
         )
-        .unwrap_or_else(|_| {
-            panic!(
-                "Expected file {:?} to be a valid .vox file!",
-                filename
-                    .as_ref()
-                    .to_str()
-                    .unwrap_or("path_name_conversion_failed")
-                    .to_owned()
-            )
-        });
+    }
+
+    /// Loads the given in-memory .vox file contents into a new boxtree,
+    /// with user data provided by the given mapping for each voxel, see `load_vox_file_with`
+    pub fn load_vox_bytes_with<F: FnMut(&VoxVoxel) -> Option<T>>(
+        bytes: &[u8],
+        brick_dimension: u32,
+        skip_hidden_layers: bool,
+        data_for: F,
+    ) -> Result<(Self, Vec<VoxLayer>), MagicaVoxelError> {
+        let vox_data = dot_vox::load_bytes(bytes)
+            .map_err(|err| MagicaVoxelError::ParseError(err.to_string()))?;
+        Self::load_vox_data_with(&vox_data, brick_dimension, skip_hidden_layers, data_for)
+    }
+
+    fn load_vox_data(
+        vox_data: &DotVoxData,
+        brick_dimension: u32,
+    ) -> Result<Self, MagicaVoxelError> {
+        let (min_position, tree_size) = Self::vox_data_extent(vox_data, brick_dimension)?;
+        let mut shocovox_boxtree = BoxTree::<T>::new(tree_size, brick_dimension)?;
+        shocovox_boxtree.load_vox_data_internal(vox_data, &min_position)?;
+        Ok(shocovox_boxtree)
+    }
+
+    fn load_vox_data_with<F: FnMut(&VoxVoxel) -> Option<T>>(
+        vox_data: &DotVoxData,
+        brick_dimension: u32,
+        skip_hidden_layers: bool,
+        data_for: F,
+    ) -> Result<(Self, Vec<VoxLayer>), MagicaVoxelError> {
+        let (min_position, tree_size) = Self::vox_data_extent(vox_data, brick_dimension)?;
+        let mut shocovox_boxtree = BoxTree::<T>::new(tree_size, brick_dimension)?;
+        let layers = VoxLayer::layers_of(vox_data);
+        shocovox_boxtree.load_vox_data_mapped(
+            vox_data,
+            &min_position,
+            &layers,
+            skip_hidden_layers,
+            data_for,
+        )?;
+        Ok((shocovox_boxtree, layers))
+    }
 
+    /// Measures the models inside the given data
+    /// * `returns` - (voxel_minimum_position_lyup, size of the boxtree to contain every model)
+    pub(crate) fn vox_data_extent(
+        vox_tree: &DotVoxData,
+        brick_dimension: u32,
+    ) -> Result<(V3c<i32>, u32), MagicaVoxelError> {
         let mut min_position_rzup = V3c::<i32>::new(i32::MAX, i32::MAX, i32::MAX);
         let mut max_position_rzup = V3c::<i32>::new(i32::MIN, i32::MIN, i32::MIN);
-        iterate_vox_tree(&vox_tree, 0, |model, model_position_rzup, orientation| {
+        iterate_vox_tree(vox_tree, 0, |model, model_position_rzup, orientation, _| {
             let model_size_half_rzup = V3c::from(model.size).transformed(orientation) / 2;
             min_position_rzup.x = min_position_rzup
                 .x
@@ -303,28 +658,67 @@ impl<T: VoxelData> BoxTree<T>
                 .z
                 .max(model_position_rzup.z + model_size_half_rzup.z)
                 .max(model_position_rzup.z - model_size_half_rzup.z);
-        });
+            Ok(())
+        })?;
 
-        (
-            vox_tree,
-            convert_coordinate(
-                min_position_rzup,
-                CoordinateSystemType::Rzup,
-                CoordinateSystemType::Lyup,
-            ),
-            convert_coordinate(
-                max_position_rzup,
-                CoordinateSystemType::Rzup,
-                CoordinateSystemType::Lyup,
-            ),
-        )
+        if min_position_rzup.x > max_position_rzup.x {
+            // There are no models in the scene
+            min_position_rzup = V3c::unit(0);
+            max_position_rzup = V3c::unit(0);
+        }
+
+        let min_position_lyup = convert_coordinate(
+            min_position_rzup,
+            CoordinateSystemType::Rzup,
+            CoordinateSystemType::Lyup,
+        );
+        let max_position_lyup = convert_coordinate(
+            max_position_rzup,
+            CoordinateSystemType::Rzup,
+            CoordinateSystemType::Lyup,
+        );
+        let model_size_lyup = V3c::new(
+            max_position_lyup.x as i64 - min_position_lyup.x as i64,
+            max_position_lyup.y as i64 - min_position_lyup.y as i64,
+            max_position_lyup.z as i64 - min_position_lyup.z as i64,
+        );
+        let too_large = || {
+            MagicaVoxelError::ModelTooLarge(V3c::new(
+                model_size_lyup.x.min(i32::MAX as i64) as i32,
+                model_size_lyup.y.min(i32::MAX as i64) as i32,
+                model_size_lyup.z.min(i32::MAX as i64) as i32,
+            ))
+        };
+        let model_size_lyup = V3c::new(
+            i32::try_from(model_size_lyup.x).map_err(|_| too_large())?,
+            i32::try_from(model_size_lyup.y).map_err(|_| too_large())?,
+            i32::try_from(model_size_lyup.z).map_err(|_| too_large())?,
+        );
+        let tree_size = model_size_to_tree_size(&model_size_lyup, brick_dimension)
+            .ok_or_else(|| MagicaVoxelError::ModelTooLarge(model_size_lyup))?;
+        Ok((min_position_lyup, tree_size))
     }
 
     pub(crate) fn load_vox_data_internal(
         &mut self,
         vox_tree: &DotVoxData,
         min_position_lyup: &V3c<i32>,
-    ) {
+    ) -> Result<(), MagicaVoxelError> {
+        self.load_vox_data_mapped(vox_tree, min_position_lyup, &[], false, |_| None)
+    }
+
+    /// Inserts the voxels of the given .vox data into the boxtree
+    /// * `layers` - The layers of the scene, as collected by `VoxLayer::layers_of`
+    /// * `skip_hidden_layers` - If true, voxels inside hidden layers are not inserted
+    /// * `data_for` - Provides the user data to store alongside the color of the given voxel
+    fn load_vox_data_mapped<F: FnMut(&VoxVoxel) -> Option<T>>(
+        &mut self,
+        vox_tree: &DotVoxData,
+        min_position_lyup: &V3c<i32>,
+        layers: &[VoxLayer],
+        skip_hidden_layers: bool,
+        mut data_for: F,
+    ) -> Result<(), MagicaVoxelError> {
         let auto_simplify_enabled = self.auto_simplify;
         self.auto_simplify = false;
 
@@ -333,52 +727,699 @@ impl<T: VoxelData> BoxTree<T>
             CoordinateSystemType::Lyup,
             CoordinateSystemType::Rzup,
         );
-        iterate_vox_tree(vox_tree, 0, |model, position_rzup, orientation| {
-            let model_size_half_rzup = V3c::from(model.size).transformed(orientation) / 2;
-            let model_bottom_left_rzup = *position_rzup - model_size_half_rzup - min_position_rzup
-                // If the index delta is negative(because of orientation),
-                // voxel is set based on model[size - i - 1][..][..], instead of model[i][..][..]
-                // this requires a correction in every dimension where the index is below 0
-                + V3c::new(
-                    if model_size_half_rzup.x < 0 { -1 } else { 0 },
-                    if model_size_half_rzup.y < 0 { -1 } else { 0 },
-                    if model_size_half_rzup.z < 0 { -1 } else { 0 },
-                );
-            for voxel in &model.voxels {
-                let voxel_position_lyup = convert_coordinate(
-                    model_bottom_left_rzup + V3c::from(*voxel).transformed(orientation),
-                    CoordinateSystemType::Rzup,
-                    CoordinateSystemType::Lyup,
-                );
-                match self.insert(
-                    &V3c::from(voxel_position_lyup),
-                    BoxTreeEntry::Visual(&(vox_tree.palette[voxel.i as usize].into())),
-                ) {
-                    Ok(_) => {}
-                    Err(boxtree_error) => match boxtree_error {
-                        OctreeError::InvalidPosition { .. } => {
-                            panic!(
-                                "inserting into boxtree at at invalid position: {boxtree_error:?}"
-                            )
+        let result = Self::materials_of(vox_tree).and_then(|materials| {
+            iterate_vox_tree(vox_tree, 0, |model, position_rzup, orientation, placement| {
+                let layer = placement.layer_id.and_then(|id| layers.get(id as usize));
+                if skip_hidden_layers && layer.is_some_and(|layer| layer.hidden) {
+                    return Ok(());
+                }
+                let model_size_half_rzup = V3c::from(model.size).transformed(orientation) / 2;
+                let model_bottom_left_rzup = *position_rzup - model_size_half_rzup - min_position_rzup
+                    // If the index delta is negative(because of orientation),
+                    // voxel is set based on model[size - i - 1][..][..], instead of model[i][..][..]
+                    // this requires a correction in every dimension where the index is below 0
+                    + V3c::new(
+                        if model_size_half_rzup.x < 0 { -1 } else { 0 },
+                        if model_size_half_rzup.y < 0 { -1 } else { 0 },
+                        if model_size_half_rzup.z < 0 { -1 } else { 0 },
+                    );
+                for voxel in &model.voxels {
+                    let voxel_position_lyup = V3c::from(convert_coordinate(
+                        model_bottom_left_rzup + V3c::from(*voxel).transformed(orientation),
+                        CoordinateSystemType::Rzup,
+                        CoordinateSystemType::Lyup,
+                    ));
+                    let color: Albedo = (*vox_tree.palette.get(voxel.i as usize).ok_or_else(
+                        || {
+                            MagicaVoxelError::ParseError(format!(
+                                "Voxel references missing palette entry {}",
+                                voxel.i
+                            ))
+                        },
+                    )?)
+                    .into();
+                    let data = data_for(&VoxVoxel {
+                        position: voxel_position_lyup,
+                        color,
+                        palette_index: voxel.i,
+                        material: materials.get(voxel.i as usize).and_then(Option::as_ref),
+                        layer,
+                        node_name: placement.node_name,
+                    });
+                    match &data {
+                        Some(data) => {
+                            self.insert(&voxel_position_lyup, BoxTreeEntry::Complex(&color, data))?
                         }
-                        _ => panic!("inserting into boxtree yielded: {boxtree_error:?}"),
-                    },
+                        None => self.insert(&voxel_position_lyup, BoxTreeEntry::Visual(&color))?,
+                    }
                 }
-            }
+                Ok(())
+            })
         });
 
         if auto_simplify_enabled {
             self.simplify(Self::ROOT_NODE_KEY as usize, true);
             self.auto_simplify = auto_simplify_enabled;
         }
+        result
+    }
+
+    /// Collects the materials of the given .vox data, indexed by the palette index they belong to
+    fn materials_of(vox_tree: &DotVoxData) -> Result<Vec<Option<VoxMaterial>>, MagicaVoxelError> {
+        let mut materials = vec![None; vox_tree.palette.len()];
+        for Material { id, properties } in vox_tree.materials.iter() {
+            // Material ids match the palette indices inside the file, which start from 1
+            if let Some(material) = (*id as usize)
+                .checked_sub(1)
+                .and_then(|palette_index| materials.get_mut(palette_index))
+            {
+                *material = Some(VoxMaterial::from_properties(properties)?);
+            }
+        }
+        Ok(materials)
+    }
+}
+
+/// The maximum size of a model in each dimension inside a .vox file
+const VOX_MODEL_SIZE: u32 = 256;
+
+/// The number of usable colors inside a .vox palette; Color index 0 is reserved for empty voxels
+const VOX_PALETTE_SIZE: usize = 255;
+
+/// Reduces the given colors into at most `max_colors` representative colors with median cut
+/// * `returns` - (representative colors, index of the representative for each given color)
+fn median_cut(colors: &[Albedo], max_colors: usize) -> (Vec<Albedo>, Vec<usize>) {
+    let channel = |color: &Albedo, channel: usize| match channel {
+        0 => color.r,
+        1 => color.g,
+        2 => color.b,
+        _ => color.a,
+    };
+    let widest_channel = |color_box: &[usize]| {
+        (0..4)
+            .map(|c| {
+                let (min, max) = color_box.iter().fold((u8::MAX, u8::MIN), |(min, max), i| {
+                    let value = channel(&colors[*i], c);
+                    (min.min(value), max.max(value))
+                });
+                (c, max.saturating_sub(min))
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![(0..colors.len()).collect::<Vec<usize>>()];
+    while boxes.len() < max_colors {
+        // Split the box with the largest color range along its widest channel
+        let Some((box_index, (split_channel, _))) = boxes
+            .iter()
+            .map(|color_box| widest_channel(color_box))
+            .enumerate()
+            .filter(|(_, (_, range))| 0 < *range)
+            .max_by_key(|(_, (_, range))| *range)
+        else {
+            break;
+        };
+        let mut color_box = boxes.swap_remove(box_index);
+        color_box.sort_by_key(|i| channel(&colors[*i], split_channel));
+        let upper_half = color_box.split_off(color_box.len() / 2);
+        boxes.push(color_box);
+        boxes.push(upper_half);
+    }
+
+    let mut representatives = Vec::with_capacity(boxes.len());
+    let mut assignment = vec![0; colors.len()];
+    for (box_index, color_box) in boxes.iter().enumerate() {
+        let mut sum = [0_u32; 4];
+        for i in color_box.iter() {
+            for (c, channel_sum) in sum.iter_mut().enumerate() {
+                *channel_sum += channel(&colors[*i], c) as u32;
+            }
+            assignment[*i] = box_index;
+        }
+        let count = color_box.len().max(1) as u32;
+        representatives.push(Albedo {
+            r: (sum[0] / count) as u8,
+            g: (sum[1] / count) as u8,
+            b: (sum[2] / count) as u8,
+            a: (sum[3] / count) as u8,
+        });
+    }
+    (representatives, assignment)
+}
+
+fn write_i32(bytes: &mut Vec<u8>, value: i32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_dict(bytes: &mut Vec<u8>, entries: &[(&str, String)]) {
+    write_i32(bytes, entries.len() as i32);
+    for (key, value) in entries {
+        write_i32(bytes, key.len() as i32);
+        bytes.extend_from_slice(key.as_bytes());
+        write_i32(bytes, value.len() as i32);
+        bytes.extend_from_slice(value.as_bytes());
+    }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend_from_slice(id);
+    write_i32(bytes, content.len() as i32);
+    write_i32(bytes, children.len() as i32);
+    bytes.extend_from_slice(content);
+    bytes.extend_from_slice(children);
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Saves the colors stored in the boxtree into the given .vox file
+    /// Voxels without color information are not exported
+    pub fn save_vox_file<P: AsRef<Path>>(&self, filename: P) -> Result<(), MagicaVoxelError> {
+        std::fs::write(filename, self.to_vox_bytes()).map_err(MagicaVoxelError::Io)
+    }
+
+    /// Converts the colors stored in the boxtree into .vox file contents
+    /// The tree is split into models of at most 256^3 voxels, placed in a common group;
+    /// In case more than 255 colors are used, the palette is reduced to fit into a .vox palette
+    pub fn to_vox_bytes(&self) -> Vec<u8> {
+        // Collect voxels for each model, along with the used colors
+        // The model at the origin is always exported, even if it's empty,
+        // so the placement of the voxels is kept when the scene is loaded again
+        let mut models = BTreeMap::<(u32, u32, u32), Vec<(u8, u8, u8, usize)>>::new();
+        models.insert((0, 0, 0), vec![]);
+        let mut used_colors = HashMap::<Albedo, usize>::new();
+        for (position_lyup, entry) in self.voxels() {
+            let Some(color) = entry.albedo().filter(|color| !color.is_transparent()) else {
+                continue;
+            };
+            let used_count = used_colors.len();
+            let color_index = *used_colors.entry(*color).or_insert(used_count);
+            let position_rzup = convert_coordinate(
+                V3c::<i32>::from(position_lyup),
+                CoordinateSystemType::Lyup,
+                CoordinateSystemType::Rzup,
+            );
+            let position_rzup = V3c::<u32>::from(position_rzup);
+            models
+                .entry((
+                    position_rzup.x / VOX_MODEL_SIZE,
+                    position_rzup.y / VOX_MODEL_SIZE,
+                    position_rzup.z / VOX_MODEL_SIZE,
+                ))
+                .or_default()
+                .push((
+                    (position_rzup.x % VOX_MODEL_SIZE) as u8,
+                    (position_rzup.y % VOX_MODEL_SIZE) as u8,
+                    (position_rzup.z % VOX_MODEL_SIZE) as u8,
+                    color_index,
+                ));
+        }
+
+        // Map the used colors into the .vox palette
+        let mut colors = vec![Albedo::default(); used_colors.len()];
+        for (color, used_index) in used_colors.iter() {
+            colors[*used_index] = *color;
+        }
+        let (vox_palette, vox_color_for_used) = if colors.len() > VOX_PALETTE_SIZE {
+            median_cut(&colors, VOX_PALETTE_SIZE)
+        } else {
+            let direct_mapping = (0..colors.len()).collect();
+            (colors, direct_mapping)
+        };
+
+        let mut children = Vec::new();
+        for ((mx, my, mz), voxels) in models.iter() {
+            let model_size = |m: u32| (self.boxtree_size - m * VOX_MODEL_SIZE).min(VOX_MODEL_SIZE);
+            let mut size = Vec::new();
+            write_i32(&mut size, model_size(*mx) as i32);
+            write_i32(&mut size, model_size(*my) as i32);
+            write_i32(&mut size, model_size(*mz) as i32);
+            write_chunk(&mut children, b"SIZE", &size, &[]);
+
+            let mut xyzi = Vec::with_capacity(4 + voxels.len() * 4);
+            write_i32(&mut xyzi, voxels.len() as i32);
+            for (x, y, z, color_index) in voxels.iter() {
+                // .vox color indices start from 1
+                let vox_color = vox_color_for_used[*color_index] as u8 + 1;
+                xyzi.extend_from_slice(&[*x, *y, *z, vox_color]);
+            }
+            write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+        }
+
+        // Scene graph: root transform -> group -> (transform -> shape) for each model
+        let mut scene = Vec::new();
+        let mut root_transform = Vec::new();
+        write_i32(&mut root_transform, 0);
+        write_dict(&mut root_transform, &[]);
+        write_i32(&mut root_transform, 1);
+        write_i32(&mut root_transform, -1);
+        write_i32(&mut root_transform, -1);
+        write_i32(&mut root_transform, 1);
+        write_dict(&mut root_transform, &[]);
+        write_chunk(&mut scene, b"nTRN", &root_transform, &[]);
+
+        let mut group = Vec::new();
+        write_i32(&mut group, 1);
+        write_dict(&mut group, &[]);
+        write_i32(&mut group, models.len() as i32);
+        for model_index in 0..models.len() {
+            write_i32(&mut group, 2 + 2 * model_index as i32);
+        }
+        write_chunk(&mut scene, b"nGRP", &group, &[]);
+
+        for (model_index, (mx, my, mz)) in models.keys().enumerate() {
+            // Model translation is the center of the model
+            let model_size = |m: u32| (self.boxtree_size - m * VOX_MODEL_SIZE).min(VOX_MODEL_SIZE);
+            let translation = |m: u32| m * VOX_MODEL_SIZE + model_size(m) / 2;
+            let mut transform = Vec::new();
+            write_i32(&mut transform, 2 + 2 * model_index as i32);
+            write_dict(&mut transform, &[]);
+            write_i32(&mut transform, 3 + 2 * model_index as i32);
+            write_i32(&mut transform, -1);
+            write_i32(&mut transform, 0);
+            write_i32(&mut transform, 1);
+            write_dict(
+                &mut transform,
+                &[(
+                    "_t",
+                    format!(
+                        "{} {} {}",
+                        translation(*mx),
+                        translation(*my),
+                        translation(*mz)
+                    ),
+                )],
+            );
+            write_chunk(&mut scene, b"nTRN", &transform, &[]);
+
+            let mut shape = Vec::new();
+            write_i32(&mut shape, 3 + 2 * model_index as i32);
+            write_dict(&mut shape, &[]);
+            write_i32(&mut shape, 1);
+            write_i32(&mut shape, model_index as i32);
+            write_dict(&mut shape, &[]);
+            write_chunk(&mut scene, b"nSHP", &shape, &[]);
+        }
+        children.extend_from_slice(&scene);
+
+        let mut layer = Vec::new();
+        write_i32(&mut layer, 0);
+        write_dict(&mut layer, &[]);
+        write_i32(&mut layer, -1);
+        write_chunk(&mut children, b"LAYR", &layer, &[]);
+
+        let mut palette = Vec::with_capacity(256 * 4);
+        for color_index in 0..256 {
+            let color = vox_palette.get(color_index).copied().unwrap_or_default();
+            palette.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        write_chunk(&mut children, b"RGBA", &palette, &[]);
+
+        let mut bytes = b"VOX ".to_vec();
+        write_i32(&mut bytes, 150);
+        write_chunk(&mut bytes, b"MAIN", &[], &children);
+        bytes
     }
 }
 
 #[cfg(test)]
 mod boxtree_tests {
-    use super::parse_rotation_matrix;
+    use super::{
+        median_cut, model_size_to_tree_size, parse_rotation_matrix, write_chunk, write_dict,
+        write_i32, MagicaVoxelError, VoxLayer,
+    };
+    use crate::boxtree::{Albedo, BoxTree, MIPMapStrategy, V3c};
+    use bendy::encoding::ToBencode;
     use nalgebra::Matrix3;
 
+    #[test]
+    fn test_tree_size_for_model() {
+        assert_eq!(model_size_to_tree_size(&V3c::new(1, 1, 1), 32), Some(128));
+        assert_eq!(model_size_to_tree_size(&V3c::new(100, 20, 3), 4), Some(256));
+        assert_eq!(
+            model_size_to_tree_size(&V3c::new(256, 256, 256), 1),
+            Some(256)
+        );
+        assert_eq!(
+            model_size_to_tree_size(&V3c::new(i32::MAX, 1, 1), 1024),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_vox_file_errors() {
+        assert!(matches!(
+            BoxTree::<u32>::load_vox_file("assets/models/does_not_exist.vox", 4),
+            Err(MagicaVoxelError::FileNotFound(_))
+        ));
+        assert!(matches!(
+            BoxTree::<u32>::load_vox_bytes(b"not a vox file", 4),
+            Err(MagicaVoxelError::ParseError(_))
+        ));
+        assert!(matches!(
+            MIPMapStrategy::default().load_vox_bytes::<u32>(4, &[]),
+            Err(MagicaVoxelError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_vox_bytes_with_cyclic_scene_graph() {
+        let chunk = |id: &[u8; 4], content: &[u8], children: &[u8]| {
+            let mut bytes = id.to_vec();
+            bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(content);
+            bytes.extend_from_slice(children);
+            bytes
+        };
+        let ints = |values: &[i32]| {
+            values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<u8>>()
+        };
+
+        // The root transform, and a group under it listing the root transform as its child
+        let mut children = chunk(b"SIZE", &ints(&[4, 4, 4]), &[]);
+        children.extend(chunk(
+            b"XYZI",
+            &[ints(&[1]), vec![0, 0, 0, 1]].concat(),
+            &[],
+        ));
+        // node id, attributes, child, reserved, layer, frames with their attributes
+        children.extend(chunk(b"nTRN", &ints(&[0, 0, 1, -1, -1, 1, 0]), &[]));
+        // node id, attributes, children
+        children.extend(chunk(b"nGRP", &ints(&[1, 0, 1, 0]), &[]));
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend_from_slice(&150_i32.to_le_bytes());
+        bytes.extend(chunk(b"MAIN", &[], &children));
+
+        assert!(matches!(
+            BoxTree::<u32>::load_vox_bytes(&bytes, 4),
+            Err(MagicaVoxelError::UnsupportedChunk(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_vox_bytes_matches_file() {
+        let path = "assets/models/navigate.vox";
+        let bytes = std::fs::read(path).expect("Expected test asset to be available");
+        let tree_from_file = BoxTree::<u32>::load_vox_file(path, 8).ok().unwrap();
+        let tree_from_bytes = BoxTree::<u32>::load_vox_bytes(&bytes, 8).ok().unwrap();
+
+        assert!(!tree_from_file.voxel_color_palette.is_empty());
+        assert!(tree_from_file.voxel_color_palette == tree_from_bytes.voxel_color_palette);
+        assert!(
+            tree_from_file.nodes.to_bencode().ok().unwrap()
+                == tree_from_bytes.nodes.to_bencode().ok().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_save_vox_roundtrip() {
+        let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        let positions = [
+            V3c::new(0, 0, 0),
+            V3c::new(1, 2, 3),
+            V3c::new(10, 0, 5),
+            V3c::new(63, 63, 63),
+            V3c::new(0, 40, 17),
+        ];
+        for (i, position) in positions.iter().enumerate() {
+            tree.insert(position, &Albedo::from(0x110000FF + ((i as u32) << 8)))
+                .ok()
+                .unwrap();
+        }
+
+        let loaded = BoxTree::<u32>::load_vox_bytes(&tree.to_vox_bytes(), 4)
+            .ok()
+            .unwrap();
+        assert_eq!(loaded.get_size(), 64);
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in 0..64 {
+                    let position = V3c::new(x, y, z);
+                    assert!(
+                        tree.get(&position).albedo() == loaded.get(&position).albedo(),
+                        "Mismatch at {:?}",
+                        position
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_vox_multiple_models() {
+        let mut tree: BoxTree = BoxTree::new(512, 8).ok().unwrap();
+        let red = Albedo::default().with_red(255).with_alpha(255);
+        let green = Albedo::default().with_green(255).with_alpha(255);
+        tree.insert(&V3c::new(0, 0, 0), &red).ok().unwrap();
+        tree.insert(&V3c::new(300, 10, 400), &green).ok().unwrap();
+
+        let path = std::env::temp_dir().join("voxelhex_test_save_vox_multiple_models.vox");
+        tree.save_vox_file(&path).ok().unwrap();
+        let loaded = BoxTree::<u32>::load_vox_file(&path, 8).ok().unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.get_size(), 512);
+        assert!(loaded.get(&V3c::new(0, 0, 0)).albedo() == Some(&red));
+        assert!(loaded.get(&V3c::new(300, 10, 400)).albedo() == Some(&green));
+        assert!(loaded.get(&V3c::new(300, 400, 10)).is_none());
+        assert!(loaded.get(&V3c::new(1, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_save_vox_keeps_offset_model_in_place() {
+        let mut tree: BoxTree = BoxTree::new(512, 8).ok().unwrap();
+        let red = Albedo::default().with_red(255).with_alpha(255);
+        tree.insert(&V3c::new(300, 10, 400), &red).ok().unwrap();
+        tree.insert(&V3c::new(301, 12, 400), &red).ok().unwrap();
+
+        let loaded = BoxTree::<u32>::load_vox_bytes(&tree.to_vox_bytes(), 8)
+            .ok()
+            .unwrap();
+        assert_eq!(loaded.get_size(), 512);
+        assert!(loaded.get(&V3c::new(300, 10, 400)).albedo() == Some(&red));
+        assert!(loaded.get(&V3c::new(301, 12, 400)).albedo() == Some(&red));
+        assert_eq!(loaded.voxels().count(), 2);
+    }
+
+    #[test]
+    fn test_save_vox_quantizes_palette() {
+        let colors = (0..1000_u32)
+            .map(|i| {
+                Albedo::default()
+                    .with_red((i % 256) as u8)
+                    .with_green((i * 7 % 256) as u8)
+                    .with_blue((i / 4) as u8)
+                    .with_alpha(255)
+            })
+            .collect::<Vec<_>>();
+        let (palette, assignment) = median_cut(&colors, 255);
+        assert_eq!(palette.len(), 255);
+        assert_eq!(assignment.len(), colors.len());
+        for (color, representative) in colors.iter().zip(assignment.iter()) {
+            assert!(*representative < palette.len());
+            assert!(color.distance_from(&palette[*representative]) < 128.);
+        }
+
+        // Colors are not merged while there is enough room for them
+        let (palette, assignment) = median_cut(&colors[0..3], 255);
+        assert_eq!(palette.len(), 3);
+        for (color, representative) in colors[0..3].iter().zip(assignment.iter()) {
+            assert!(palette[*representative] == *color);
+        }
+    }
+
+    /// Two 4^3 models side by side: one in a visible layer, and one in a hidden layer
+    /// with a material for each used color
+    fn layered_vox_bytes() -> Vec<u8> {
+        let mut children = Vec::new();
+        for (x, y, z, i) in [(0_u8, 0_u8, 0_u8, 1_u8), (1, 1, 1, 2)] {
+            let mut size = Vec::new();
+            for _ in 0..3 {
+                write_i32(&mut size, 4);
+            }
+            write_chunk(&mut children, b"SIZE", &size, &[]);
+            let mut xyzi = Vec::new();
+            write_i32(&mut xyzi, 1);
+            xyzi.extend_from_slice(&[x, y, z, i]);
+            write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+        }
+
+        let transform = |node_id, child, layer_id, name: &str, translation: &str| {
+            let mut content = Vec::new();
+            write_i32(&mut content, node_id);
+            if name.is_empty() {
+                write_dict(&mut content, &[]);
+            } else {
+                write_dict(&mut content, &[("_name", name.to_string())]);
+            }
+            write_i32(&mut content, child);
+            write_i32(&mut content, -1);
+            write_i32(&mut content, layer_id);
+            write_i32(&mut content, 1);
+            write_dict(&mut content, &[("_t", translation.to_string())]);
+            content
+        };
+        write_chunk(
+            &mut children,
+            b"nTRN",
+            &transform(0, 1, -1, "", "0 0 0"),
+            &[],
+        );
+        let mut group = Vec::new();
+        write_i32(&mut group, 1);
+        write_dict(&mut group, &[]);
+        write_i32(&mut group, 2);
+        write_i32(&mut group, 2);
+        write_i32(&mut group, 4);
+        write_chunk(&mut children, b"nGRP", &group, &[]);
+        for (model_id, layer_id, name, translation) in
+            [(0, 0, "ground", "2 2 2"), (1, 1, "", "6 2 2")]
+        {
+            let content = transform(
+                2 + 2 * model_id,
+                3 + 2 * model_id,
+                layer_id,
+                name,
+                translation,
+            );
+            write_chunk(&mut children, b"nTRN", &content, &[]);
+            let mut shape = Vec::new();
+            write_i32(&mut shape, 3 + 2 * model_id);
+            write_dict(&mut shape, &[]);
+            write_i32(&mut shape, 1);
+            write_i32(&mut shape, model_id);
+            write_dict(&mut shape, &[]);
+            write_chunk(&mut children, b"nSHP", &shape, &[]);
+        }
+
+        for (layer_id, attributes) in [
+            (0, vec![("_name", "visible".to_string())]),
+            (
+                1,
+                vec![
+                    ("_name", "secret".to_string()),
+                    ("_hidden", "1".to_string()),
+                ],
+            ),
+        ] {
+            let mut layer = Vec::new();
+            write_i32(&mut layer, layer_id);
+            write_dict(&mut layer, &attributes);
+            write_i32(&mut layer, -1);
+            write_chunk(&mut children, b"LAYR", &layer, &[]);
+        }
+
+        let mut palette = Vec::new();
+        for i in 0..256_u32 {
+            palette.extend_from_slice(&[i as u8, 0, 0, 255]);
+        }
+        write_chunk(&mut children, b"RGBA", &palette, &[]);
+
+        for (material_id, properties) in [
+            (
+                1,
+                vec![("_type", "_emit".to_string()), ("_emit", "0.5".to_string())],
+            ),
+            (
+                2,
+                vec![
+                    ("_type", "_metal".to_string()),
+                    ("_metal", "0.75".to_string()),
+                    ("_rough", "0.25".to_string()),
+                ],
+            ),
+        ] {
+            let mut material = Vec::new();
+            write_i32(&mut material, material_id);
+            write_dict(&mut material, &properties);
+            write_chunk(&mut children, b"MATL", &material, &[]);
+        }
+
+        let mut bytes = b"VOX ".to_vec();
+        write_i32(&mut bytes, 150);
+        write_chunk(&mut bytes, b"MAIN", &[], &children);
+        bytes
+    }
+
+    #[test]
+    fn test_load_vox_with_materials_and_layers() {
+        let mut imported = Vec::new();
+        let (tree, layers) =
+            BoxTree::<u32>::load_vox_bytes_with(&layered_vox_bytes(), 4, false, |voxel| {
+                imported.push((
+                    voxel.position,
+                    voxel.palette_index,
+                    voxel.material.cloned(),
+                    voxel.layer.map(|layer| layer.id),
+                    voxel.node_name.map(str::to_string),
+                ));
+                Some(voxel.palette_index as u32 + 10)
+            })
+            .ok()
+            .unwrap();
+
+        assert_eq!(
+            layers,
+            vec![
+                VoxLayer {
+                    id: 0,
+                    name: Some("visible".to_string()),
+                    hidden: false,
+                },
+                VoxLayer {
+                    id: 1,
+                    name: Some("secret".to_string()),
+                    hidden: true,
+                },
+            ]
+        );
+        assert_eq!(imported.len(), 2);
+
+        let (position, palette_index, material, layer, node_name) = &imported[0];
+        assert_eq!(*position, V3c::new(0, 0, 0));
+        assert_eq!(*palette_index, 0);
+        let material = material.as_ref().unwrap();
+        assert_eq!(material.material_type.as_deref(), Some("_emit"));
+        assert_eq!(material.emission, Some(0.5));
+        assert_eq!(material.metalness, None);
+        assert_eq!(*layer, Some(0));
+        assert_eq!(node_name.as_deref(), Some("ground"));
+
+        let (position, palette_index, material, layer, node_name) = &imported[1];
+        assert_eq!(*position, V3c::new(5, 1, 1));
+        assert_eq!(*palette_index, 1);
+        let material = material.as_ref().unwrap();
+        assert_eq!(material.metalness, Some(0.75));
+        assert_eq!(material.roughness, Some(0.25));
+        assert_eq!(*layer, Some(1));
+        assert_eq!(*node_name, None);
+
+        assert_eq!(tree.get(&V3c::new(0, 0, 0)).data(), Some(&10));
+        assert_eq!(tree.get(&V3c::new(5, 1, 1)).data(), Some(&11));
+        assert!(tree.get(&V3c::new(5, 1, 1)).albedo() == Some(&Albedo::from(0x010000FF)));
+    }
+
+    #[test]
+    fn test_load_vox_skip_hidden_layers() {
+        let (tree, layers) =
+            BoxTree::<u32>::load_vox_bytes_with(&layered_vox_bytes(), 4, true, |_| None)
+                .ok()
+                .unwrap();
+        assert_eq!(layers.len(), 2);
+        assert!(tree.get(&V3c::new(0, 0, 0)).albedo().is_some());
+        assert!(tree.get(&V3c::new(0, 0, 0)).data().is_none());
+        assert!(tree.get(&V3c::new(5, 1, 1)).is_none());
+
+        // Hidden layers are imported with the default import
+        let tree = BoxTree::<u32>::load_vox_bytes(&layered_vox_bytes(), 4)
+            .ok()
+            .unwrap();
+        assert!(tree.get(&V3c::new(5, 1, 1)).albedo().is_some());
+    }
+
     #[test]
     fn test_matrix_parse() {
         let test_matrix = Matrix3::<i8>::new(1, 0, 0, 0, 1, 0, 0, 0, 1);
@@ -1,9 +1,13 @@
 #[cfg(feature = "bytecode")]
-mod bytecode;
+pub(crate) mod bytecode;
+
+#[cfg(feature = "bytecode")]
+mod paged;
 
 #[cfg(feature = "bytecode")]
 #[cfg(test)]
 mod tests;
 
+/// Import and export of MagicaVoxel .vox files
 #[cfg(all(feature = "bytecode", feature = "dot_vox_support"))]
-mod magicavoxel;
+pub mod magicavoxel;
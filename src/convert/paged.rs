@@ -0,0 +1,464 @@
+use crate::{
+    boxtree::{
+        types::{
+            BrickData, NodeContent, NodeData, PaletteIndexValues, PaletteIndexWidth,
+            SerializationError,
+        },
+        update::palette::{map_brick_voxels, map_node_voxels},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    convert::bytecode::check_loaded_node,
+    object_pool::{ObjectPool, PageError},
+};
+use bendy::{decoding::FromBencode, encoding::ToBencode};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+//####################################################################################
+//  ███████████    █████████     █████████  ██████████ ██████████
+// ░░███░░░░░███  ███░░░░░███   ███░░░░░███░░███░░░░░█░░███░░░░███
+//  ░███    ░███ ░███    ░███  ███     ░░░  ░███  █ ░  ░███   ░░███
+//  ░██████████  ░███████████ ░███          ░██████    ░███    ░███
+//  ░███░░░░░░   ░███░░░░░███ ░███    █████ ░███░░█    ░███    ░███
+//  ░███         ░███    ░███ ░░███  ░░███  ░███ ░   █ ░███    ███
+//  █████        █████   █████ ░░█████████  ██████████ ██████████
+// ░░░░░        ░░░░░   ░░░░░   ░░░░░░░░░  ░░░░░░░░░░ ░░░░░░░░░░
+//####################################################################################
+// Layout of a paged boxtree file:
+// | magic | header size | header | node count | brick count | brick index offset | node index table | pages... | brick index table |
+// - header: the bencoded boxtree without its nodes
+// - node index table: (offset, size, brick count) of the page of each node; freed nodes have 0 offset
+// - brick index table: (slot, offset, size) of each brick page, in the order of the nodes they belong to
+// - node page: the bencoded node without its parted bricks
+// - brick page: a bencoded parted brick
+// Every number outside the bencoded parts is a little endian u64
+
+/// Identifies paged boxtree files
+const PAGED_MAGIC: &[u8; 8] = b"VHEXPAGE";
+
+/// The slot of the MIP brick inside the brick references of a node page
+const MIP_BRICK_SLOT: usize = BOX_NODE_CHILDREN_COUNT;
+
+/// Location of a parted brick inside a paged boxtree file
+#[derive(Clone, Copy)]
+struct BrickPageRef {
+    /// The index of the brick inside the node: a sectant for node content, or `MIP_BRICK_SLOT`
+    slot: usize,
+    offset: u64,
+    size: u64,
+}
+
+/// Provides mutable access to the brick of the node in the given slot, see `BrickPageRef::slot`
+fn brick_in_slot(node: &mut NodeData, slot: usize) -> Option<&mut BrickData<PaletteIndexValues>> {
+    match (&mut node.content, slot) {
+        (_, MIP_BRICK_SLOT) => Some(&mut node.mip),
+        (NodeContent::UniformLeaf(brick), 0) => Some(brick),
+        (NodeContent::Leaf(bricks), slot) => bricks.get_mut(slot),
+        _ => None,
+    }
+}
+
+/// Estimates the memory usage of the given node
+fn node_memory_size(node: &NodeData) -> usize {
+    let content_size = match &node.content {
+        NodeContent::Nothing | NodeContent::Internal => 0,
+        NodeContent::UniformLeaf(brick) => brick.memory_usage(),
+        NodeContent::Leaf(bricks) => bricks.iter().map(BrickData::memory_usage).sum(),
+    };
+    std::mem::size_of::<NodeData>() + content_size + node.mip.memory_usage()
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, SerializationError> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads the given section of the file
+fn read_page(file: &mut File, offset: u64, size: u64) -> Result<Vec<u8>, SerializationError> {
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    // Page sizes are not trusted to allocate memory up front
+    file.take(size).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != size {
+        return Err(SerializationError::Malformed(format!(
+            "Page at {offset} of size {size} is outside the file"
+        )));
+    }
+    Ok(bytes)
+}
+
+/// A paged boxtree file, read by any number of readers at once
+struct PagedFile {
+    path: PathBuf,
+
+    /// Handles of the file not used by any reader
+    handles: Mutex<Vec<File>>,
+}
+
+impl PagedFile {
+    /// Reads the given section of the file, with a handle not used by other readers
+    fn read_page(&self, offset: u64, size: u64) -> Result<Vec<u8>, SerializationError> {
+        let handle = self
+            .handles
+            .lock()
+            .expect("Expected to be able to lock paged boxtree file handles")
+            .pop();
+        let mut file = match handle {
+            Some(file) => file,
+            None => File::open(&self.path)?,
+        };
+        let page = read_page(&mut file, offset, size);
+        self.handles
+            .lock()
+            .expect("Expected to be able to lock paged boxtree file handles")
+            .push(file);
+        page
+    }
+}
+
+/// Location of a node page inside a paged boxtree file
+struct NodePageRef {
+    offset: u64,
+    size: u64,
+
+    /// The range of the bricks of the node inside the brick index table
+    bricks: Range<usize>,
+}
+
+/// Loads the given parted bricks into the outline of a node, which is stored without them
+fn read_bricks(
+    file: &PagedFile,
+    node_key: usize,
+    node: &mut NodeData,
+    bricks: &[BrickPageRef],
+    palette_index_width: PaletteIndexWidth,
+) -> Result<(), SerializationError> {
+    for brick_ref in bricks {
+        let mut brick = BrickData::<PaletteIndexValues>::from_bencode(
+            &file.read_page(brick_ref.offset, brick_ref.size)?,
+        )?;
+        if PaletteIndexWidth::Narrow == palette_index_width {
+            map_brick_voxels(&mut brick, &NodeContent::pix_widened);
+        }
+        *brick_in_slot(node, brick_ref.slot).ok_or_else(|| {
+            SerializationError::MalformedNode {
+                node_key,
+                reason: format!("Brick slot {} does not exist in node", brick_ref.slot),
+            }
+        })? = brick;
+    }
+    Ok(())
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Saves the data structure into the given file path in a paged format,
+    /// where each node and brick can be loaded separately, see `load_paged`
+    pub fn save_paged<P: AsRef<Path>>(&self, path: P) -> Result<(), SerializationError> {
+        self.check_nodes_loaded()?;
+        let mut file = BufWriter::new(File::create(path)?);
+
+        // Header: everything besides the nodes, with the unused palette entries left out if needed
+        let remap = self
+            .compact_palettes_on_save
+            .then(|| self.palette_remap())
+            .filter(|remap| !remap.is_identity());
+        let (voxel_color_palette, voxel_data_palette) = match remap.as_ref() {
+            Some(remap) => remap.compact(&self.voxel_color_palette, &self.voxel_data_palette),
+            None => (
+                self.voxel_color_palette.clone(),
+                self.voxel_data_palette.clone(),
+            ),
+        };
+        let header = BoxTree::<T> {
+            nodes: ObjectPool::with_capacity(0),
+            update_triggers: vec![],
+            subscriptions: vec![],
+            journal: None,
+            compact_palettes_on_save: false,
+            voxel_color_palette,
+            voxel_data_palette,
+            map_to_color_index_in_palette: Default::default(),
+            map_to_data_index_in_palette: Default::default(),
+            mip_map_strategy: self.mip_map_strategy.clone(),
+            ..*self
+        }
+        .to_bencode()?;
+        file.write_all(PAGED_MAGIC)?;
+        file.write_all(&(header.len() as u64).to_le_bytes())?;
+        file.write_all(&header)?;
+
+        // Index tables are written after the pages, as their positions are not yet known
+        let node_count = self.nodes.len();
+        let counts_offset = (PAGED_MAGIC.len() + 8 + header.len()) as u64;
+        file.write_all(&vec![0; 24 + node_count * 24])?;
+
+        let mut offset = counts_offset + 24 + node_count as u64 * 24;
+        let mut node_index_table = Vec::with_capacity(node_count * 24);
+        let mut brick_index_table = Vec::new();
+        let mut brick_count = 0_u64;
+        for node_key in 0..node_count {
+            if !self.nodes.key_is_valid(node_key) {
+                node_index_table.extend_from_slice(&[0; 24]);
+                continue;
+            }
+
+            // Write the parted bricks into separate pages
+            let mut node = {
+                let node = self.nodes.get(node_key);
+                self.converted_for_save(&node, remap.as_ref())
+                    .unwrap_or_else(|| node.clone())
+            };
+            let mut node_brick_count = 0_u64;
+            for slot in 0..=MIP_BRICK_SLOT {
+                let Some(brick) = brick_in_slot(&mut node, slot) else {
+                    continue;
+                };
+                if !matches!(brick, BrickData::Parted(_) | BrickData::Packed(_)) {
+                    continue;
+                }
+                let brick_bytes = std::mem::take(brick).to_bencode()?;
+                file.write_all(&brick_bytes)?;
+                brick_index_table.extend_from_slice(&(slot as u64).to_le_bytes());
+                brick_index_table.extend_from_slice(&offset.to_le_bytes());
+                brick_index_table.extend_from_slice(&(brick_bytes.len() as u64).to_le_bytes());
+                offset += brick_bytes.len() as u64;
+                node_brick_count += 1;
+            }
+            brick_count += node_brick_count;
+
+            let node_bytes = node.to_bencode()?;
+            file.write_all(&node_bytes)?;
+            node_index_table.extend_from_slice(&offset.to_le_bytes());
+            node_index_table.extend_from_slice(&(node_bytes.len() as u64).to_le_bytes());
+            node_index_table.extend_from_slice(&node_brick_count.to_le_bytes());
+            offset += node_bytes.len() as u64;
+        }
+        // Without the index tables, the file can not be loaded
+        self.check_nodes_loaded()?;
+        file.write_all(&brick_index_table)?;
+
+        file.seek(SeekFrom::Start(counts_offset))?;
+        file.write_all(&(node_count as u64).to_le_bytes())?;
+        file.write_all(&brick_count.to_le_bytes())?;
+        file.write_all(&offset.to_le_bytes())?;
+        file.write_all(&node_index_table)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Loads a boxtree saved with `save_paged`, without reading its nodes into memory
+    /// Nodes are loaded on demand when accessed e.g. by `get`, `get_by_ray` or GPU streaming.
+    /// The parted bricks of a node are only loaded once its content is needed,
+    /// so nodes only passed through while looking up a position are kept small.
+    /// Loaded nodes are evicted when their memory usage exceeds the given budget,
+    /// except for modified nodes, which are kept in memory until saved with a new file.
+    /// The file must not change while the boxtree is in use
+    /// * Nodes which can not be loaded from the file are treated as empty, and loaded again on the next access,
+    ///   see `take_load_errors`. The tree can not be saved while any of them fails to load
+    /// * `memory_budget` - The memory usage of the loaded nodes in bytes, above which nodes are evicted
+    pub fn load_paged<P: AsRef<Path>>(
+        path: P,
+        memory_budget: usize,
+    ) -> Result<Self, SerializationError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let file_size = file.metadata()?.len();
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != PAGED_MAGIC {
+            return Err(SerializationError::Malformed(
+                "Not a paged boxtree file".to_string(),
+            ));
+        }
+
+        let header_size = read_u64(&mut file)?;
+        if header_size > file_size {
+            return Err(SerializationError::Malformed(format!(
+                "Header size {header_size} exceeds file size {file_size}"
+            )));
+        }
+        let mut header = vec![0; header_size as usize];
+        file.read_exact(&mut header)?;
+        let mut tree = Self::try_from_bytes_without_nodes(&header)?;
+
+        let node_count = read_u64(&mut file)?;
+        let brick_count = read_u64(&mut file)?;
+        let brick_index_offset = read_u64(&mut file)?;
+        if node_count.saturating_mul(24) > file_size {
+            return Err(SerializationError::Malformed(format!(
+                "Node count {node_count} exceeds file size {file_size}"
+            )));
+        }
+        let mut node_index_table = Vec::with_capacity(node_count as usize);
+        let mut bricks_start = 0_usize;
+        for node_key in 0..node_count as usize {
+            let offset = read_u64(&mut file)?;
+            let size = read_u64(&mut file)?;
+            let node_brick_count = read_u64(&mut file)?;
+            if offset.checked_add(size).is_none_or(|end| end > file_size) {
+                return Err(SerializationError::MalformedNode {
+                    node_key,
+                    reason: format!("Page at {offset} of size {size} is outside the file"),
+                });
+            }
+            let bricks_end = bricks_start.saturating_add(node_brick_count as usize);
+            if bricks_end as u64 > brick_count {
+                return Err(SerializationError::MalformedNode {
+                    node_key,
+                    reason: format!("Bricks up to {bricks_end} exceed brick count {brick_count}"),
+                });
+            }
+            node_index_table.push(NodePageRef {
+                offset,
+                size,
+                bricks: bricks_start..bricks_end,
+            });
+            bricks_start = bricks_end;
+        }
+
+        if brick_count
+            .checked_mul(24)
+            .and_then(|table_size| table_size.checked_add(brick_index_offset))
+            .is_none_or(|end| end > file_size)
+        {
+            return Err(SerializationError::Malformed(format!(
+                "Brick index table of {brick_count} bricks at {brick_index_offset} is outside the file"
+            )));
+        }
+        file.seek(SeekFrom::Start(brick_index_offset))?;
+        let mut brick_index_table = Vec::with_capacity(brick_count as usize);
+        for brick_key in 0..brick_count {
+            let slot = read_u64(&mut file)?;
+            let offset = read_u64(&mut file)?;
+            let size = read_u64(&mut file)?;
+            if slot > MIP_BRICK_SLOT as u64
+                || offset.checked_add(size).is_none_or(|end| end > file_size)
+            {
+                return Err(SerializationError::Malformed(format!(
+                    "Brick[{brick_key}] in slot {slot} at {offset} of size {size} is outside its node or the file"
+                )));
+            }
+            brick_index_table.push(BrickPageRef {
+                slot: slot as usize,
+                offset,
+                size,
+            });
+        }
+
+        let items_reserved = node_index_table
+            .iter()
+            .map(|page| 0 != page.offset)
+            .collect::<Vec<bool>>();
+        if !items_reserved
+            .get(Self::ROOT_NODE_KEY as usize)
+            .is_some_and(|reserved| *reserved)
+        {
+            return Err(SerializationError::Malformed(
+                "Root node is missing".to_string(),
+            ));
+        }
+
+        let brick_dim = tree.brick_dim;
+        let color_palette_size = tree.voxel_color_palette.len();
+        let data_palette_size = tree.voxel_data_palette.len();
+        let palette_index_width = tree.palette_index_width;
+        let key_is_valid = items_reserved.clone();
+        let check_node = move |node_key: usize, node: &NodeData| {
+            check_loaded_node(
+                node_key,
+                node,
+                brick_dim,
+                color_palette_size,
+                data_palette_size,
+                |key| key_is_valid.get(key).is_some_and(|reserved| *reserved),
+            )
+        };
+        let check_node = Arc::new(check_node);
+        let node_index_table = Arc::new(node_index_table);
+        let file = Arc::new(PagedFile {
+            path,
+            handles: Mutex::new(vec![file]),
+        });
+        let source = {
+            let (file, node_index_table, check_node) =
+                (file.clone(), node_index_table.clone(), check_node.clone());
+            move |node_key: usize| -> Result<NodeData, PageError> {
+                let page = &node_index_table[node_key];
+                let mut node = NodeData::from_bencode(&file.read_page(page.offset, page.size)?)
+                    .map_err(SerializationError::from)?;
+                if PaletteIndexWidth::Narrow == palette_index_width {
+                    map_node_voxels(&mut node, NodeContent::pix_widened);
+                }
+                check_node(node_key, &node)?;
+                Ok(node)
+            }
+        };
+        let completion = move |node_key: usize, node: &mut NodeData| -> Result<(), PageError> {
+            let bricks = &brick_index_table[node_index_table[node_key].bricks.clone()];
+            if bricks.is_empty() {
+                return Ok(());
+            }
+            read_bricks(&file, node_key, node, bricks, palette_index_width)?;
+            check_node(node_key, node)?;
+            Ok(())
+        };
+        tree.nodes = ObjectPool::paged(
+            items_reserved,
+            Box::new(source),
+            Box::new(completion),
+            node_memory_size,
+            memory_budget,
+        );
+        Ok(tree)
+    }
+
+    /// Takes the errors encountered while loading nodes on demand, since the last call
+    /// Nodes which could not be loaded are treated as empty, until they are loaded successfully
+    /// For boxtrees not loaded with `load_paged`, there are no such errors
+    pub fn take_load_errors(&self) -> Vec<SerializationError> {
+        self.nodes
+            .take_load_errors()
+            .into_iter()
+            .map(|err| match err.downcast::<SerializationError>() {
+                Ok(err) => *err,
+                Err(err) => SerializationError::Malformed(err.to_string()),
+            })
+            .collect()
+    }
+
+    /// Makes sure every node is available, so saving the tree does not lose
+    /// the nodes which could not be loaded from a paged file, see `load_paged`
+    pub(crate) fn check_nodes_loaded(&self) -> Result<(), SerializationError> {
+        let node_keys = self.nodes.failed_keys();
+        if node_keys.is_empty() {
+            Ok(())
+        } else {
+            Err(SerializationError::NodesNotLoaded { node_keys })
+        }
+    }
+
+    /// The number of nodes currently available in memory
+    /// For boxtrees loaded with `load_paged`, this is the number of nodes not evicted
+    pub fn loaded_node_count(&self) -> usize {
+        self.nodes.loaded_count()
+    }
+
+    /// parses the boxtree header of a paged file, without any nodes
+    fn try_from_bytes_without_nodes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        let tree_version = Self::parse_version(bytes)?;
+        let library_version = crate::version();
+        if !library_version.compatible(&tree_version) {
+            return Err(SerializationError::VersionMismatch {
+                library: library_version,
+                tree: tree_version,
+            });
+        }
+        Ok(Self::from_bencode(bytes)?)
+    }
+}
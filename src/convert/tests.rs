@@ -1,6 +1,10 @@
-use crate::boxtree::{
-    types::{Albedo, BrickData, NodeChildren, NodeContent, NodeData, PaletteIndexValues},
-    BoxTree, BoxTreeEntry, MIPResamplingMethods, V3c, BOX_NODE_CHILDREN_COUNT,
+use crate::{
+    boxtree::{
+        types::{Albedo, BrickData, NodeChildren, NodeContent, NodeData, PaletteIndexValues},
+        BoxTree, BoxTreeEntry, MIPResamplingMethods, PaletteIndexWidth, SerializationError, V3c,
+        BOX_NODE_CHILDREN_COUNT,
+    },
+    convert::bytecode::Compressed,
 };
 use bendy::{decoding::FromBencode, encoding::ToBencode};
 
@@ -8,7 +12,7 @@ use bendy::{decoding::FromBencode, encoding::ToBencode};
 fn test_node_brickdata_serialization() {
     let brick_data_empty = BrickData::<Albedo>::Empty;
     let brick_data_solid = BrickData::<Albedo>::Solid(Albedo::default().with_red(50));
-    let brick_data_parted = BrickData::Parted(vec![Albedo::default(); 4 * 4 * 4]);
+    let brick_data_parted = BrickData::parted(vec![Albedo::default(); 4 * 4 * 4]);
 
     let brick_data_empty_deserialized =
         BrickData::<Albedo>::from_bencode(&brick_data_empty.to_bencode().ok().unwrap())
@@ -36,7 +40,7 @@ fn test_nodecontent_serialization() {
         (0..BOX_NODE_CHILDREN_COUNT)
             .map(|sectant| match sectant % 3 {
                 1 => BrickData::Solid(NodeContent::pix_complex(69, 420)),
-                2 => BrickData::Parted(vec![NodeContent::pix_visual(666)]),
+                2 => BrickData::parted(vec![NodeContent::pix_visual(666)]),
                 _ => BrickData::Empty,
             })
             .collect::<Vec<_>>()
@@ -384,3 +388,582 @@ fn test_big_boxtree_serialize_where_dim_is_2() {
         }
     }
 }
+
+#[test]
+fn test_boxtree_serialize_after_nodes_freed() {
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    for x in 0..64 {
+        for y in 0..8 {
+            for z in 0..64 {
+                let color = if 0 == (x + y + z) % 3 { &red } else { &blue };
+                tree.insert(&V3c::new(x, y, z), color).ok().unwrap();
+            }
+        }
+    }
+
+    // Clearing a part of the tree frees up the nodes inside it
+    tree.clear_at_lod(&V3c::new(0, 0, 0), 16).ok().unwrap();
+    assert!((0..tree.nodes.len()).any(|key| !tree.nodes.key_is_valid(key)));
+
+    let deserialized = BoxTree::<u32>::try_from_bytes(&tree.to_bytes())
+        .expect("Expected to be able to deserialize tree with freed nodes");
+    for x in 0..64 {
+        for y in 0..8 {
+            for z in 0..64 {
+                let pos = V3c::new(x, y, z);
+                assert_eq!(
+                    deserialized.get(&pos),
+                    tree.get(&pos),
+                    "Mismatch at {pos:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_boxtree_deserialize_truncated_bytes() {
+    let mut tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    tree.insert(&V3c::new(3, 0, 0), &Albedo::from(0x66CC33FF))
+        .ok()
+        .unwrap();
+    let serialized = tree.to_bytes();
+
+    for length in [0, 1, 10, serialized.len() / 2, serialized.len() - 1] {
+        assert!(
+            matches!(
+                BoxTree::<u32>::try_from_bytes(&serialized[0..length]),
+                Err(SerializationError::Malformed(_))
+            ),
+            "Expected truncated bytes of length {length} to be rejected"
+        );
+    }
+}
+
+#[test]
+fn test_boxtree_deserialize_version_mismatch() {
+    let tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    let version = crate::version();
+    let stored_version = format!(
+        "li{}ei{}ei{}ee",
+        version.major(),
+        version.minor(),
+        version.patch()
+    );
+    let incompatible_version = format!(
+        "li{}ei{}ei{}ee",
+        version.major(),
+        version.minor() + 1,
+        version.patch()
+    );
+    let serialized = String::from_utf8_lossy(&tree.to_bytes())
+        .replacen(&stored_version, &incompatible_version, 1)
+        .into_bytes();
+
+    assert!(matches!(
+        BoxTree::<u32>::try_from_bytes(&serialized),
+        Err(SerializationError::VersionMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_boxtree_deserialize_invalid_child_key() {
+    let mut tree: BoxTree = BoxTree::new(64, 1).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &Albedo::from(0x66CC33FF))
+        .ok()
+        .unwrap();
+    if let NodeChildren::Children(children) = &mut tree.nodes.get_mut(0).children {
+        children[BOX_NODE_CHILDREN_COUNT - 1] = 5000;
+    } else {
+        panic!("Expected root node to have children");
+    }
+
+    assert!(matches!(
+        BoxTree::<u32>::try_from_bytes(&tree.to_bytes()),
+        Err(SerializationError::MalformedNode { .. })
+    ));
+}
+
+#[test]
+fn test_boxtree_deserialize_palette_index_out_of_range() {
+    let mut tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &Albedo::from(0x66CC33FF))
+        .ok()
+        .unwrap();
+    tree.voxel_color_palette.clear();
+
+    assert!(matches!(
+        BoxTree::<u32>::try_from_bytes(&tree.to_bytes()),
+        Err(SerializationError::PaletteIndexOutOfRange {
+            index: 0,
+            palette_size: 0,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_boxtree_load_corrupt_file() {
+    assert!(matches!(
+        BoxTree::<u32>::load("test_junk_boxtree_which_does_not_exist"),
+        Err(SerializationError::Io(_))
+    ));
+
+    let mut tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &Albedo::from(0x66CC33FF))
+        .ok()
+        .unwrap();
+    let serialized = tree.to_bytes();
+    std::fs::write(
+        "test_junk_corrupt_boxtree",
+        &serialized[0..serialized.len() / 2],
+    )
+    .ok()
+    .unwrap();
+    let loaded = BoxTree::<u32>::load("test_junk_corrupt_boxtree");
+    std::fs::write("test_junk_corrupt_boxtree", b"not a boxtree at all")
+        .ok()
+        .unwrap();
+    let version = BoxTree::<u32>::version("test_junk_corrupt_boxtree");
+    std::fs::remove_file("test_junk_corrupt_boxtree")
+        .ok()
+        .unwrap();
+
+    assert!(matches!(loaded, Err(SerializationError::Malformed(_))));
+    assert!(version.is_err());
+}
+
+/// Fills a part of a boxtree with a pattern, leaving some freed nodes behind
+fn make_paged_test_tree() -> BoxTree {
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    for x in 0..64 {
+        for y in 0..8 {
+            for z in 0..64 {
+                let color = if 0 == (x + y + z) % 3 { &red } else { &blue };
+                tree.insert(&V3c::new(x, y, z), color).ok().unwrap();
+            }
+        }
+    }
+    tree.clear_at_lod(&V3c::new(0, 0, 0), 16).ok().unwrap();
+    tree
+}
+
+#[test]
+fn test_paged_boxtree_lazy_loading() {
+    let tree = make_paged_test_tree();
+    tree.save_paged("test_junk_paged_boxtree").ok().unwrap();
+    let paged = BoxTree::<u32>::load_paged("test_junk_paged_boxtree", usize::MAX)
+        .ok()
+        .unwrap();
+    assert_eq!(paged.loaded_node_count(), 0);
+
+    paged.get(&V3c::new(20, 0, 20));
+    let loaded_after_single_get = paged.loaded_node_count();
+    assert!(0 < loaded_after_single_get);
+    assert!(loaded_after_single_get < tree.loaded_node_count());
+
+    for x in 0..64 {
+        for y in 0..10 {
+            for z in 0..64 {
+                let pos = V3c::new(x, y, z);
+                assert_eq!(paged.get(&pos), tree.get(&pos), "Mismatch at {pos:?}");
+            }
+        }
+    }
+    assert_eq!(paged.loaded_node_count(), tree.loaded_node_count());
+    std::fs::remove_file("test_junk_paged_boxtree")
+        .ok()
+        .unwrap();
+}
+
+#[test]
+fn test_paged_boxtree_memory_budget() {
+    let tree = make_paged_test_tree();
+    tree.save_paged("test_junk_paged_boxtree_budget")
+        .ok()
+        .unwrap();
+    let mut paged = BoxTree::<u32>::load_paged("test_junk_paged_boxtree_budget", 0)
+        .ok()
+        .unwrap();
+    std::fs::remove_file("test_junk_paged_boxtree_budget")
+        .ok()
+        .unwrap();
+
+    for x in 0..64 {
+        for z in 0..64 {
+            let pos = V3c::new(x, 5, z);
+            assert_eq!(paged.get(&pos), tree.get(&pos), "Mismatch at {pos:?}");
+            assert!(paged.loaded_node_count() <= 1);
+        }
+    }
+
+    #[cfg(feature = "raytracing")]
+    for x in 0..64 {
+        let ray = crate::spatial::raytracing::Ray {
+            origin: V3c::new(x as f32 + 0.5, 5.5, -1.),
+            direction: V3c::new(0., 0., 1.),
+        };
+        assert_eq!(
+            paged.get_by_ray(&ray).map(|hit| hit.0),
+            tree.get_by_ray(&ray).map(|hit| hit.0)
+        );
+    }
+
+    // Modified nodes are kept in memory
+    let green: Albedo = 0x00FF00FF.into();
+    paged.insert(&V3c::new(1, 1, 1), &green).ok().unwrap();
+    assert_eq!(paged.get(&V3c::new(1, 1, 1)), (&green).into());
+    for x in 0..64 {
+        paged.get(&V3c::new(x, 7, 63));
+    }
+    assert_eq!(paged.get(&V3c::new(1, 1, 1)), (&green).into());
+
+    let deserialized = BoxTree::<u32>::try_from_bytes(&paged.to_bytes())
+        .expect("Expected to be able to serialize a paged tree");
+    assert_eq!(deserialized.get(&V3c::new(1, 1, 1)), (&green).into());
+    assert_eq!(
+        deserialized.get(&V3c::new(40, 3, 40)),
+        tree.get(&V3c::new(40, 3, 40))
+    );
+}
+
+#[test]
+fn test_paged_boxtree_load_invalid_file() {
+    assert!(matches!(
+        BoxTree::<u32>::load_paged("test_junk_paged_boxtree_which_does_not_exist", 0),
+        Err(SerializationError::Io(_))
+    ));
+
+    let tree = make_paged_test_tree();
+    tree.save("test_junk_paged_boxtree_invalid").ok().unwrap();
+    let not_paged = BoxTree::<u32>::load_paged("test_junk_paged_boxtree_invalid", 0);
+
+    tree.save_paged("test_junk_paged_boxtree_invalid")
+        .ok()
+        .unwrap();
+    let bytes = std::fs::read("test_junk_paged_boxtree_invalid")
+        .ok()
+        .unwrap();
+    std::fs::write(
+        "test_junk_paged_boxtree_invalid",
+        &bytes[0..bytes.len() / 2],
+    )
+    .ok()
+    .unwrap();
+    let truncated = BoxTree::<u32>::load_paged("test_junk_paged_boxtree_invalid", 0);
+    std::fs::remove_file("test_junk_paged_boxtree_invalid")
+        .ok()
+        .unwrap();
+
+    assert!(matches!(not_paged, Err(SerializationError::Malformed(_))));
+    assert!(matches!(
+        truncated,
+        Err(SerializationError::MalformedNode { .. })
+    ));
+}
+
+#[test]
+fn test_paged_boxtree_corrupt_pages() {
+    let tree = make_paged_test_tree();
+    tree.save_paged("test_junk_paged_boxtree_corrupt")
+        .ok()
+        .unwrap();
+    let bytes = std::fs::read("test_junk_paged_boxtree_corrupt")
+        .ok()
+        .unwrap();
+    let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+    let counts_offset = 16 + read_u64(8);
+    let brick_index_offset = read_u64(counts_offset + 16);
+    let root_page_offset = read_u64(counts_offset + 24);
+
+    // Corrupt the first brick page
+    let mut corrupt_brick = bytes.clone();
+    let (brick_offset, brick_size) = (
+        read_u64(brick_index_offset + 8),
+        read_u64(brick_index_offset + 16),
+    );
+    corrupt_brick[brick_offset..brick_offset + brick_size].fill(b'x');
+    std::fs::write("test_junk_paged_boxtree_corrupt", &corrupt_brick)
+        .ok()
+        .unwrap();
+    let paged = BoxTree::<u32>::load_paged("test_junk_paged_boxtree_corrupt", usize::MAX)
+        .ok()
+        .unwrap();
+    let mut mismatches = 0;
+    for x in 0..64 {
+        for y in 0..10 {
+            for z in 0..64 {
+                let pos = V3c::new(x, y, z);
+                if paged.get(&pos) != tree.get(&pos) {
+                    assert_eq!(paged.get(&pos), BoxTreeEntry::Empty);
+                    mismatches += 1;
+                }
+            }
+        }
+    }
+    assert!(0 < mismatches);
+    assert!(mismatches <= 16 * 16 * 16);
+    let errors = paged.take_load_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(paged.take_load_errors().is_empty());
+
+    // The tree is not saved without the node which could not be loaded
+    assert!(matches!(
+        paged.save("test_junk_paged_boxtree_corrupt_saved"),
+        Err(SerializationError::NodesNotLoaded { .. })
+    ));
+    assert!(!std::path::Path::new("test_junk_paged_boxtree_corrupt_saved").exists());
+
+    // The node is loaded again once its page can be read
+    std::fs::write("test_junk_paged_boxtree_corrupt", &bytes)
+        .ok()
+        .unwrap();
+    for x in 0..64 {
+        for y in 0..10 {
+            for z in 0..64 {
+                let pos = V3c::new(x, y, z);
+                assert_eq!(paged.get(&pos), tree.get(&pos), "Mismatch at {pos:?}");
+            }
+        }
+    }
+    assert!(paged.take_load_errors().is_empty());
+    assert!(paged.save("test_junk_paged_boxtree_corrupt_saved").is_ok());
+    std::fs::remove_file("test_junk_paged_boxtree_corrupt_saved")
+        .ok()
+        .unwrap();
+
+    // Corrupt the page of the root node
+    let mut corrupt_root = bytes.clone();
+    corrupt_root[root_page_offset] = b'x';
+    std::fs::write("test_junk_paged_boxtree_corrupt", &corrupt_root)
+        .ok()
+        .unwrap();
+    let paged = BoxTree::<u32>::load_paged("test_junk_paged_boxtree_corrupt", usize::MAX)
+        .ok()
+        .unwrap();
+    std::fs::remove_file("test_junk_paged_boxtree_corrupt")
+        .ok()
+        .unwrap();
+    assert_eq!(paged.get(&V3c::new(20, 0, 20)), BoxTreeEntry::Empty);
+    assert!(matches!(
+        paged.take_load_errors().as_slice(),
+        [SerializationError::Malformed(_)]
+    ));
+}
+
+#[test]
+fn test_compressed_brickdata_serialization() {
+    let mut many_values = vec![0; 16 * 16 * 16];
+    for (i, voxel) in many_values.iter_mut().enumerate() {
+        *voxel = ((i * 7919) % 1000) as PaletteIndexValues;
+    }
+    let mut long_runs = vec![3; 16 * 16 * 16];
+    long_runs[0] = 5;
+    long_runs[2000..2005].fill(PaletteIndexValues::MAX);
+    let bricks = [
+        BrickData::<PaletteIndexValues>::Empty,
+        BrickData::Solid(42),
+        BrickData::parted(vec![1; 4 * 4 * 4]),
+        BrickData::parted((0..4 * 4 * 4).map(|i| i % 2).collect()),
+        BrickData::parted(many_values),
+        BrickData::parted(long_runs),
+    ];
+    for brick in bricks.iter() {
+        let compressed = Compressed(brick).to_bencode().ok().unwrap();
+        let deserialized = BrickData::<PaletteIndexValues>::from_bencode(&compressed)
+            .expect("Expected compressed brick to be deserialized");
+        assert!(deserialized == *brick);
+    }
+}
+
+#[test]
+fn test_compressed_brickdata_deserialize_corrupt() {
+    let compressed_brick = |len: usize, index_bits: u32, run_count: usize, packed: u8| {
+        let mut bytes = format!("l4:#zb#i{len}eli7eei{index_bits}ei2ei{run_count}e1:").into_bytes();
+        bytes.push(packed);
+        bytes.push(b'e');
+        BrickData::<PaletteIndexValues>::from_bencode(&bytes)
+    };
+
+    // One run of the only voxel in the palette, 4 voxels long
+    assert!(compressed_brick(4, 0, 1, 0b11).ok().unwrap() == BrickData::parted(vec![7; 4]));
+
+    // Palette index 1 is out of range
+    assert!(compressed_brick(4, 1, 1, 0b111).is_err());
+
+    // Runs add up to more, or less voxels than the brick length
+    assert!(compressed_brick(3, 0, 1, 0b11).is_err());
+    assert!(compressed_brick(5, 0, 1, 0b11).is_err());
+
+    // More runs than available in the packed bytes
+    assert!(compressed_brick(20, 0, 5, 0xFF).is_err());
+}
+
+#[test]
+fn test_boxtree_compressed_serialize() {
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    for x in 0..64 {
+        for y in 0..16 {
+            for z in 0..64 {
+                let color = if y < 8 || 0 == (x + y + z) % 3 {
+                    &red
+                } else {
+                    &blue
+                };
+                tree.insert(&V3c::new(x, y, z), color).ok().unwrap();
+            }
+        }
+    }
+
+    let compressed = tree.to_bytes_compressed();
+    assert!(compressed.len() < tree.to_bytes().len());
+
+    let deserialized = BoxTree::<u32>::try_from_bytes(&compressed)
+        .expect("Expected to be able to deserialize compressed tree");
+    assert_eq!(
+        deserialized.nodes.to_bencode().ok().unwrap(),
+        tree.nodes.to_bencode().ok().unwrap()
+    );
+
+    tree.save_compressed("test_junk_compressed_boxtree")
+        .ok()
+        .unwrap();
+    let loaded = BoxTree::<u32>::load("test_junk_compressed_boxtree");
+    std::fs::remove_file("test_junk_compressed_boxtree")
+        .ok()
+        .unwrap();
+    let loaded = loaded.expect("Expected to be able to load compressed tree");
+    for x in 0..64 {
+        for y in 0..16 {
+            for z in 0..64 {
+                let pos = V3c::new(x, y, z);
+                assert_eq!(loaded.get(&pos), tree.get(&pos), "Mismatch at {pos:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_boxtree_compressed_rejected_by_previous_versions() {
+    // Compressed bricks were introduced in 0.6.1
+    let previous_library = crate::Version {
+        major: 0,
+        minor: 6,
+        patch: 0,
+    };
+    let tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    let tree_version = BoxTree::<u32>::parse_version(&tree.to_bytes_compressed())
+        .ok()
+        .unwrap();
+    assert!(!previous_library.compatible(&tree_version));
+    assert!(crate::version().compatible(&tree_version));
+}
+
+#[test]
+fn test_serialize_with_compacted_palettes() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    for x in 0..16 {
+        let color: Albedo = (0x000000FF | (x << 8)).into();
+        tree.insert(&V3c::new(x, x, x), &color).ok().unwrap();
+    }
+    tree.fill_box(
+        &V3c::new(0, 0, 0),
+        &V3c::new(8, 8, 8),
+        &Albedo::from(0xFF0000FF),
+    )
+    .ok()
+    .unwrap();
+
+    tree.compact_palettes_on_save = true;
+    let deserialized = BoxTree::<u32>::try_from_bytes(&tree.to_bytes())
+        .ok()
+        .unwrap();
+    assert_eq!(17, tree.voxel_color_palette.len());
+    assert_eq!(9, deserialized.voxel_color_palette.len());
+    tree.save_paged("test_junk_compacted_paged_boxtree")
+        .ok()
+        .unwrap();
+    let paged = BoxTree::<u32>::load_paged("test_junk_compacted_paged_boxtree", usize::MAX)
+        .ok()
+        .unwrap();
+    assert_eq!(9, paged.voxel_color_palette.len());
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert_eq!(tree.get(&position), deserialized.get(&position));
+                assert_eq!(tree.get(&position), paged.get(&position));
+            }
+        }
+    }
+    std::fs::remove_file("test_junk_compacted_paged_boxtree")
+        .ok()
+        .unwrap();
+}
+
+#[test]
+fn test_serialize_with_wide_palette_indices() {
+    // Every voxel has a different color, which doesn't fit into narrow palette indices
+    let colors = (0..64 * 64 * 64)
+        .map(|i: u32| Albedo::from((i << 8) | 0xFF))
+        .collect::<Vec<_>>();
+    let color_index =
+        |position: &V3c<u32>| (position.x + position.y * 64 + position.z * 64 * 64) as usize;
+    let mut tree: BoxTree = BoxTree::from_fn(64, 4, |position| &colors[color_index(position)])
+        .ok()
+        .unwrap();
+    tree.insert(&V3c::new(1, 2, 3), (&colors[0], &70000))
+        .ok()
+        .unwrap();
+    assert_eq!(PaletteIndexWidth::Wide, tree.palette_index_width());
+    assert!(tree
+        .set_palette_index_width(PaletteIndexWidth::Narrow)
+        .is_err());
+
+    let deserialized = BoxTree::<u32>::try_from_bytes(&tree.to_bytes())
+        .ok()
+        .unwrap();
+    tree.save_paged("test_junk_wide_paged_boxtree")
+        .ok()
+        .unwrap();
+    let paged = BoxTree::<u32>::load_paged("test_junk_wide_paged_boxtree", usize::MAX)
+        .ok()
+        .unwrap();
+    assert_eq!(PaletteIndexWidth::Wide, deserialized.palette_index_width());
+    assert_eq!(PaletteIndexWidth::Wide, paged.palette_index_width());
+    for x in 0..64 {
+        for y in 0..64 {
+            for z in 0..64 {
+                let position = V3c::new(x, y, z);
+                let expected = tree.get(&position);
+                if position != V3c::new(1, 2, 3) {
+                    assert!(expected == (&colors[color_index(&position)]).into());
+                }
+                assert!(deserialized.get(&position) == expected);
+                assert!(paged.get(&position) == expected);
+            }
+        }
+    }
+    std::fs::remove_file("test_junk_wide_paged_boxtree")
+        .ok()
+        .unwrap();
+
+    // Narrow trees can be widened on demand
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    tree.insert(&V3c::new(1, 2, 3), &colors[5]).ok().unwrap();
+    assert_eq!(PaletteIndexWidth::Narrow, tree.palette_index_width());
+    tree.set_palette_index_width(PaletteIndexWidth::Wide)
+        .ok()
+        .unwrap();
+    let deserialized = BoxTree::<u32>::try_from_bytes(&tree.to_bytes())
+        .ok()
+        .unwrap();
+    assert_eq!(PaletteIndexWidth::Wide, deserialized.palette_index_width());
+    assert!(deserialized.get(&V3c::new(1, 2, 3)) == (&colors[5]).into());
+}
@@ -18,7 +18,7 @@ pub mod convert;
 pub mod raytracing;
 
 /// Library version
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Version {
     major: u32,
     minor: u32,
@@ -1,5 +1,7 @@
 use std::{
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    collections::VecDeque,
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex},
     vec::Vec,
 };
 
@@ -10,7 +12,7 @@ pub fn empty_marker<T: num_traits::Bounded>() -> T {
 #[cfg(feature = "bytecode")]
 use bendy::{
     decoding::{FromBencode, Object},
-    encoding::{Error as BencodeError, SingleItemEncoder, ToBencode},
+    encoding::{Encoder, Error as BencodeError, SingleItemEncoder, ToBencode},
 };
 
 //####################################################################################
@@ -31,29 +33,420 @@ use bendy::{
 //  █████        ░░░███████░   ░░░███████░   ███████████
 //####################################################################################
 
+/// Number of items stored together in a single chunk of an ObjectPool
+const CHUNK_SIZE: usize = 64;
+
+/// Number of bits addressing an entry inside a single level of the chunk table of an ObjectPool
+const TABLE_LEVEL_BITS: usize = 6;
+
+/// Number of entries inside a single level of the chunk table of an ObjectPool
+const TABLE_LEVEL_SIZE: usize = 1 << TABLE_LEVEL_BITS;
+
 /// Data required for the operation of ObjectPool
 #[derive(Clone)]
 struct ObjectPoolMetaData {
-    /// Array of "reserved" state for stored elements
-    items_reserved: Vec<bool>,
-
     /// Index of the first available item
     first_available: usize,
 
-    /// The Capacity of the ObjectPool buffer vector
-    /// aligns to Vec::capacity
+    /// The initial capacity of the ObjectPool
     capacity: usize,
 }
 
+/// A fixed number of items in an ObjectPool, stored next to each other
+/// Chunks are shared between copies of the pool, and only copied once they are modified in one of them
+#[derive(Clone)]
+struct Chunk<T> {
+    /// The @CHUNK_SIZE items inside the chunk
+    items: Box<[T]>,
+
+    /// The "reserved" state of each item inside the chunk, one bit for each
+    reserved: u64,
+
+    /// For paged pools: one bit for each item which is stored inside the chunk,
+    /// instead of being available from the backing storage
+    stored: u64,
+}
+
+impl<T: Default> Default for Chunk<T> {
+    fn default() -> Self {
+        Chunk {
+            items: (0..CHUNK_SIZE).map(|_| T::default()).collect(),
+            reserved: 0,
+            stored: 0,
+        }
+    }
+}
+
+/// A single level inside the chunk table of an ObjectPool, with at most @TABLE_LEVEL_SIZE entries
+#[derive(Clone)]
+enum TableLevel<T> {
+    /// The lowest level of the table, containing the chunks
+    Chunks(Vec<Arc<Chunk<T>>>),
+
+    /// Any level above the lowest one, containing the levels below it
+    Levels(Vec<Arc<TableLevel<T>>>),
+}
+
+/// Persistent table of the chunks inside an ObjectPool
+/// Copies of the table share each of its levels until they are modified,
+/// so modifying a chunk in a copy only copies the levels on the path to the chunk
+#[derive(Clone)]
+struct ChunkTable<T> {
+    /// The highest level of the table
+    root: Arc<TableLevel<T>>,
+
+    /// The number of levels above the lowest one
+    height: usize,
+
+    /// The number of chunks inside the table
+    len: usize,
+}
+
+/// The reason an item of a paged ObjectPool could not be loaded from its backing storage
+pub(crate) type PageError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Loads the outline of an item of the pool from its backing storage:
+/// the item without the parts which are loaded on demand by a `PageCompletion`
+pub(crate) type PageSource<T> = dyn Fn(usize) -> Result<T, PageError> + Send + Sync;
+
+/// Loads the parts of an item left out from its outline by the `PageSource`
+/// The item is discarded in case of an error, so it can be modified freely
+pub(crate) type PageCompletion<T> = dyn Fn(usize, &mut T) -> Result<(), PageError> + Send + Sync;
+
+/// Residency information of a single item of the backing storage of a paged ObjectPool
+#[derive(Clone)]
+struct PageSlot<T> {
+    /// The item as loaded from the backing storage, while it is available in memory
+    loaded: Option<Arc<T>>,
+
+    /// True if the loaded item is not only an outline
+    complete: bool,
+
+    /// True while the item is being loaded by a reader, other readers wait for it to finish
+    loading: bool,
+
+    /// True if the last load of the item failed, it is loaded again on the next access
+    failed: bool,
+
+    /// Set on every access, gives the item a second chance before eviction
+    referenced: bool,
+
+    /// The estimated memory usage of the item when loaded
+    size: usize,
+}
+
+struct PagerState<T> {
+    /// Residency of each item in the backing storage
+    slots: Vec<PageSlot<T>>,
+
+    /// Candidates for eviction in the order of loading
+    clock: VecDeque<usize>,
+
+    /// The estimated memory usage of the loaded items
+    memory_used: usize,
+
+    /// Errors of the items which failed to load since they were last taken
+    errors: Vec<PageError>,
+}
+
+/// Loads items of an ObjectPool on demand, and evicts them when a memory budget is exceeded
+/// Loaded items are kept outside of the pool until they are modified, so they can be
+/// provided to readers without mutable access to the pool, and shared between its copies
+/// Items which can not be loaded are provided with their default value, or without the parts which
+/// could not be loaded; they are not kept in memory, and the error is kept until it is taken
+/// The backing storage is read without locking the state, so readers of other items are not blocked
+pub(crate) struct Pager<T> {
+    /// Provides the outline of the item under the given key from the backing storage
+    source: Box<PageSource<T>>,
+
+    /// Loads the rest of an item outline from the backing storage
+    completion: Box<PageCompletion<T>>,
+
+    /// Estimates the memory usage of an item
+    size_of: fn(&T) -> usize,
+
+    /// The memory usage above which items are evicted
+    memory_budget: usize,
+
+    state: Mutex<PagerState<T>>,
+
+    /// Notifies readers waiting for an item loaded by another reader
+    load_finished: Condvar,
+}
+
+/// Read access to an item inside an ObjectPool
+pub(crate) enum ItemRef<'a, T> {
+    /// The item is stored inside the pool
+    Stored(&'a T),
+
+    /// The item is loaded from the backing storage of a paged pool
+    Loaded(Arc<T>),
+}
+
+impl<T> Deref for ItemRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            ItemRef::Stored(item) => item,
+            ItemRef::Loaded(item) => item,
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ItemRef<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
 /// Stores re-usable objects to eliminate data allocation overhead when inserting and removing Nodes
-/// It keeps track of different buffers for different levels in the graph, allocating more space initially to lower levels
+/// Reading an item only requires shared access to the pool, while modifying it requires
+/// exclusive access, so any number of readers or a single writer might use the pool at once
+/// Copies of the pool share their chunks until they are modified, so cloning the pool is cheap,
+/// and modifying an item of a copy only copies the chunk containing it, and the table entries leading to it
 #[derive(Clone)]
 pub(crate) struct ObjectPool<T> {
-    /// Pool of objects to be reused
-    buffer: Vec<Arc<RwLock<T>>>,
+    /// Pool of objects to be reused, in chunks of @CHUNK_SIZE items
+    chunks: ChunkTable<T>,
+
+    /// The number of items in the pool, including the ones not reserved
+    len: usize,
 
     /// Statistics about the stored data
     meta: ObjectPoolMetaData,
+
+    /// Loads items on demand for pools backed by storage
+    pager: Option<Arc<Pager<T>>>,
+}
+
+impl<T: Clone> ChunkTable<T> {
+    fn new() -> Self {
+        ChunkTable {
+            root: Arc::new(TableLevel::Chunks(Vec::new())),
+            height: 0,
+            len: 0,
+        }
+    }
+
+    /// The index of the entry inside the level at the given height, on the path to the given chunk
+    fn entry_index(chunk_index: usize, height: usize) -> usize {
+        (chunk_index >> (height * TABLE_LEVEL_BITS)) % TABLE_LEVEL_SIZE
+    }
+
+    fn get(&self, chunk_index: usize) -> &Chunk<T> {
+        let mut level = &*self.root;
+        let mut height = self.height;
+        loop {
+            let entry = Self::entry_index(chunk_index, height);
+            match level {
+                TableLevel::Chunks(chunks) => return &chunks[entry],
+                TableLevel::Levels(levels) => {
+                    level = &levels[entry];
+                    height -= 1;
+                }
+            }
+        }
+    }
+
+    /// Provides mutable access to the given chunk,
+    /// copying it and the levels above it in case they are shared with other tables
+    fn get_mut(&mut self, chunk_index: usize) -> &mut Chunk<T> {
+        let mut level = Arc::make_mut(&mut self.root);
+        let mut height = self.height;
+        loop {
+            let entry = Self::entry_index(chunk_index, height);
+            match level {
+                TableLevel::Chunks(chunks) => return Arc::make_mut(&mut chunks[entry]),
+                TableLevel::Levels(levels) => {
+                    level = Arc::make_mut(&mut levels[entry]);
+                    height -= 1;
+                }
+            }
+        }
+    }
+
+    /// Adds the given chunk to the end of the table
+    fn push(&mut self, chunk: Chunk<T>) {
+        if self.len == TABLE_LEVEL_SIZE.pow(self.height as u32 + 1) {
+            // Every level is full, the table grows by a new level on top
+            let root = std::mem::replace(&mut self.root, Arc::new(TableLevel::Levels(Vec::new())));
+            self.root = Arc::new(TableLevel::Levels(vec![root]));
+            self.height += 1;
+        }
+
+        let chunk_index = self.len;
+        let mut level = Arc::make_mut(&mut self.root);
+        let mut height = self.height;
+        loop {
+            let entry = Self::entry_index(chunk_index, height);
+            match level {
+                TableLevel::Chunks(chunks) => {
+                    chunks.push(Arc::new(chunk));
+                    break;
+                }
+                TableLevel::Levels(levels) => {
+                    if entry == levels.len() {
+                        levels.push(Arc::new(if 1 == height {
+                            TableLevel::Chunks(Vec::new())
+                        } else {
+                            TableLevel::Levels(Vec::new())
+                        }));
+                    }
+                    level = Arc::make_mut(&mut levels[entry]);
+                    height -= 1;
+                }
+            }
+        }
+        self.len += 1;
+    }
+
+    /// The estimated number of bytes the table takes up, including the chunks
+    /// Memory shared with copies of the table is divided between them
+    fn memory_usage(&self) -> usize {
+        Self::level_memory_usage(&self.root, 1)
+    }
+
+    /// The estimated number of bytes the given level and the levels below it take up
+    /// * `shared_by` - The number of tables sharing the levels above the given one
+    fn level_memory_usage(level: &Arc<TableLevel<T>>, shared_by: usize) -> usize {
+        // Every level and chunk is allocated behind an Arc, along with its strong and weak reference counters
+        let arc_counters_size = 2 * std::mem::size_of::<usize>();
+        let shared_by = shared_by * Arc::strong_count(level);
+        let (entries_size, below_size) = match &**level {
+            TableLevel::Chunks(chunks) => (
+                chunks.capacity() * std::mem::size_of::<Arc<Chunk<T>>>(),
+                chunks
+                    .iter()
+                    .map(|chunk| {
+                        (std::mem::size_of::<Chunk<T>>()
+                            + arc_counters_size
+                            + CHUNK_SIZE * std::mem::size_of::<T>())
+                            / (shared_by * Arc::strong_count(chunk))
+                    })
+                    .sum::<usize>(),
+            ),
+            TableLevel::Levels(levels) => (
+                levels.capacity() * std::mem::size_of::<Arc<TableLevel<T>>>(),
+                levels
+                    .iter()
+                    .map(|child| Self::level_memory_usage(child, shared_by))
+                    .sum::<usize>(),
+            ),
+        };
+        (std::mem::size_of::<TableLevel<T>>() + arc_counters_size + entries_size) / shared_by
+            + below_size
+    }
+}
+
+impl<T: Default + Clone> Pager<T> {
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, PagerState<T>> {
+        self.state
+            .lock()
+            .expect("Expected to be able to lock ObjectPool pager")
+    }
+
+    /// Provides the item under the given key, loading it from the backing storage if needed
+    fn read(&self, key: usize) -> Arc<T> {
+        self.load(key, true)
+    }
+
+    /// Provides the item under the given key, loading only its outline from the backing storage if needed
+    fn read_outline(&self, key: usize) -> Arc<T> {
+        self.load(key, false)
+    }
+
+    fn load(&self, key: usize, complete: bool) -> Arc<T> {
+        // Reserve the slot, unless the item is already available
+        let mut state = self.lock_state();
+        let outline = loop {
+            let slot = &mut state.slots[key];
+            slot.referenced = true;
+            match &slot.loaded {
+                Some(item) if slot.complete || !complete => return item.clone(),
+                _ if slot.loading => {
+                    state = self
+                        .load_finished
+                        .wait(state)
+                        .expect("Expected to be able to lock ObjectPool pager");
+                }
+                outline => break outline.clone(),
+            }
+        };
+        state.slots[key].loading = true;
+        drop(state);
+
+        // Read the backing storage without blocking other readers
+        let loaded = match outline {
+            Some(outline) => Ok(outline),
+            None => (self.source)(key).map(Arc::new),
+        };
+        let loaded = match loaded {
+            Ok(item) if complete => {
+                let mut completed = (*item).clone();
+                match (self.completion)(key, &mut completed) {
+                    Ok(()) => Ok(Arc::new(completed)),
+                    // The outline is still provided without the parts which could not be loaded
+                    Err(err) => Err((Some(item), err)),
+                }
+            }
+            Ok(item) => Ok(item),
+            Err(err) => Err((None, err)),
+        };
+
+        // Publish the result
+        let mut guard = self.lock_state();
+        let state = &mut *guard;
+        state.slots[key].loading = false;
+        self.load_finished.notify_all();
+        let item = match loaded {
+            Ok(item) => item,
+            Err((outline, err)) => {
+                if !state.slots[key].failed {
+                    state.errors.push(err);
+                }
+                state.slots[key].failed = true;
+                return outline.unwrap_or_default();
+            }
+        };
+        let slot = &mut state.slots[key];
+        slot.failed = false;
+        match slot.loaded.replace(item.clone()) {
+            Some(_) => state.memory_used -= slot.size,
+            None => state.clock.push_back(key),
+        }
+        slot.complete = complete;
+        slot.size = (self.size_of)(&item);
+        state.memory_used += slot.size;
+        self.evict(state, key);
+        item
+    }
+
+    /// Evicts items until the memory budget is satisfied, or no more items can be evicted
+    /// Items currently in use by readers are released once they are not used anymore
+    fn evict(&self, state: &mut PagerState<T>, keep: usize) {
+        // Every candidate is visited at most twice: once to clear its referenced flag, once to evict it
+        let mut candidates_left = state.clock.len() * 2;
+        while state.memory_used > self.memory_budget && 0 < candidates_left {
+            candidates_left -= 1;
+            let Some(key) = state.clock.pop_front() else {
+                break;
+            };
+            let slot = &mut state.slots[key];
+            if slot.loaded.is_none() {
+                // Not an eviction candidate anymore
+                continue;
+            }
+            if key == keep || slot.referenced {
+                slot.referenced = false;
+                state.clock.push_back(key);
+                continue;
+            }
+            slot.loaded = None;
+            slot.complete = false;
+            state.memory_used -= slot.size;
+        }
+    }
 }
 
 #[cfg(feature = "bytecode")]
@@ -63,15 +456,30 @@ where
 {
     const MAX_DEPTH: usize = 8;
     fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        self.encode_with(encoder, |item, e| e.emit(item))
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl<T> ObjectPool<T>
+where
+    T: Default + Clone,
+{
+    /// Serializes the pool, while the items in use are written out by the given function
+    pub(crate) fn encode_with(
+        &self,
+        encoder: SingleItemEncoder,
+        emit_item: impl Fn(&T, &mut Encoder) -> Result<(), BencodeError>,
+    ) -> Result<(), BencodeError> {
         encoder.emit_list(|e| {
             e.emit(self.meta.capacity)?;
-            for index in 0..self.buffer.len() {
-                if self.meta.items_reserved[index] {
+            for index in 0..self.len {
+                if self.key_is_valid(index) {
                     // Item is in use, write it out!
-                    let item = self.buffer[index]
-                        .read()
-                        .expect("Expected to be able to read ObjectPool buffer");
-                    e.emit(item.clone())?;
+                    emit_item(&self.get(index), e)?;
+                } else {
+                    // Freed items keep their place, so the keys stay valid after loading
+                    e.emit("#f")?;
                 }
             }
             // Emit list end token
@@ -89,54 +497,46 @@ where
     fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
         match data {
             Object::List(mut list) => {
-                let capacity = match list.next_object()?.unwrap() {
-                    Object::Integer(i) => Ok(i.parse::<usize>().ok().unwrap()),
+                let capacity = match list
+                    .next_object()?
+                    .ok_or_else(|| bendy::decoding::Error::missing_field("ObjectPool capacity"))?
+                {
+                    Object::Integer(i) => Ok(i.parse::<usize>()?),
                     _ => Err(bendy::decoding::Error::unexpected_token(
                         "ObjectPool int field capacity",
                         "Something else",
                     )),
                 }?;
-                let mut items_reserved = Vec::with_capacity(capacity);
-                let mut buffer = Vec::with_capacity(capacity);
+                // Capacity is only an initial estimation, the item count may exceed it
+                let mut pool = ObjectPool::with_capacity(capacity);
                 loop {
-                    let next_object = list
-                        .next_object()?
-                        .expect("Expected another object within ObjectPool");
+                    let next_object = list.next_object()?.ok_or_else(|| {
+                        bendy::decoding::Error::missing_field("ObjectPool end token")
+                    })?;
                     match next_object {
-                        Object::Bytes(b) => {
+                        Object::Bytes(b"#f") => {
+                            // A freed item
+                            pool.push_item(None);
+                        }
+                        Object::Bytes(b"#") => {
                             // A token means the end of the object stream
-                            debug_assert!(matches!(
-                                String::from_utf8(b.to_vec())
-                                    .unwrap_or("".to_string())
-                                    .as_str(),
-                                "#"
-                            ));
                             break;
                         }
+                        Object::Bytes(b) => {
+                            return Err(bendy::decoding::Error::unexpected_token(
+                                "ObjectPool end token # or freed item token #f",
+                                String::from_utf8(b.to_vec()).unwrap_or("".to_string()),
+                            ));
+                        }
                         _ => {
-                            buffer.push(Arc::new(RwLock::new(T::decode_bencode_object(
-                                next_object,
-                            )?)));
-                            items_reserved.push(true);
+                            pool.push_item(Some(T::decode_bencode_object(next_object)?));
                         }
                     }
-                    if items_reserved.len() >= capacity {
-                        debug_assert!(
-                            false,
-                            "More items in ObjectPool than the stored capacity of {capacity}"
-                        );
-                        break;
-                    }
                 }
-
-                Ok(Self {
-                    meta: ObjectPoolMetaData {
-                        first_available: items_reserved.len(),
-                        items_reserved,
-                        capacity,
-                    },
-                    buffer,
-                })
+                pool.meta.first_available = (0..pool.len)
+                    .position(|key| !pool.key_is_valid(key))
+                    .unwrap_or(pool.len);
+                Ok(pool)
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
                 "List of ObjectPool<T> fields",
@@ -154,26 +554,136 @@ where
     /// Create Objectpool with given capacity
     pub(crate) fn with_capacity(capacity: usize) -> Self {
         ObjectPool {
-            buffer: Vec::with_capacity(capacity),
+            chunks: ChunkTable::new(),
+            len: 0,
             meta: ObjectPoolMetaData {
                 capacity,
-                items_reserved: Vec::with_capacity(capacity),
                 first_available: 0,
             },
+            pager: None,
+        }
+    }
+
+    /// Create Objectpool where the items are loaded on demand from a backing storage
+    /// * `items_reserved` - The reserved state of each item in the backing storage
+    /// * `source` - Provides the outline of the item under the given key from the backing storage
+    /// * `completion` - Loads the rest of an item outline, once the whole item is accessed
+    /// * `size_of` - Estimates the memory usage of an item
+    /// * `memory_budget` - Loaded items are evicted above this memory usage, unless modified
+    pub(crate) fn paged(
+        items_reserved: Vec<bool>,
+        source: Box<PageSource<T>>,
+        completion: Box<PageCompletion<T>>,
+        size_of: fn(&T) -> usize,
+        memory_budget: usize,
+    ) -> Self {
+        let mut pool = ObjectPool::with_capacity(items_reserved.len());
+        for reserved in items_reserved.iter() {
+            pool.push_item(None);
+            if *reserved {
+                // Reserved items are available from the backing storage
+                let (chunk_index, bit) = Self::chunk_position(pool.len - 1);
+                let chunk = pool.chunks.get_mut(chunk_index);
+                chunk.reserved |= bit;
+                chunk.stored &= !bit;
+            }
+        }
+        pool.meta.first_available = items_reserved
+            .iter()
+            .position(|reserved| !reserved)
+            .unwrap_or(items_reserved.len());
+        pool.pager = Some(Arc::new(Pager {
+            source,
+            completion,
+            size_of,
+            memory_budget,
+            state: Mutex::new(PagerState {
+                slots: (0..items_reserved.len())
+                    .map(|_| PageSlot {
+                        loaded: None,
+                        complete: false,
+                        loading: false,
+                        failed: false,
+                        referenced: false,
+                        size: 0,
+                    })
+                    .collect(),
+                clock: VecDeque::new(),
+                memory_used: 0,
+                errors: Vec::new(),
+            }),
+            load_finished: Condvar::new(),
+        }));
+        pool
+    }
+
+    /// The number of items currently available in memory
+    pub(crate) fn loaded_count(&self) -> usize {
+        match &self.pager {
+            Some(pager) => {
+                let state = pager.lock_state();
+                (0..self.len)
+                    .filter(|key| {
+                        self.key_is_valid(*key)
+                            && (self.is_stored(*key) || state.slots[*key].loaded.is_some())
+                    })
+                    .count()
+            }
+            None => (0..self.len).filter(|key| self.key_is_valid(*key)).count(),
+        }
+    }
+
+    /// The index of the chunk containing the given key, and the bit of the key inside the chunk
+    fn chunk_position(key: usize) -> (usize, u64) {
+        (key / CHUNK_SIZE, 0x01 << (key % CHUNK_SIZE))
+    }
+
+    /// True if the item under the given key is stored inside the pool,
+    /// instead of being available from the backing storage
+    fn is_stored(&self, key: usize) -> bool {
+        let (chunk_index, bit) = Self::chunk_position(key);
+        0 != (self.chunks.get(chunk_index).stored & bit)
+    }
+
+    /// Adds a new item to the end of the pool, reserved if it contains a value
+    fn push_item(&mut self, item: Option<T>) {
+        let (chunk_index, bit) = Self::chunk_position(self.len);
+        if chunk_index == self.chunks.len {
+            self.chunks.push(Chunk::default());
+        }
+        let chunk = self.chunks.get_mut(chunk_index);
+        chunk.stored |= bit;
+        if let Some(item) = item {
+            chunk.items[self.len % CHUNK_SIZE] = item;
+            chunk.reserved |= bit;
+        }
+        self.len += 1;
+    }
+
+    /// Sets the reserved state of the item under the given key
+    fn set_reserved(&mut self, key: usize, reserved: bool) {
+        let (chunk_index, bit) = Self::chunk_position(key);
+        let chunk = self.chunks.get_mut(chunk_index);
+        if reserved {
+            // Re-used items are only available in memory, their previous contents are discarded
+            chunk.reserved |= bit;
+            chunk.stored |= bit;
+        } else {
+            chunk.reserved &= !bit;
         }
     }
 
     /// pushes the next available marker into the next node, in case it's not reserved
     /// Returns true if first_available index points to available node
     fn try_set_next_available(&mut self) -> bool {
-        if (self.meta.first_available + 1) < self.buffer.len()
-            && !self.meta.items_reserved[self.meta.first_available]
+        if (self.meta.first_available + 1) < self.len
+            && !self.key_is_valid(self.meta.first_available)
         {
             return true;
         }
 
-        if (self.meta.first_available + 1) < self.buffer.len()
-            && !self.meta.items_reserved[self.meta.first_available + 1]
+        if (self.meta.first_available + 1) < self.len
+            && !self.key_is_valid(self.meta.first_available + 1)
         {
             self.meta.first_available += 1;
             return true;
@@ -184,7 +694,13 @@ where
 
     /// Length of the ObjectPool
     pub(crate) fn len(&self) -> usize {
-        self.buffer.len()
+        self.len
+    }
+
+    /// The estimated number of bytes the pool buffers take up, without the memory owned by the items
+    /// Memory shared with copies of the pool is divided between them
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.chunks.memory_usage()
     }
 
     /// Pushes the given item into the pool
@@ -198,24 +714,13 @@ where
     pub(crate) fn allocate(&mut self) -> usize {
         let key = if self.try_set_next_available() {
             let first_available = self.meta.first_available;
-            self.meta.items_reserved[first_available] = true;
+            self.set_reserved(first_available, true);
             self.try_set_next_available();
             first_available
         } else {
-            debug_assert_eq!(self.meta.items_reserved.len(), self.buffer.len());
-
-            // reserve place for additional items, but
-            // reserve less additional items for larger buffers
-            let x = self.buffer.len().max(10) as f32;
-            let new_item_count = ((100. * x.log10().powf(2.)) / x) as usize;
-
-            self.buffer.reserve(new_item_count);
-            self.meta.items_reserved.reserve(new_item_count);
-
             // mark item as reserved and return with the key
-            self.meta.items_reserved.push(true);
-            self.buffer.push(Arc::default());
-            self.buffer.len() - 1
+            self.push_item(Some(T::default()));
+            self.len - 1
         };
         self.try_set_next_available();
         key
@@ -223,19 +728,15 @@ where
 
     /// Returns the ownership of the item under the given key from the pool
     pub(crate) fn pop(&mut self, key: usize) -> T {
-        debug_assert!(self.key_is_valid(key));
-        let mut item = self.buffer[key]
-            .write()
-            .expect("Expected to be able to update ReusableItem in Object pool");
-
-        self.meta.items_reserved[key] = false;
+        let item = std::mem::take(self.get_mut(key));
+        self.set_reserved(key, false);
         self.meta.first_available = self.meta.first_available.min(key);
-        std::mem::take(&mut item)
+        item
     }
 
     pub(crate) fn free(&mut self, key: usize) -> bool {
         if self.key_is_valid(key) {
-            self.meta.items_reserved[key] = false;
+            self.set_reserved(key, false);
             self.meta.first_available = self.meta.first_available.min(key);
             true
         } else {
@@ -243,32 +744,95 @@ where
         }
     }
 
-    pub(crate) fn get(&self, key: usize) -> RwLockReadGuard<T> {
+    pub(crate) fn get(&self, key: usize) -> ItemRef<'_, T> {
         debug_assert!(self.key_is_valid(key));
-        self.buffer[key]
-            .read()
-            .expect("Expected to be able to read ReusableItem in Object pool")
+        if let Some(pager) = &self.pager
+            && !self.is_stored(key)
+        {
+            return ItemRef::Loaded(pager.read(key));
+        }
+        ItemRef::Stored(&self.chunks.get(key / CHUNK_SIZE).items[key % CHUNK_SIZE])
+    }
+
+    /// Provides the item under the given key, which for paged pools might only be an outline,
+    /// see `PageSource`. Cheaper than `get` when the parts loaded on demand are not needed
+    pub(crate) fn get_outline(&self, key: usize) -> ItemRef<'_, T> {
+        debug_assert!(self.key_is_valid(key));
+        if let Some(pager) = &self.pager
+            && !self.is_stored(key)
+        {
+            return ItemRef::Loaded(pager.read_outline(key));
+        }
+        ItemRef::Stored(&self.chunks.get(key / CHUNK_SIZE).items[key % CHUNK_SIZE])
+    }
+
+    /// Takes the errors of the items which could not be loaded from the backing storage,
+    /// since the last call. Every failing item is reported once, until it is loaded successfully
+    pub(crate) fn take_load_errors(&self) -> Vec<PageError> {
+        match &self.pager {
+            Some(pager) => std::mem::take(&mut pager.lock_state().errors),
+            None => Vec::new(),
+        }
+    }
+
+    /// The keys of the items whose last load from the backing storage failed,
+    /// including the ones modified since, as they were modified without their original content
+    pub(crate) fn failed_keys(&self) -> Vec<usize> {
+        match &self.pager {
+            Some(pager) => {
+                let state = pager.lock_state();
+                (0..self.len)
+                    .filter(|key| self.key_is_valid(*key) && state.slots[*key].failed)
+                    .collect()
+            }
+            None => Vec::new(),
+        }
     }
 
-    pub(crate) fn get_mut(&mut self, key: usize) -> RwLockWriteGuard<T> {
+    pub(crate) fn get_mut(&mut self, key: usize) -> &mut T {
         debug_assert!(self.key_is_valid(key));
-        self.buffer[key]
-            .write()
-            .expect("Expected to be able to update ReusableItem in Object pool")
+        // Modified items are not available in the backing storage anymore
+        self.store(key);
+        // The chunk is copied in case it is shared with other copies of the pool
+        &mut self.chunks.get_mut(key / CHUNK_SIZE).items[key % CHUNK_SIZE]
+    }
+
+    /// Moves the item under the given key inside the pool,
+    /// in case it is only available from the backing storage
+    fn store(&mut self, key: usize) {
+        let Some(pager) = &self.pager else {
+            return;
+        };
+        if !self.key_is_valid(key) || self.is_stored(key) {
+            return;
+        }
+        let item = pager.read(key);
+        let (chunk_index, bit) = Self::chunk_position(key);
+        let chunk = self.chunks.get_mut(chunk_index);
+        chunk.items[key % CHUNK_SIZE] = (*item).clone();
+        chunk.stored |= bit;
     }
 
     pub(crate) fn swap(&mut self, src: usize, dst: usize) {
-        self.buffer.swap(src, dst);
+        // Both items are stored inside the pool, so they can be moved freely
+        self.store(src);
+        self.store(dst);
+        let src_item = std::mem::take(self.get_mut(src));
+        let dst_item = std::mem::replace(self.get_mut(dst), src_item);
+        *self.get_mut(src) = dst_item;
     }
 
     pub(crate) fn key_is_valid(&self, key: usize) -> bool {
-        key < self.buffer.len() && self.meta.items_reserved[key]
+        key < self.len && {
+            let (chunk_index, bit) = Self::chunk_position(key);
+            0 != (self.chunks.get(chunk_index).reserved & bit)
+        }
     }
 }
 
 #[cfg(test)]
 mod object_pool_tests {
-    use crate::object_pool::ObjectPool;
+    use crate::object_pool::{ObjectPool, CHUNK_SIZE};
     use rayon::prelude::*;
     use std::sync::{Arc, RwLock};
 
@@ -322,6 +886,66 @@ mod object_pool_tests {
         assert!(*pool.get(key_1) == test_value * 3.); // the original key is reused to hold the latest value
     }
 
+    #[test]
+    fn test_clone_keeps_items_modified_in_original() {
+        let mut pool = ObjectPool::<f32>::with_capacity(3);
+        let key = pool.push(5.);
+        let copy = pool.clone();
+        *pool.get_mut(key) = 10.;
+        assert_eq!(*pool.get(key), 10.);
+        assert_eq!(*copy.get(key), 5.);
+    }
+
+    #[test]
+    fn test_clone_shares_unmodified_chunks() {
+        let mut pool = ObjectPool::<u32>::with_capacity(3);
+        let keys = (0..10_000).map(|i| pool.push(i)).collect::<Vec<_>>();
+        let copy = pool.clone();
+        *pool.get_mut(keys[150]) = 1000;
+        for key in keys.iter() {
+            // Only the chunk of the modified item is copied
+            assert_eq!(
+                key / CHUNK_SIZE != keys[150] / CHUNK_SIZE,
+                std::ptr::eq(&*pool.get(*key), &*copy.get(*key))
+            );
+        }
+        assert_eq!(*copy.get(keys[150]), 150);
+        assert_eq!(*pool.get(keys[150]), 1000);
+    }
+
+    #[test]
+    fn test_memory_usage_is_divided_between_clones() {
+        let mut pool = ObjectPool::<u32>::with_capacity(3);
+        let keys = (0..10_000).map(|i| pool.push(i)).collect::<Vec<_>>();
+        let usage = pool.memory_usage();
+        let copy = pool.clone();
+        assert!(pool.memory_usage() <= usage / 2);
+        assert!(pool.memory_usage() + copy.memory_usage() <= usage);
+
+        // Modifying an item only adds the memory of a single chunk, and the table levels above it
+        *pool.get_mut(keys[150]) = 1000;
+        assert!(pool.memory_usage() + copy.memory_usage() < usage + usage / 10);
+        drop(copy);
+        assert!(usage - usage / 10 < pool.memory_usage());
+    }
+
+    #[test]
+    fn test_multithreaded_read_without_locks() {
+        let item_count = 100;
+        let mut pool = ObjectPool::<u32>::with_capacity(item_count);
+        let keys = (0..item_count)
+            .map(|i| pool.push(i as u32))
+            .collect::<Vec<_>>();
+
+        // Readers only need shared access to the pool
+        let pool = &pool;
+        let sum: u32 = (0..item_count * 10)
+            .into_par_iter()
+            .map(|i| *pool.get(keys[i % item_count]))
+            .sum();
+        assert_eq!(sum, 10 * (0..item_count as u32).sum::<u32>());
+    }
+
     #[test]
     fn test_singlethreaded_insert() {
         let item_count = 10;
@@ -9,7 +9,7 @@ pub use crate::raytracing::bevy::types::{
 use crate::{
     boxtree::{
         types::{BoxTreeNodeAccessStack, BoxTreeUpdatedSignalParams},
-        Albedo, BoxTree, V3c, VoxelData,
+        Albedo, BoxTree, BoxTreeSnapshot, DefragmentOrder, SharedBoxTree, V3c, VoxelData,
     },
     raytracing::bevy::{
         pipeline::prepare_bind_groups,
@@ -30,7 +30,7 @@ use bevy::{
     tasks::AsyncComputeTaskPool,
 };
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockResult},
 };
 
@@ -121,14 +121,12 @@ fn handle_viewport_position_updates<T: VoxelData>(
                     let viewport_center = view.spyglass.viewport.origin;
                     let viewing_distance = view.spyglass.viewport.frustum.z;
                     let brick_ownership = view.data_handler.upload_targets.brick_ownership.clone();
-                    let tree_arc = tree_host.tree.clone();
+                    let tree = tree_host.tree();
                     let nodes_to_see = view.data_handler.upload_targets.nodes_to_see.clone();
                     commands.insert_resource(UploadQueueUpdateTask(thread_pool.spawn(
                         async move {
                             rebuild::<T>(
-                                &tree_arc
-                                    .read()
-                                    .expect("Expected to be able to read tree from GPU host"),
+                                &tree,
                                 viewport_center,
                                 viewing_distance,
                                 brick_ownership,
@@ -145,11 +143,7 @@ fn handle_viewport_position_updates<T: VoxelData>(
                 }
                 view.brick_slot = Cube::brick_slot_for(
                     &view.spyglass.viewport.origin,
-                    tree_host
-                        .tree
-                        .read()
-                        .expect("Expected to be able to read tree from GPU host")
-                        .brick_dim,
+                    tree_host.tree().brick_dim,
                 );
             }
 
@@ -162,8 +156,8 @@ fn handle_viewport_position_updates<T: VoxelData>(
 
 impl<T: VoxelData> BoxTreeGPUHost<T> {
     pub fn new(mut tree: BoxTree<T>) -> Self {
-        let changes_buffer: Arc<RwLock<VecDeque<BoxTreeUpdatedSignalParams>>> = Arc::default();
-        let changes_arc = changes_buffer.clone();
+        let pending_changes: Arc<RwLock<VecDeque<BoxTreeUpdatedSignalParams>>> = Arc::default();
+        let changes_arc = pending_changes.clone();
         tree.update_triggers.push(Arc::new(
             move |node_stack: BoxTreeNodeAccessStack, updated_sectants: Vec<u8>| {
                 changes_arc
@@ -173,10 +167,75 @@ impl<T: VoxelData> BoxTreeGPUHost<T> {
             },
         ));
         BoxTreeGPUHost {
-            tree: Arc::new(RwLock::new(tree)),
-            changes_buffer,
+            tree: SharedBoxTree::new(tree),
+            pending_changes,
+            changes_buffer: Arc::default(),
         }
     }
+
+    /// Provides the state of the hosted tree after the latest finished edit
+    pub fn tree(&self) -> Arc<BoxTreeSnapshot<T>> {
+        self.tree.read()
+    }
+
+    /// Edits the hosted tree with the given function, the views display the result once it's finished
+    /// Views keep streaming the previous state of the tree while the edit is in progress
+    pub fn edit<R>(&self, edit: impl FnOnce(&mut BoxTree<T>) -> R) -> R {
+        self.tree.edit_then(edit, |_| {
+            // Updates are only processed once the edited tree is visible to the views
+            let pending = std::mem::take(
+                &mut *self
+                    .pending_changes
+                    .write()
+                    .expect("Expected to be able to update BoxTree changes buffer"),
+            );
+            self.changes_buffer
+                .write()
+                .expect("Expected to be able to update BoxTree changes buffer")
+                .extend(pending);
+        })
+    }
+
+    /// Defragments the hosted tree, see @BoxTree::defragment
+    /// Every view in the given viewset is reloaded, as the node keys they use might have changed
+    /// Updates not yet processed by the views are kept, using the new node keys
+    /// * `viewset` - The views displaying the hosted tree
+    /// * `order` - The order of the nodes in the rebuilt storage
+    /// * Returns with the new key of every node, by its previous key
+    pub fn defragment(
+        &self,
+        viewset: &mut VhxViewSet,
+        order: DefragmentOrder,
+    ) -> HashMap<usize, usize> {
+        // The changes buffer is locked until its keys are updated, so views never see
+        // the defragmented tree with the previous keys
+        let (key_map, mut changes_buffer) = self.tree.edit_then(
+            |tree| {
+                let key_map = tree.defragment(order);
+                let changes_buffer = self
+                    .changes_buffer
+                    .write()
+                    .expect("Expected to be able to update BoxTree changes buffer");
+                (key_map, changes_buffer)
+            },
+            |_| {},
+        );
+        changes_buffer.retain_mut(|(node_stack, _)| {
+            node_stack.iter_mut().all(|(node_key, _)| {
+                key_map
+                    .get(&*node_key)
+                    .map(|new_key| *node_key = *new_key)
+                    .is_some()
+            })
+        });
+        drop(changes_buffer);
+        for view_index in 0..viewset.len() {
+            if let Some(mut view) = viewset.view_mut(view_index) {
+                view.reload();
+            }
+        }
+        key_map
+    }
 }
 
 //##############################################################################
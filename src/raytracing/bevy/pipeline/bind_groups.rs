@@ -1,9 +1,6 @@
-use crate::{
-    boxtree::types::PaletteIndexValues,
-    raytracing::bevy::types::{
-        BoxTreeGPUView, BoxTreeMetaData, RenderStageData, VhxRenderPipeline, Viewport,
-        VHX_PREPASS_STAGE_ID, VHX_RENDER_STAGE_ID,
-    },
+use crate::raytracing::bevy::types::{
+    BoxTreeGPUView, BoxTreeMetaData, RenderStageData, VhxRenderPipeline, Viewport,
+    VHX_PREPASS_STAGE_ID, VHX_RENDER_STAGE_ID,
 };
 use bevy::{
     ecs::system::Res,
@@ -158,7 +155,7 @@ pub(crate) fn create_bind_group_layouts(
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
-                    min_binding_size: Some(<Vec<PaletteIndexValues> as ShaderType>::min_size()),
+                    min_binding_size: Some(<Vec<u32> as ShaderType>::min_size()),
                 },
                 count: None,
             },
@@ -420,7 +417,8 @@ pub(crate) fn create_tree_bind_group(
     });
 
     let brick_size = (render_data.boxtree_meta.tree_properties & 0x0000FFFF).pow(3) as u64;
-    let one_voxel_byte_size = std::mem::size_of::<PaletteIndexValues>() as u64;
+    // Only the color index of each voxel is stored on the GPU, see @NodeContent::pix_gpu
+    let one_voxel_byte_size = std::mem::size_of::<u32>() as u64;
     let voxels_buffer = render_device.create_buffer(&BufferDescriptor {
         mapped_at_creation: false,
         size: one_voxel_byte_size * brick_size * tree_view.data_handler.bricks_in_view as u64,
@@ -114,7 +114,10 @@ impl BoxTreeGPUDataHandler {
                 let child_mip = self.render_data.node_mips[child_descriptor];
                 if child_mip != empty_marker::<u32>() {
                     self.render_data.node_mips[child_descriptor] = empty_marker();
-                    if matches!(tree.nodes.get(*child_key).mip, BrickData::Parted(_)) {
+                    if matches!(
+                        tree.nodes.get(*child_key).mip,
+                        BrickData::Parted(_) | BrickData::Packed(_)
+                    ) {
                         self.upload_targets
                             .brick_ownership
                             .write()
@@ -368,13 +371,13 @@ impl BoxTreeGPUDataHandler {
                 match brick {
                     BrickData::Solid(voxel) => {
                         self.render_data.node_children[parent_first_child_index] =
-                            0x80000000 | *voxel;
+                            0x80000000 | NodeContent::pix_gpu(voxel);
                     }
                     BrickData::Empty => {
                         self.render_data.node_children[parent_first_child_index] =
                             empty_marker::<u32>();
                     }
-                    BrickData::Parted(_brick) => {
+                    BrickData::Parted(_) | BrickData::Packed(_) => {
                         let brick_ownership_entry = self
                             .upload_targets
                             .brick_ownership
@@ -401,13 +404,13 @@ impl BoxTreeGPUDataHandler {
                     match brick {
                         BrickData::Solid(voxel) => {
                             self.render_data.node_children[parent_first_child_index + sectant] =
-                                0x80000000 | voxel;
+                                0x80000000 | NodeContent::pix_gpu(voxel);
                         }
                         BrickData::Empty => {
                             self.render_data.node_children[parent_first_child_index + sectant] =
                                 empty_marker::<u32>();
                         }
-                        BrickData::Parted(_brick) => {
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
                             if let Some(brick_index) = self
                                 .upload_targets
                                 .brick_ownership
@@ -435,8 +438,8 @@ impl BoxTreeGPUDataHandler {
         // Try to collect node MIP entry
         self.render_data.node_mips[node_index] = match tree.nodes.get(node_key).mip {
             BrickData::Empty => empty_marker::<u32>(), // empty MIPS are stored with empty_marker
-            BrickData::Solid(voxel) => 0x80000000 | voxel, // In case MIP is solid, it is pointing to the color palette
-            BrickData::Parted(_) => {
+            BrickData::Solid(voxel) => 0x80000000 | NodeContent::pix_gpu(&voxel), // In case MIP is solid, it is pointing to the color palette
+            BrickData::Parted(_) | BrickData::Packed(_) => {
                 // Try to add MIP if it's parted, and not already available
                 if let Some(brick_index) = self
                     .upload_targets
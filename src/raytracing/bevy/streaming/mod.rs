@@ -17,11 +17,12 @@ use crate::{
     spatial::Cube,
 };
 use bevy::{
+    math::Vec4,
     prelude::{Commands, Res, ResMut},
     render::{
         render_resource::{
             encase::{internal::WriteInto, UniformBuffer},
-            Buffer, ShaderSize,
+            Buffer, BufferSize, ShaderSize,
         },
         renderer::RenderQueue,
     },
@@ -32,16 +33,14 @@ use std::{
 };
 
 /// Process updates made to the Boxtree inside the given tree host
+/// * `tree` - The published state of the hosted tree, containing every update in the changes buffer
 pub(crate) fn handle_tree_updates<T: VoxelData>(
+    tree: &BoxTree<T>,
     tree_host: &BoxTreeGPUHost<T>,
     view: &mut BoxTreeGPUView,
     nodes_to_process: usize,
 ) -> Vec<CacheUpdatePackage> {
     let mut cache_updates = vec![];
-    let tree = &tree_host
-        .tree
-        .read()
-        .expect("Expected to be able to read tree");
 
     for _ in 0..nodes_to_process {
         let Some((node_access_stack, updated_sectants)) = tree_host
@@ -82,7 +81,7 @@ pub(crate) fn handle_tree_updates<T: VoxelData>(
         {
             match &tree.nodes.get(BoxTree::<T>::ROOT_NODE_KEY as usize).mip {
                 BrickData::Empty | BrickData::Solid(_) => {}
-                BrickData::Parted(_brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     cache_updates.push(CacheUpdatePackage {
                         added_node: None,
                         brick_updates: vec![BrickUpdate {
@@ -144,7 +143,7 @@ pub(crate) fn handle_tree_updates<T: VoxelData>(
                 {
                     match &tree.nodes.get(node_key).mip {
                         BrickData::Empty | BrickData::Solid(_) => {}
-                        BrickData::Parted(_brick) => {
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
                             cache_updates.push(CacheUpdatePackage {
                                 added_node: None,
                                 brick_updates: vec![BrickUpdate {
@@ -175,7 +174,7 @@ pub(crate) fn handle_tree_updates<T: VoxelData>(
             }
             NodeContent::UniformLeaf(brick) => match brick {
                 BrickData::Empty | BrickData::Solid(_) => {}
-                BrickData::Parted(_brick_data) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     debug_assert_eq!(updated_sectants, vec![0]);
                     let brick_ownership_entry = BrickOwnedBy::NodeAsChild(
                         parent_key as u32,
@@ -216,7 +215,7 @@ pub(crate) fn handle_tree_updates<T: VoxelData>(
                     .into_iter()
                     .filter_map(|sec| {
                         debug_assert!(
-                            matches!(bricks[sec as usize], BrickData::Parted(_)),
+                            matches!(bricks[sec as usize], BrickData::Parted(_) | BrickData::Packed(_)),
                             "Expected BrickData of Leaf node to be parted during the processing of the brick upload list"
                         );
                         let brick_ownership_entry = BrickOwnedBy::NodeAsChild(
@@ -274,12 +273,7 @@ pub(crate) fn handle_tree_updates<T: VoxelData>(
         {
             // Set target_node_stack to the start of the tree
             view.data_handler.upload_state.target_node_stack =
-                UploadQueueStatus::node_stack_init_value(
-                    &tree_host
-                        .tree
-                        .read()
-                        .expect("Expected to be able to read BoxTree size"),
-                );
+                UploadQueueStatus::node_stack_init_value(tree);
         }
     }
     cache_updates
@@ -434,10 +428,15 @@ pub(crate) fn upload<T: VoxelData>(
     if viewset.is_empty() {
         return; // Nothing to do without views..
     }
-    let tree = tree_host
-        .tree
-        .read()
-        .expect("Expected to be able to read tree from GPU host");
+    // The tree is read while the changes buffer is locked, so every update inside the buffer
+    // is already visible in it, see @BoxTreeGPUHost::edit
+    let tree = {
+        let _changes_buffer = tree_host
+            .changes_buffer
+            .read()
+            .expect("Expected to be able to read BoxTree updates buffer");
+        tree_host.tree()
+    };
     let mut view = viewset.view_mut(0).unwrap();
     if view.resources.is_none() {
         return; // Can't write to buffers as there are not created
@@ -445,12 +444,12 @@ pub(crate) fn upload<T: VoxelData>(
 
     // Decide target nodes/bricks to upload
     let cache_updates = if view.reload {
-        upload_queue::process(&mut commands, tree_host, &mut view, upload_queue_update)
+        upload_queue::process(&mut commands, &tree, &mut view, upload_queue_update)
     } else {
         let nodes_to_process = view.data_handler.node_uploads_per_frame;
-        let tree_updates = handle_tree_updates(tree_host, &mut view, nodes_to_process);
+        let tree_updates = handle_tree_updates(&tree, tree_host, &mut view, nodes_to_process);
         if tree_updates.is_empty() {
-            upload_queue::process(&mut commands, tree_host, &mut view, upload_queue_update)
+            upload_queue::process(&mut commands, &tree, &mut view, upload_queue_update)
         } else {
             tree_updates
         }
@@ -493,32 +492,47 @@ pub(crate) fn upload<T: VoxelData>(
     }
 
     // Data updates for color palette
-    let host_color_count = tree.map_to_color_index_in_palette.keys().len();
-    let color_palette_size_diff =
-        host_color_count - view.data_handler.upload_state.uploaded_color_palette_size;
+    let host_color_count = tree.voxel_color_palette.len();
+    if view.data_handler.upload_state.uploaded_palette_revision != tree.palette_revision {
+        // Existing colors were changed, the whole palette needs to be uploaded again
+        view.data_handler.upload_state.uploaded_color_palette_size = 0;
+        view.data_handler.upload_state.uploaded_palette_revision = tree.palette_revision;
+    }
+    if host_color_count > view.data_handler.render_data.color_palette.len() {
+        // Palette doesn't fit into the GPU buffer, which needs to be re-created in a larger size
+        let new_palette_size = (host_color_count as f32 * 1.2) as usize;
+        view.data_handler
+            .render_data
+            .color_palette
+            .resize(new_palette_size, Vec4::ZERO);
+        view.resize = true;
+    } else if !view.resize {
+        // Upload is postponed while the buffers are being re-created
+        let color_palette_size_diff =
+            host_color_count - view.data_handler.upload_state.uploaded_color_palette_size;
+
+        debug_assert!(
+            host_color_count >= view.data_handler.upload_state.uploaded_color_palette_size,
+            "Expected host color palette({:?}), to be larger, than colors stored on the GPU({:?})",
+            host_color_count,
+            view.data_handler.upload_state.uploaded_color_palette_size
+        );
 
-    debug_assert!(
-        host_color_count >= view.data_handler.upload_state.uploaded_color_palette_size,
-        "Expected host color palette({:?}), to be larger, than colors stored on the GPU({:?})",
-        host_color_count,
-        view.data_handler.upload_state.uploaded_color_palette_size
-    );
+        if 0 < color_palette_size_diff {
+            for i in view.data_handler.upload_state.uploaded_color_palette_size..host_color_count {
+                view.data_handler.render_data.color_palette[i] = tree.voxel_color_palette[i].into();
+            }
 
-    if 0 < color_palette_size_diff {
-        for i in view.data_handler.upload_state.uploaded_color_palette_size..host_color_count {
-            view.data_handler.render_data.color_palette[i] = tree.voxel_color_palette[i].into();
+            // Upload color palette delta to GPU
+            write_range_to_buffer(
+                &view.data_handler.render_data.color_palette,
+                (host_color_count - color_palette_size_diff)..(host_color_count),
+                &view.resources.as_ref().unwrap().color_palette_buffer,
+                render_queue,
+            );
         }
-
-        // Upload color palette delta to GPU
-        write_range_to_buffer(
-            &view.data_handler.render_data.color_palette,
-            (host_color_count - color_palette_size_diff)..(host_color_count),
-            &view.resources.as_ref().unwrap().color_palette_buffer,
-            render_queue,
-        );
+        view.data_handler.upload_state.uploaded_color_palette_size = host_color_count;
     }
-    view.data_handler.upload_state.uploaded_color_palette_size =
-        tree.map_to_color_index_in_palette.keys().len();
 
     // compile cache updates into write batches
     #[allow(clippy::reversed_empty_ranges)]
@@ -585,26 +599,32 @@ pub(crate) fn upload<T: VoxelData>(
                 BrickOwnedBy::NodeAsMIP(node_key) => &tree.nodes.get(node_key as usize).mip,
             };
             debug_assert!(
-                matches!(brick_data, BrickData::Parted(_)),
+                matches!(brick_data, BrickData::Parted(_) | BrickData::Packed(_)),
                 "Expected requested brick update to upload parted data!"
             );
-            let BrickData::Parted(brick_data) = brick_data else {
+            // Voxels are written into the staging buffer one by one,
+            // as the GPU expects every voxel in full
+            let Some(voxels) = brick_data.voxels_iter() else {
                 continue;
             };
-
-            let voxel_start_index = modified_brick_data.brick_index * brick_data.len();
+            let brick_size = tree.brick_dim.pow(3) as usize;
+            let voxel_size = std::mem::size_of::<u32>();
+            let Some(mut staging) = render_queue.write_buffer_with(
+                &view.resources.as_ref().unwrap().voxels_buffer,
+                (modified_brick_data.brick_index * brick_size * voxel_size) as u64,
+                BufferSize::new((brick_size * voxel_size) as u64).unwrap(),
+            ) else {
+                continue;
+            };
+            let mut written = 0;
+            for (target, voxel) in staging.chunks_exact_mut(voxel_size).zip(voxels) {
+                target.copy_from_slice(&NodeContent::pix_gpu(&voxel).to_ne_bytes());
+                written += 1;
+            }
             debug_assert_eq!(
-                brick_data.len(),
-                tree.brick_dim.pow(3) as usize,
+                written, brick_size,
                 "Expected Brick slice to align to tree brick dimension"
             );
-            unsafe {
-                render_queue.write_buffer(
-                    &view.resources.as_ref().unwrap().voxels_buffer,
-                    (voxel_start_index * std::mem::size_of_val(&brick_data[0])) as u64,
-                    brick_data.align_to::<u8>().1,
-                );
-            }
         }
     }
 
@@ -85,6 +85,9 @@ pub(crate) struct UploadQueueStatus {
 
     /// The number of colors uploaded to the GPU
     pub(crate) uploaded_color_palette_size: usize,
+
+    /// The revision of the color palette uploaded to the GPU, see @BoxTree::palette_revision
+    pub(crate) uploaded_palette_revision: u32,
 }
 
 #[derive(Debug, Resource)]
@@ -2,19 +2,17 @@ use crate::{
     boxtree::{
         iterate::execute_for_relevant_sectants,
         types::{BrickData, NodeChildren, NodeContent},
-        BoxTree, V3c, V3cf32, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+        BoxTree, BoxTreeSnapshot, V3c, V3cf32, VoxelData, BOX_NODE_CHILDREN_COUNT,
+        BOX_NODE_DIMENSION,
     },
     object_pool::empty_marker,
     raytracing::{
-        bevy::{
-            streaming::{
-                re_evaluate_view_size,
-                types::{
-                    BrickOwnedBy, BrickOwnership, CacheUpdatePackage, UploadQueueStatus,
-                    UploadQueueTargets, UploadQueueUpdateTask,
-                },
+        bevy::streaming::{
+            re_evaluate_view_size,
+            types::{
+                BrickOwnedBy, BrickOwnership, CacheUpdatePackage, UploadQueueStatus,
+                UploadQueueTargets, UploadQueueUpdateTask,
             },
-            types::BoxTreeGPUHost,
         },
         BoxTreeGPUView,
     },
@@ -219,16 +217,12 @@ fn add_children_nodes_to_upload_queue<T: VoxelData>(
 /// Recreate the list of nodes currently required to be inside the viewport
 pub(crate) fn process<T: VoxelData>(
     commands: &mut Commands,
-    tree_host: &BoxTreeGPUHost<T>,
+    tree: &Arc<BoxTreeSnapshot<T>>,
     view: &mut BoxTreeGPUView,
     mut upload_queue_update: Option<ResMut<UploadQueueUpdateTask>>,
 ) -> Vec<CacheUpdatePackage> {
     let mut cache_updates = vec![];
     let view_distance = view.spyglass.viewport.frustum.z;
-    let tree = &tree_host
-        .tree
-        .read()
-        .expect("Expected to be able to read tree");
 
     if view.reload {
         // rebuild upload queue if not already in progress
@@ -236,13 +230,11 @@ pub(crate) fn process<T: VoxelData>(
             let thread_pool = AsyncComputeTaskPool::get();
             let viewport_center = view.spyglass.viewport.origin;
             let brick_ownership = view.data_handler.upload_targets.brick_ownership.clone();
-            let tree_arc = tree_host.tree.clone();
+            let tree_arc = tree.clone();
             let nodes_to_see = view.data_handler.upload_targets.nodes_to_see.clone();
             commands.insert_resource(UploadQueueUpdateTask(thread_pool.spawn(async move {
                 rebuild::<T>(
-                    &tree_arc
-                        .read()
-                        .expect("Expected to be able to read tree from GPU host"),
+                    &tree_arc,
                     viewport_center,
                     view_distance,
                     brick_ownership,
@@ -271,14 +263,12 @@ pub(crate) fn process<T: VoxelData>(
     {
         let thread_pool = AsyncComputeTaskPool::get();
         let brick_ownership = view.data_handler.upload_targets.brick_ownership.clone();
-        let tree_arc = tree_host.tree.clone();
+        let tree_arc = tree.clone();
         let nodes_to_see = view.data_handler.upload_targets.nodes_to_see.clone();
 
         commands.insert_resource(UploadQueueUpdateTask(thread_pool.spawn(async move {
             rebuild::<T>(
-                &tree_arc
-                    .read()
-                    .expect("Expected to be able to read tree from GPU host"),
+                &tree_arc,
                 viewport_center,
                 viewing_distance,
                 brick_ownership,
@@ -418,7 +408,7 @@ fn process_node_child_bricks<T: VoxelData>(
                     // Empty brickdata is not uploaded,
                     // while solid brickdata should be present in the nodes data
                 }
-                BrickData::Parted(_brick) => {
+                BrickData::Parted(_) | BrickData::Packed(_) => {
                     let brick_ownership_entry = BrickOwnedBy::NodeAsChild(
                         node_key as u32,
                         0,
@@ -444,7 +434,7 @@ fn process_node_child_bricks<T: VoxelData>(
                             // Empty brickdata is not uploaded,
                             // while solid brickdata should be present in the nodes data
                         }
-                        BrickData::Parted(_brick) => {
+                        BrickData::Parted(_) | BrickData::Packed(_) => {
                             let brick_ownership_entry = BrickOwnedBy::NodeAsChild(
                                 node_key as u32,
                                 target_child_sectant,
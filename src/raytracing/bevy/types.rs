@@ -1,5 +1,5 @@
 use crate::{
-    boxtree::{types::BoxTreeUpdatedSignalParams, BoxTree, V3cf32, VoxelData},
+    boxtree::{types::BoxTreeUpdatedSignalParams, SharedBoxTree, V3cf32, VoxelData},
     raytracing::bevy::streaming::types::BoxTreeGPUDataHandler,
     spatial::Cube,
 };
@@ -94,11 +94,16 @@ pub struct BoxTreeGPUHost<T = u32>
 where
     T: Default + Clone + Eq + VoxelData + Send + Sync + Hash + 'static,
 {
-    /// The BoxTree hosted within the bevy library
-    pub tree: Arc<RwLock<BoxTree<T>>>,
+    /// The BoxTree hosted within the bevy library, see @BoxTreeGPUHost::edit
+    /// Views stream the latest published state of the tree, so they are not blocked by edits
+    pub(crate) tree: SharedBoxTree<T>,
+
+    /// Updates made to the tree during an edit, moved into @changes_buffer once the edit is published
+    pub(crate) pending_changes: Arc<RwLock<VecDeque<BoxTreeUpdatedSignalParams>>>,
 
     /// Updates made to the tree are collected in this buffer
     /// Changes made to nodes within the tree will automatically include them into
+    /// Every update inside the buffer is visible in the published state of the tree
     pub(crate) changes_buffer: Arc<RwLock<VecDeque<BoxTreeUpdatedSignalParams>>>,
 }
 
@@ -40,10 +40,7 @@ impl<T: VoxelData> BoxTreeGPUHost<T> {
         resolution: [u32; 2],
         mut images: ResMut<Assets<Image>>,
     ) -> usize {
-        let tree = &self
-            .tree
-            .read()
-            .expect("Expected to be able to read tree from GPU host");
+        let tree = &self.tree();
 
         // This is an estimation for the required number of nodes in the given view
         // which sums the number of nodes within the viewport on every level
@@ -102,6 +99,7 @@ impl<T: VoxelData> BoxTreeGPUHost<T> {
                 bricks_to_upload: vec![],
                 target_node_stack: vec![],
                 uploaded_color_palette_size: 0,
+                uploaded_palette_revision: 0,
             },
             pending_upload_queue_update: None,
             nodes_in_view,
@@ -294,7 +294,7 @@ fn probe_brick(
             // Whole brick is solid, ray hits it at first connection
             return OctreeRayIntersection(
                 !is_empty(brick_descriptor),
-                color_palette[brick_descriptor & 0x0000FFFF], // Albedo is in color_palette, it's not a brick index in this case
+                color_palette[brick_descriptor & 0x7FFFFFFF], // Albedo is in color_palette, it's not a brick index in this case
                 *ray_current_point,
                 vec3f(0.,1.,0.) // see issue #11
             );
@@ -315,7 +315,7 @@ fn probe_brick(
             if leaf_brick_hit.hit == true {
                 return OctreeRayIntersection(
                     true,
-                    color_palette[voxels[leaf_brick_hit.flat_index] & 0x0000FFFF],
+                    color_palette[voxels[leaf_brick_hit.flat_index] & 0x7FFFFFFF],
                     *ray_current_point,
                     vec3f(0.,1.,0.) // see issue #11
                 );
@@ -338,7 +338,7 @@ fn probe_MIP(
             // Whole brick is solid, ray hits it at first connection
             return OctreeRayIntersection(
                 !is_empty(node_mips[node_key]),
-                color_palette[node_mips[node_key] & 0x0000FFFF], // Albedo is in color_palette, it's not a brick index in this case
+                color_palette[node_mips[node_key] & 0x7FFFFFFF], // Albedo is in color_palette, it's not a brick index in this case
                 ray_current_point,
                 vec3f(0.,1.,0.) // see issue #11
             );
@@ -353,7 +353,7 @@ fn probe_MIP(
             if leaf_brick_hit.hit == true {
                 return OctreeRayIntersection(
                     true,
-                    color_palette[voxels[leaf_brick_hit.flat_index] & 0x0000FFFF],
+                    color_palette[voxels[leaf_brick_hit.flat_index] & 0x7FFFFFFF],
                     brick_point,
                     vec3f(0.,1.,0.) // see issue #11
                 );
@@ -611,16 +611,17 @@ fn get_by_ray(ray: ptr<function, Line>, start_distance: f32) -> OctreeRayInterse
     return OctreeRayIntersection(false, vec4f(0.), ray_current_point, vec3f(0., 0., 1.));
 }
 
+// The color palette index of a voxel, or an empty marker without a color
 alias PaletteIndexValues = u32;
 
 fn is_empty(e: PaletteIndexValues) -> bool {
     return (
-        (0x0000FFFF == (0x0000FFFF & e))
+        (0x7FFFFFFF == (0x7FFFFFFF & e))
         ||(
-            0. == color_palette[e & 0x0000FFFF].a
-            && 0. == color_palette[e & 0x0000FFFF].r
-            && 0. == color_palette[e & 0x0000FFFF].g
-            && 0. == color_palette[e & 0x0000FFFF].b
+            0. == color_palette[e & 0x7FFFFFFF].a
+            && 0. == color_palette[e & 0x7FFFFFFF].r
+            && 0. == color_palette[e & 0x7FFFFFFF].g
+            && 0. == color_palette[e & 0x7FFFFFFF].b
         )
     );
 }
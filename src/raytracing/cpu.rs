@@ -137,7 +137,7 @@ impl<T: VoxelData> BoxTree<T> {
         &self,
         ray: &Ray,
         ray_current_point: &mut V3c<f32>,
-        brick: &[PaletteIndexValues],
+        brick: &BrickData<PaletteIndexValues>,
         brick_bounds: &Cube,
         brick_dim: usize,
         ray_scale_factors: &V3c<f32>,
@@ -196,7 +196,7 @@ impl<T: VoxelData> BoxTree<T> {
             );
 
             if !NodeContent::pix_points_to_empty(
-                &brick[current_flat_index as usize],
+                &brick.voxel(current_flat_index as usize).unwrap(),
                 &self.voxel_color_palette,
                 &self.voxel_data_palette,
             ) {
@@ -258,7 +258,7 @@ impl<T: VoxelData> BoxTree<T> {
                     cube_impact_normal(brick_bounds, impact_point),
                 ))
             }
-            BrickData::Parted(brick) => {
+            BrickData::Parted(_) | BrickData::Packed(_) => {
                 if let Some((leaf_brick_hit, leaf_brick_hit_flat_index)) = self.traverse_brick(
                     ray,
                     ray_current_point,
@@ -277,7 +277,7 @@ impl<T: VoxelData> BoxTree<T> {
                     let impact_normal = cube_impact_normal(&hit_bounds, impact_point);
                     Some((
                         NodeContent::pix_get_ref(
-                            &brick[leaf_brick_hit_flat_index],
+                            &brick.voxel(leaf_brick_hit_flat_index).unwrap(),
                             &self.voxel_color_palette,
                             &self.voxel_data_palette,
                         ),
@@ -319,12 +319,14 @@ impl<T: VoxelData> BoxTree<T> {
             current_bounds = Cube::root_bounds(self.boxtree_size as f32);
             node_stack.push(Self::ROOT_NODE_KEY);
             while let Some(node_stack_last) = node_stack.last() {
-                let current_node_occupied_bits =
-                    self.nodes.get(*node_stack_last as usize).occupied_bits;
+                let current_node_occupied_bits = self
+                    .nodes
+                    .get_outline(*node_stack_last as usize)
+                    .occupied_bits;
                 debug_assert!(self.nodes.key_is_valid(*node_stack_last as usize));
 
                 let mut do_backtrack_after_leaf_miss = matches!(
-                    self.nodes.get(current_node_key).content,
+                    self.nodes.get_outline(current_node_key).content,
                     NodeContent::UniformLeaf(_)
                 );
 
@@ -394,13 +396,15 @@ impl<T: VoxelData> BoxTree<T> {
                 }
 
                 if matches!(
-                    self.nodes.get(current_node_key).content,
+                    self.nodes.get_outline(current_node_key).content,
                     NodeContent::Internal
                 ) && 0 != (current_node_occupied_bits & (0x01 << target_sectant))
                 {
                     // PUSH
-                    let target_child_key =
-                        self.nodes.get(current_node_key).child(target_sectant) as u32;
+                    let target_child_key = self
+                        .nodes
+                        .get_outline(current_node_key)
+                        .child(target_sectant) as u32;
                     current_node_key = target_child_key as usize;
                     current_bounds = target_bounds;
                     target_sectant = offset_sectant(
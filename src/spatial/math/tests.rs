@@ -233,3 +233,45 @@ mod dot_vox_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod sectant_tests {
+    use crate::boxtree::{BoxTree, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION};
+    use crate::spatial::math::{flat_projection, octant_in_sectants};
+
+    /// The octant of a sectant, with each coordinate halved from the sectant coordinates
+    fn expected_octant(sectant: usize) -> usize {
+        let half = BOX_NODE_DIMENSION / 2;
+        flat_projection(
+            (sectant % BOX_NODE_DIMENSION) / half,
+            ((sectant / BOX_NODE_DIMENSION) % BOX_NODE_DIMENSION) / half,
+            (sectant / (BOX_NODE_DIMENSION * BOX_NODE_DIMENSION)) / half,
+            2,
+        )
+    }
+
+    #[test]
+    fn test_octant_in_sectants_follows_flat_projection() {
+        for sectant in 0..BOX_NODE_CHILDREN_COUNT {
+            assert_eq!(
+                octant_in_sectants(sectant),
+                expected_octant(sectant),
+                "Mismatch for sectant {sectant}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_dilute_brick_data_where_dim_is_2() {
+        // Every voxel of the brick covers the sectants of one octant
+        let brick_data = (0..8).collect::<Vec<u32>>();
+        let diluted = BoxTree::<u32>::dilute_brick_data(brick_data, 2);
+        for (sectant, child_brick) in diluted.iter().enumerate() {
+            assert_eq!(
+                *child_brick,
+                vec![expected_octant(sectant) as u32; 8],
+                "Mismatch for sectant {sectant}"
+            );
+        }
+    }
+}
@@ -80,11 +80,13 @@ fn load_task_from_path(path: &Path, confirmed: bool) -> TreeLoadingTask {
                     Err(err) => Err(err.to_string()),
                 }
             } else {
-                let tree = BoxTree::<u32>::load_vox_file(model_path.as_str(), BRICK_DIMENSION);
-                if let Ok(mut tree) = tree {
-                    tree.albedo_mip_map_resampling_strategy()
-                        .switch_albedo_mip_maps(true);
-                    tree.save(&tmp_file_path_).ok().unwrap();
+                match BoxTree::<u32>::load_vox_file(model_path.as_str(), BRICK_DIMENSION) {
+                    Ok(mut tree) => {
+                        tree.albedo_mip_map_resampling_strategy()
+                            .switch_albedo_mip_maps(true);
+                        tree.save(&tmp_file_path_).ok().unwrap();
+                    }
+                    Err(err) => return Err(err.to_string()),
                 }
                 match BoxTree::load(&tmp_file_path_) {
                     Ok(tree) => Ok(tree),
@@ -323,11 +325,12 @@ pub(crate) fn load_last_loaded_model(
 
                 let thread_pool = AsyncComputeTaskPool::get();
                 let file_path_ = last_loaded_cache_file.to_string();
-                let model_version = BoxTree::<u32>::version(&last_loaded_cache_file)
-                    .expect("Expected to be able to parse model version");
                 let lib_version = voxelhex::version();
                 let model_path = pkv.get::<String>("last_loaded_model_path").ok();
-                if lib_version.compatible(&model_version) {
+                if let Some(model_version) = BoxTree::<u32>::version(&last_loaded_cache_file)
+                    .ok()
+                    .filter(|model_version| lib_version.compatible(model_version))
+                {
                     commands.insert_resource(TreeLoadingTask {
                         model_version,
                         confirmed: true,
@@ -348,7 +351,7 @@ pub(crate) fn load_last_loaded_model(
                         })),
                     });
                 } else {
-                    // Version incompatibility, try to re-parse model if still available
+                    // Version incompatibility or unreadable cache, try to re-parse model if still available
                     delete_by_path(&last_loaded_cache_file);
                     if let Some(model_path) = model_path {
                         message_text.0 = "Cache version mismatch, re-parsing model".to_string();